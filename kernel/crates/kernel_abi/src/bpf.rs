@@ -1,5 +1,7 @@
 use bitflags::bitflags;
 
+use crate::{EPERM, Errno};
+
 bitflags! {
     pub struct BpfMapTags: u32 {
         const UNSPEC       = 0;
@@ -31,6 +33,7 @@ bitflags! {
         const STRUCT_OPS   = 26;
         const RINGBUF      = 27;
         const INODE_STORAGE = 28;
+        const TIMESERIES   = 100;
     }
 }
 
@@ -71,6 +74,8 @@ pub const BPF_ITER_CREATE: u32 = 33;
 pub const BPF_LINK_DETACH: u32 = 34;
 pub const BPF_PROG_BIND_MAP: u32 = 35;
 pub const BPF_PROG_LOAD_ELF: u32 = 36; // Custom command for loading ELF files
+pub const BPF_PROG_DUMP: u32 = 37; // Custom command for dumping a program with source lines
+pub const BPF_MAP_QUERY_WINDOW: u32 = 38; // Custom command for reading a time-series window
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
@@ -106,8 +111,87 @@ pub struct BpfAttr {
     pub attach_prog_fd: u32, // Also used as map_fd for MAP_LOOKUP/UPDATE
 
     // Map element operations (MAP_LOOKUP_ELEM, MAP_UPDATE_ELEM, MAP_DELETE_ELEM)
+    // - PROG_ATTACH/PROG_DETACH: key/value select the target within the attach type
+    //   GPIO: key = chip << 32 | line, value = edge mask (1 rising, 2 falling, 0/3 both)
+    //   IIO: key = device, value = channel id
+    //   PWM: key = controller, value = channel
+    //   CAN: key = interface, value = mask << 32 | identifier
+    //   Serial: key = port, value = framing << 16 | argument
+    //     (0 delimiter byte, 1 fixed length, 2 SLIP, 3 COBS)
+    //   I2C, SPI: key = bus << 32 | address or chip select (0xffffffff = any),
+    //     value = 0 for all registers or 1 << 16 | last << 8 | first
+    //   Syscall, syscall exit: value = bitmask of syscall numbers (0 = all)
+    //   Syscall filter: no target, installs the program on the calling process
+    //   Tracepoint: key = pointer to "category:name", value = its length
+    //   Kprobe, kretprobe: key = pointer to the function name, value = its length
+    //   High-resolution timer: key = timer (0-31), value = phase << 32 | period in ns
+    // - MAP_QUERY_WINDOW: key = start, value = end of the window in ns; the
+    //   samples are written to log_buf as (u64 timestamp, value) records
     pub map_fd: u32,
     pub key: u64,   // pointer to key
     pub value: u64, // pointer to value (or next_key for GET_NEXT_KEY)
     pub flags: u64, // update flags
 }
+
+/// Statistics over the newest samples of a time-series map, written by the
+/// `bpf_timeseries_stats` helper. Values are the first 8 bytes of each
+/// sample, read as `i64`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BpfTimeSeriesStats {
+    pub count: u64,
+    pub min: i64,
+    pub max: i64,
+    /// Rounded towards zero
+    pub mean: i64,
+    /// Population variance, rounded down
+    pub variance: u64,
+    pub first_ns: u64,
+    pub last_ns: u64,
+}
+
+// Syscall filter verdicts, returned by programs installed with the syscall
+// filter attach type. The high 16 bits select the action and the low 16 bits
+// carry its data. When several filters are installed, the action with the
+// lowest value wins; unknown actions kill the process.
+/// Action bits of a verdict
+pub const SYSCALL_FILTER_ACTION: u32 = 0xffff_0000;
+/// Data bits of a verdict
+pub const SYSCALL_FILTER_DATA: u32 = 0x0000_ffff;
+/// Kill the calling process
+pub const SYSCALL_FILTER_KILL: u32 = 0x0000_0000;
+/// Fail with the errno in the data bits without running the syscall. Data
+/// outside `1..=4095` fails with `EPERM`, see [`syscall_filter_errno`].
+pub const SYSCALL_FILTER_ERRNO: u32 = 0x0005_0000;
+/// Run the syscall and hit the `syscalls:filter_trace` tracepoint with the
+/// data bits
+pub const SYSCALL_FILTER_TRACE: u32 = 0x7ff0_0000;
+/// Run the syscall
+pub const SYSCALL_FILTER_ALLOW: u32 = 0x7fff_0000;
+
+/// Errno a [`SYSCALL_FILTER_ERRNO`] verdict fails the syscall with.
+///
+/// Like seccomp, data that is not a valid errno fails with `EPERM`, so a
+/// filter returning 0 cannot skip the syscall and report success.
+#[must_use]
+pub fn syscall_filter_errno(verdict: u32) -> Errno {
+    match verdict & SYSCALL_FILTER_DATA {
+        data @ 1..=4095 => Errno::from(data as isize),
+        _ => EPERM,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EINVAL;
+
+    #[test]
+    fn syscall_filter_errno_rejects_invalid_data() {
+        assert_eq!(syscall_filter_errno(SYSCALL_FILTER_ERRNO), EPERM);
+        assert_eq!(syscall_filter_errno(SYSCALL_FILTER_ERRNO | 4096), EPERM);
+        assert_eq!(syscall_filter_errno(SYSCALL_FILTER_ERRNO | 0xffff), EPERM);
+        let einval = i32::from(EINVAL) as u32;
+        assert_eq!(syscall_filter_errno(SYSCALL_FILTER_ERRNO | einval), EINVAL);
+    }
+}
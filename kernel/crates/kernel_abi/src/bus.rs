@@ -0,0 +1,16 @@
+/// Most bytes written or read in one transfer on `/dev/i2cN` or `/dev/spiN`
+pub const BUS_TRANSFER_MAX: usize = 256;
+
+/// Header of a transfer written to `/dev/i2cN` or `/dev/spiN`
+///
+/// The header is followed by the bytes to write. The device is sent those
+/// bytes and then `read_len` bytes are read from it in the same transaction;
+/// the next read of the file returns them.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BusTransfer {
+    /// 7-bit target address (I2C) or chip select (SPI)
+    pub addr: u32,
+    /// Number of bytes to read after writing
+    pub read_len: u32,
+}
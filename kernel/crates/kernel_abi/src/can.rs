@@ -0,0 +1,28 @@
+/// Identifier is a 29-bit extended identifier (`CanFrame::can_id`)
+pub const CAN_EFF_FLAG: u32 = 0x8000_0000;
+/// Frame is a remote transmission request (`CanFrame::can_id`)
+pub const CAN_RTR_FLAG: u32 = 0x4000_0000;
+/// Frame is an error frame (`CanFrame::can_id`)
+pub const CAN_ERR_FLAG: u32 = 0x2000_0000;
+
+/// Bits of a standard identifier
+pub const CAN_SFF_MASK: u32 = 0x0000_07FF;
+/// Bits of an extended identifier
+pub const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+
+/// Maximum payload of a classic CAN frame
+pub const CAN_MAX_DLEN: usize = 8;
+
+/// Classic CAN frame, laid out like the SocketCAN `struct can_frame`
+#[repr(C, align(8))]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CanFrame {
+    /// Identifier with `CAN_*_FLAG` bits
+    pub can_id: u32,
+    /// Number of payload bytes (0-8)
+    pub len: u8,
+    pub _pad: u8,
+    pub _res0: u8,
+    pub _len8_dlc: u8,
+    pub data: [u8; CAN_MAX_DLEN],
+}
@@ -1,17 +1,23 @@
 #![no_std]
 
 mod bpf;
+mod bus;
+mod can;
 mod errno;
 mod fcntl;
 mod limits;
 mod mman;
+mod safety;
 pub mod syscall;
 mod time;
 
 pub use bpf::*;
+pub use bus::*;
+pub use can::*;
 pub use errno::*;
 pub use fcntl::*;
 pub use limits::*;
 pub use mman::*;
+pub use safety::*;
 pub use syscall::*;
 pub use time::*;
@@ -0,0 +1,39 @@
+/// Actuator kinds for `SYS_ACTUATOR_REGISTER`
+pub const ACTUATOR_PWM: usize = 0;
+pub const ACTUATOR_GPIO: usize = 1;
+
+/// `SafetyEvent::event_type` of safety events
+pub const SAFETY_EVENT_TYPE: u32 = 3;
+
+/// `SafetyEvent::safety_type` values
+pub const SAFETY_LIMIT_SWITCH: u32 = 0;
+pub const SAFETY_EMERGENCY_STOP: u32 = 1;
+pub const SAFETY_THRESHOLD_EXCEEDED: u32 = 2;
+pub const SAFETY_COMM_TIMEOUT: u32 = 3;
+pub const SAFETY_MOTOR_FAULT: u32 = 4;
+
+/// `SafetyEvent::action` values
+pub const SAFETY_ACTION_NONE: u32 = 0;
+pub const SAFETY_ACTION_MOTOR_STOP: u32 = 1;
+pub const SAFETY_ACTION_SYSTEM_HALT: u32 = 2;
+pub const SAFETY_ACTION_ALERT: u32 = 3;
+
+/// `SafetyEvent::source_id` of emergency stops
+pub const ESTOP_SOURCE_BPF: u32 = 0;
+pub const ESTOP_SOURCE_SYSCALL: u32 = 1;
+pub const ESTOP_SOURCE_WATCHDOG: u32 = 2;
+
+/// Safety event, laid out like the rk_bridge `SafetyEvent` (header included)
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SafetyEvent {
+    pub timestamp_ns: u64,
+    pub event_type: u32,
+    pub cpu: u32,
+    pub pid: u32,
+    pub _reserved: u32,
+    pub safety_type: u32,
+    pub source_id: u32,
+    pub value: i32,
+    pub action: u32,
+}
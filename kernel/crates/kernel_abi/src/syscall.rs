@@ -61,4 +61,11 @@ n! {
     SYS_CLOCK_GETTIME = 54,
     SYS_NANOSLEEP = 55,
     SYS_SPAWN = 56,
+    SYS_ACTUATOR_REGISTER = 57,
+    SYS_ESTOP = 58,
+    SYS_ESTOP_REARM = 59,
+    SYS_ESTOP_STATUS = 60,
+    SYS_WATCHDOG_CONFIG = 61,
+    SYS_CAN_SEND = 62,
+    SYS_CAN_RECV = 63,
 }
@@ -0,0 +1,354 @@
+//! I2C and SPI Attach Points
+//!
+//! Attach BPF programs to transactions on an I2C or SPI bus. Every transfer
+//! raises an event with the bytes written before anything is sent, and one
+//! with the bytes read once the transfer completes.
+//!
+//! # Filtering
+//!
+//! An attach point selects transactions by bus, by I2C target address or
+//! SPI chip select, and by register range. The register of a transaction is
+//! the first byte written, which selects the register on most I2C devices;
+//! SPI devices often send an instruction first, so programs for those
+//! inspect the payload themselves.
+//!
+//! A program that returns non-zero rejects the transaction. A rejected
+//! write is never sent, and a rejected read fails without handing the data
+//! to the driver, so programs can keep drivers away from safety-relevant
+//! registers and check what devices report.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Reject writes to the power management registers of an MPU-6050
+//! let config = AttachConfig::i2c("i2c1", 0x68, 0x6B..=0x6C);
+//! let id = manager.attach(&config, &firewall_program)?;
+//! ```
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::RangeInclusive;
+
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
+use crate::bytecode::program::BpfProgram;
+use crate::profile::{ActiveProfile, PhysicalProfile};
+
+/// Most bytes of a transaction delivered to programs
+pub const BUS_DATA_MAX: usize = 64;
+
+/// The bytes were read from the device rather than written
+/// (`BusEvent::flags`)
+pub const BUS_EVENT_READ: u32 = 1 << 0;
+
+/// Address matching every target or chip select
+pub const BUS_ADDR_ANY: u32 = u32::MAX;
+
+/// `BusEvent::reg` of a transaction that writes nothing
+pub const BUS_REG_NONE: u32 = u32::MAX;
+
+/// Bus transaction event structure passed to BPF programs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BusEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Bus number (e.g. 1 for i2c1)
+    pub bus: u32,
+    /// 7-bit target address (I2C) or chip select (SPI)
+    pub addr: u32,
+    /// First byte written in the transaction, or [`BUS_REG_NONE`]
+    pub reg: u32,
+    /// `BUS_EVENT_*` flags
+    pub flags: u32,
+    /// Number of bytes transferred in this direction, which may exceed
+    /// [`BUS_DATA_MAX`]
+    pub len: u32,
+    /// The first bytes transferred, zero beyond `len`
+    pub data: [u8; BUS_DATA_MAX],
+}
+
+impl BusEvent {
+    /// Create an event for the bytes written to the device (`read` false)
+    /// or read from it (`read` true). `reg` is the first byte written in
+    /// the transaction, if any.
+    pub fn new(
+        timestamp: u64,
+        bus: u32,
+        addr: u32,
+        reg: Option<u8>,
+        read: bool,
+        bytes: &[u8],
+    ) -> Self {
+        let copied = bytes.len().min(BUS_DATA_MAX);
+        let mut data = [0; BUS_DATA_MAX];
+        data[..copied].copy_from_slice(&bytes[..copied]);
+        Self {
+            timestamp,
+            bus,
+            addr,
+            reg: reg.map_or(BUS_REG_NONE, u32::from),
+            flags: if read { BUS_EVENT_READ } else { 0 },
+            len: bytes.len() as u32,
+            data,
+        }
+    }
+
+    /// Check if the bytes were read from the device.
+    pub fn is_read(&self) -> bool {
+        self.flags & BUS_EVENT_READ != 0
+    }
+
+    /// Get the register of the transaction.
+    pub fn register(&self) -> Option<u8> {
+        u8::try_from(self.reg).ok()
+    }
+
+    /// Get the bytes delivered to programs.
+    pub fn payload(&self) -> &[u8] {
+        &self.data[..(self.len as usize).min(BUS_DATA_MAX)]
+    }
+}
+
+/// The transactions of one bus an attach point fires on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusFilter {
+    /// Bus number
+    pub bus: u32,
+    /// Target address or chip select, or [`BUS_ADDR_ANY`]
+    pub addr: u32,
+    /// First and last register, or `None` for every transaction including
+    /// those that write nothing
+    pub regs: Option<(u8, u8)>,
+}
+
+impl BusFilter {
+    /// Check if a transaction passes this filter.
+    pub fn matches(&self, event: &BusEvent) -> bool {
+        self.bus == event.bus
+            && (self.addr == BUS_ADDR_ANY || self.addr == event.addr)
+            && self.regs.is_none_or(|(first, last)| {
+                event
+                    .register()
+                    .is_some_and(|reg| (first..=last).contains(&reg))
+            })
+    }
+}
+
+/// I2C or SPI transaction attach point.
+pub struct BusAttach<P: PhysicalProfile = ActiveProfile> {
+    /// `AttachType::I2c` or `AttachType::Spi`
+    attach_type: AttachType,
+    /// Bus name (e.g., "i2c1")
+    bus: String,
+    /// Bus number parsed from the name, as reported in `BusEvent::bus`
+    bus_id: Option<u32>,
+    /// Target address or chip select, or `BUS_ADDR_ANY`
+    addr: u32,
+    /// Register range, `None` for all registers
+    regs: Option<(u8, u8)>,
+    /// Attached programs
+    attached: AttachedPrograms,
+    /// Profile marker (using fn pointer for Send + Sync)
+    _profile: PhantomData<fn() -> P>,
+}
+
+impl<P: PhysicalProfile> BusAttach<P> {
+    /// Create a new I2C attach point for transactions with the target at
+    /// `addr` on registers `regs`.
+    pub fn i2c(bus: &str, addr: u32, regs: RangeInclusive<u8>) -> AttachResult<Self> {
+        Self::new(AttachType::I2c, bus, addr, regs)
+    }
+
+    /// Create a new SPI attach point for transactions with the device on
+    /// `chip_select` on registers `regs`.
+    pub fn spi(bus: &str, chip_select: u32, regs: RangeInclusive<u8>) -> AttachResult<Self> {
+        Self::new(AttachType::Spi, bus, chip_select, regs)
+    }
+
+    /// Create a new attach point of `attach_type`, which must be
+    /// `AttachType::I2c` or `AttachType::Spi`. The full register range
+    /// matches every transaction.
+    pub fn new(
+        attach_type: AttachType,
+        bus: &str,
+        addr: u32,
+        regs: RangeInclusive<u8>,
+    ) -> AttachResult<Self> {
+        if !matches!(attach_type, AttachType::I2c | AttachType::Spi) {
+            return Err(AttachError::NotSupported(attach_type));
+        }
+        if bus.is_empty() {
+            return Err(AttachError::InvalidTarget(bus.into()));
+        }
+        if regs.is_empty() {
+            return Err(AttachError::InvalidConfig);
+        }
+
+        let regs = match regs.into_inner() {
+            (0, u8::MAX) => None,
+            range => Some(range),
+        };
+        Ok(Self {
+            attach_type,
+            bus: bus.into(),
+            bus_id: super::device_index(bus),
+            addr,
+            regs,
+            attached: AttachedPrograms::new(),
+            _profile: PhantomData,
+        })
+    }
+
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached.
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
+    /// Get the bus name.
+    pub fn bus(&self) -> &str {
+        &self.bus
+    }
+
+    /// Get the target address or chip select.
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    /// Get the register range.
+    pub fn regs(&self) -> RangeInclusive<u8> {
+        let (first, last) = self.regs.unwrap_or((0, u8::MAX));
+        first..=last
+    }
+
+    /// Check if a transaction on this bus passes the filter.
+    pub fn matches(&self, event: &BusEvent) -> bool {
+        let event = match self.attach_type {
+            AttachType::Spi => super::AttachEvent::Spi(event),
+            _ => super::AttachEvent::I2c(event),
+        };
+        self.event_filter().matches(&event)
+    }
+}
+
+impl<P: PhysicalProfile> AttachPoint<P> for BusAttach<P> {
+    fn attach_type(&self) -> AttachType {
+        self.attach_type
+    }
+
+    fn target(&self) -> &str {
+        &self.bus
+    }
+
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        let Some(bus) = self.bus_id else {
+            return EventFilter::Never;
+        };
+        let filter = BusFilter {
+            bus,
+            addr: self.addr,
+            regs: self.regs,
+        };
+        match self.attach_type {
+            AttachType::Spi => EventFilter::Spi(filter),
+            _ => EventFilter::I2c(filter),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::AttachEvent;
+    use super::*;
+
+    fn write(bus: u32, addr: u32, bytes: &[u8]) -> BusEvent {
+        BusEvent::new(0, bus, addr, bytes.first().copied(), false, bytes)
+    }
+
+    #[test]
+    fn create_bus_attach() {
+        let i2c = BusAttach::<ActiveProfile>::i2c("i2c1", 0x68, 0x6B..=0x6C).unwrap();
+        assert_eq!(i2c.attach_type(), AttachType::I2c);
+        assert_eq!(i2c.bus(), "i2c1");
+        assert_eq!(i2c.addr(), 0x68);
+        assert_eq!(i2c.regs(), 0x6B..=0x6C);
+
+        let spi = BusAttach::<ActiveProfile>::spi("spi0", 1, 0..=u8::MAX).unwrap();
+        assert_eq!(spi.attach_type(), AttachType::Spi);
+        assert_eq!(spi.regs(), 0..=u8::MAX);
+
+        assert!(BusAttach::<ActiveProfile>::i2c("", 0x68, 0..=1).is_err());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 2..=1;
+        assert_eq!(
+            BusAttach::<ActiveProfile>::i2c("i2c1", 0x68, empty).err(),
+            Some(AttachError::InvalidConfig)
+        );
+        assert!(BusAttach::<ActiveProfile>::new(AttachType::Serial, "i2c1", 0, 0..=1).is_err());
+    }
+
+    #[test]
+    fn bus_attach_matches_address_and_registers() {
+        let pwr = BusAttach::<ActiveProfile>::i2c("i2c1", 0x68, 0x6B..=0x6C).unwrap();
+        assert!(pwr.matches(&write(1, 0x68, &[0x6B, 0x40])));
+        assert!(pwr.matches(&write(1, 0x68, &[0x6C])));
+        assert!(!pwr.matches(&write(1, 0x68, &[0x3B])));
+        assert!(!pwr.matches(&write(1, 0x69, &[0x6B, 0x40])));
+        assert!(!pwr.matches(&write(0, 0x68, &[0x6B, 0x40])));
+
+        // Reads without a register only match the full range
+        let read = BusEvent::new(0, 1, 0x68, None, true, &[0x12]);
+        assert!(!pwr.matches(&read));
+        let all = BusAttach::<ActiveProfile>::i2c("i2c1", BUS_ADDR_ANY, 0..=u8::MAX).unwrap();
+        assert!(all.matches(&read));
+        assert!(all.matches(&write(1, 0x50, &[0x00])));
+
+        // A transaction on spi1 is not one on i2c1
+        let spi = BusAttach::<ActiveProfile>::spi("spi1", 0, 0..=u8::MAX).unwrap();
+        let event = write(1, 0, &[0x02]);
+        assert!(spi.matches(&event));
+        assert!(!all.event_filter().matches(&AttachEvent::Spi(&event)));
+
+        let unnumbered = BusAttach::<ActiveProfile>::i2c("i2c", 0x68, 0..=1).unwrap();
+        assert_eq!(unnumbered.event_filter(), EventFilter::Never);
+    }
+
+    #[test]
+    fn bus_event_accessors() {
+        let event = BusEvent::new(5, 0, 0x68, Some(0x3B), true, &[1, 2, 3]);
+        assert!(event.is_read());
+        assert_eq!(event.register(), Some(0x3B));
+        assert_eq!(event.payload(), &[1, 2, 3]);
+
+        let long = [0xAA; 100];
+        let event = BusEvent::new(5, 0, 0, None, false, &long);
+        assert!(!event.is_read());
+        assert_eq!(event.register(), None);
+        assert_eq!(event.len, 100);
+        assert_eq!(event.payload().len(), BUS_DATA_MAX);
+    }
+}
@@ -0,0 +1,224 @@
+//! CAN Bus Attach Point
+//!
+//! Attach BPF programs to frames received on a CAN interface. Programs see
+//! the identifier, length and payload of every matching frame before it is
+//! queued for userspace.
+//!
+//! # Filtering
+//!
+//! An attach point selects frames like a SocketCAN filter: a frame matches
+//! if `frame_id & mask == id & mask`. A program that returns non-zero drops
+//! the frame, so programs can keep unwanted or malformed traffic away from
+//! userspace and trigger an emergency stop on fault messages.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Watch CANopen emergency messages (0x080 + node id) on can0
+//! let config = AttachConfig::can_bus("can0", 0x080, 0x780);
+//! let id = manager.attach(&config, &emcy_program)?;
+//! ```
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
+use crate::bytecode::program::BpfProgram;
+use crate::profile::{ActiveProfile, PhysicalProfile};
+
+/// Frame uses a 29-bit extended identifier (`CanEvent::flags`)
+pub const CAN_EVENT_EXTENDED: u32 = 1 << 0;
+
+/// Frame is a remote transmission request (`CanEvent::flags`)
+pub const CAN_EVENT_RTR: u32 = 1 << 1;
+
+/// CAN frame event structure passed to BPF programs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CanEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Interface number (e.g. 0 for can0)
+    pub iface: u32,
+    /// Identifier without flag bits (11 or 29 bits)
+    pub can_id: u32,
+    /// `CAN_EVENT_*` flags
+    pub flags: u32,
+    /// Number of payload bytes (0-8)
+    pub dlc: u32,
+    /// Payload, zero beyond `dlc`
+    pub data: [u8; 8],
+}
+
+impl CanEvent {
+    /// Check if the frame uses an extended identifier.
+    pub fn is_extended(&self) -> bool {
+        self.flags & CAN_EVENT_EXTENDED != 0
+    }
+
+    /// Check if the frame is a remote transmission request.
+    pub fn is_rtr(&self) -> bool {
+        self.flags & CAN_EVENT_RTR != 0
+    }
+
+    /// Get the payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.data[..(self.dlc as usize).min(8)]
+    }
+}
+
+/// CAN frame attach point.
+pub struct CanAttach<P: PhysicalProfile = ActiveProfile> {
+    /// Interface name (e.g., "can0")
+    iface: String,
+    /// Interface number parsed from the name, as reported in
+    /// `CanEvent::iface`
+    iface_id: Option<u32>,
+    /// Identifier to match
+    id: u32,
+    /// Identifier bits that must match
+    mask: u32,
+    /// Attached programs
+    attached: AttachedPrograms,
+    /// Profile marker (using fn pointer for Send + Sync)
+    _profile: PhantomData<fn() -> P>,
+}
+
+impl<P: PhysicalProfile> CanAttach<P> {
+    /// Create a new CAN attach point for frames with
+    /// `frame_id & mask == id & mask`. A zero mask matches every frame.
+    pub fn new(iface: &str, id: u32, mask: u32) -> AttachResult<Self> {
+        if iface.is_empty() {
+            return Err(AttachError::InvalidTarget(iface.into()));
+        }
+
+        Ok(Self {
+            iface: iface.into(),
+            iface_id: super::device_index(iface),
+            id,
+            mask,
+            attached: AttachedPrograms::new(),
+            _profile: PhantomData,
+        })
+    }
+
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached (e.g. the controller's receive filters).
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
+    /// Get the interface name.
+    pub fn iface(&self) -> &str {
+        &self.iface
+    }
+
+    /// Get the identifier and mask frames are matched against.
+    pub fn id_filter(&self) -> (u32, u32) {
+        (self.id, self.mask)
+    }
+
+    /// Check if a frame was received on this interface and passes the
+    /// identifier filter.
+    pub fn matches(&self, event: &CanEvent) -> bool {
+        self.event_filter().matches(&super::AttachEvent::Can(event))
+    }
+}
+
+impl<P: PhysicalProfile> AttachPoint<P> for CanAttach<P> {
+    fn attach_type(&self) -> AttachType {
+        AttachType::CanBus
+    }
+
+    fn target(&self) -> &str {
+        &self.iface
+    }
+
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match self.iface_id {
+            Some(iface) => EventFilter::Can {
+                iface,
+                id: self.id,
+                mask: self.mask,
+            },
+            None => EventFilter::Never,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(iface: u32, can_id: u32) -> CanEvent {
+        CanEvent {
+            timestamp: 0,
+            iface,
+            can_id,
+            flags: 0,
+            dlc: 2,
+            data: [0x81, 0x10, 0, 0, 0, 0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn create_can_attach() {
+        let can = CanAttach::<ActiveProfile>::new("can0", 0x080, 0x780).unwrap();
+        assert_eq!(can.iface(), "can0");
+        assert_eq!(can.id_filter(), (0x080, 0x780));
+        assert!(CanAttach::<ActiveProfile>::new("", 0, 0).is_err());
+    }
+
+    #[test]
+    fn can_attach_matches_id_and_mask() {
+        let emcy = CanAttach::<ActiveProfile>::new("can1", 0x080, 0x780).unwrap();
+        assert!(emcy.matches(&frame(1, 0x081)));
+        assert!(emcy.matches(&frame(1, 0x0FF)));
+        assert!(!emcy.matches(&frame(1, 0x181)));
+        assert!(!emcy.matches(&frame(0, 0x081)));
+
+        let all = CanAttach::<ActiveProfile>::new("can0", 0, 0).unwrap();
+        assert!(all.matches(&frame(0, 0x7FF)));
+
+        let unnumbered = CanAttach::<ActiveProfile>::new("vcan", 0, 0).unwrap();
+        assert_eq!(unnumbered.event_filter(), EventFilter::Never);
+    }
+
+    #[test]
+    fn can_event_accessors() {
+        let mut event = frame(0, 0x181);
+        assert_eq!(event.payload(), &[0x81, 0x10]);
+        assert!(!event.is_extended());
+        assert!(!event.is_rtr());
+
+        event.flags = CAN_EVENT_EXTENDED | CAN_EVENT_RTR;
+        event.dlc = 15;
+        assert!(event.is_extended());
+        assert!(event.is_rtr());
+        assert_eq!(event.payload().len(), 8);
+    }
+}
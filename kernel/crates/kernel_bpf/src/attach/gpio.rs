@@ -20,11 +20,15 @@
 
 extern crate alloc;
 
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use super::{AttachError, AttachId, AttachPoint, AttachResult, AttachType};
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
 use crate::bytecode::program::BpfProgram;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
@@ -48,6 +52,11 @@ impl GpioEdge {
             _ => Self::Both,
         }
     }
+
+    /// Check if an event edge (1=rising, 2=falling) triggers this edge type.
+    pub fn matches(self, edge: u32) -> bool {
+        edge & self as u32 != 0
+    }
 }
 
 /// GPIO event structure passed to BPF programs.
@@ -87,14 +96,14 @@ impl GpioEvent {
 pub struct GpioAttach<P: PhysicalProfile = ActiveProfile> {
     /// GPIO chip name (e.g., "gpiochip0")
     chip: String,
+    /// Chip number parsed from the name, as reported in `GpioEvent::chip_id`
+    chip_id: Option<u32>,
     /// GPIO line number
     line: u32,
     /// Edge trigger type
     edge: GpioEdge,
-    /// Attached program IDs
-    attached: Vec<AttachId>,
-    /// Next ID counter
-    next_id: u32,
+    /// Attached programs
+    attached: AttachedPrograms,
     /// Profile marker (using fn pointer for Send + Sync)
     _profile: PhantomData<fn() -> P>,
 }
@@ -108,14 +117,21 @@ impl<P: PhysicalProfile> GpioAttach<P> {
 
         Ok(Self {
             chip: chip.into(),
+            chip_id: super::device_index(chip),
             line,
             edge,
-            attached: Vec::new(),
-            next_id: 1,
+            attached: AttachedPrograms::new(),
             _profile: PhantomData,
         })
     }
 
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached (e.g. the line's edge interrupt).
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
     /// Get the GPIO chip name.
     pub fn chip(&self) -> &str {
         &self.chip
@@ -130,6 +146,13 @@ impl<P: PhysicalProfile> GpioAttach<P> {
     pub fn edge(&self) -> GpioEdge {
         self.edge
     }
+
+    /// Check if an event was raised by this chip, line and edge.
+    pub fn matches(&self, event: &GpioEvent) -> bool {
+        self.chip_id == Some(event.chip_id)
+            && self.line == event.line
+            && self.edge.matches(event.edge)
+    }
 }
 
 impl<P: PhysicalProfile> AttachPoint<P> for GpioAttach<P> {
@@ -141,34 +164,31 @@ impl<P: PhysicalProfile> AttachPoint<P> for GpioAttach<P> {
         &self.chip
     }
 
-    fn attach(&mut self, _program: &BpfProgram<P>) -> AttachResult<AttachId> {
-        let id = AttachId(self.next_id);
-        self.next_id += 1;
-        self.attached.push(id);
-
-        // In a real implementation:
-        // 1. Open the GPIO chip via /dev/gpiochipN
-        // 2. Request the line with edge detection
-        // 3. Register a callback that invokes the BPF program
-
-        Ok(id)
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
     }
 
     fn detach(&mut self, id: AttachId) -> AttachResult<()> {
-        if let Some(idx) = self.attached.iter().position(|&i| i == id) {
-            self.attached.remove(idx);
-            Ok(())
-        } else {
-            Err(AttachError::ResourceNotFound)
-        }
+        self.attached.detach(id)
     }
 
     fn is_attached(&self, id: AttachId) -> bool {
-        self.attached.contains(&id)
+        self.attached.contains(id)
     }
 
     fn attached_ids(&self) -> Vec<AttachId> {
-        self.attached.clone()
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match self.chip_id {
+            Some(chip_id) => EventFilter::Gpio {
+                chip_id,
+                line: self.line,
+                edge: self.edge,
+            },
+            None => EventFilter::Never,
+        }
     }
 }
 
@@ -192,6 +212,30 @@ mod tests {
         assert_eq!(GpioEdge::from_flags(0), GpioEdge::Both);
     }
 
+    #[test]
+    fn gpio_attach_matches_event() {
+        let gpio = GpioAttach::<ActiveProfile>::new("gpiochip0", 17, GpioEdge::Rising).unwrap();
+        let mut event = GpioEvent {
+            timestamp: 0,
+            chip_id: 0,
+            line: 17,
+            edge: 1,
+            value: 1,
+        };
+        assert!(gpio.matches(&event));
+
+        event.edge = 2;
+        assert!(!gpio.matches(&event));
+
+        event.edge = 1;
+        event.line = 18;
+        assert!(!gpio.matches(&event));
+
+        event.line = 17;
+        event.chip_id = 1;
+        assert!(!gpio.matches(&event));
+    }
+
     #[test]
     fn gpio_event_helpers() {
         let rising = GpioEvent {
@@ -0,0 +1,231 @@
+//! High-Resolution Timer Attach Point
+//!
+//! Run BPF programs on a kernel high-resolution timer rather than the
+//! scheduler tick, for control loops that need a fixed rate.
+//!
+//! A timer is identified by a number chosen by userspace and fires every
+//! `period_ns` at `phase_ns` past a multiple of the period on the kernel's
+//! monotonic clock, so loops sharing a period keep a fixed offset to each
+//! other. A timer with no period is one-shot: it only fires when a program
+//! arms it with `bpf_timer_start`.
+//!
+//! Programs receive a [`TimerEvent`] carrying the expected and actual fire
+//! time, from which they can measure their jitter, and the number of periods
+//! missed since the previous expiry.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // 1 kHz control loop on timer 0, 250 us into each period
+//! let config = AttachConfig::hrtimer(0, 1_000_000, 250_000);
+//! let point = HrTimerAttach::new(0, 1_000_000, 250_000)?;
+//! ```
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
+use crate::bytecode::program::BpfProgram;
+use crate::profile::{ActiveProfile, PhysicalProfile};
+
+/// Timer expiry context passed to BPF programs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TimerEvent {
+    /// Time the timer was due, in nanoseconds since boot
+    pub expected_ns: u64,
+    /// Time the programs started, in nanoseconds since boot
+    pub actual_ns: u64,
+    /// Periods missed since the previous expiry
+    pub overruns: u64,
+    /// Period, 0 for one-shot timers
+    pub period_ns: u64,
+    /// Timer number
+    pub timer: u32,
+    /// CPU running the programs
+    pub cpu: u32,
+}
+
+impl TimerEvent {
+    /// Get how late the timer fired.
+    pub fn latency_ns(&self) -> u64 {
+        self.actual_ns.saturating_sub(self.expected_ns)
+    }
+}
+
+/// Get the first expiry of a periodic timer after `now`: the next time that
+/// is `phase_ns` past a multiple of `period_ns`.
+///
+/// Returns `None` for one-shot timers (`period_ns == 0`).
+pub fn first_expiry(period_ns: u64, phase_ns: u64, now: u64) -> Option<u64> {
+    if period_ns == 0 {
+        return None;
+    }
+    if now < phase_ns {
+        return Some(phase_ns);
+    }
+    let periods = (now - phase_ns) / period_ns + 1;
+    Some(phase_ns.saturating_add(periods.saturating_mul(period_ns)))
+}
+
+/// Advance a periodic timer that was due at `deadline` and fires at `now`.
+///
+/// Returns the next deadline after `now`, keeping the timer's phase, and the
+/// number of periods skipped because they were already past.
+pub fn advance_expiry(deadline: u64, period_ns: u64, now: u64) -> (u64, u64) {
+    let overruns = now.saturating_sub(deadline) / period_ns.max(1);
+    let next = deadline.saturating_add((overruns + 1).saturating_mul(period_ns));
+    (next, overruns)
+}
+
+/// High-resolution timer attach point.
+pub struct HrTimerAttach<P: PhysicalProfile = ActiveProfile> {
+    /// `hrtimer<n>`
+    target: String,
+    /// Timer number
+    timer: u32,
+    /// Period, 0 for one-shot
+    period_ns: u64,
+    /// Offset of expiries into the period
+    phase_ns: u64,
+    /// Attached programs
+    attached: AttachedPrograms,
+    /// Profile marker (using fn pointer for Send + Sync)
+    _profile: PhantomData<fn() -> P>,
+}
+
+impl<P: PhysicalProfile> HrTimerAttach<P> {
+    /// Create a timer attach point firing every `period_ns` at `phase_ns`
+    /// into the period, or a one-shot timer if `period_ns` is 0.
+    pub fn new(timer: u32, period_ns: u64, phase_ns: u64) -> AttachResult<Self> {
+        if (period_ns == 0 && phase_ns != 0) || (period_ns != 0 && phase_ns >= period_ns) {
+            return Err(AttachError::InvalidConfig);
+        }
+
+        Ok(Self {
+            target: alloc::format!("hrtimer{}", timer),
+            timer,
+            period_ns,
+            phase_ns,
+            attached: AttachedPrograms::new(),
+            _profile: PhantomData,
+        })
+    }
+
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached (the kernel timer queue entry).
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
+    /// Get the timer number.
+    pub fn timer(&self) -> u32 {
+        self.timer
+    }
+
+    /// Get the period, 0 for one-shot timers.
+    pub fn period_ns(&self) -> u64 {
+        self.period_ns
+    }
+
+    /// Get the offset of expiries into the period.
+    pub fn phase_ns(&self) -> u64 {
+        self.phase_ns
+    }
+}
+
+impl<P: PhysicalProfile> AttachPoint<P> for HrTimerAttach<P> {
+    fn attach_type(&self) -> AttachType {
+        AttachType::PerfEvent
+    }
+
+    fn target(&self) -> &str {
+        &self.target
+    }
+
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        EventFilter::HrTimer { timer: self.timer }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attach::AttachEvent;
+
+    fn event(timer: u32) -> TimerEvent {
+        TimerEvent {
+            expected_ns: 1_000_000,
+            actual_ns: 1_004_000,
+            overruns: 0,
+            period_ns: 1_000_000,
+            timer,
+            cpu: 0,
+        }
+    }
+
+    #[test]
+    fn create_hrtimer_attach() {
+        let hr = HrTimerAttach::<ActiveProfile>::new(2, 1_000_000, 250_000).unwrap();
+        assert_eq!(hr.target(), "hrtimer2");
+        assert_eq!(hr.period_ns(), 1_000_000);
+        assert_eq!(hr.phase_ns(), 250_000);
+
+        assert!(HrTimerAttach::<ActiveProfile>::new(0, 0, 0).is_ok());
+        assert_eq!(
+            HrTimerAttach::<ActiveProfile>::new(0, 1_000, 1_000).err(),
+            Some(AttachError::InvalidConfig)
+        );
+        assert_eq!(
+            HrTimerAttach::<ActiveProfile>::new(0, 0, 5).err(),
+            Some(AttachError::InvalidConfig)
+        );
+    }
+
+    #[test]
+    fn hrtimer_filters_by_timer() {
+        let hr = HrTimerAttach::<ActiveProfile>::new(1, 1_000_000, 0).unwrap();
+        let filter = hr.event_filter();
+        assert!(filter.matches(&AttachEvent::HrTimer(&event(1))));
+        assert!(!filter.matches(&AttachEvent::HrTimer(&event(2))));
+        assert!(!filter.matches(&AttachEvent::Timer));
+        assert_eq!(event(1).latency_ns(), 4_000);
+    }
+
+    #[test]
+    fn expiries_keep_phase() {
+        assert_eq!(first_expiry(0, 0, 123), None);
+        assert_eq!(first_expiry(1_000, 250, 100), Some(250));
+        assert_eq!(first_expiry(1_000, 250, 250), Some(1_250));
+        assert_eq!(first_expiry(1_000, 250, 5_999), Some(6_250));
+
+        // On time
+        assert_eq!(advance_expiry(6_250, 1_000, 6_260), (7_250, 0));
+        // Two periods late: 7_250 and 8_250 were missed
+        assert_eq!(advance_expiry(6_250, 1_000, 8_300), (9_250, 2));
+    }
+}
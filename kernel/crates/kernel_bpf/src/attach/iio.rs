@@ -20,11 +20,15 @@
 
 extern crate alloc;
 
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use super::{AttachError, AttachId, AttachPoint, AttachResult, AttachType};
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
 use crate::bytecode::program::BpfProgram;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
@@ -89,6 +93,67 @@ impl IioChannel {
             }
         }
     }
+
+    /// Numeric channel id, as reported in `IioEvent::channel`.
+    ///
+    /// Voltage channels are numbered from 0x100. Generic channels have no id.
+    pub fn id(&self) -> Option<u32> {
+        let id = match self {
+            Self::AccelX => 0,
+            Self::AccelY => 1,
+            Self::AccelZ => 2,
+            Self::AnglVelX => 3,
+            Self::AnglVelY => 4,
+            Self::AnglVelZ => 5,
+            Self::MagnX => 6,
+            Self::MagnY => 7,
+            Self::MagnZ => 8,
+            Self::Temp => 9,
+            Self::Proximity => 10,
+            Self::Voltage(n) => 0x100 + u32::from(*n),
+            Self::Generic(_) => return None,
+        };
+        Some(id)
+    }
+
+    /// Create from a numeric channel id.
+    pub fn from_id(id: u32) -> Option<Self> {
+        let channel = match id {
+            0 => Self::AccelX,
+            1 => Self::AccelY,
+            2 => Self::AccelZ,
+            3 => Self::AnglVelX,
+            4 => Self::AnglVelY,
+            5 => Self::AnglVelZ,
+            6 => Self::MagnX,
+            7 => Self::MagnY,
+            8 => Self::MagnZ,
+            9 => Self::Temp,
+            10 => Self::Proximity,
+            0x100..=0x1ff => Self::Voltage((id - 0x100) as u8),
+            _ => return None,
+        };
+        Some(channel)
+    }
+
+    /// Canonical sysfs-style name of the channel.
+    pub fn name(&self) -> String {
+        match self {
+            Self::AccelX => "in_accel_x".into(),
+            Self::AccelY => "in_accel_y".into(),
+            Self::AccelZ => "in_accel_z".into(),
+            Self::AnglVelX => "in_anglvel_x".into(),
+            Self::AnglVelY => "in_anglvel_y".into(),
+            Self::AnglVelZ => "in_anglvel_z".into(),
+            Self::MagnX => "in_magn_x".into(),
+            Self::MagnY => "in_magn_y".into(),
+            Self::MagnZ => "in_magn_z".into(),
+            Self::Voltage(n) => alloc::format!("in_voltage{}", n),
+            Self::Temp => "in_temp".into(),
+            Self::Proximity => "in_proximity".into(),
+            Self::Generic(name) => name.clone(),
+        }
+    }
 }
 
 /// IIO event structure passed to BPF programs.
@@ -99,7 +164,7 @@ pub struct IioEvent {
     pub timestamp: u64,
     /// Device ID
     pub device_id: u32,
-    /// Channel type
+    /// Channel id (see [`IioChannel::id`])
     pub channel: u32,
     /// Raw value
     pub value: i32,
@@ -121,14 +186,14 @@ impl IioEvent {
 pub struct IioAttach<P: PhysicalProfile = ActiveProfile> {
     /// Device name (e.g., "iio:device0")
     device: String,
+    /// Device number parsed from the name, as reported in `IioEvent::device_id`
+    device_id: Option<u32>,
     /// Channel name
     channel: String,
     /// Parsed channel type
     channel_type: IioChannel,
-    /// Attached program IDs
-    attached: Vec<AttachId>,
-    /// Next ID counter
-    next_id: u32,
+    /// Attached programs
+    attached: AttachedPrograms,
     /// Profile marker (using fn pointer for Send + Sync)
     _profile: PhantomData<fn() -> P>,
 }
@@ -146,14 +211,21 @@ impl<P: PhysicalProfile> IioAttach<P> {
 
         Ok(Self {
             device: device.into(),
+            device_id: super::device_index(device),
             channel: channel.into(),
             channel_type,
-            attached: Vec::new(),
-            next_id: 1,
+            attached: AttachedPrograms::new(),
             _profile: PhantomData,
         })
     }
 
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached (e.g. capture on the channel).
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
     /// Get the device name.
     pub fn device(&self) -> &str {
         &self.device
@@ -168,6 +240,11 @@ impl<P: PhysicalProfile> IioAttach<P> {
     pub fn channel_type(&self) -> &IioChannel {
         &self.channel_type
     }
+
+    /// Check if an event was raised by this device and channel.
+    pub fn matches(&self, event: &IioEvent) -> bool {
+        self.device_id == Some(event.device_id) && self.channel_type.id() == Some(event.channel)
+    }
 }
 
 impl<P: PhysicalProfile> AttachPoint<P> for IioAttach<P> {
@@ -179,34 +256,27 @@ impl<P: PhysicalProfile> AttachPoint<P> for IioAttach<P> {
         &self.channel
     }
 
-    fn attach(&mut self, _program: &BpfProgram<P>) -> AttachResult<AttachId> {
-        let id = AttachId(self.next_id);
-        self.next_id += 1;
-        self.attached.push(id);
-
-        // In a real implementation:
-        // 1. Open the IIO device
-        // 2. Configure buffered capture for the channel
-        // 3. Register a callback that invokes the BPF program
-
-        Ok(id)
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
     }
 
     fn detach(&mut self, id: AttachId) -> AttachResult<()> {
-        if let Some(idx) = self.attached.iter().position(|&i| i == id) {
-            self.attached.remove(idx);
-            Ok(())
-        } else {
-            Err(AttachError::ResourceNotFound)
-        }
+        self.attached.detach(id)
     }
 
     fn is_attached(&self, id: AttachId) -> bool {
-        self.attached.contains(&id)
+        self.attached.contains(id)
     }
 
     fn attached_ids(&self) -> Vec<AttachId> {
-        self.attached.clone()
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match (self.device_id, self.channel_type.id()) {
+            (Some(device_id), Some(channel)) => EventFilter::Iio { device_id, channel },
+            _ => EventFilter::Never,
+        }
     }
 }
 
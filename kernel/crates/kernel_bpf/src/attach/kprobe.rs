@@ -1,17 +1,164 @@
 //! Kprobe Attach Point
 //!
 //! Kernel probes allow attaching BPF programs to kernel function entry/exit.
+//!
+//! The kernel resolves the function name to an address through its symbol
+//! table and plants a breakpoint there. Events carry that address, and
+//! programs receive a [`PtRegs`] snapshot of the registers at the probe:
+//! the arguments on entry, the return value on return.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let point = KprobeAttach::new("kernel::syscall::dispatch_syscall", KprobeType::Entry)?
+//!     .with_address(addr)
+//!     .with_hardware(breakpoint);
+//! ```
 
 extern crate alloc;
 
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use super::{AttachError, AttachId, AttachPoint, AttachResult, AttachType};
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
 use crate::bytecode::program::BpfProgram;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
+/// Register snapshot at a kprobe, laid out like Linux's x86_64 `pt_regs`.
+///
+/// This is also the trap frame the kernel's breakpoint entry builds, so
+/// writes to `rip`, `rflags` and `rsp` take effect when the trap returns.
+#[cfg(not(target_arch = "aarch64"))]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PtRegs {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    /// Syscall number for syscall entries, otherwise unused
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+impl PtRegs {
+    /// Get the instruction pointer.
+    pub fn ip(&self) -> u64 {
+        self.rip
+    }
+
+    /// Set the instruction pointer.
+    pub fn set_ip(&mut self, ip: u64) {
+        self.rip = ip;
+    }
+
+    /// Get the stack pointer.
+    pub fn sp(&self) -> u64 {
+        self.rsp
+    }
+
+    /// Get the frame pointer.
+    pub fn fp(&self) -> u64 {
+        self.rbp
+    }
+
+    /// Whether the registers were taken in user mode (ring 3).
+    pub fn user_mode(&self) -> bool {
+        self.cs & 3 == 3
+    }
+
+    /// Get integer argument `n` (from 0) of the System V calling convention,
+    /// valid at function entry. Only the six register arguments are
+    /// available.
+    pub fn arg(&self, n: usize) -> Option<u64> {
+        [self.rdi, self.rsi, self.rdx, self.rcx, self.r8, self.r9]
+            .get(n)
+            .copied()
+    }
+
+    /// Get the return value, valid at function return.
+    pub fn return_value(&self) -> u64 {
+        self.rax
+    }
+}
+
+/// Register snapshot at a kprobe, laid out like Linux's arm64
+/// `user_pt_regs`.
+///
+/// This is also the trap frame the kernel's exception entry builds, so
+/// writes to `pc` and `pstate` take effect when the trap returns.
+#[cfg(target_arch = "aarch64")]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PtRegs {
+    /// x0 to x30
+    pub regs: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub pstate: u64,
+}
+
+#[cfg(target_arch = "aarch64")]
+impl PtRegs {
+    /// Get the instruction pointer.
+    pub fn ip(&self) -> u64 {
+        self.pc
+    }
+
+    /// Set the instruction pointer.
+    pub fn set_ip(&mut self, ip: u64) {
+        self.pc = ip;
+    }
+
+    /// Get the stack pointer.
+    pub fn sp(&self) -> u64 {
+        self.sp
+    }
+
+    /// Get the frame pointer.
+    pub fn fp(&self) -> u64 {
+        self.regs[29]
+    }
+
+    /// Whether the registers were taken in user mode (EL0).
+    pub fn user_mode(&self) -> bool {
+        self.pstate & 0xf == 0
+    }
+
+    /// Get integer argument `n` (from 0) of the AAPCS64 calling convention,
+    /// valid at function entry. Only the eight register arguments are
+    /// available.
+    pub fn arg(&self, n: usize) -> Option<u64> {
+        self.regs[..8].get(n).copied()
+    }
+
+    /// Get the return value, valid at function return.
+    pub fn return_value(&self) -> u64 {
+        self.regs[0]
+    }
+}
+
 /// Type of kernel probe.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KprobeType {
@@ -27,16 +174,19 @@ pub struct KprobeAttach<P: PhysicalProfile = ActiveProfile> {
     function: String,
     /// Probe type (entry or return)
     probe_type: KprobeType,
-    /// Attached program IDs
-    attached: Vec<AttachId>,
-    /// Next ID counter
-    next_id: u32,
+    /// Address of the function, as carried by events
+    address: Option<u64>,
+    /// Attached programs
+    attached: AttachedPrograms,
     /// Profile marker (using fn pointer for Send + Sync)
     _profile: PhantomData<fn() -> P>,
 }
 
 impl<P: PhysicalProfile> KprobeAttach<P> {
     /// Create a new kprobe attach point.
+    ///
+    /// It fires on nothing until given the address the function resolves
+    /// to with [`with_address`](Self::with_address).
     pub fn new(function: &str, probe_type: KprobeType) -> AttachResult<Self> {
         if function.is_empty() {
             return Err(AttachError::InvalidTarget(function.into()));
@@ -45,8 +195,8 @@ impl<P: PhysicalProfile> KprobeAttach<P> {
         Ok(Self {
             function: function.into(),
             probe_type,
-            attached: Vec::new(),
-            next_id: 1,
+            address: None,
+            attached: AttachedPrograms::new(),
             _profile: PhantomData,
         })
     }
@@ -60,6 +210,24 @@ impl<P: PhysicalProfile> KprobeAttach<P> {
     pub fn probe_type(&self) -> KprobeType {
         self.probe_type
     }
+
+    /// Set the address of the function.
+    pub fn with_address(mut self, address: u64) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Get the address of the function, if resolved.
+    pub fn address(&self) -> Option<u64> {
+        self.address
+    }
+
+    /// Set the breakpoint that delivers events, armed while programs are
+    /// attached.
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
 }
 
 impl<P: PhysicalProfile> AttachPoint<P> for KprobeAttach<P> {
@@ -74,39 +242,37 @@ impl<P: PhysicalProfile> AttachPoint<P> for KprobeAttach<P> {
         &self.function
     }
 
-    fn attach(&mut self, _program: &BpfProgram<P>) -> AttachResult<AttachId> {
-        let id = AttachId(self.next_id);
-        self.next_id += 1;
-        self.attached.push(id);
-
-        // In a real implementation, this would:
-        // 1. Register the kprobe with the kernel
-        // 2. Associate the BPF program with the probe
-
-        Ok(id)
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
     }
 
     fn detach(&mut self, id: AttachId) -> AttachResult<()> {
-        if let Some(idx) = self.attached.iter().position(|&i| i == id) {
-            self.attached.remove(idx);
-            Ok(())
-        } else {
-            Err(AttachError::ResourceNotFound)
-        }
+        self.attached.detach(id)
     }
 
     fn is_attached(&self, id: AttachId) -> bool {
-        self.attached.contains(&id)
+        self.attached.contains(id)
     }
 
     fn attached_ids(&self) -> Vec<AttachId> {
-        self.attached.clone()
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match self.address {
+            Some(addr) => EventFilter::Kprobe {
+                addr,
+                probe_type: self.probe_type,
+            },
+            None => EventFilter::Never,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::attach::AttachEvent;
 
     #[test]
     fn create_kprobe() {
@@ -127,4 +293,71 @@ mod tests {
         let result = KprobeAttach::<ActiveProfile>::new("", KprobeType::Entry);
         assert!(matches!(result, Err(AttachError::InvalidTarget(_))));
     }
+
+    #[test]
+    fn kprobe_filters_by_address_and_type() {
+        let kprobe = KprobeAttach::<ActiveProfile>::new("sys_write", KprobeType::Entry).unwrap();
+        assert_eq!(kprobe.event_filter(), EventFilter::Never);
+
+        let kprobe = kprobe.with_address(0x1000);
+        assert_eq!(kprobe.address(), Some(0x1000));
+        let filter = kprobe.event_filter();
+        assert!(filter.matches(&AttachEvent::Kprobe {
+            addr: 0x1000,
+            probe_type: KprobeType::Entry,
+        }));
+        assert!(!filter.matches(&AttachEvent::Kprobe {
+            addr: 0x1000,
+            probe_type: KprobeType::Return,
+        }));
+        assert!(!filter.matches(&AttachEvent::Kprobe {
+            addr: 0x2000,
+            probe_type: KprobeType::Entry,
+        }));
+    }
+
+    #[test]
+    fn pt_regs_arguments() {
+        let mut regs = PtRegs::default();
+        #[cfg(not(target_arch = "aarch64"))]
+        {
+            regs.rdi = 1;
+            regs.r9 = 6;
+            regs.rax = 42;
+            assert_eq!(regs.arg(5), Some(6));
+            assert_eq!(regs.arg(6), None);
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            regs.regs[7] = 8;
+            assert_eq!(regs.arg(7), Some(8));
+            assert_eq!(regs.arg(8), None);
+            regs.regs[0] = 42;
+        }
+        assert_eq!(regs.return_value(), 42);
+
+        regs.set_ip(0xdead);
+        assert_eq!(regs.ip(), 0xdead);
+    }
+
+    #[test]
+    fn pt_regs_mode() {
+        let mut regs = PtRegs::default();
+        #[cfg(not(target_arch = "aarch64"))]
+        {
+            regs.cs = 0x08;
+            assert!(!regs.user_mode());
+            regs.cs = 0x23;
+            regs.rbp = 0x7000;
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            regs.pstate = 0x5; // EL1h
+            assert!(!regs.user_mode());
+            regs.pstate = 0;
+            regs.regs[29] = 0x7000;
+        }
+        assert!(regs.user_mode());
+        assert_eq!(regs.fp(), 0x7000);
+    }
 }
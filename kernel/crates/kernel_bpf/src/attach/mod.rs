@@ -30,27 +30,68 @@
 //!
 //! ## PWM Observation
 //! Observe PWM duty cycle changes for motor control tracing.
+//!
+//! ## CAN Bus
+//! Inspect received CAN frames, filtered by identifier, and drop them before
+//! they reach userspace.
+//!
+//! ## I2C and SPI
+//! Trace transactions on I2C and SPI buses and reject writes to configured
+//! register ranges before they reach the device.
+//!
+//! ## Serial
+//! Parse and check frames received on a UART (GPS, lidar, motor drivers),
+//! split from the byte stream by a delimiter, length, SLIP or COBS.
+//!
+//! ## Timer Sampling
+//! Sample the code interrupted by each timer tick, e.g. to profile where
+//! time is spent.
+//!
+//! ## Watchdog Liveness
+//! Decide whether the system is healthy; the kernel only pets the hardware
+//! watchdog while every liveness program returns non-zero.
 
 extern crate alloc;
 
+mod bus;
+mod can;
 mod gpio;
+mod hrtimer;
 mod iio;
 mod kprobe;
+mod perf_event;
 mod pwm;
+mod serial;
 mod tracepoint;
 
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
+use core::ops::RangeInclusive;
 
+pub use bus::{
+    BUS_ADDR_ANY, BUS_DATA_MAX, BUS_EVENT_READ, BUS_REG_NONE, BusAttach, BusEvent, BusFilter,
+};
+pub use can::{CAN_EVENT_EXTENDED, CAN_EVENT_RTR, CanAttach, CanEvent};
 pub use gpio::{GpioAttach, GpioEdge, GpioEvent};
+pub use hrtimer::{HrTimerAttach, TimerEvent, advance_expiry, first_expiry};
 pub use iio::{IioAttach, IioChannel, IioEvent};
-pub use kprobe::{KprobeAttach, KprobeType};
+pub use kprobe::{KprobeAttach, KprobeType, PtRegs};
+pub use perf_event::PerfEventData;
 pub use pwm::{PwmAttach, PwmEvent};
-pub use tracepoint::TracepointAttach;
+pub use serial::{
+    Framing, SERIAL_EVENT_MALFORMED, SERIAL_EVENT_OVERRUN, SERIAL_EVENT_TRUNCATED,
+    SERIAL_FRAME_MAX, SerialAttach, SerialEvent, SerialFramer,
+};
+pub use tracepoint::{
+    BlockRqEvent, IrqEvent, PAGE_FAULT_PROTECTION, PAGE_FAULT_USER, PAGE_FAULT_WRITE,
+    PageFaultEvent, SchedSwitchEvent, SyscallFilterEvent, TaskEvent, TracepointAttach,
+    VFS_PATH_LEN, VfsIoEvent, VfsOpenEvent,
+};
 
-use crate::bytecode::program::BpfProgram;
+use crate::bytecode::program::{BpfProgType, BpfProgram};
+use crate::execution::{SyscallExitContext, SyscallTraceContext};
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
 /// Unique identifier for an attached program.
@@ -96,6 +137,8 @@ pub enum AttachType {
     I2c,
     /// SPI transaction event
     Spi,
+    /// Watchdog liveness check
+    Watchdog,
 }
 
 impl AttachType {
@@ -130,7 +173,43 @@ impl AttachType {
             | Self::Serial
             | Self::CanBus
             | Self::I2c
-            | Self::Spi => true,
+            | Self::Spi
+            | Self::Watchdog => true,
+        }
+    }
+
+    /// Check if programs of the given type may attach here.
+    pub fn accepts(&self, prog_type: BpfProgType) -> bool {
+        // Raw instruction loads have no type and may attach anywhere
+        if prog_type == BpfProgType::Unspec {
+            return true;
+        }
+
+        match self {
+            Self::Kprobe | Self::Kretprobe => prog_type == BpfProgType::Kprobe,
+            Self::Tracepoint | Self::RawTracepoint => prog_type == BpfProgType::Tracepoint,
+            Self::PerfEvent => prog_type == BpfProgType::PerfEvent,
+            Self::Xdp => prog_type == BpfProgType::Xdp,
+            Self::CgroupSkb => prog_type == BpfProgType::CgroupSkb,
+            Self::SocketFilter => prog_type == BpfProgType::SocketFilter,
+            Self::SchedCls => matches!(prog_type, BpfProgType::SchedCls | BpfProgType::SchedAct),
+
+            // Robotics events have no dedicated program type. ELF sections
+            // without a known prefix load as socket filters, so accept those
+            // alongside tracepoint-style and real-time programs.
+            Self::IioSensor
+            | Self::GpioEvent
+            | Self::PwmObserve
+            | Self::Serial
+            | Self::CanBus
+            | Self::I2c
+            | Self::Spi
+            | Self::Watchdog => {
+                matches!(
+                    prog_type,
+                    BpfProgType::Tracepoint | BpfProgType::SocketFilter
+                ) || prog_type.requires_realtime()
+            }
         }
     }
 }
@@ -156,6 +235,8 @@ pub enum AttachError {
     HardwareError,
     /// Invalid configuration
     InvalidConfig,
+    /// Program type cannot attach to this attach type
+    IncompatibleProgram(BpfProgType),
 }
 
 impl core::fmt::Display for AttachError {
@@ -170,6 +251,7 @@ impl core::fmt::Display for AttachError {
             Self::TooManyAttachments => write!(f, "too many attachments"),
             Self::HardwareError => write!(f, "hardware error"),
             Self::InvalidConfig => write!(f, "invalid configuration"),
+            Self::IncompatibleProgram(t) => write!(f, "incompatible program type: {:?}", t),
         }
     }
 }
@@ -177,6 +259,243 @@ impl core::fmt::Display for AttachError {
 /// Result type for attach operations.
 pub type AttachResult<T> = Result<T, AttachError>;
 
+/// Parse the instance number from a device name such as `gpiochip0` or
+/// `iio:device2`.
+///
+/// Hardware events identify their source by number, while attach points are
+/// configured by name; this bridges the two.
+pub(crate) fn device_index(name: &str) -> Option<u32> {
+    let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    name[name.len() - digits..].parse().ok()
+}
+
+/// An event raised by a hook, matched against attach points to select the
+/// programs to run.
+#[derive(Debug, Clone, Copy)]
+pub enum AttachEvent<'a> {
+    /// Periodic timer tick
+    Timer,
+    /// High-resolution timer expiry
+    HrTimer(&'a TimerEvent),
+    /// Syscall entry
+    Syscall(&'a SyscallTraceContext),
+    /// Syscall exit
+    SyscallExit(&'a SyscallExitContext),
+    /// GPIO edge
+    Gpio(&'a GpioEvent),
+    /// IIO sample
+    Iio(&'a IioEvent),
+    /// PWM state change
+    Pwm(&'a PwmEvent),
+    /// CAN frame received
+    Can(&'a CanEvent),
+    /// Serial frame received
+    Serial(&'a SerialEvent),
+    /// I2C transaction
+    I2c(&'a BusEvent),
+    /// SPI transaction
+    Spi(&'a BusEvent),
+    /// Watchdog liveness check
+    Watchdog,
+    /// Kernel tracepoint hit, by tracepoint number
+    Tracepoint { id: u32 },
+    /// Kprobe hit, by function address
+    Kprobe { addr: u64, probe_type: KprobeType },
+}
+
+/// The events an attach point fires on, as a plain value.
+///
+/// Hooks may run in interrupt context while attach points are being changed,
+/// so dispatch tables keep a copy of each attach point's filter rather than a
+/// reference to the attach point itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFilter {
+    /// Fires on nothing
+    Never,
+    /// Every timer tick
+    Timer,
+    /// Expiries of one high-resolution timer
+    HrTimer { timer: u32 },
+    /// Syscall entry; bit `n` selects syscall `n`, and a full mask selects
+    /// every syscall
+    Syscall { mask: u64 },
+    /// Syscall exit, selected like `Syscall`
+    SyscallExit { mask: u64 },
+    /// Edges of one GPIO line
+    Gpio {
+        chip_id: u32,
+        line: u32,
+        edge: GpioEdge,
+    },
+    /// Samples from one IIO channel
+    Iio { device_id: u32, channel: u32 },
+    /// State changes of one PWM channel
+    Pwm { chip_id: u32, channel: u32 },
+    /// Frames on one CAN interface with `can_id & mask == id & mask`
+    Can { iface: u32, id: u32, mask: u32 },
+    /// Frames on one serial port
+    Serial { port: u32 },
+    /// Transactions on one I2C bus
+    I2c(BusFilter),
+    /// Transactions on one SPI bus
+    Spi(BusFilter),
+    /// Watchdog liveness checks
+    Watchdog,
+    /// Hits of one kernel tracepoint
+    Tracepoint { id: u32 },
+    /// Entries to or returns from one kernel function
+    Kprobe { addr: u64, probe_type: KprobeType },
+}
+
+impl EventFilter {
+    /// Check if an event passes this filter.
+    pub fn matches(&self, event: &AttachEvent<'_>) -> bool {
+        match (*self, event) {
+            (Self::Timer, AttachEvent::Timer) => true,
+            (Self::Watchdog, AttachEvent::Watchdog) => true,
+            (Self::HrTimer { timer }, AttachEvent::HrTimer(event)) => timer == event.timer,
+            (Self::Syscall { mask }, AttachEvent::Syscall(trace)) => {
+                syscall_selected(mask, trace.syscall_nr)
+            }
+            (Self::SyscallExit { mask }, AttachEvent::SyscallExit(exit)) => {
+                syscall_selected(mask, exit.syscall_nr)
+            }
+            (
+                Self::Gpio {
+                    chip_id,
+                    line,
+                    edge,
+                },
+                AttachEvent::Gpio(event),
+            ) => chip_id == event.chip_id && line == event.line && edge.matches(event.edge),
+            (Self::Iio { device_id, channel }, AttachEvent::Iio(event)) => {
+                device_id == event.device_id && channel == event.channel
+            }
+            (Self::Pwm { chip_id, channel }, AttachEvent::Pwm(event)) => {
+                chip_id == event.chip_id && channel == event.channel
+            }
+            (Self::Can { iface, id, mask }, AttachEvent::Can(event)) => {
+                iface == event.iface && (event.can_id ^ id) & mask == 0
+            }
+            (Self::Serial { port }, AttachEvent::Serial(event)) => port == event.port,
+            (Self::I2c(filter), AttachEvent::I2c(event))
+            | (Self::Spi(filter), AttachEvent::Spi(event)) => filter.matches(event),
+            (Self::Tracepoint { id }, AttachEvent::Tracepoint { id: hit }) => id == *hit,
+            (
+                Self::Kprobe { addr, probe_type },
+                AttachEvent::Kprobe {
+                    addr: hit,
+                    probe_type: hit_type,
+                },
+            ) => addr == *hit && probe_type == *hit_type,
+            _ => false,
+        }
+    }
+}
+
+/// Check if syscall `nr` is selected by a syscall filter mask.
+pub fn syscall_selected(mask: u64, nr: u64) -> bool {
+    mask == u64::MAX || (nr < 64 && mask & (1 << nr) != 0)
+}
+
+/// Hardware behind an attach point.
+///
+/// Attach points enable their hardware when the first program attaches and
+/// disable it when the last one detaches.
+pub trait AttachHardware: Send + Sync {
+    /// Start delivering events (e.g. unmask an interrupt).
+    fn enable(&mut self) -> AttachResult<()>;
+
+    /// Stop delivering events.
+    fn disable(&mut self);
+}
+
+/// Programs attached to one attach point, and the hardware behind it.
+///
+/// Shared bookkeeping for [`AttachPoint`] implementations.
+pub struct AttachedPrograms {
+    /// Attached program IDs
+    ids: Vec<AttachId>,
+    /// Next ID counter
+    next_id: u32,
+    /// Hardware to configure on first attach and last detach
+    hardware: Option<Box<dyn AttachHardware>>,
+}
+
+impl AttachedPrograms {
+    /// Create an empty set with no hardware.
+    pub fn new() -> Self {
+        Self {
+            ids: Vec::new(),
+            next_id: 1,
+            hardware: None,
+        }
+    }
+
+    /// Set the hardware to configure.
+    pub fn set_hardware(&mut self, hardware: Box<dyn AttachHardware>) {
+        self.hardware = Some(hardware);
+    }
+
+    /// Attach a program, checking that its type fits `attach_type`.
+    pub fn attach<P: PhysicalProfile>(
+        &mut self,
+        attach_type: AttachType,
+        program: &BpfProgram<P>,
+    ) -> AttachResult<AttachId> {
+        if !attach_type.is_available_for_profile::<P>() {
+            return Err(AttachError::NotSupported(attach_type));
+        }
+        if !attach_type.accepts(program.prog_type()) {
+            return Err(AttachError::IncompatibleProgram(program.prog_type()));
+        }
+
+        if self.ids.is_empty()
+            && let Some(hardware) = &mut self.hardware
+        {
+            hardware.enable()?;
+        }
+
+        let id = AttachId(self.next_id);
+        self.next_id += 1;
+        self.ids.push(id);
+        Ok(id)
+    }
+
+    /// Detach a program.
+    pub fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        let idx = self
+            .ids
+            .iter()
+            .position(|&i| i == id)
+            .ok_or(AttachError::ResourceNotFound)?;
+        self.ids.remove(idx);
+
+        if self.ids.is_empty()
+            && let Some(hardware) = &mut self.hardware
+        {
+            hardware.disable();
+        }
+        Ok(())
+    }
+
+    /// Check if a program is attached.
+    pub fn contains(&self, id: AttachId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// Get all attached program IDs.
+    pub fn ids(&self) -> Vec<AttachId> {
+        self.ids.clone()
+    }
+}
+
+impl Default for AttachedPrograms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Trait for attach point implementations.
 pub trait AttachPoint<P: PhysicalProfile = ActiveProfile>: Send + Sync {
     /// Get the attach type.
@@ -196,10 +515,22 @@ pub trait AttachPoint<P: PhysicalProfile = ActiveProfile>: Send + Sync {
 
     /// Get all attached program IDs.
     fn attached_ids(&self) -> Vec<AttachId>;
+
+    /// Get the events this attach point fires on.
+    fn event_filter(&self) -> EventFilter {
+        EventFilter::Never
+    }
+
+    /// Check if an event was raised by this attach point's target.
+    fn matches(&self, event: &AttachEvent<'_>) -> bool {
+        self.event_filter().matches(event)
+    }
 }
 
 /// Configuration for creating attach points.
-#[derive(Debug, Clone)]
+///
+/// Two equal configurations describe the same attach point.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AttachConfig {
     /// Attach type
     pub attach_type: AttachType,
@@ -219,6 +550,15 @@ impl AttachConfig {
         }
     }
 
+    /// Create a kretprobe attach configuration.
+    pub fn kretprobe(function: &str) -> Self {
+        Self {
+            attach_type: AttachType::Kretprobe,
+            target: function.into(),
+            flags: 0,
+        }
+    }
+
     /// Create a tracepoint attach configuration.
     pub fn tracepoint(category: &str, name: &str) -> Self {
         Self {
@@ -237,6 +577,15 @@ impl AttachConfig {
         }
     }
 
+    /// Create a high-resolution timer attach configuration.
+    pub fn hrtimer(timer: u32, period_ns: u64, phase_ns: u64) -> Self {
+        Self {
+            attach_type: AttachType::PerfEvent,
+            target: alloc::format!("hrtimer{}:{}:{}", timer, period_ns, phase_ns),
+            flags: 0,
+        }
+    }
+
     /// Create a GPIO event attach configuration.
     pub fn gpio_event(chip: &str, line: u32, edge: GpioEdge) -> Self {
         Self {
@@ -254,15 +603,68 @@ impl AttachConfig {
             flags: 0,
         }
     }
+
+    /// Create a CAN bus attach configuration for frames with
+    /// `frame_id & mask == id & mask`.
+    pub fn can_bus(iface: &str, id: u32, mask: u32) -> Self {
+        Self {
+            attach_type: AttachType::CanBus,
+            target: alloc::format!("{}:{:#x}:{:#x}", iface, id, mask),
+            flags: 0,
+        }
+    }
+
+    /// Create a serial attach configuration for frames on `port`.
+    pub fn serial(port: &str, framing: Framing) -> Self {
+        Self {
+            attach_type: AttachType::Serial,
+            target: alloc::format!("{}:{:?}", port, framing),
+            flags: framing.flags(),
+        }
+    }
+
+    /// Create an I2C attach configuration for transactions with the target
+    /// at `addr` (or [`BUS_ADDR_ANY`]) on registers `regs`.
+    pub fn i2c(bus: &str, addr: u32, regs: RangeInclusive<u8>) -> Self {
+        Self::bus(AttachType::I2c, bus, addr, regs)
+    }
+
+    /// Create an SPI attach configuration for transactions with the device
+    /// on `chip_select` (or [`BUS_ADDR_ANY`]) on registers `regs`.
+    pub fn spi(bus: &str, chip_select: u32, regs: RangeInclusive<u8>) -> Self {
+        Self::bus(AttachType::Spi, bus, chip_select, regs)
+    }
+
+    fn bus(attach_type: AttachType, bus: &str, addr: u32, regs: RangeInclusive<u8>) -> Self {
+        Self {
+            attach_type,
+            target: alloc::format!("{}:{:#x}:{:#x}-{:#x}", bus, addr, regs.start(), regs.end()),
+            flags: 0,
+        }
+    }
+}
+
+/// A live attach point and the programs attached through it.
+struct AttachEntry<P: PhysicalProfile> {
+    /// Configuration the attach point was created from
+    config: AttachConfig,
+    /// The attach point
+    point: Box<dyn AttachPoint<P>>,
+    /// Manager-wide IDs, paired with the attach point's own IDs
+    links: Vec<(AttachId, AttachId)>,
 }
 
 /// Manager for all attach points.
+///
+/// Programs attached with equal configurations share one attach point, which
+/// lives while at least one program is attached to it. Attach points number
+/// their programs independently, so the manager hands out its own IDs.
 pub struct AttachManager<P: PhysicalProfile = ActiveProfile> {
-    /// Active attachments
-    attachments: Vec<Box<dyn AttachPoint<P>>>,
+    /// Live attach points
+    entries: Vec<AttachEntry<P>>,
     /// Next attachment ID
     next_id: u32,
-    /// Maximum attachments
+    /// Maximum attach points
     max_attachments: usize,
     /// Profile marker
     _profile: PhantomData<P>,
@@ -279,7 +681,7 @@ impl<P: PhysicalProfile> AttachManager<P> {
     /// Create a new attach manager.
     pub fn new() -> Self {
         Self {
-            attachments: Vec::new(),
+            entries: Vec::new(),
             next_id: 1,
             max_attachments: Self::DEFAULT_MAX_ATTACHMENTS,
             _profile: PhantomData,
@@ -287,10 +689,7 @@ impl<P: PhysicalProfile> AttachManager<P> {
     }
 
     /// Create an attach point from configuration.
-    pub fn create_attach_point(
-        &mut self,
-        config: &AttachConfig,
-    ) -> AttachResult<Box<dyn AttachPoint<P>>> {
+    pub fn create_attach_point(config: &AttachConfig) -> AttachResult<Box<dyn AttachPoint<P>>> {
         if !config.attach_type.is_available_for_profile::<P>() {
             return Err(AttachError::NotSupported(config.attach_type));
         }
@@ -339,6 +738,51 @@ impl<P: PhysicalProfile> AttachManager<P> {
                     .map_err(|_| AttachError::InvalidTarget(config.target.clone()))?;
                 Ok(Box::new(PwmAttach::<P>::new(parts[0], channel)?))
             }
+            AttachType::CanBus => {
+                let parts: Vec<&str> = config.target.split(':').collect();
+                let hex = |s: &str| {
+                    u32::from_str_radix(s.trim_start_matches("0x"), 16)
+                        .map_err(|_| AttachError::InvalidTarget(config.target.clone()))
+                };
+                if parts.len() != 3 {
+                    return Err(AttachError::InvalidTarget(config.target.clone()));
+                }
+                Ok(Box::new(CanAttach::<P>::new(
+                    parts[0],
+                    hex(parts[1])?,
+                    hex(parts[2])?,
+                )?))
+            }
+            AttachType::Serial => {
+                let (port, _) = config
+                    .target
+                    .split_once(':')
+                    .ok_or_else(|| AttachError::InvalidTarget(config.target.clone()))?;
+                let framing =
+                    Framing::from_flags(config.flags).ok_or(AttachError::InvalidConfig)?;
+                Ok(Box::new(SerialAttach::<P>::new(port, framing)?))
+            }
+            AttachType::I2c | AttachType::Spi => {
+                let invalid = || AttachError::InvalidTarget(config.target.clone());
+                let hex = |s: &str| u32::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+                let parts: Vec<&str> = config.target.split(':').collect();
+                let [bus, addr, regs] = parts[..] else {
+                    return Err(invalid());
+                };
+                let (first, last) = regs.split_once('-').ok_or_else(invalid)?;
+                let reg = |s| {
+                    hex(s)
+                        .and_then(|r| u8::try_from(r).ok())
+                        .ok_or_else(invalid)
+                };
+                let addr = hex(addr).ok_or_else(invalid)?;
+                Ok(Box::new(BusAttach::<P>::new(
+                    config.attach_type,
+                    bus,
+                    addr,
+                    reg(first)?..=reg(last)?,
+                )?))
+            }
             _ => Err(AttachError::NotSupported(config.attach_type)),
         }
     }
@@ -349,39 +793,127 @@ impl<P: PhysicalProfile> AttachManager<P> {
         config: &AttachConfig,
         program: &BpfProgram<P>,
     ) -> AttachResult<AttachId> {
-        if self.attachments.len() >= self.max_attachments {
-            return Err(AttachError::TooManyAttachments);
-        }
+        self.attach_with(config, program, Self::create_attach_point)
+    }
 
-        let mut attach_point = self.create_attach_point(config)?;
-        let id = attach_point.attach(program)?;
+    /// Attach a program, creating the attach point with `create` if no
+    /// attach point exists for `config` yet.
+    ///
+    /// This lets callers supply attach points the crate does not know about,
+    /// or attach hardware to the built-in ones.
+    pub fn attach_with<F>(
+        &mut self,
+        config: &AttachConfig,
+        program: &BpfProgram<P>,
+        create: F,
+    ) -> AttachResult<AttachId>
+    where
+        F: FnOnce(&AttachConfig) -> AttachResult<Box<dyn AttachPoint<P>>>,
+    {
+        let idx = match self.entries.iter().position(|e| e.config == *config) {
+            Some(idx) => idx,
+            None => {
+                if self.entries.len() >= self.max_attachments {
+                    return Err(AttachError::TooManyAttachments);
+                }
+                self.entries.push(AttachEntry {
+                    config: config.clone(),
+                    point: create(config)?,
+                    links: Vec::new(),
+                });
+                self.entries.len() - 1
+            }
+        };
 
-        self.attachments.push(attach_point);
+        let entry = &mut self.entries[idx];
+        let local_id = match entry.point.attach(program) {
+            Ok(id) => id,
+            Err(e) => {
+                if entry.links.is_empty() {
+                    self.entries.remove(idx);
+                }
+                return Err(e);
+            }
+        };
 
+        let id = self.alloc_id();
+        self.entries[idx].links.push((id, local_id));
         Ok(id)
     }
 
     /// Detach a program by ID.
+    ///
+    /// The attach point is dropped once its last program is detached.
     pub fn detach(&mut self, id: AttachId) -> AttachResult<()> {
-        for attachment in &mut self.attachments {
-            if attachment.is_attached(id) {
-                return attachment.detach(id);
-            }
+        let idx = self
+            .entries
+            .iter()
+            .position(|e| e.links.iter().any(|&(global, _)| global == id))
+            .ok_or(AttachError::ResourceNotFound)?;
+
+        let entry = &mut self.entries[idx];
+        let link = entry.links.iter().position(|&(global, _)| global == id);
+        let (_, local_id) = entry.links.remove(link.unwrap_or_default());
+        let result = entry.point.detach(local_id);
+
+        if entry.links.is_empty() {
+            self.entries.remove(idx);
         }
-        Err(AttachError::ResourceNotFound)
+        result
     }
 
     /// Get all attached program IDs.
     pub fn attached_ids(&self) -> Vec<AttachId> {
-        self.attachments
+        self.entries
             .iter()
-            .flat_map(|a| a.attached_ids())
+            .flat_map(|e| e.links.iter().map(|&(global, _)| global))
             .collect()
     }
 
-    /// Get number of active attachments.
+    /// Get the IDs of programs attached to the target that raised `event`.
+    pub fn matching<'a>(
+        &'a self,
+        event: &'a AttachEvent<'_>,
+    ) -> impl Iterator<Item = AttachId> + 'a {
+        self.entries
+            .iter()
+            .filter(|e| e.point.matches(event))
+            .flat_map(|e| e.links.iter().map(|&(global, _)| global))
+    }
+
+    /// Get the IDs of programs attached through the attach point for
+    /// `config`.
+    pub fn attached_with<'a>(
+        &'a self,
+        config: &'a AttachConfig,
+    ) -> impl Iterator<Item = AttachId> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| e.config == *config)
+            .flat_map(|e| e.links.iter().map(|&(global, _)| global))
+    }
+
+    /// Get every attached program ID with the filter of its attach point.
+    ///
+    /// Dispatch tables are built from this.
+    pub fn hooks(&self) -> impl Iterator<Item = (AttachId, EventFilter)> + '_ {
+        self.entries.iter().flat_map(|e| {
+            let filter = e.point.event_filter();
+            e.links.iter().map(move |&(global, _)| (global, filter))
+        })
+    }
+
+    /// Get the attach point a program was attached through.
+    pub fn attach_point(&self, id: AttachId) -> Option<&dyn AttachPoint<P>> {
+        self.entries
+            .iter()
+            .find(|e| e.links.iter().any(|&(global, _)| global == id))
+            .map(|e| e.point.as_ref())
+    }
+
+    /// Get number of live attach points.
     pub fn attachment_count(&self) -> usize {
-        self.attachments.len()
+        self.entries.len()
     }
 
     /// Allocate a new attach ID.
@@ -409,6 +941,10 @@ mod tests {
         assert!(AttachType::IioSensor.is_available_for_profile::<ActiveProfile>());
         assert!(AttachType::GpioEvent.is_available_for_profile::<ActiveProfile>());
         assert!(AttachType::PwmObserve.is_available_for_profile::<ActiveProfile>());
+        assert!(AttachType::CanBus.is_available_for_profile::<ActiveProfile>());
+        assert!(AttachType::Serial.is_available_for_profile::<ActiveProfile>());
+        assert!(AttachType::I2c.is_available_for_profile::<ActiveProfile>());
+        assert!(AttachType::Spi.is_available_for_profile::<ActiveProfile>());
     }
 
     #[test]
@@ -429,5 +965,239 @@ mod tests {
 
         let pwm = AttachConfig::pwm_observe("pwmchip0", 0);
         assert_eq!(pwm.attach_type, AttachType::PwmObserve);
+
+        let can = AttachConfig::can_bus("can0", 0x080, 0x780);
+        assert_eq!(can.attach_type, AttachType::CanBus);
+        assert_eq!(can.target, "can0:0x80:0x780");
+        let point = AttachManager::<ActiveProfile>::create_attach_point(&can).unwrap();
+        assert_eq!(
+            point.event_filter(),
+            EventFilter::Can {
+                iface: 0,
+                id: 0x080,
+                mask: 0x780
+            }
+        );
+
+        let serial = AttachConfig::serial("uart1", Framing::Cobs);
+        assert_eq!(serial.attach_type, AttachType::Serial);
+        assert_eq!(serial.target, "uart1:Cobs");
+        let point = AttachManager::<ActiveProfile>::create_attach_point(&serial).unwrap();
+        assert_eq!(point.event_filter(), EventFilter::Serial { port: 1 });
+
+        let i2c = AttachConfig::i2c("i2c1", 0x68, 0x6B..=0x6C);
+        assert_eq!(i2c.attach_type, AttachType::I2c);
+        assert_eq!(i2c.target, "i2c1:0x68:0x6b-0x6c");
+        let point = AttachManager::<ActiveProfile>::create_attach_point(&i2c).unwrap();
+        assert_eq!(
+            point.event_filter(),
+            EventFilter::I2c(BusFilter {
+                bus: 1,
+                addr: 0x68,
+                regs: Some((0x6B, 0x6C))
+            })
+        );
+
+        let spi = AttachConfig::spi("spi0", BUS_ADDR_ANY, 0..=u8::MAX);
+        assert_eq!(spi.target, "spi0:0xffffffff:0x0-0xff");
+        let point = AttachManager::<ActiveProfile>::create_attach_point(&spi).unwrap();
+        assert_eq!(point.attach_type(), AttachType::Spi);
+        assert_eq!(
+            point.event_filter(),
+            EventFilter::Spi(BusFilter {
+                bus: 0,
+                addr: BUS_ADDR_ANY,
+                regs: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_device_index() {
+        assert_eq!(device_index("gpiochip0"), Some(0));
+        assert_eq!(device_index("iio:device12"), Some(12));
+        assert_eq!(device_index("pwmchip"), None);
+        assert_eq!(device_index(""), None);
+    }
+
+    fn program(prog_type: BpfProgType) -> BpfProgram<ActiveProfile> {
+        use crate::bytecode::insn::BpfInsn;
+        BpfProgram::new(
+            prog_type,
+            alloc::vec![BpfInsn::mov64_imm(0, 0), BpfInsn::exit()],
+            0,
+        )
+        .unwrap()
+    }
+
+    /// Hardware that records how often it was enabled and disabled.
+    struct CountingHardware(alloc::sync::Arc<core::sync::atomic::AtomicI32>);
+
+    impl AttachHardware for CountingHardware {
+        fn enable(&mut self) -> AttachResult<()> {
+            self.0.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn disable(&mut self) {
+            self.0.fetch_sub(1, core::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn attach_type_accepts_program_types() {
+        assert!(AttachType::GpioEvent.accepts(BpfProgType::Unspec));
+        assert!(AttachType::GpioEvent.accepts(BpfProgType::SocketFilter));
+        assert!(!AttachType::GpioEvent.accepts(BpfProgType::Xdp));
+        assert!(AttachType::Kprobe.accepts(BpfProgType::Kprobe));
+        assert!(!AttachType::Kprobe.accepts(BpfProgType::Tracepoint));
+    }
+
+    #[test]
+    fn attach_rejects_incompatible_program() {
+        let mut manager = AttachManager::<ActiveProfile>::new();
+        let config = AttachConfig::kprobe("sys_write");
+        assert_eq!(
+            manager.attach(&config, &program(BpfProgType::Xdp)),
+            Err(AttachError::IncompatibleProgram(BpfProgType::Xdp))
+        );
+        assert_eq!(manager.attachment_count(), 0);
+    }
+
+    #[test]
+    fn manager_shares_attach_points() {
+        let mut manager = AttachManager::<ActiveProfile>::new();
+        let prog = program(BpfProgType::Unspec);
+        let line17 = AttachConfig::gpio_event("gpiochip0", 17, GpioEdge::Rising);
+        let line18 = AttachConfig::gpio_event("gpiochip0", 18, GpioEdge::Rising);
+
+        let a = manager.attach(&line17, &prog).unwrap();
+        let b = manager.attach(&line17, &prog).unwrap();
+        let c = manager.attach(&line18, &prog).unwrap();
+        assert_eq!(manager.attachment_count(), 2);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+
+        let event = GpioEvent {
+            timestamp: 0,
+            chip_id: 0,
+            line: 17,
+            edge: 1,
+            value: 1,
+        };
+        let event = AttachEvent::Gpio(&event);
+        let matched: Vec<AttachId> = manager.matching(&event).collect();
+        assert_eq!(matched, alloc::vec![a, b]);
+        assert!(manager.matching(&AttachEvent::Timer).next().is_none());
+
+        manager.detach(a).unwrap();
+        manager.detach(b).unwrap();
+        assert_eq!(manager.attachment_count(), 1);
+        assert_eq!(manager.detach(a), Err(AttachError::ResourceNotFound));
+        assert_eq!(manager.attached_ids(), alloc::vec![c]);
+
+        let hooks: Vec<_> = manager.hooks().collect();
+        assert_eq!(
+            hooks,
+            alloc::vec![(
+                c,
+                EventFilter::Gpio {
+                    chip_id: 0,
+                    line: 18,
+                    edge: GpioEdge::Rising,
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn event_filter_matches() {
+        let syscall = |nr| crate::execution::SyscallTraceContext {
+            syscall_nr: nr,
+            arg1: 0,
+            arg2: 0,
+            arg3: 0,
+            arg4: 0,
+            arg5: 0,
+            arg6: 0,
+        };
+        let write = syscall(1);
+        let high = syscall(100);
+
+        let some = EventFilter::Syscall { mask: 1 << 1 };
+        assert!(some.matches(&AttachEvent::Syscall(&write)));
+        assert!(!some.matches(&AttachEvent::Syscall(&high)));
+        assert!(!some.matches(&AttachEvent::Timer));
+
+        let all = EventFilter::Syscall { mask: u64::MAX };
+        assert!(all.matches(&AttachEvent::Syscall(&high)));
+
+        let exit = crate::execution::SyscallExitContext {
+            syscall_nr: 1,
+            ret: -22,
+        };
+        let exits = EventFilter::SyscallExit { mask: 1 << 1 };
+        assert!(exits.matches(&AttachEvent::SyscallExit(&exit)));
+        assert!(!exits.matches(&AttachEvent::Syscall(&write)));
+        assert!(!some.matches(&AttachEvent::SyscallExit(&exit)));
+
+        assert!(EventFilter::Timer.matches(&AttachEvent::Timer));
+        assert!(!EventFilter::Never.matches(&AttachEvent::Timer));
+        assert!(EventFilter::Watchdog.matches(&AttachEvent::Watchdog));
+        assert!(!EventFilter::Timer.matches(&AttachEvent::Watchdog));
+
+        let pwm = PwmEvent {
+            timestamp: 0,
+            chip_id: 1,
+            channel: 2,
+            period_ns: 0,
+            duty_ns: 0,
+            polarity: 0,
+            enabled: 1,
+            old_period_ns: 0,
+            old_duty_ns: 0,
+            old_polarity: 0,
+            old_enabled: 0,
+            last_change: 0,
+        };
+        let filter = EventFilter::Pwm {
+            chip_id: 1,
+            channel: 2,
+        };
+        assert!(filter.matches(&AttachEvent::Pwm(&pwm)));
+        assert!(
+            !EventFilter::Pwm {
+                chip_id: 0,
+                channel: 2
+            }
+            .matches(&AttachEvent::Pwm(&pwm))
+        );
+    }
+
+    #[test]
+    fn hardware_follows_first_attach_and_last_detach() {
+        let enabled = alloc::sync::Arc::new(core::sync::atomic::AtomicI32::new(0));
+        let count = || enabled.load(core::sync::atomic::Ordering::Relaxed);
+
+        let mut manager = AttachManager::<ActiveProfile>::new();
+        let prog = program(BpfProgType::Unspec);
+        let config = AttachConfig::pwm_observe("pwmchip0", 1);
+        let create = |_: &AttachConfig| {
+            let point = PwmAttach::<ActiveProfile>::new("pwmchip0", 1)?
+                .with_hardware(Box::new(CountingHardware(enabled.clone())));
+            Ok(Box::new(point) as Box<dyn AttachPoint<ActiveProfile>>)
+        };
+
+        let a = manager.attach_with(&config, &prog, create).unwrap();
+        assert_eq!(count(), 1);
+        let b = manager
+            .attach_with(&config, &prog, |_| unreachable!("attach point is shared"))
+            .unwrap();
+        assert_eq!(count(), 1);
+
+        manager.detach(a).unwrap();
+        assert_eq!(count(), 1);
+        manager.detach(b).unwrap();
+        assert_eq!(count(), 0);
     }
 }
@@ -0,0 +1,79 @@
+//! Perf Event Sampling Context
+//!
+//! Programs attached to the periodic timer tick (`cpu-clock`) sample
+//! whatever the tick interrupted. They receive a [`PerfEventData`], laid out
+//! like Linux's `bpf_perf_event_data`, so profilers written against Linux
+//! read the same fields:
+//!
+//! ```c
+//! SEC("perf_event")
+//! int sample(struct bpf_perf_event_data *ctx)
+//! {
+//!     u64 ip = PT_REGS_IP(&ctx->regs);
+//!     long stack = bpf_get_stackid(ctx, &stacks, 0);
+//!     ...
+//! }
+//! ```
+
+use super::PtRegs;
+
+/// Sample of the code interrupted by a timer tick.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerfEventData {
+    /// Registers of the interrupted code
+    pub regs: PtRegs,
+    /// Time between samples in nanoseconds
+    pub sample_period: u64,
+    /// Data address of the event; timer samples have none
+    pub addr: u64,
+}
+
+impl PerfEventData {
+    /// Create a sample of `regs`, taken every `sample_period` nanoseconds.
+    pub fn new(regs: PtRegs, sample_period: u64) -> Self {
+        Self {
+            regs,
+            sample_period,
+            addr: 0,
+        }
+    }
+
+    /// View the sample as the bytes programs read.
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: PerfEventData is repr(C) and consists of u64 fields only,
+        // so it has no padding and every byte is initialized.
+        unsafe {
+            core::slice::from_raw_parts(
+                (self as *const Self).cast::<u8>(),
+                core::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::{offset_of, size_of};
+
+    use super::*;
+
+    #[test]
+    fn linux_layout() {
+        assert_eq!(offset_of!(PerfEventData, regs), 0);
+        assert_eq!(
+            offset_of!(PerfEventData, sample_period),
+            size_of::<PtRegs>()
+        );
+        assert_eq!(offset_of!(PerfEventData, addr), size_of::<PtRegs>() + 8);
+    }
+
+    #[test]
+    fn bytes_cover_sample() {
+        let sample = PerfEventData::new(PtRegs::default(), 10_000_000);
+        let bytes = sample.as_bytes();
+        assert_eq!(bytes.len(), size_of::<PerfEventData>());
+        let period = &bytes[size_of::<PtRegs>()..size_of::<PtRegs>() + 8];
+        assert_eq!(period, 10_000_000u64.to_ne_bytes());
+    }
+}
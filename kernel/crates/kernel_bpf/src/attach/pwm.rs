@@ -9,6 +9,14 @@
 //! - Correlate motor commands with sensor readings
 //! - Detect PWM jitter and timing issues
 //! - Profile control loop latency
+//! - Veto motor commands that change too fast
+//!
+//! # Vetoes
+//!
+//! Events are raised before a change is applied and carry both the current
+//! and the requested state of the channel. A program that returns non-zero
+//! vetoes the change, so programs can enforce limits on how far or how fast
+//! a motor command may move. Disabling a channel cannot be vetoed.
 //!
 //! # Example
 //!
@@ -20,15 +28,22 @@
 
 extern crate alloc;
 
+use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use super::{AttachError, AttachId, AttachPoint, AttachResult, AttachType};
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
 use crate::bytecode::program::BpfProgram;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
 /// PWM event structure passed to BPF programs.
+///
+/// `period_ns`, `duty_ns`, `polarity` and `enabled` describe the requested
+/// state, the `old_` fields the state before the change.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct PwmEvent {
@@ -46,6 +61,16 @@ pub struct PwmEvent {
     pub polarity: u32,
     /// Enable state (0 = disabled, 1 = enabled)
     pub enabled: u32,
+    /// Period before the change
+    pub old_period_ns: u32,
+    /// Duty cycle before the change
+    pub old_duty_ns: u32,
+    /// Polarity before the change
+    pub old_polarity: u32,
+    /// Enable state before the change
+    pub old_enabled: u32,
+    /// Timestamp of the previous applied change, 0 if there was none
+    pub last_change: u64,
 }
 
 impl PwmEvent {
@@ -76,18 +101,23 @@ impl PwmEvent {
     pub fn is_inverted(&self) -> bool {
         self.polarity != 0
     }
+
+    /// Change of the duty cycle in nanoseconds.
+    pub fn duty_delta_ns(&self) -> i64 {
+        i64::from(self.duty_ns) - i64::from(self.old_duty_ns)
+    }
 }
 
 /// PWM observation attach point.
 pub struct PwmAttach<P: PhysicalProfile = ActiveProfile> {
     /// PWM chip name (e.g., "pwmchip0")
     chip: String,
+    /// Chip number parsed from the name, as reported in `PwmEvent::chip_id`
+    chip_id: Option<u32>,
     /// PWM channel number
     channel: u32,
-    /// Attached program IDs
-    attached: Vec<AttachId>,
-    /// Next ID counter
-    next_id: u32,
+    /// Attached programs
+    attached: AttachedPrograms,
     /// Profile marker (using fn pointer for Send + Sync)
     _profile: PhantomData<fn() -> P>,
 }
@@ -101,13 +131,20 @@ impl<P: PhysicalProfile> PwmAttach<P> {
 
         Ok(Self {
             chip: chip.into(),
+            chip_id: super::device_index(chip),
             channel,
-            attached: Vec::new(),
-            next_id: 1,
+            attached: AttachedPrograms::new(),
             _profile: PhantomData,
         })
     }
 
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached (e.g. state change reporting).
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
     /// Get the PWM chip name.
     pub fn chip(&self) -> &str {
         &self.chip
@@ -117,6 +154,11 @@ impl<P: PhysicalProfile> PwmAttach<P> {
     pub fn channel(&self) -> u32 {
         self.channel
     }
+
+    /// Check if an event was raised by this controller and channel.
+    pub fn matches(&self, event: &PwmEvent) -> bool {
+        self.chip_id == Some(event.chip_id) && self.channel == event.channel
+    }
 }
 
 impl<P: PhysicalProfile> AttachPoint<P> for PwmAttach<P> {
@@ -128,34 +170,30 @@ impl<P: PhysicalProfile> AttachPoint<P> for PwmAttach<P> {
         &self.chip
     }
 
-    fn attach(&mut self, _program: &BpfProgram<P>) -> AttachResult<AttachId> {
-        let id = AttachId(self.next_id);
-        self.next_id += 1;
-        self.attached.push(id);
-
-        // In a real implementation:
-        // 1. Hook into the PWM subsystem (e.g., via kprobe on pwm_apply_state)
-        // 2. Filter for the specific chip/channel
-        // 3. Invoke the BPF program on each PWM state change
-
-        Ok(id)
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
     }
 
     fn detach(&mut self, id: AttachId) -> AttachResult<()> {
-        if let Some(idx) = self.attached.iter().position(|&i| i == id) {
-            self.attached.remove(idx);
-            Ok(())
-        } else {
-            Err(AttachError::ResourceNotFound)
-        }
+        self.attached.detach(id)
     }
 
     fn is_attached(&self, id: AttachId) -> bool {
-        self.attached.contains(&id)
+        self.attached.contains(id)
     }
 
     fn attached_ids(&self) -> Vec<AttachId> {
-        self.attached.clone()
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match self.chip_id {
+            Some(chip_id) => EventFilter::Pwm {
+                chip_id,
+                channel: self.channel,
+            },
+            None => EventFilter::Never,
+        }
     }
 }
 
@@ -170,6 +208,33 @@ mod tests {
         assert_eq!(pwm.channel(), 0);
     }
 
+    #[test]
+    fn pwm_attach_matches_event() {
+        let pwm = PwmAttach::<ActiveProfile>::new("pwmchip1", 2).unwrap();
+        let mut event = PwmEvent {
+            timestamp: 0,
+            chip_id: 1,
+            channel: 2,
+            period_ns: 1_000_000,
+            duty_ns: 0,
+            polarity: 0,
+            enabled: 1,
+            old_period_ns: 0,
+            old_duty_ns: 0,
+            old_polarity: 0,
+            old_enabled: 0,
+            last_change: 0,
+        };
+        assert!(pwm.matches(&event));
+
+        event.channel = 1;
+        assert!(!pwm.matches(&event));
+
+        event.channel = 2;
+        event.chip_id = 0;
+        assert!(!pwm.matches(&event));
+    }
+
     #[test]
     fn pwm_event_calculations() {
         let event = PwmEvent {
@@ -180,12 +245,18 @@ mod tests {
             duty_ns: 500_000,     // 50% duty
             polarity: 0,
             enabled: 1,
+            old_period_ns: 0,
+            old_duty_ns: 0,
+            old_polarity: 0,
+            old_enabled: 0,
+            last_change: 0,
         };
 
         assert!((event.duty_percent() - 50.0).abs() < 0.001);
         assert!((event.frequency_hz() - 1000.0).abs() < 0.1);
         assert!(event.is_enabled());
         assert!(!event.is_inverted());
+        assert_eq!(event.duty_delta_ns(), 500_000);
     }
 
     #[test]
@@ -199,6 +270,11 @@ mod tests {
             duty_ns: 0,
             polarity: 0,
             enabled: 0,
+            old_period_ns: 0,
+            old_duty_ns: 0,
+            old_polarity: 0,
+            old_enabled: 0,
+            last_change: 0,
         };
 
         assert_eq!(zero_period.duty_percent(), 0.0);
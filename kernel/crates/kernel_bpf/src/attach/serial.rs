@@ -0,0 +1,503 @@
+//! Serial/UART Attach Point
+//!
+//! Attach BPF programs to frames received on a serial port. Programs see
+//! every complete frame, with its bytes in the context, before it is queued
+//! for userspace.
+//!
+//! # Framing
+//!
+//! A [`SerialFramer`] splits the byte stream of a port into frames:
+//!
+//! - [`Framing::Delimiter`]: frames end with a delimiter byte, which is not
+//!   part of the frame (e.g. `\n` for NMEA sentences)
+//! - [`Framing::FixedLength`]: frames are a fixed number of bytes (e.g. lidar
+//!   measurement packets)
+//! - [`Framing::Slip`]: SLIP (RFC 1055) frames, unescaped
+//! - [`Framing::Cobs`]: zero-delimited COBS frames, decoded
+//!
+//! Empty frames are skipped. A program that returns non-zero drops the
+//! frame, so programs can check checksums and ranges in the kernel and keep
+//! malformed data away from userspace.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Check NMEA sentences from a GPS receiver on uart1
+//! let config = AttachConfig::serial("uart1", Framing::Delimiter(b'\n'));
+//! let id = manager.attach(&config, &nmea_program)?;
+//! ```
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use super::{
+    AttachError, AttachHardware, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms,
+    EventFilter,
+};
+use crate::bytecode::program::BpfProgram;
+use crate::profile::{ActiveProfile, PhysicalProfile};
+
+/// Longest frame delivered to programs; longer frames are truncated
+pub const SERIAL_FRAME_MAX: usize = 256;
+
+/// Bytes were lost before or within the frame (`SerialEvent::flags`)
+pub const SERIAL_EVENT_OVERRUN: u32 = 1 << 0;
+
+/// The frame was longer than [`SERIAL_FRAME_MAX`] and was truncated
+/// (`SerialEvent::flags`)
+pub const SERIAL_EVENT_TRUNCATED: u32 = 1 << 1;
+
+/// The frame was not validly encoded, such as an invalid SLIP escape or a
+/// COBS frame cut short (`SerialEvent::flags`)
+pub const SERIAL_EVENT_MALFORMED: u32 = 1 << 2;
+
+/// SLIP frame end
+const SLIP_END: u8 = 0xC0;
+/// SLIP escape
+const SLIP_ESC: u8 = 0xDB;
+/// Escaped `SLIP_END`
+const SLIP_ESC_END: u8 = 0xDC;
+/// Escaped `SLIP_ESC`
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// How a byte stream is split into frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Frames end with this byte
+    Delimiter(u8),
+    /// Frames are this many bytes (1 to [`SERIAL_FRAME_MAX`])
+    FixedLength(u16),
+    /// SLIP (RFC 1055)
+    Slip,
+    /// Consistent Overhead Byte Stuffing, frames end with a zero byte
+    Cobs,
+}
+
+impl Framing {
+    /// Create from flags value: the mode in bits 16-23 (0 delimiter, 1 fixed
+    /// length, 2 SLIP, 3 COBS) and the delimiter byte or frame length in
+    /// bits 0-15.
+    pub fn from_flags(flags: u32) -> Option<Self> {
+        let arg = flags & 0xFFFF;
+        match (flags >> 16) & 0xFF {
+            0 => u8::try_from(arg).ok().map(Self::Delimiter),
+            1 if (1..=SERIAL_FRAME_MAX as u32).contains(&arg) => {
+                Some(Self::FixedLength(arg as u16))
+            }
+            2 => Some(Self::Slip),
+            3 => Some(Self::Cobs),
+            _ => None,
+        }
+    }
+
+    /// Get the flags value, as accepted by [`Framing::from_flags`].
+    pub fn flags(self) -> u32 {
+        match self {
+            Self::Delimiter(byte) => u32::from(byte),
+            Self::FixedLength(len) => 1 << 16 | u32::from(len),
+            Self::Slip => 2 << 16,
+            Self::Cobs => 3 << 16,
+        }
+    }
+}
+
+/// Serial frame event structure passed to BPF programs.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SerialEvent {
+    /// Timestamp in nanoseconds, when the frame was completed
+    pub timestamp: u64,
+    /// Port number (e.g. 0 for uart0)
+    pub port: u32,
+    /// `SERIAL_EVENT_*` flags
+    pub flags: u32,
+    /// Number of frame bytes (0-256)
+    pub len: u32,
+    /// Frame, zero beyond `len`
+    pub data: [u8; SERIAL_FRAME_MAX],
+}
+
+impl SerialEvent {
+    /// Create an event for a frame, truncated to [`SERIAL_FRAME_MAX`] bytes.
+    pub fn new(timestamp: u64, port: u32, frame: &[u8], flags: u32) -> Self {
+        let len = frame.len().min(SERIAL_FRAME_MAX);
+        let mut data = [0; SERIAL_FRAME_MAX];
+        data[..len].copy_from_slice(&frame[..len]);
+        Self {
+            timestamp,
+            port,
+            flags,
+            len: len as u32,
+            data,
+        }
+    }
+
+    /// Get the frame bytes.
+    pub fn payload(&self) -> &[u8] {
+        &self.data[..(self.len as usize).min(SERIAL_FRAME_MAX)]
+    }
+}
+
+/// Splits a byte stream into frames.
+pub struct SerialFramer {
+    framing: Framing,
+    /// Frame being assembled, or the last frame once complete
+    buf: [u8; SERIAL_FRAME_MAX],
+    len: usize,
+    /// `SERIAL_EVENT_*` flags of the frame
+    flags: u32,
+    /// The last frame is complete and is discarded on the next byte
+    complete: bool,
+    /// SLIP: the previous byte was an escape
+    escape: bool,
+    /// COBS: data bytes left in the current block
+    block_left: u8,
+    /// COBS: a zero byte follows the current block unless the frame ends
+    block_zero: bool,
+}
+
+impl SerialFramer {
+    /// Create a framer waiting for the start of a frame.
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            framing,
+            buf: [0; SERIAL_FRAME_MAX],
+            len: 0,
+            flags: 0,
+            complete: false,
+            escape: false,
+            block_left: 0,
+            block_zero: false,
+        }
+    }
+
+    /// Get the framing.
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Note that received bytes were lost, marking the frame in progress.
+    pub fn overrun(&mut self) {
+        self.start();
+        self.flags |= SERIAL_EVENT_OVERRUN;
+    }
+
+    /// Feed one received byte.
+    ///
+    /// Returns the frame and its `SERIAL_EVENT_*` flags if the byte
+    /// completed one.
+    pub fn push(&mut self, byte: u8) -> Option<(&[u8], u32)> {
+        self.start();
+
+        let end = match self.framing {
+            Framing::Delimiter(delimiter) => {
+                if byte == delimiter {
+                    true
+                } else {
+                    self.append(byte);
+                    false
+                }
+            }
+            Framing::FixedLength(len) => {
+                self.append(byte);
+                self.len == usize::from(len)
+            }
+            Framing::Slip => self.push_slip(byte),
+            Framing::Cobs => self.push_cobs(byte),
+        };
+
+        if !end || (self.len == 0 && self.flags == 0) {
+            return None;
+        }
+        self.complete = true;
+        Some((&self.buf[..self.len], self.flags))
+    }
+
+    /// Discard the last frame if it is complete.
+    fn start(&mut self) {
+        if self.complete {
+            self.complete = false;
+            self.len = 0;
+            self.flags = 0;
+        }
+    }
+
+    fn append(&mut self, byte: u8) {
+        match self.buf.get_mut(self.len) {
+            Some(slot) => {
+                *slot = byte;
+                self.len += 1;
+            }
+            None => self.flags |= SERIAL_EVENT_TRUNCATED,
+        }
+    }
+
+    /// Returns whether the byte ended the frame.
+    fn push_slip(&mut self, byte: u8) -> bool {
+        if self.escape {
+            self.escape = false;
+            match byte {
+                SLIP_ESC_END => self.append(SLIP_END),
+                SLIP_ESC_ESC => self.append(SLIP_ESC),
+                SLIP_END => {
+                    self.flags |= SERIAL_EVENT_MALFORMED;
+                    return true;
+                }
+                _ => {
+                    self.flags |= SERIAL_EVENT_MALFORMED;
+                    self.append(byte);
+                }
+            }
+            return false;
+        }
+
+        match byte {
+            SLIP_END => true,
+            SLIP_ESC => {
+                self.escape = true;
+                false
+            }
+            _ => {
+                self.append(byte);
+                false
+            }
+        }
+    }
+
+    /// Returns whether the byte ended the frame.
+    fn push_cobs(&mut self, byte: u8) -> bool {
+        if byte == 0 {
+            if self.block_left != 0 {
+                self.flags |= SERIAL_EVENT_MALFORMED;
+            }
+            self.block_left = 0;
+            self.block_zero = false;
+            return true;
+        }
+
+        if self.block_left == 0 {
+            // A code byte, starting the next block
+            if self.block_zero {
+                self.append(0);
+            }
+            self.block_left = byte - 1;
+            self.block_zero = byte != 0xFF;
+        } else {
+            self.append(byte);
+            self.block_left -= 1;
+        }
+        false
+    }
+}
+
+/// Serial frame attach point.
+pub struct SerialAttach<P: PhysicalProfile = ActiveProfile> {
+    /// Port name (e.g., "uart0")
+    port: String,
+    /// Port number parsed from the name, as reported in `SerialEvent::port`
+    port_id: Option<u32>,
+    /// Framing of the port's byte stream
+    framing: Framing,
+    /// Attached programs
+    attached: AttachedPrograms,
+    /// Profile marker (using fn pointer for Send + Sync)
+    _profile: PhantomData<fn() -> P>,
+}
+
+impl<P: PhysicalProfile> SerialAttach<P> {
+    /// Create a new serial attach point for frames on `port`.
+    pub fn new(port: &str, framing: Framing) -> AttachResult<Self> {
+        if port.is_empty() {
+            return Err(AttachError::InvalidTarget(port.into()));
+        }
+
+        Ok(Self {
+            port: port.into(),
+            port_id: super::device_index(port),
+            framing,
+            attached: AttachedPrograms::new(),
+            _profile: PhantomData,
+        })
+    }
+
+    /// Set the hardware that delivers events, enabled while programs are
+    /// attached (e.g. the port's receive interrupt and framer).
+    pub fn with_hardware(mut self, hardware: Box<dyn AttachHardware>) -> Self {
+        self.attached.set_hardware(hardware);
+        self
+    }
+
+    /// Get the port name.
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// Get the framing.
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Check if a frame was received on this port.
+    pub fn matches(&self, event: &SerialEvent) -> bool {
+        self.event_filter()
+            .matches(&super::AttachEvent::Serial(event))
+    }
+}
+
+impl<P: PhysicalProfile> AttachPoint<P> for SerialAttach<P> {
+    fn attach_type(&self) -> AttachType {
+        AttachType::Serial
+    }
+
+    fn target(&self) -> &str {
+        &self.port
+    }
+
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match self.port_id {
+            Some(port) => EventFilter::Serial { port },
+            None => EventFilter::Never,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(framing: Framing, input: &[u8]) -> Vec<(Vec<u8>, u32)> {
+        let mut framer = SerialFramer::new(framing);
+        input
+            .iter()
+            .filter_map(|&byte| {
+                framer
+                    .push(byte)
+                    .map(|(frame, flags)| (frame.to_vec(), flags))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn framing_flags_round_trip() {
+        for framing in [
+            Framing::Delimiter(b'\n'),
+            Framing::FixedLength(SERIAL_FRAME_MAX as u16),
+            Framing::Slip,
+            Framing::Cobs,
+        ] {
+            assert_eq!(Framing::from_flags(framing.flags()), Some(framing));
+        }
+        assert_eq!(Framing::from_flags(1 << 16), None);
+        assert_eq!(Framing::from_flags(1 << 16 | 257), None);
+        assert_eq!(Framing::from_flags(0x100), None);
+        assert_eq!(Framing::from_flags(4 << 16), None);
+    }
+
+    #[test]
+    fn delimiter_framing() {
+        let out = frames(Framing::Delimiter(b'\n'), b"$GPGGA,1\r\n\n$GPRMC\n$GP");
+        assert_eq!(
+            out,
+            alloc::vec![(b"$GPGGA,1\r".to_vec(), 0), (b"$GPRMC".to_vec(), 0)]
+        );
+    }
+
+    #[test]
+    fn fixed_length_framing() {
+        let out = frames(Framing::FixedLength(3), &[1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(
+            out,
+            alloc::vec![(alloc::vec![1, 2, 3], 0), (alloc::vec![4, 5, 6], 0)]
+        );
+    }
+
+    #[test]
+    fn slip_framing() {
+        let mut input = alloc::vec![SLIP_END, 1];
+        input.extend([SLIP_ESC, SLIP_ESC_END, SLIP_ESC, SLIP_ESC_ESC]);
+        input.extend([2, SLIP_END, 3, SLIP_ESC, 4, SLIP_END]);
+        let out = frames(Framing::Slip, &input);
+        assert_eq!(
+            out,
+            alloc::vec![
+                (alloc::vec![1, SLIP_END, SLIP_ESC, 2], 0),
+                (alloc::vec![3, 4], SERIAL_EVENT_MALFORMED),
+            ]
+        );
+    }
+
+    #[test]
+    fn cobs_framing() {
+        // [0x11, 0x00, 0x00, 0x22] and [] encoded, then a frame cut short
+        let input = [
+            0x02, 0x11, 0x01, 0x02, 0x22, 0x00, 0x01, 0x00, 0x03, 0x33, 0x00,
+        ];
+        let out = frames(Framing::Cobs, &input);
+        assert_eq!(
+            out,
+            alloc::vec![
+                (alloc::vec![0x11, 0x00, 0x00, 0x22], 0),
+                (alloc::vec![0x33], SERIAL_EVENT_MALFORMED),
+            ]
+        );
+
+        // A full 254-byte block has no implied zero
+        let mut input = alloc::vec![0xFF];
+        input.extend(1..=254u8);
+        input.extend([0x02, 0x55, 0x00]);
+        let out = frames(Framing::Cobs, &input);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].0.len(), 255);
+        assert_eq!(out[0].0[253..], [254, 0x55]);
+    }
+
+    #[test]
+    fn long_frames_truncated_and_overruns_flagged() {
+        let mut framer = SerialFramer::new(Framing::Delimiter(0));
+        for _ in 0..SERIAL_FRAME_MAX + 10 {
+            assert!(framer.push(0xAA).is_none());
+        }
+        let (frame, flags) = framer.push(0).unwrap();
+        assert_eq!(frame.len(), SERIAL_FRAME_MAX);
+        assert_eq!(flags, SERIAL_EVENT_TRUNCATED);
+
+        framer.overrun();
+        let (frame, flags) = framer.push(0).unwrap();
+        assert!(frame.is_empty());
+        assert_eq!(flags, SERIAL_EVENT_OVERRUN);
+        assert!(framer.push(0).is_none());
+    }
+
+    #[test]
+    fn serial_attach_matches_port() {
+        let gps = SerialAttach::<ActiveProfile>::new("uart1", Framing::Delimiter(b'\n')).unwrap();
+        assert_eq!(gps.port(), "uart1");
+        assert_eq!(gps.framing(), Framing::Delimiter(b'\n'));
+        assert!(gps.matches(&SerialEvent::new(0, 1, b"$GP", 0)));
+        assert!(!gps.matches(&SerialEvent::new(0, 0, b"$GP", 0)));
+        assert!(SerialAttach::<ActiveProfile>::new("", Framing::Slip).is_err());
+
+        let event = SerialEvent::new(0, 1, &[7; SERIAL_FRAME_MAX + 1], 0);
+        assert_eq!(event.payload().len(), SERIAL_FRAME_MAX);
+    }
+}
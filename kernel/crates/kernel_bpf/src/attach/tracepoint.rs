@@ -1,6 +1,19 @@
 //! Tracepoint Attach Point
 //!
 //! Static kernel tracepoints for observability.
+//!
+//! The kernel keeps a registry of its tracepoints, each named
+//! `category:name` and numbered. An attach point is created by name and
+//! given the number the name resolves to, which is what events carry.
+//! Programs receive the context struct of their tracepoint, defined here.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // Watch context switches
+//! let config = AttachConfig::tracepoint("sched", "sched_switch");
+//! let point = TracepointAttach::new("sched", "sched_switch")?.with_id(id);
+//! ```
 
 extern crate alloc;
 
@@ -8,26 +21,187 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use super::{AttachError, AttachId, AttachPoint, AttachResult, AttachType};
+use super::{
+    AttachError, AttachId, AttachPoint, AttachResult, AttachType, AttachedPrograms, EventFilter,
+};
 use crate::bytecode::program::BpfProgram;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
+/// `sched:sched_switch` context: the CPU switched tasks.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SchedSwitchEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Process of the task switched away from
+    pub prev_pid: u64,
+    /// Task switched away from
+    pub prev_tid: u64,
+    /// Process of the task switched to
+    pub next_pid: u64,
+    /// Task switched to
+    pub next_tid: u64,
+    /// CPU switching tasks
+    pub cpu: u32,
+    /// 1 if the previous task is exiting
+    pub prev_exiting: u32,
+}
+
+/// `sched:task_create` and `sched:task_exit` context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TaskEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Process the task belongs to
+    pub pid: u64,
+    /// Task created or exiting
+    pub tid: u64,
+}
+
+/// Fault was a write (`PageFaultEvent::flags`)
+pub const PAGE_FAULT_WRITE: u32 = 1 << 0;
+
+/// Fault happened in user mode (`PageFaultEvent::flags`)
+pub const PAGE_FAULT_USER: u32 = 1 << 1;
+
+/// Page was mapped, so the access broke its permissions
+/// (`PageFaultEvent::flags`)
+pub const PAGE_FAULT_PROTECTION: u32 = 1 << 2;
+
+/// `mm:page_fault` context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PageFaultEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Faulting address
+    pub address: u64,
+    /// Address of the faulting instruction
+    pub ip: u64,
+    /// Process of the faulting task, 0 before multitasking
+    pub pid: u64,
+    /// `PAGE_FAULT_*` flags
+    pub flags: u32,
+    /// CPU that faulted
+    pub cpu: u32,
+}
+
+/// Bytes of the path kept in `VfsOpenEvent`
+pub const VFS_PATH_LEN: usize = 64;
+
+/// `vfs:vfs_open` context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VfsOpenEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Process opening the file
+    pub pid: u64,
+    /// New file descriptor
+    pub fd: i32,
+    /// Length of the full path
+    pub path_len: u32,
+    /// Path, truncated to `VFS_PATH_LEN` bytes and zero padded
+    pub path: [u8; VFS_PATH_LEN],
+}
+
+impl VfsOpenEvent {
+    /// Get the recorded part of the path.
+    pub fn path(&self) -> &[u8] {
+        &self.path[..(self.path_len as usize).min(VFS_PATH_LEN)]
+    }
+}
+
+/// `vfs:vfs_read` and `vfs:vfs_write` context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct VfsIoEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Process doing the I/O
+    pub pid: u64,
+    /// File offset
+    pub offset: u64,
+    /// Bytes requested
+    pub count: u64,
+    /// Bytes transferred, or -1 on error
+    pub ret: i64,
+    /// File descriptor
+    pub fd: i32,
+    pub _reserved: u32,
+}
+
+/// `block:block_rq_issue` and `block:block_rq_complete` context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BlockRqEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Block device
+    pub device: u64,
+    /// First sector
+    pub sector: u64,
+    /// Time since the request was issued, 0 on issue
+    pub latency_ns: u64,
+    /// Number of sectors
+    pub nr_sectors: u32,
+    /// 1 for writes, 0 for reads
+    pub write: u32,
+    /// 0 on success, -1 on error; always 0 on issue
+    pub error: i32,
+    pub _reserved: u32,
+}
+
+/// `irq:irq_entry` and `irq:irq_exit` context.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IrqEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Time spent in the handler, 0 on entry
+    pub duration_ns: u64,
+    /// Interrupt number (vector on x86_64, GIC interrupt ID on aarch64)
+    pub irq: u32,
+    /// CPU handling the interrupt
+    pub cpu: u32,
+}
+
+/// `syscalls:filter_trace` context: a syscall filter asked for a syscall to
+/// be traced.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallFilterEvent {
+    /// Timestamp in nanoseconds
+    pub timestamp: u64,
+    /// Process making the syscall
+    pub pid: u64,
+    /// Syscall number
+    pub syscall_nr: u64,
+    /// Data bits of the verdict
+    pub data: u32,
+    pub _reserved: u32,
+}
+
 /// Tracepoint attach point.
 pub struct TracepointAttach<P: PhysicalProfile = ActiveProfile> {
-    /// Tracepoint category (e.g., "syscalls", "sched")
-    category: String,
-    /// Tracepoint name (e.g., "sys_enter_write")
-    name: String,
-    /// Attached program IDs
-    attached: Vec<AttachId>,
-    /// Next ID counter
-    next_id: u32,
+    /// `category:name`
+    target: String,
+    /// Length of the category in `target`
+    category_len: usize,
+    /// Number of the kernel tracepoint `target` names, as carried by events
+    id: Option<u32>,
+    /// Attached programs
+    attached: AttachedPrograms,
     /// Profile marker (using fn pointer for Send + Sync)
     _profile: PhantomData<fn() -> P>,
 }
 
 impl<P: PhysicalProfile> TracepointAttach<P> {
     /// Create a new tracepoint attach point.
+    ///
+    /// It fires on nothing until given the number of the kernel tracepoint
+    /// with [`with_id`](Self::with_id).
     pub fn new(category: &str, name: &str) -> AttachResult<Self> {
         if category.is_empty() || name.is_empty() {
             return Err(AttachError::InvalidTarget(alloc::format!(
@@ -36,22 +210,33 @@ impl<P: PhysicalProfile> TracepointAttach<P> {
         }
 
         Ok(Self {
-            category: category.into(),
-            name: name.into(),
-            attached: Vec::new(),
-            next_id: 1,
+            target: alloc::format!("{}:{}", category, name),
+            category_len: category.len(),
+            id: None,
+            attached: AttachedPrograms::new(),
             _profile: PhantomData,
         })
     }
 
+    /// Set the number of the kernel tracepoint this attach point names.
+    pub fn with_id(mut self, id: u32) -> Self {
+        self.id = Some(id);
+        self
+    }
+
     /// Get the category.
     pub fn category(&self) -> &str {
-        &self.category
+        &self.target[..self.category_len]
     }
 
     /// Get the tracepoint name.
     pub fn name(&self) -> &str {
-        &self.name
+        &self.target[self.category_len + 1..]
+    }
+
+    /// Get the number of the kernel tracepoint, if resolved.
+    pub fn id(&self) -> Option<u32> {
+        self.id
     }
 }
 
@@ -61,44 +246,72 @@ impl<P: PhysicalProfile> AttachPoint<P> for TracepointAttach<P> {
     }
 
     fn target(&self) -> &str {
-        // Return category:name format
-        // Note: this allocates, consider caching if performance matters
-        &self.name
+        &self.target
     }
 
-    fn attach(&mut self, _program: &BpfProgram<P>) -> AttachResult<AttachId> {
-        let id = AttachId(self.next_id);
-        self.next_id += 1;
-        self.attached.push(id);
-        Ok(id)
+    fn attach(&mut self, program: &BpfProgram<P>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
     }
 
     fn detach(&mut self, id: AttachId) -> AttachResult<()> {
-        if let Some(idx) = self.attached.iter().position(|&i| i == id) {
-            self.attached.remove(idx);
-            Ok(())
-        } else {
-            Err(AttachError::ResourceNotFound)
-        }
+        self.attached.detach(id)
     }
 
     fn is_attached(&self, id: AttachId) -> bool {
-        self.attached.contains(&id)
+        self.attached.contains(id)
     }
 
     fn attached_ids(&self) -> Vec<AttachId> {
-        self.attached.clone()
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        match self.id {
+            Some(id) => EventFilter::Tracepoint { id },
+            None => EventFilter::Never,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::attach::AttachEvent;
 
     #[test]
     fn create_tracepoint() {
         let tp = TracepointAttach::<ActiveProfile>::new("syscalls", "sys_enter_write").unwrap();
         assert_eq!(tp.category(), "syscalls");
         assert_eq!(tp.name(), "sys_enter_write");
+        assert_eq!(tp.target(), "syscalls:sys_enter_write");
+    }
+
+    #[test]
+    fn tracepoint_filters_by_id() {
+        let tp = TracepointAttach::<ActiveProfile>::new("sched", "sched_switch").unwrap();
+        assert_eq!(tp.event_filter(), EventFilter::Never);
+
+        let tp = tp.with_id(3);
+        assert_eq!(tp.id(), Some(3));
+        let filter = tp.event_filter();
+        assert!(filter.matches(&AttachEvent::Tracepoint { id: 3 }));
+        assert!(!filter.matches(&AttachEvent::Tracepoint { id: 4 }));
+        assert!(!filter.matches(&AttachEvent::Timer));
+    }
+
+    #[test]
+    fn vfs_open_event_path() {
+        let mut event = VfsOpenEvent {
+            timestamp: 0,
+            pid: 1,
+            fd: 3,
+            path_len: 8,
+            path: [0; VFS_PATH_LEN],
+        };
+        event.path[..8].copy_from_slice(b"/dev/pwm");
+        assert_eq!(event.path(), b"/dev/pwm");
+
+        event.path_len = 200;
+        assert_eq!(event.path().len(), VFS_PATH_LEN);
     }
 }
@@ -58,6 +58,10 @@ pub enum BpfProgType {
     /// SK_SKB programs
     SkSkb = 14,
 
+    // Kernel-specific program types
+    /// Per-process syscall filters, returning a verdict for each syscall
+    SyscallFilter = 300,
+
     // Profile-specific program types
     /// Real-time programs (embedded only)
     #[cfg(feature = "embedded-profile")]
@@ -109,7 +113,8 @@ impl BpfProgType {
             | Self::LwtOut
             | Self::LwtXmit
             | Self::SockOps
-            | Self::SkSkb => true,
+            | Self::SkSkb
+            | Self::SyscallFilter => true,
 
             #[cfg(feature = "embedded-profile")]
             Self::RealTime | Self::DeadlineCritical => true,
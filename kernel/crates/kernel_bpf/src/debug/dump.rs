@@ -0,0 +1,164 @@
+//! Program Dump
+//!
+//! Renders a loaded program as text: the translated BPF instructions, the
+//! native code the JIT produced for each of them, and the C source line
+//! they came from.
+//!
+//! ```text
+//! ; prog.c:12:9
+//! ; if (ev->line == BUTTON_PIN)
+//!    1: 61 12 0c 00 00 00 00 00  op=0x61 dst=r2 src=r1 off=12 imm=0
+//!       +0x0024: b9400c22
+//! ```
+
+extern crate alloc;
+
+use core::fmt::{self, Write};
+
+use super::line_info::LineTable;
+use crate::bytecode::insn::BpfInsn;
+
+/// Native code produced by a JIT compiler, with per-instruction offsets.
+#[derive(Debug, Clone, Copy)]
+pub struct JitImage<'a> {
+    /// Native machine code
+    pub code: &'a [u8],
+    /// Code offset of each BPF instruction (indexed by instruction)
+    pub insn_offsets: &'a [usize],
+}
+
+impl JitImage<'_> {
+    /// Code range of a BPF instruction.
+    fn range(&self, insn_idx: usize) -> Option<(usize, usize)> {
+        let start = *self.insn_offsets.get(insn_idx)?;
+        let end = self
+            .insn_offsets
+            .get(insn_idx + 1)
+            .copied()
+            .unwrap_or(self.code.len());
+        Some((start, end.min(self.code.len())))
+    }
+}
+
+/// Write native code as 32-bit words, one per line.
+fn dump_native<W: Write>(out: &mut W, code: &[u8], start: usize, end: usize) -> fmt::Result {
+    let mut offset = start;
+    while offset + 4 <= end {
+        let word = u32::from_le_bytes([
+            code[offset],
+            code[offset + 1],
+            code[offset + 2],
+            code[offset + 3],
+        ]);
+        writeln!(out, "      +{:#06x}: {:08x}", offset, word)?;
+        offset += 4;
+    }
+    Ok(())
+}
+
+/// Dump a program with optional source lines and JIT code.
+pub fn dump_program<W: Write>(
+    out: &mut W,
+    insns: &[BpfInsn],
+    lines: Option<&LineTable>,
+    jit: Option<JitImage<'_>>,
+) -> fmt::Result {
+    if let Some(jit) = jit
+        && let Some(&first) = jit.insn_offsets.first()
+        && first > 0
+    {
+        writeln!(out, "; prologue")?;
+        dump_native(out, jit.code, 0, first)?;
+    }
+
+    for (idx, insn) in insns.iter().enumerate() {
+        if let Some(line) = lines.and_then(|l| l.line_starting_at(idx)) {
+            writeln!(out, "; {}", line)?;
+            if !line.text.is_empty() {
+                writeln!(out, "; {}", line.text)?;
+            }
+        }
+
+        let prev_is_wide = idx > 0 && insns[idx - 1].is_wide();
+        write!(
+            out,
+            "{:4}: {:02x} {:02x} {:02x} {:02x} {:02x} {:02x} {:02x} {:02x}",
+            idx,
+            insn.opcode,
+            insn.regs,
+            insn.offset.to_le_bytes()[0],
+            insn.offset.to_le_bytes()[1],
+            insn.imm.to_le_bytes()[0],
+            insn.imm.to_le_bytes()[1],
+            insn.imm.to_le_bytes()[2],
+            insn.imm.to_le_bytes()[3],
+        )?;
+        if insn.is_wide() && idx + 1 < insns.len() {
+            let imm64 = (insn.imm as u32 as u64) | ((insns[idx + 1].imm as u32 as u64) << 32);
+            writeln!(out, "  lddw r{}, {:#x}", insn.dst_reg(), imm64)?;
+        } else if prev_is_wide {
+            writeln!(out)?;
+        } else {
+            writeln!(out, "  {}", insn)?;
+        }
+
+        if let Some(jit) = jit
+            && let Some((start, end)) = jit.range(idx)
+        {
+            dump_native(out, jit.code, start, end)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::bytecode::insn::WideInsn;
+    use crate::debug::line_info::SourceLine;
+
+    #[test]
+    fn dump_interleaves_source_and_jit() {
+        let insns = [BpfInsn::mov64_imm(0, 1), BpfInsn::exit()];
+
+        let mut lines = LineTable::new();
+        lines.insert(
+            0,
+            SourceLine {
+                file: "prog.c".into(),
+                line: 3,
+                column: 5,
+                text: "return 1;".into(),
+            },
+        );
+
+        let code = [0u8; 16];
+        let jit = JitImage {
+            code: &code,
+            insn_offsets: &[4, 8],
+        };
+
+        let mut out = String::new();
+        dump_program(&mut out, &insns, Some(&lines), Some(jit)).unwrap();
+
+        let expected = "; prologue\n      +0x0000: 00000000\n\
+                        ; prog.c:3:5\n; return 1;\n\
+                        \x20  0: b7 00 00 00 01 00 00 00  mov r0, 1\n      +0x0004: 00000000\n\
+                        \x20  1: 95 00 00 00 00 00 00 00  exit\n      +0x0008: 00000000\n      +0x000c: 00000000\n";
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn dump_without_debug_info() {
+        let wide = WideInsn::ld_dw_imm(1, 0x1_0000_0002);
+        let insns = [wide.insn, wide.next, BpfInsn::exit()];
+
+        let mut out = String::new();
+        dump_program(&mut out, &insns, None, None).unwrap();
+        assert!(out.contains("lddw r1, 0x100000002"));
+        assert!(out.contains("   2: 95"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Instruction to Source Line Mapping
+//!
+//! A [`LineTable`] maps instruction indices to the C source line they were
+//! compiled from. Each record covers every instruction up to the next
+//! record, matching how clang emits BTF line info.
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::loader::{BpfLineInfo, Btf, LoadError, LoadResult};
+
+/// A resolved source location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+    /// Source file name
+    pub file: String,
+    /// Line number (1-based, 0 if unknown)
+    pub line: u32,
+    /// Column number (1-based, 0 if unknown)
+    pub column: u32,
+    /// Source text of the line
+    pub text: String,
+}
+
+impl fmt::Display for SourceLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if self.column != 0 {
+            write!(f, ":{}", self.column)?;
+        }
+        Ok(())
+    }
+}
+
+/// Mapping from instruction index to source line.
+#[derive(Debug, Clone, Default)]
+pub struct LineTable {
+    /// Entries sorted by instruction index
+    entries: Vec<(usize, SourceLine)>,
+}
+
+impl LineTable {
+    /// Create an empty line table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a line table from BTF line info records.
+    ///
+    /// Records must use instruction indices in `insn_off` and refer to
+    /// strings in `btf`.
+    pub fn from_btf(btf: &Btf, records: &[BpfLineInfo]) -> LoadResult<Self> {
+        let mut table = Self::new();
+
+        for record in records {
+            let file = btf
+                .string(record.file_name_off)
+                .ok_or(LoadError::BtfError)?;
+            let text = btf.string(record.line_off).ok_or(LoadError::BtfError)?;
+
+            table.insert(
+                record.insn_off as usize,
+                SourceLine {
+                    file: file.to_string(),
+                    line: record.line(),
+                    column: record.column(),
+                    text: text.trim().to_string(),
+                },
+            );
+        }
+
+        Ok(table)
+    }
+
+    /// Insert a source line starting at an instruction index.
+    ///
+    /// A later insert for the same index replaces the earlier one.
+    pub fn insert(&mut self, insn_idx: usize, line: SourceLine) {
+        match self
+            .entries
+            .binary_search_by_key(&insn_idx, |(idx, _)| *idx)
+        {
+            Ok(pos) => self.entries[pos].1 = line,
+            Err(pos) => self.entries.insert(pos, (insn_idx, line)),
+        }
+    }
+
+    /// Look up the source line covering an instruction.
+    pub fn lookup(&self, insn_idx: usize) -> Option<&SourceLine> {
+        let pos = match self
+            .entries
+            .binary_search_by_key(&insn_idx, |(idx, _)| *idx)
+        {
+            Ok(pos) => pos,
+            Err(0) => return None,
+            Err(pos) => pos - 1,
+        };
+        Some(&self.entries[pos].1)
+    }
+
+    /// Get the source line that starts exactly at an instruction.
+    ///
+    /// Used when interleaving source with disassembly, so each line is
+    /// printed once.
+    pub fn line_starting_at(&self, insn_idx: usize) -> Option<&SourceLine> {
+        self.entries
+            .binary_search_by_key(&insn_idx, |(idx, _)| *idx)
+            .ok()
+            .map(|pos| &self.entries[pos].1)
+    }
+
+    /// Number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Annotate an error with the source line of the faulting instruction.
+    pub fn annotate<'a, E: fmt::Display>(
+        &'a self,
+        error: &'a E,
+        insn_idx: Option<usize>,
+    ) -> Annotated<'a, E> {
+        Annotated {
+            error,
+            source: insn_idx.and_then(|idx| self.lookup(idx)),
+        }
+    }
+}
+
+/// An error annotated with its source location.
+///
+/// Displays as the error followed by the file, line and source text when
+/// the location is known, or as the bare error otherwise.
+pub struct Annotated<'a, E> {
+    /// The underlying error
+    error: &'a E,
+    /// Source line of the faulting instruction
+    source: Option<&'a SourceLine>,
+}
+
+impl<E> Annotated<'_, E> {
+    /// Get the resolved source line, if any.
+    pub fn source(&self) -> Option<&SourceLine> {
+        self.source
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Annotated<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(source) = self.source {
+            write!(f, "\n  at {}", source)?;
+            if !source.text.is_empty() {
+                write!(f, "\n  | {}", source.text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+    use crate::execution::BpfError;
+
+    fn sample_table() -> LineTable {
+        let btf = Btf::from_strings(b"\0prog.c\0int x = 1;\0return x / y;\0".to_vec());
+        let records = [
+            BpfLineInfo {
+                insn_off: 0,
+                file_name_off: 1,
+                line_off: 8,
+                line_col: (3 << 10) | 5,
+            },
+            BpfLineInfo {
+                insn_off: 2,
+                file_name_off: 1,
+                line_off: 19,
+                line_col: 4 << 10,
+            },
+        ];
+        LineTable::from_btf(&btf, &records).expect("valid records")
+    }
+
+    #[test]
+    fn lookup_covers_following_insns() {
+        let table = sample_table();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.lookup(0).map(|l| l.line), Some(3));
+        assert_eq!(table.lookup(1).map(|l| l.line), Some(3));
+        assert_eq!(table.lookup(2).map(|l| l.line), Some(4));
+        assert_eq!(table.lookup(9).map(|l| l.line), Some(4));
+        assert!(table.line_starting_at(1).is_none());
+    }
+
+    #[test]
+    fn annotate_error() {
+        let table = sample_table();
+        let err = BpfError::DivisionByZero;
+
+        let text = format!("{}", table.annotate(&err, Some(2)));
+        assert_eq!(text, "division by zero\n  at prog.c:4\n  | return x / y;");
+
+        let bare = format!("{}", table.annotate(&err, None));
+        assert_eq!(bare, "division by zero");
+    }
+
+    #[test]
+    fn reject_bad_string_offset() {
+        let btf = Btf::from_strings(b"\0".to_vec());
+        let records = [BpfLineInfo {
+            insn_off: 0,
+            file_name_off: 40,
+            line_off: 0,
+            line_col: 0,
+        }];
+        assert!(LineTable::from_btf(&btf, &records).is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! Debugging Support
+//!
+//! Source-level debugging aids built on BTF line info:
+//!
+//! - [`LineTable`] maps instruction indices to C source lines, and annotates
+//!   verifier and runtime errors with the location of the faulting instruction.
+//! - [`dump_program`] renders translated and JIT-compiled code interleaved
+//!   with the source lines they were compiled from.
+
+mod dump;
+mod line_info;
+
+pub use dump::{JitImage, dump_program};
+pub use line_info::{Annotated, LineTable, SourceLine};
@@ -14,9 +14,10 @@
 extern crate alloc;
 
 use alloc::vec;
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 
-use super::{BpfContext, BpfError, BpfExecutor, BpfResult};
+use super::{BpfContext, BpfError, BpfExecutor, BpfFault, BpfResult};
 use crate::bytecode::insn::BpfInsn;
 use crate::bytecode::opcode::{AluOp, JmpOp, MemSize, OpcodeClass, SourceType};
 use crate::bytecode::program::BpfProgram;
@@ -28,14 +29,44 @@ use crate::profile::{ActiveProfile, PhysicalProfile};
 unsafe extern "C" {
     fn bpf_ktime_get_ns() -> u64;
     fn bpf_trace_printk(fmt: *const u8, size: u32) -> i32;
+    fn bpf_get_prandom_u32() -> u32;
+    fn bpf_get_smp_processor_id() -> u32;
     fn bpf_map_lookup_elem(map_id: u32, key: *const u8) -> *mut u8;
     fn bpf_map_update_elem(map_id: u32, key: *const u8, value: *const u8, flags: u64) -> i32;
     fn bpf_map_delete_elem(map_id: u32, key: *const u8) -> i32;
     fn bpf_ringbuf_output(map_id: u32, data: *const u8, size: u64, flags: u64) -> i64;
+    fn bpf_ringbuf_reserve(map_id: u32, size: u64, flags: u64) -> *mut u8;
+    fn bpf_ringbuf_submit(data: *mut u8, flags: u64);
+    fn bpf_ringbuf_discard(data: *mut u8, flags: u64);
+    fn bpf_perf_event_output(
+        ctx: *const u8,
+        map_id: u32,
+        flags: u64,
+        data: *const u8,
+        size: u64,
+    ) -> i64;
+    // Stack helpers
+    fn bpf_get_stackid(ctx: *const u8, map_id: u32, flags: u64) -> i64;
+    fn bpf_get_stack(ctx: *const u8, buf: *mut u8, size: u32, flags: u64) -> i64;
+    // Memory helpers
+    fn bpf_probe_read(dst: *mut u8, size: u32, src: *const u8) -> i64;
+    fn bpf_probe_read_user(dst: *mut u8, size: u32, src: *const u8) -> i64;
+    fn bpf_probe_read_kernel(dst: *mut u8, size: u32, src: *const u8) -> i64;
+    // Process helpers
+    fn bpf_get_current_pid_tgid() -> u64;
+    fn bpf_get_current_uid_gid() -> u64;
+    fn bpf_get_current_comm(buf: *mut u8, size: u32) -> i64;
     // Robotics helpers
+    fn bpf_motor_emergency_stop(reason: u32) -> i64;
     fn bpf_gpio_read(pin: u32) -> i64;
     fn bpf_gpio_write(pin: u32, value: u32) -> i64;
     fn bpf_pwm_write(pwm_id: u32, channel: u32, duty: u32) -> i64;
+    fn bpf_sensor_last_timestamp(device: u32) -> u64;
+    fn bpf_iio_read(device: u32, channel: u32) -> i64;
+    fn bpf_can_send(iface: u32, can_id: u32, data: *const u8, len: u32) -> i64;
+    fn bpf_timer_start(timer: u32, delay_ns: u64) -> i64;
+    fn bpf_timeseries_push(map_id: u32, value: *const u8) -> i64;
+    fn bpf_timeseries_stats(map_id: u32, n: u32, stats: *mut u8, size: u32) -> i64;
 }
 
 /// BPF bytecode interpreter.
@@ -61,6 +92,7 @@ impl<P: PhysicalProfile> Interpreter<P> {
         regs: &mut RegisterFile,
         stack: &mut [u8],
         ctx: &BpfContext,
+        records: &mut Vec<Reservation>,
     ) -> Result<InsnResult, BpfError> {
         // Exit instruction
         if insn.is_exit() {
@@ -76,15 +108,15 @@ impl<P: PhysicalProfile> Interpreter<P> {
             }
 
             OpcodeClass::Jmp | OpcodeClass::Jmp32 => {
-                return self.execute_jmp(insn, regs, class == OpcodeClass::Jmp);
+                return self.execute_jmp(insn, regs, class == OpcodeClass::Jmp, records);
             }
 
             OpcodeClass::Ldx => {
-                self.execute_load(insn, regs, stack, ctx)?;
+                self.execute_load(insn, regs, stack, ctx, records)?;
             }
 
             OpcodeClass::Stx | OpcodeClass::St => {
-                self.execute_store(insn, regs, stack)?;
+                self.execute_store(insn, regs, stack, records)?;
             }
 
             OpcodeClass::Ld => {
@@ -168,12 +200,13 @@ impl<P: PhysicalProfile> Interpreter<P> {
         insn: &BpfInsn,
         regs: &mut RegisterFile,
         is_64bit: bool,
+        records: &mut Vec<Reservation>,
     ) -> Result<InsnResult, BpfError> {
         let jmp_op = JmpOp::from_opcode(insn.opcode).ok_or(BpfError::InvalidInstruction)?;
 
         // Handle call and exit
         if matches!(jmp_op, JmpOp::Call) {
-            return self.execute_call(insn, regs);
+            return self.execute_call(insn, regs, records);
         }
 
         if matches!(jmp_op, JmpOp::Exit) {
@@ -230,6 +263,7 @@ impl<P: PhysicalProfile> Interpreter<P> {
         &self,
         insn: &BpfInsn,
         regs: &mut RegisterFile,
+        records: &mut Vec<Reservation>,
     ) -> Result<InsnResult, BpfError> {
         let helper_id = insn.imm;
 
@@ -245,6 +279,17 @@ impl<P: PhysicalProfile> Interpreter<P> {
         // Execute helper (simplified - would need helper registry)
         let result = self.call_helper(helper_id, args)?;
 
+        // Track reserved ring buffer records so the program can access them
+        // in place until they are submitted or discarded.
+        match helper_id {
+            131 if result != 0 => records.push(Reservation {
+                addr: result,
+                size: args[1],
+            }),
+            132 | 133 => records.retain(|r| r.addr != args[0]),
+            _ => {}
+        }
+
         // Store result in R0
         regs.set(Register::R0, result);
 
@@ -252,6 +297,9 @@ impl<P: PhysicalProfile> Interpreter<P> {
     }
 
     /// Call a helper function.
+    ///
+    /// Helper IDs are those of [`HelperId`](crate::verifier::HelperId), so
+    /// that verified programs run the helpers they were checked against.
     fn call_helper(&self, helper_id: i32, args: [u64; 5]) -> Result<u64, BpfError> {
         // SAFETY: Calling BPF helpers is inherently unsafe as they are extern "C" functions.
         // We rely on the BPF verifier (in a full implementation) to ensure arguments are valid.
@@ -264,11 +312,17 @@ impl<P: PhysicalProfile> Interpreter<P> {
                 // bpf_trace_printk
                 2 => Ok(bpf_trace_printk(args[0] as *const u8, args[1] as u32) as u64),
 
+                // bpf_get_prandom_u32
+                3 => Ok(bpf_get_prandom_u32() as u64),
+
+                // bpf_get_smp_processor_id
+                4 => Ok(bpf_get_smp_processor_id() as u64),
+
                 // bpf_map_lookup_elem
-                3 => Ok(bpf_map_lookup_elem(args[0] as u32, args[1] as *const u8) as u64),
+                5 => Ok(bpf_map_lookup_elem(args[0] as u32, args[1] as *const u8) as u64),
 
                 // bpf_map_update_elem
-                4 => Ok(bpf_map_update_elem(
+                6 => Ok(bpf_map_update_elem(
                     args[0] as u32,
                     args[1] as *const u8,
                     args[2] as *const u8,
@@ -276,15 +330,84 @@ impl<P: PhysicalProfile> Interpreter<P> {
                 ) as u64),
 
                 // bpf_map_delete_elem
-                5 => Ok(bpf_map_delete_elem(args[0] as u32, args[1] as *const u8) as u64),
+                7 => Ok(bpf_map_delete_elem(args[0] as u32, args[1] as *const u8) as u64),
+
+                // bpf_probe_read
+                8 => Ok(
+                    bpf_probe_read(args[0] as *mut u8, args[1] as u32, args[2] as *const u8) as u64,
+                ),
+
+                // bpf_get_current_pid_tgid
+                9 => Ok(bpf_get_current_pid_tgid()),
+
+                // bpf_get_current_uid_gid
+                10 => Ok(bpf_get_current_uid_gid()),
+
+                // bpf_get_current_comm
+                11 => Ok(bpf_get_current_comm(args[0] as *mut u8, args[1] as u32) as u64),
+
+                // bpf_probe_read_user
+                12 => Ok(bpf_probe_read_user(
+                    args[0] as *mut u8,
+                    args[1] as u32,
+                    args[2] as *const u8,
+                ) as u64),
+
+                // bpf_probe_read_kernel
+                13 => Ok(bpf_probe_read_kernel(
+                    args[0] as *mut u8,
+                    args[1] as u32,
+                    args[2] as *const u8,
+                ) as u64),
+
+                // bpf_ringbuf_reserve
+                131 => Ok(bpf_ringbuf_reserve(args[0] as u32, args[1], args[2]) as u64),
+
+                // bpf_ringbuf_submit
+                132 => {
+                    bpf_ringbuf_submit(args[0] as *mut u8, args[1]);
+                    Ok(0)
+                }
+
+                // bpf_ringbuf_discard
+                133 => {
+                    bpf_ringbuf_discard(args[0] as *mut u8, args[1]);
+                    Ok(0)
+                }
 
                 // bpf_ringbuf_output
-                6 => Ok(
+                134 => Ok(
                     bpf_ringbuf_output(args[0] as u32, args[1] as *const u8, args[2], args[3])
                         as u64,
                 ),
 
+                // bpf_perf_event_output
+                25 => Ok(bpf_perf_event_output(
+                    args[0] as *const u8,
+                    args[1] as u32,
+                    args[2],
+                    args[3] as *const u8,
+                    args[4],
+                ) as u64),
+
+                // bpf_get_stackid
+                27 => Ok(bpf_get_stackid(args[0] as *const u8, args[1] as u32, args[2]) as u64),
+
+                // bpf_get_stack
+                67 => Ok(bpf_get_stack(
+                    args[0] as *const u8,
+                    args[1] as *mut u8,
+                    args[2] as u32,
+                    args[3],
+                ) as u64),
+
                 // Robotics Helpers
+                // bpf_motor_emergency_stop (1000)
+                1000 => Ok(bpf_motor_emergency_stop(args[0] as u32) as u64),
+
+                // bpf_sensor_last_timestamp (1002)
+                1002 => Ok(bpf_sensor_last_timestamp(args[0] as u32)),
+
                 // bpf_gpio_set (1003) -> bpf_gpio_write
                 1003 => Ok(bpf_gpio_write(args[0] as u32, args[1] as u32) as u64),
 
@@ -294,6 +417,31 @@ impl<P: PhysicalProfile> Interpreter<P> {
                 // bpf_pwm_write (1005)
                 1005 => Ok(bpf_pwm_write(args[0] as u32, args[1] as u32, args[2] as u32) as u64),
 
+                // bpf_iio_read (1006)
+                1006 => Ok(bpf_iio_read(args[0] as u32, args[1] as u32) as u64),
+
+                // bpf_can_send (1007)
+                1007 => Ok(bpf_can_send(
+                    args[0] as u32,
+                    args[1] as u32,
+                    args[2] as *const u8,
+                    args[3] as u32,
+                ) as u64),
+
+                // bpf_timer_start (1008)
+                1008 => Ok(bpf_timer_start(args[0] as u32, args[1]) as u64),
+
+                // bpf_timeseries_push (1001)
+                1001 => Ok(bpf_timeseries_push(args[0] as u32, args[1] as *const u8) as u64),
+
+                // bpf_timeseries_stats (1009)
+                1009 => Ok(bpf_timeseries_stats(
+                    args[0] as u32,
+                    args[1] as u32,
+                    args[2] as *mut u8,
+                    args[3] as u32,
+                ) as u64),
+
                 // Unknown helper
                 _ => Err(BpfError::InvalidHelper(helper_id)),
             }
@@ -307,6 +455,7 @@ impl<P: PhysicalProfile> Interpreter<P> {
         regs: &mut RegisterFile,
         stack: &[u8],
         ctx: &BpfContext,
+        records: &[Reservation],
     ) -> Result<(), BpfError> {
         let dst = Register::from_raw(insn.dst_reg()).ok_or(BpfError::InvalidInstruction)?;
         let src = Register::from_raw(insn.src_reg()).ok_or(BpfError::InvalidInstruction)?;
@@ -319,9 +468,10 @@ impl<P: PhysicalProfile> Interpreter<P> {
 
         // 1. Stack access (src = R10)
         if src == Register::R10 {
-            let _fp = base;
-            let offset = insn.offset as i64;
-            let stack_offset = -(offset + (size.size_bytes() as i64));
+            // R10 points one past the end of the stack buffer, so the slot
+            // for fp + offset lives at stack.len() + offset. This keeps the
+            // layout identical to the addresses helpers see.
+            let stack_offset = stack.len() as i64 + insn.offset as i64;
 
             if stack_offset < 0 || stack_offset as usize + size.size_bytes() > stack.len() {
                 return Err(BpfError::OutOfBounds);
@@ -395,6 +545,22 @@ impl<P: PhysicalProfile> Interpreter<P> {
             return Ok(());
         }
 
+        // 4. Reserved ring buffer record
+        if records.iter().any(|r| r.contains(addr, size)) {
+            // SAFETY: The address lies within a record handed out by
+            // bpf_ringbuf_reserve that has not yet been submitted or discarded.
+            let value = unsafe {
+                match size {
+                    MemSize::Byte => core::ptr::read_unaligned(addr as *const u8) as u64,
+                    MemSize::Half => core::ptr::read_unaligned(addr as *const u16) as u64,
+                    MemSize::Word => core::ptr::read_unaligned(addr as *const u32) as u64,
+                    MemSize::DWord => core::ptr::read_unaligned(addr as *const u64),
+                }
+            };
+            regs.set(dst, value);
+            return Ok(());
+        }
+
         Err(BpfError::OutOfBounds)
     }
 
@@ -404,6 +570,7 @@ impl<P: PhysicalProfile> Interpreter<P> {
         insn: &BpfInsn,
         regs: &RegisterFile,
         stack: &mut [u8],
+        records: &[Reservation],
     ) -> Result<(), BpfError> {
         let dst = Register::from_raw(insn.dst_reg()).ok_or(BpfError::InvalidInstruction)?;
         let class = insn.class().ok_or(BpfError::InvalidInstruction)?;
@@ -421,9 +588,8 @@ impl<P: PhysicalProfile> Interpreter<P> {
 
         // For stack access (dst = R10)
         if dst == Register::R10 {
-            let _fp = regs.get(Register::R10);
-            let offset = insn.offset as i64;
-            let stack_offset = -(offset + (size.size_bytes() as i64));
+            // Same layout as in execute_load: fp + offset is stack[len + offset]
+            let stack_offset = stack.len() as i64 + insn.offset as i64;
 
             if stack_offset < 0 || stack_offset as usize + size.size_bytes() > stack.len() {
                 return Err(BpfError::OutOfBounds);
@@ -450,6 +616,22 @@ impl<P: PhysicalProfile> Interpreter<P> {
             return Ok(());
         }
 
+        // Reserved ring buffer record
+        let addr = regs.get(dst).wrapping_add(insn.offset as i64 as u64);
+        if records.iter().any(|r| r.contains(addr, size)) {
+            // SAFETY: The address lies within a record handed out by
+            // bpf_ringbuf_reserve that has not yet been submitted or discarded.
+            unsafe {
+                match size {
+                    MemSize::Byte => core::ptr::write_unaligned(addr as *mut u8, value as u8),
+                    MemSize::Half => core::ptr::write_unaligned(addr as *mut u16, value as u16),
+                    MemSize::Word => core::ptr::write_unaligned(addr as *mut u32, value as u32),
+                    MemSize::DWord => core::ptr::write_unaligned(addr as *mut u64, value),
+                }
+            }
+            return Ok(());
+        }
+
         // Generic memory access would require context pointer validation
         Err(BpfError::OutOfBounds)
     }
@@ -461,12 +643,17 @@ impl<P: PhysicalProfile> Default for Interpreter<P> {
     }
 }
 
-impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
-    fn execute(&self, program: &BpfProgram<P>, ctx: &BpfContext) -> BpfResult {
+impl<P: PhysicalProfile> Interpreter<P> {
+    /// Execute a program, reporting the faulting instruction on error.
+    ///
+    /// This is the same as [`BpfExecutor::execute`], except that errors carry
+    /// the index of the instruction that raised them so they can be mapped
+    /// back to a source line.
+    pub fn run(&self, program: &BpfProgram<P>, ctx: &BpfContext) -> Result<u64, BpfFault> {
         let insns = program.instructions();
 
         if insns.is_empty() {
-            return Err(BpfError::NotLoaded);
+            return Err(BpfError::NotLoaded.into());
         }
 
         // Initialize register file
@@ -475,6 +662,9 @@ impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
         // Allocate stack
         let mut stack = vec![0u8; P::MAX_STACK_SIZE];
 
+        // Ring buffer records reserved by the program
+        let mut records = Vec::new();
+
         // R1 = context pointer
         regs.set(Register::R1, ctx as *const _ as u64);
 
@@ -491,16 +681,21 @@ impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
         let mut insn_count = 0usize;
         let insn_limit = P::MAX_INSN_COUNT;
 
+        let fault = |error, pc| BpfFault {
+            error,
+            insn_idx: Some(pc),
+        };
+
         loop {
             // Check bounds
             if pc >= insns.len() {
-                return Err(BpfError::OutOfBounds);
+                return Err(fault(BpfError::OutOfBounds, pc));
             }
 
             // Check instruction limit
             insn_count += 1;
             if insn_count > insn_limit {
-                return Err(BpfError::Timeout);
+                return Err(fault(BpfError::Timeout, pc));
             }
 
             let insn = &insns[pc];
@@ -508,12 +703,13 @@ impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
             // Handle wide instruction
             if insn.is_wide() {
                 if pc + 1 >= insns.len() {
-                    return Err(BpfError::InvalidInstruction);
+                    return Err(fault(BpfError::InvalidInstruction, pc));
                 }
                 let next_insn = &insns[pc + 1];
                 let imm64 = (insn.imm as u32 as u64) | ((next_insn.imm as u32 as u64) << 32);
 
-                let dst = Register::from_raw(insn.dst_reg()).ok_or(BpfError::InvalidInstruction)?;
+                let dst = Register::from_raw(insn.dst_reg())
+                    .ok_or_else(|| fault(BpfError::InvalidInstruction, pc))?;
                 regs.set(dst, imm64);
 
                 pc += 2;
@@ -521,7 +717,10 @@ impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
             }
 
             // Execute instruction
-            match self.execute_insn(insn, &mut regs, &mut stack, ctx)? {
+            match self
+                .execute_insn(insn, &mut regs, &mut stack, ctx, &mut records)
+                .map_err(|e| fault(e, pc))?
+            {
                 InsnResult::Continue => {
                     pc += 1;
                 }
@@ -533,13 +732,19 @@ impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
                 }
                 InsnResult::WideLoad => {
                     // Handled above, shouldn't reach here
-                    return Err(BpfError::InvalidInstruction);
+                    return Err(fault(BpfError::InvalidInstruction, pc));
                 }
             }
         }
     }
 }
 
+impl<P: PhysicalProfile> BpfExecutor<P> for Interpreter<P> {
+    fn execute(&self, program: &BpfProgram<P>, ctx: &BpfContext) -> BpfResult {
+        self.run(program, ctx).map_err(|fault| fault.error)
+    }
+}
+
 /// Result of executing a single instruction.
 enum InsnResult {
     /// Continue to next instruction
@@ -552,6 +757,25 @@ enum InsnResult {
     WideLoad,
 }
 
+/// A ring buffer record reserved by the running program.
+struct Reservation {
+    /// Start of the record's data area
+    addr: u64,
+    /// Size requested from `bpf_ringbuf_reserve`
+    size: u64,
+}
+
+impl Reservation {
+    /// Check whether an access of `size` bytes at `addr` stays in the record.
+    fn contains(&self, addr: u64, size: MemSize) -> bool {
+        addr >= self.addr
+            && addr
+                .checked_add(size.size_bytes() as u64)
+                .zip(self.addr.checked_add(self.size))
+                .is_some_and(|(end, limit)| end <= limit)
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::missing_safety_doc)]
 mod helpers_stub {
@@ -560,6 +784,10 @@ mod helpers_stub {
     // Simple test map: single u64 value at key 0
     static TEST_MAP_VALUE: AtomicU64 = AtomicU64::new(0);
 
+    // Single-record test ring buffer: reserved record and its state
+    static TEST_RINGBUF_RECORD: AtomicU64 = AtomicU64::new(0);
+    static TEST_RINGBUF_SUBMITTED: AtomicU64 = AtomicU64::new(0);
+
     // SAFETY: Test stub for BPF helper. Safe to be called from C/BPF context in tests.
     #[unsafe(no_mangle)]
     pub extern "C" fn bpf_ktime_get_ns() -> u64 {
@@ -568,8 +796,26 @@ mod helpers_stub {
 
     // SAFETY: Test stub for BPF helper.
     #[unsafe(no_mangle)]
-    pub extern "C" fn bpf_trace_printk(_fmt: *const u8, _len: u32) -> i32 {
-        0
+    pub extern "C" fn bpf_trace_printk(fmt: *const u8, len: u32) -> i32 {
+        // Echo the first 4 bytes so tests can check what a helper sees
+        if fmt.is_null() || len < 4 {
+            return 0;
+        }
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        unsafe { (fmt as *const i32).read_unaligned() }
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_prandom_u32() -> u32 {
+        // Chosen by fair dice roll
+        4
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_smp_processor_id() -> u32 {
+        2
     }
 
     // SAFETY: Test stub for BPF helper.
@@ -615,6 +861,101 @@ mod helpers_stub {
         0
     }
 
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_ringbuf_reserve(_map_id: u32, size: u64, _flags: u64) -> *mut u8 {
+        if size > 8 {
+            return core::ptr::null_mut();
+        }
+        TEST_RINGBUF_RECORD.as_ptr() as *mut u8
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_ringbuf_submit(_data: *mut u8, _flags: u64) {
+        TEST_RINGBUF_SUBMITTED.store(TEST_RINGBUF_RECORD.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_ringbuf_discard(_data: *mut u8, _flags: u64) {}
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_perf_event_output(
+        _ctx: *const u8,
+        map_id: u32,
+        _flags: u64,
+        data: *const u8,
+        size: u64,
+    ) -> i64 {
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        let first = unsafe { *data };
+        i64::from(map_id) * 1_000 + size as i64 * 10 + i64::from(first)
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_stackid(_ctx: *const u8, map_id: u32, flags: u64) -> i64 {
+        i64::from(map_id) * 1_000 + flags as i64
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_stack(_ctx: *const u8, buf: *mut u8, size: u32, _flags: u64) -> i64 {
+        // Fake a stack of one frame
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        let dst = unsafe { core::slice::from_raw_parts_mut(buf, size as usize) };
+        dst[..8].copy_from_slice(&0x1234_u64.to_ne_bytes());
+        8
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_probe_read(dst: *mut u8, size: u32, src: *const u8) -> i64 {
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        unsafe { core::ptr::copy_nonoverlapping(src, dst, size as usize) };
+        0
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_probe_read_user(dst: *mut u8, size: u32, src: *const u8) -> i64 {
+        bpf_probe_read(dst, size, src)
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_probe_read_kernel(_dst: *mut u8, _size: u32, _src: *const u8) -> i64 {
+        // Pretend the address is unmapped
+        -20
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_current_pid_tgid() -> u64 {
+        // tgid 7, pid 42
+        (7 << 32) | 42
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_current_uid_gid() -> u64 {
+        0
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_get_current_comm(buf: *mut u8, size: u32) -> i64 {
+        let name = b"init\0";
+        if size < name.len() as u32 {
+            return -1;
+        }
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        unsafe { core::ptr::copy_nonoverlapping(name.as_ptr(), buf, name.len()) };
+        0
+    }
+
     // SAFETY: Test stub for BPF helper.
     #[unsafe(no_mangle)]
     pub extern "C" fn bpf_gpio_read(_pin: u32) -> i64 {
@@ -633,6 +974,62 @@ mod helpers_stub {
         0
     }
 
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_motor_emergency_stop(_reason: u32) -> i64 {
+        0
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_sensor_last_timestamp(device: u32) -> u64 {
+        1_000 + u64::from(device)
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_iio_read(device: u32, channel: u32) -> i64 {
+        -i64::from(device * 100 + channel)
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_can_send(iface: u32, can_id: u32, data: *const u8, len: u32) -> i64 {
+        if len > 8 {
+            return -1;
+        }
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        let data = unsafe { core::slice::from_raw_parts(data, len as usize) };
+        let sum: u32 = data.iter().map(|&b| u32::from(b)).sum();
+        i64::from(iface << 16 | can_id) + i64::from(sum)
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_timer_start(timer: u32, delay_ns: u64) -> i64 {
+        if timer >= 32 {
+            return -1;
+        }
+        (delay_ns / 1_000) as i64 + i64::from(timer)
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_timeseries_push(map_id: u32, value: *const u8) -> i64 {
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        let value = unsafe { core::ptr::read_unaligned(value as *const i64) };
+        i64::from(map_id) * 1_000 + value
+    }
+
+    // SAFETY: Test stub for BPF helper.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn bpf_timeseries_stats(map_id: u32, n: u32, stats: *mut u8, size: u32) -> i64 {
+        // SAFETY: In tests, we assume valid pointers are passed to helpers.
+        let stats = unsafe { core::slice::from_raw_parts_mut(stats, size as usize) };
+        stats.fill(n as u8);
+        i64::from(map_id)
+    }
+
     pub fn get_test_map_value() -> u64 {
         TEST_MAP_VALUE.load(Ordering::SeqCst)
     }
@@ -640,6 +1037,10 @@ mod helpers_stub {
     pub fn reset_test_map() {
         TEST_MAP_VALUE.store(0, Ordering::SeqCst);
     }
+
+    pub fn get_submitted_record() -> u64 {
+        TEST_RINGBUF_SUBMITTED.load(Ordering::SeqCst)
+    }
 }
 
 #[cfg(test)]
@@ -738,13 +1139,13 @@ mod tests {
     #[test]
     fn execute_map_lookup_helper() {
         // Test that calling bpf_map_lookup_elem helper works
-        // Helper 3 = bpf_map_lookup_elem(map_id, key_ptr) -> value_ptr
+        // Helper 5 = bpf_map_lookup_elem(map_id, key_ptr) -> value_ptr
         helpers_stub::reset_test_map();
 
         let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
             .insn(BpfInsn::mov64_imm(1, 0)) // r1 = map_id (0)
             .insn(BpfInsn::mov64_imm(2, 0)) // r2 = key_ptr (dummy)
-            .insn(BpfInsn::call(3)) // r0 = bpf_map_lookup_elem(r1, r2)
+            .insn(BpfInsn::call(5)) // r0 = bpf_map_lookup_elem(r1, r2)
             .exit()
             .build()
             .expect("valid program");
@@ -761,7 +1162,7 @@ mod tests {
     #[test]
     fn execute_map_update_helper() {
         // Test that calling bpf_map_update_elem helper works
-        // Helper 4 = bpf_map_update_elem(map_id, key_ptr, value_ptr, flags) -> result
+        // Helper 6 = bpf_map_update_elem(map_id, key_ptr, value_ptr, flags) -> result
         helpers_stub::reset_test_map();
         assert_eq!(helpers_stub::get_test_map_value(), 0);
 
@@ -772,7 +1173,7 @@ mod tests {
             .insn(BpfInsn::mov64_imm(2, 0)) // r2 = key_ptr (dummy)
             .insn(BpfInsn::mov64_imm(3, 0)) // r3 = value_ptr (dummy)
             .insn(BpfInsn::mov64_imm(4, 0)) // r4 = flags (0)
-            .insn(BpfInsn::call(4)) // r0 = bpf_map_update_elem(r1, r2, r3, r4)
+            .insn(BpfInsn::call(6)) // r0 = bpf_map_update_elem(r1, r2, r3, r4)
             .exit()
             .build()
             .expect("valid program");
@@ -785,16 +1186,43 @@ mod tests {
         assert_eq!(result, Ok(0));
     }
 
+    #[test]
+    fn stack_slots_match_helper_pointers() {
+        // A store to fp - 8 must land where a helper given fp - 8 reads.
+        // Helper 2 = bpf_trace_printk(fmt, len), stubbed to echo 4 bytes.
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::new(0x62, 10, 0, -8, 0x1122_3344)) // *(u32 *)(fp - 8) = imm
+            .insn(BpfInsn::mov64_reg(1, 10))
+            .insn(BpfInsn::add64_imm(1, -8)) // r1 = fp - 8
+            .insn(BpfInsn::mov64_imm(2, 4)) // r2 = len
+            .insn(BpfInsn::call(2))
+            .insn(BpfInsn::mov64_reg(6, 0)) // r6 = bytes the helper read
+            .insn(BpfInsn::new(0x61, 7, 10, -8, 0)) // r7 = *(u32 *)(fp - 8)
+            .insn(BpfInsn::lsh64_imm(6, 32))
+            .insn(BpfInsn::mov64_reg(0, 6))
+            .insn(BpfInsn::new(0x4f, 0, 7, 0, 0)) // r0 |= r7
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(
+            interpreter.execute(&program, &ctx),
+            Ok(0x1122_3344_1122_3344)
+        );
+    }
+
     #[test]
     fn execute_map_delete_helper() {
         // Test that calling bpf_map_delete_elem helper works
-        // Helper 5 = bpf_map_delete_elem(map_id, key_ptr) -> result
+        // Helper 7 = bpf_map_delete_elem(map_id, key_ptr) -> result
         helpers_stub::reset_test_map();
 
         let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
             .insn(BpfInsn::mov64_imm(1, 0)) // r1 = map_id (0)
             .insn(BpfInsn::mov64_imm(2, 0)) // r2 = key_ptr (dummy)
-            .insn(BpfInsn::call(5)) // r0 = bpf_map_delete_elem(r1, r2)
+            .insn(BpfInsn::call(7)) // r0 = bpf_map_delete_elem(r1, r2)
             .exit()
             .build()
             .expect("valid program");
@@ -827,4 +1255,277 @@ mod tests {
         // Helper stub returns 0
         assert_eq!(result, Ok(0));
     }
+
+    #[test]
+    fn execute_core_helpers() {
+        // Helper 3 = bpf_get_prandom_u32(), helper 4 = bpf_get_smp_processor_id()
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::call(3))
+            .insn(BpfInsn::mov64_reg(6, 0)) // r6 = random
+            .insn(BpfInsn::call(4))
+            .insn(BpfInsn::lsh64_imm(0, 8))
+            .insn(BpfInsn::add64_reg(0, 6)) // r0 = cpu << 8 | random
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(0x204));
+    }
+
+    #[test]
+    fn execute_sensor_helpers() {
+        // Helper 1006 = bpf_iio_read(device, channel),
+        // helper 1002 = bpf_sensor_last_timestamp(device)
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_imm(1, 2))
+            .insn(BpfInsn::mov64_imm(2, 5))
+            .insn(BpfInsn::call(1006))
+            .insn(BpfInsn::mov64_reg(6, 0)) // r6 = -205
+            .insn(BpfInsn::mov64_imm(1, 2))
+            .insn(BpfInsn::call(1002))
+            .insn(BpfInsn::add64_reg(0, 6)) // r0 = 1002 - 205
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(797));
+    }
+
+    #[test]
+    fn execute_can_send_helper() {
+        // Helper 1007 = bpf_can_send(iface, id, data, len); the stub returns
+        // iface << 16 | id plus the sum of the payload bytes
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::new(0x62, 10, 0, -8, 0x0201)) // *(u32 *)(fp - 8) = 0x0201
+            .insn(BpfInsn::mov64_imm(1, 1))
+            .insn(BpfInsn::mov64_imm(2, 0x123))
+            .insn(BpfInsn::mov64_reg(3, 10))
+            .insn(BpfInsn::add64_imm(3, -8)) // r3 = fp - 8
+            .insn(BpfInsn::mov64_imm(4, 2))
+            .insn(BpfInsn::call(1007))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(0x1_0123 + 3));
+    }
+
+    #[test]
+    fn execute_timer_start_helper() {
+        // Helper 1008 = bpf_timer_start(timer, delay_ns); the stub returns
+        // the delay in microseconds plus the timer number
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_imm(1, 3))
+            .insn(BpfInsn::mov64_imm(2, 500_000))
+            .insn(BpfInsn::call(1008))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(503));
+    }
+
+    #[test]
+    fn execute_perf_event_output() {
+        // Helper 25 = bpf_perf_event_output(ctx, map, flags, data, size); the
+        // stub returns map * 1000 + size * 10 + the first data byte
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::new(0x7a, 10, 0, -8, 7)) // *(u64 *)(fp - 8) = 7
+            .insn(BpfInsn::mov64_imm(2, 4))
+            .insn(BpfInsn::mov64_imm(3, -1)) // BPF_F_CURRENT_CPU
+            .insn(BpfInsn::mov64_reg(4, 10))
+            .insn(BpfInsn::add64_imm(4, -8)) // r4 = fp - 8
+            .insn(BpfInsn::mov64_imm(5, 8))
+            .insn(BpfInsn::call(25))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(4_087));
+    }
+
+    #[test]
+    fn execute_stack_helpers() {
+        // Helper 27 = bpf_get_stackid(ctx, map, flags); the stub returns
+        // map * 1000 + flags
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_imm(2, 2))
+            .insn(BpfInsn::mov64_imm(3, 256)) // BPF_F_USER_STACK
+            .insn(BpfInsn::call(27))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(2_256));
+
+        // Helper 67 = bpf_get_stack(ctx, buf, size, flags); the stub writes
+        // one frame, which the program returns
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_reg(2, 10))
+            .insn(BpfInsn::add64_imm(2, -16)) // r2 = fp - 16
+            .insn(BpfInsn::mov64_imm(3, 16))
+            .insn(BpfInsn::mov64_imm(4, 0))
+            .insn(BpfInsn::call(67))
+            .insn(BpfInsn::new(0x79, 0, 10, -16, 0)) // r0 = *(u64 *)(fp - 16)
+            .exit()
+            .build()
+            .expect("valid program");
+
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(0x1234));
+    }
+
+    #[test]
+    fn execute_timeseries_helpers() {
+        // Helper 1001 = bpf_timeseries_push(map, value); the stub returns
+        // map * 1000 plus the value
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::new(0x7a, 10, 0, -8, 42)) // *(u64 *)(fp - 8) = 42
+            .insn(BpfInsn::mov64_imm(1, 3))
+            .insn(BpfInsn::mov64_reg(2, 10))
+            .insn(BpfInsn::add64_imm(2, -8)) // r2 = fp - 8
+            .insn(BpfInsn::call(1001))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(3_042));
+
+        // Helper 1009 = bpf_timeseries_stats(map, n, buf, size); the stub
+        // fills the buffer with n and returns the map, read back one byte
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_imm(1, 2))
+            .insn(BpfInsn::mov64_imm(2, 5))
+            .insn(BpfInsn::mov64_reg(3, 10))
+            .insn(BpfInsn::add64_imm(3, -16)) // r3 = fp - 16
+            .insn(BpfInsn::mov64_imm(4, 16))
+            .insn(BpfInsn::call(1009))
+            .insn(BpfInsn::mov64_reg(6, 0))
+            .insn(BpfInsn::new(0x71, 0, 10, -9, 0)) // r0 = *(u8 *)(fp - 9)
+            .insn(BpfInsn::add64_reg(0, 6))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(5 + 2));
+    }
+
+    #[test]
+    fn execute_process_helpers() {
+        // Helper 9 = bpf_get_current_pid_tgid() -> tgid << 32 | pid
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::call(9))
+            .insn(BpfInsn::rsh64_imm(0, 32)) // r0 = tgid
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(7));
+
+        // Helper 11 = bpf_get_current_comm(buf, size), read back first byte
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_reg(1, 10))
+            .insn(BpfInsn::add64_imm(1, -16)) // r1 = fp - 16
+            .insn(BpfInsn::mov64_imm(2, 16)) // r2 = size
+            .insn(BpfInsn::call(11))
+            .insn(BpfInsn::new(0x71, 0, 10, -16, 0)) // r0 = *(u8 *)(fp - 16)
+            .exit()
+            .build()
+            .expect("valid program");
+
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(b'i' as u64));
+    }
+
+    #[test]
+    fn execute_probe_read_helpers() {
+        let source: u64 = 0x1122_3344_5566_7788;
+        let src = crate::bytecode::insn::WideInsn::ld_dw_imm(3, &source as *const u64 as u64);
+
+        // Helper 12 = bpf_probe_read_user(dst, size, src), read back the copy
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_reg(1, 10))
+            .insn(BpfInsn::add64_imm(1, -8)) // r1 = fp - 8
+            .insn(BpfInsn::mov64_imm(2, 8)) // r2 = size
+            .insn(src.insn)
+            .insn(src.next) // r3 = &source
+            .insn(BpfInsn::call(12))
+            .insn(BpfInsn::new(0x79, 0, 10, -8, 0)) // r0 = *(u64 *)(fp - 8)
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(source));
+
+        // Helper 13 = bpf_probe_read_kernel, the stub reports -EFAULT
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_reg(1, 10))
+            .insn(BpfInsn::add64_imm(1, -8))
+            .insn(BpfInsn::mov64_imm(2, 8))
+            .insn(BpfInsn::mov64_imm(3, 0))
+            .insn(BpfInsn::call(13))
+            .exit()
+            .build()
+            .expect("valid program");
+
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(-20i64 as u64));
+    }
+
+    #[test]
+    fn execute_ringbuf_reserve_submit() {
+        // Reserve a record, write it in place and submit it
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_imm(1, 0)) // r1 = map 0
+            .insn(BpfInsn::mov64_imm(2, 8)) // r2 = 8 bytes
+            .insn(BpfInsn::mov64_imm(3, 0)) // r3 = flags
+            .insn(BpfInsn::call(131)) // r0 = bpf_ringbuf_reserve(r1, r2, r3)
+            .insn(BpfInsn::jeq_imm(0, 0, 4)) // if r0 == 0 goto exit
+            .insn(BpfInsn::new(0x7a, 0, 0, 0, 77)) // *(u64 *)(r0 + 0) = 77
+            .insn(BpfInsn::mov64_reg(1, 0)) // r1 = r0
+            .insn(BpfInsn::mov64_imm(2, 0)) // r2 = flags
+            .insn(BpfInsn::call(132)) // bpf_ringbuf_submit(r1, r2)
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+
+        assert_eq!(interpreter.execute(&program, &ctx), Ok(0));
+        assert_eq!(helpers_stub::get_submitted_record(), 77);
+    }
+
+    #[test]
+    fn run_reports_faulting_instruction() {
+        let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+            .insn(BpfInsn::mov64_imm(0, 10)) // r0 = 10
+            .insn(BpfInsn::mov64_imm(1, 0)) // r1 = 0
+            .insn(BpfInsn::new(0x3f, 0, 1, 0, 0)) // r0 /= r1
+            .exit()
+            .build()
+            .expect("valid program");
+
+        let interpreter = Interpreter::<ActiveProfile>::new();
+        let ctx = BpfContext::empty();
+
+        let fault = interpreter.run(&program, &ctx).unwrap_err();
+        assert_eq!(fault.error, BpfError::DivisionByZero);
+        assert_eq!(fault.insn_idx, Some(2));
+    }
 }
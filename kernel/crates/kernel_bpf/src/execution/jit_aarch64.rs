@@ -562,11 +562,27 @@ impl Arm64Emitter {
 /// ARM64 JIT-compiled BPF program.
 pub struct Arm64JitProgram {
     /// Executable code
-    #[allow(dead_code)]
     code: Vec<u8>,
     /// Entry point function
     #[allow(dead_code)]
     entry: usize,
+    /// Code offset of each BPF instruction
+    insn_offsets: Vec<usize>,
+}
+
+impl Arm64JitProgram {
+    /// Get the generated machine code.
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Get the code offset of each BPF instruction.
+    ///
+    /// Indexed by BPF instruction; both slots of a wide load map to the
+    /// same offset.
+    pub fn insn_offsets(&self) -> &[usize] {
+        &self.insn_offsets
+    }
 }
 
 /// ARM64 JIT compiler error.
@@ -632,6 +648,7 @@ impl<P: PhysicalProfile> Arm64JitCompiler<P> {
         Ok(Arm64JitProgram {
             code: emitter.code,
             entry: 0,
+            insn_offsets: emitter.insn_offsets,
         })
     }
 
@@ -1020,6 +1037,8 @@ impl<P: PhysicalProfile> Arm64JitCompiler<P> {
         unsafe extern "C" {
             fn bpf_ktime_get_ns() -> u64;
             fn bpf_trace_printk(fmt: *const u8, size: u32) -> i32;
+            fn bpf_get_prandom_u32() -> u32;
+            fn bpf_get_smp_processor_id() -> u32;
             fn bpf_map_lookup_elem(map_id: u32, key: *const u8) -> *mut u8;
             fn bpf_map_update_elem(
                 map_id: u32,
@@ -1029,15 +1048,61 @@ impl<P: PhysicalProfile> Arm64JitCompiler<P> {
             ) -> i32;
             fn bpf_map_delete_elem(map_id: u32, key: *const u8) -> i32;
             fn bpf_ringbuf_output(map_id: u32, data: *const u8, size: u64, flags: u64) -> i64;
+            fn bpf_perf_event_output(
+                ctx: *const u8,
+                map_id: u32,
+                flags: u64,
+                data: *const u8,
+                size: u64,
+            ) -> i64;
+            fn bpf_get_stackid(ctx: *const u8, map_id: u32, flags: u64) -> i64;
+            fn bpf_get_stack(ctx: *const u8, buf: *mut u8, size: u32, flags: u64) -> i64;
+            fn bpf_ringbuf_reserve(map_id: u32, size: u64, flags: u64) -> *mut u8;
+            fn bpf_ringbuf_submit(data: *mut u8, flags: u64);
+            fn bpf_ringbuf_discard(data: *mut u8, flags: u64);
+            fn bpf_probe_read(dst: *mut u8, size: u32, src: *const u8) -> i64;
+            fn bpf_probe_read_user(dst: *mut u8, size: u32, src: *const u8) -> i64;
+            fn bpf_probe_read_kernel(dst: *mut u8, size: u32, src: *const u8) -> i64;
+            fn bpf_get_current_pid_tgid() -> u64;
+            fn bpf_get_current_uid_gid() -> u64;
+            fn bpf_get_current_comm(buf: *mut u8, size: u32) -> i64;
+            fn bpf_motor_emergency_stop(reason: u32) -> i64;
+            fn bpf_sensor_last_timestamp(device: u32) -> u64;
+            fn bpf_iio_read(device: u32, channel: u32) -> i64;
+            fn bpf_can_send(iface: u32, can_id: u32, data: *const u8, len: u32) -> i64;
+            fn bpf_timer_start(timer: u32, delay_ns: u64) -> i64;
+            fn bpf_timeseries_push(map_id: u32, value: *const u8) -> i64;
+            fn bpf_timeseries_stats(map_id: u32, n: u32, stats: *mut u8, size: u32) -> i64;
         }
 
         match helper_id {
             1 => Ok(bpf_ktime_get_ns as *const () as u64),
             2 => Ok(bpf_trace_printk as *const () as u64),
-            3 => Ok(bpf_map_lookup_elem as *const () as u64),
-            4 => Ok(bpf_map_update_elem as *const () as u64),
-            5 => Ok(bpf_map_delete_elem as *const () as u64),
-            6 => Ok(bpf_ringbuf_output as *const () as u64),
+            3 => Ok(bpf_get_prandom_u32 as *const () as u64),
+            4 => Ok(bpf_get_smp_processor_id as *const () as u64),
+            5 => Ok(bpf_map_lookup_elem as *const () as u64),
+            6 => Ok(bpf_map_update_elem as *const () as u64),
+            7 => Ok(bpf_map_delete_elem as *const () as u64),
+            8 => Ok(bpf_probe_read as *const () as u64),
+            9 => Ok(bpf_get_current_pid_tgid as *const () as u64),
+            10 => Ok(bpf_get_current_uid_gid as *const () as u64),
+            11 => Ok(bpf_get_current_comm as *const () as u64),
+            12 => Ok(bpf_probe_read_user as *const () as u64),
+            13 => Ok(bpf_probe_read_kernel as *const () as u64),
+            25 => Ok(bpf_perf_event_output as *const () as u64),
+            27 => Ok(bpf_get_stackid as *const () as u64),
+            67 => Ok(bpf_get_stack as *const () as u64),
+            131 => Ok(bpf_ringbuf_reserve as *const () as u64),
+            132 => Ok(bpf_ringbuf_submit as *const () as u64),
+            133 => Ok(bpf_ringbuf_discard as *const () as u64),
+            134 => Ok(bpf_ringbuf_output as *const () as u64),
+            1000 => Ok(bpf_motor_emergency_stop as *const () as u64),
+            1001 => Ok(bpf_timeseries_push as *const () as u64),
+            1002 => Ok(bpf_sensor_last_timestamp as *const () as u64),
+            1006 => Ok(bpf_iio_read as *const () as u64),
+            1007 => Ok(bpf_can_send as *const () as u64),
+            1008 => Ok(bpf_timer_start as *const () as u64),
+            1009 => Ok(bpf_timeseries_stats as *const () as u64),
             _ => Err(Arm64JitError::UnsupportedInstruction),
         }
     }
@@ -1324,6 +1389,10 @@ mod tests {
         let jit_prog = result.unwrap();
         // Should have generated some code
         assert!(!jit_prog.code.is_empty());
+        // One code offset per BPF instruction, after the prologue
+        assert_eq!(jit_prog.insn_offsets().len(), 2);
+        assert!(jit_prog.insn_offsets()[0] > 0);
+        assert!(jit_prog.insn_offsets()[1] < jit_prog.code().len());
     }
 
     #[test]
@@ -1410,7 +1479,7 @@ mod tests {
         // 1. TO_LE (SourceType::Imm) on LE machine = truncation/zero-extension
         let program_le = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
             .insn(BpfInsn::mov64_imm(0, 0x12345678)) // r0 = 0x12345678
-            .insn(BpfInsn::new(0xd4, 0, 0, 0, 16))   // r0 = to_le16(r0) -> 0x5678
+            .insn(BpfInsn::new(0xd4, 0, 0, 0, 16)) // r0 = to_le16(r0) -> 0x5678
             .exit()
             .build()
             .expect("valid program");
@@ -1426,7 +1495,7 @@ mod tests {
         // Source bit is 0x08. So 0xdc is correct for TO_BE.
         let program_be = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
             .insn(BpfInsn::mov64_imm(0, 0x12345678))
-            .insn(BpfInsn::new(0xdc, 0, 0, 0, 32))   // r0 = to_be32(r0) -> 0x78563412
+            .insn(BpfInsn::new(0xdc, 0, 0, 0, 32)) // r0 = to_be32(r0) -> 0x78563412
             .exit()
             .build()
             .expect("valid program");
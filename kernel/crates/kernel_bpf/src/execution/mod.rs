@@ -63,6 +63,15 @@ pub struct SyscallTraceContext {
     pub arg6: u64,
 }
 
+/// Context for syscall exit tracepoints.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct SyscallExitContext {
+    pub syscall_nr: u64,
+    /// Value returned to userspace, a negated errno on failure
+    pub ret: i64,
+}
+
 impl BpfContext {
     /// Create an empty context.
     pub const fn empty() -> Self {
@@ -146,6 +155,33 @@ impl core::fmt::Display for BpfError {
     }
 }
 
+/// A runtime error together with the instruction that raised it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BpfFault {
+    /// The error
+    pub error: BpfError,
+    /// Index of the faulting instruction, if the engine can attribute it
+    pub insn_idx: Option<usize>,
+}
+
+impl From<BpfError> for BpfFault {
+    fn from(error: BpfError) -> Self {
+        Self {
+            error,
+            insn_idx: None,
+        }
+    }
+}
+
+impl core::fmt::Display for BpfFault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.insn_idx {
+            Some(idx) => write!(f, "{} at instruction {}", self.error, idx),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
 /// Trait for BPF execution engines.
 ///
 /// This trait defines the interface for executing BPF programs.
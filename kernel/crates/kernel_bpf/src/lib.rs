@@ -40,6 +40,7 @@
 //! - [`execution`] - Program execution engines (interpreter, JIT)
 //! - [`maps`] - BPF map implementations for data storage
 //! - [`scheduler`] - Profile-aware program scheduling
+//! - [`debug`] - Source line info, error annotation and program dumps
 //!
 //! # Quick Start
 //!
@@ -101,6 +102,7 @@ compile_error!(
 
 pub mod attach;
 pub mod bytecode;
+pub mod debug;
 pub mod execution;
 pub mod loader;
 pub mod maps;
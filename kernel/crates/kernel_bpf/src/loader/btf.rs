@@ -0,0 +1,317 @@
+//! Minimal BTF Parser
+//!
+//! Parses just enough of the BPF Type Format to recover source line
+//! information: the string table of the `.BTF` section and the line info
+//! records of the `.BTF.ext` section. Type records are skipped.
+//!
+//! # Line Info Layout
+//!
+//! ```text
+//! .BTF.ext line_info:
+//! ┌──────────────┬─────────────────────────────────────────────┐
+//! │ rec_size u32 │ { sec_name_off u32, num_info u32,           │
+//! │              │   bpf_line_info[num_info] } ...             │
+//! └──────────────┴─────────────────────────────────────────────┘
+//! ```
+//!
+//! In `.BTF.ext`, `insn_off` is a byte offset into the program section.
+//! Records passed to `BPF_PROG_LOAD` use instruction indices instead; the
+//! loader converts the former into the latter.
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use super::error::{LoadError, LoadResult};
+
+/// BTF magic number.
+pub const BTF_MAGIC: u16 = 0xeb9f;
+
+/// Size of a BPF instruction in bytes, used to convert `.BTF.ext` offsets.
+const INSN_SIZE: u32 = 8;
+
+/// Read a u16 with the given endianness.
+fn read_u16(data: &[u8], offset: usize, little_endian: bool) -> LoadResult<u16> {
+    let bytes: [u8; 2] = data
+        .get(offset..offset + 2)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(LoadError::BtfError)?;
+    Ok(if little_endian {
+        u16::from_le_bytes(bytes)
+    } else {
+        u16::from_be_bytes(bytes)
+    })
+}
+
+/// Read a u32 with the given endianness.
+fn read_u32(data: &[u8], offset: usize, little_endian: bool) -> LoadResult<u32> {
+    let bytes: [u8; 4] = data
+        .get(offset..offset + 4)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(LoadError::BtfError)?;
+    Ok(if little_endian {
+        u32::from_le_bytes(bytes)
+    } else {
+        u32::from_be_bytes(bytes)
+    })
+}
+
+/// Detect endianness from the magic number at the start of a BTF blob.
+fn detect_endian(data: &[u8]) -> LoadResult<bool> {
+    if read_u16(data, 0, true)? == BTF_MAGIC {
+        Ok(true)
+    } else if read_u16(data, 0, false)? == BTF_MAGIC {
+        Ok(false)
+    } else {
+        Err(LoadError::BtfError)
+    }
+}
+
+/// Parsed `.BTF` blob.
+///
+/// Only the string table is retained, since that is all line info needs.
+#[derive(Debug, Clone, Default)]
+pub struct Btf {
+    /// String section (NUL-separated strings)
+    strings: Vec<u8>,
+}
+
+impl Btf {
+    /// Parse a raw BTF blob.
+    pub fn parse(data: &[u8]) -> LoadResult<Self> {
+        let le = detect_endian(data)?;
+
+        let hdr_len = read_u32(data, 4, le)? as usize;
+        let str_off = read_u32(data, 16, le)? as usize;
+        let str_len = read_u32(data, 20, le)? as usize;
+
+        let start = hdr_len.checked_add(str_off).ok_or(LoadError::BtfError)?;
+        let end = start.checked_add(str_len).ok_or(LoadError::BtfError)?;
+        let strings = data.get(start..end).ok_or(LoadError::BtfError)?;
+
+        Ok(Self {
+            strings: strings.to_vec(),
+        })
+    }
+
+    /// Build a BTF string table directly (used by tests and synthetic loaders).
+    pub fn from_strings(strings: Vec<u8>) -> Self {
+        Self { strings }
+    }
+
+    /// Look up a string by offset.
+    pub fn string(&self, offset: u32) -> Option<&str> {
+        let start = offset as usize;
+        let rest = self.strings.get(start..)?;
+        let len = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+        core::str::from_utf8(&rest[..len]).ok()
+    }
+}
+
+/// A single BTF line info record (`struct bpf_line_info`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BpfLineInfo {
+    /// Instruction index (or byte offset inside `.BTF.ext`)
+    pub insn_off: u32,
+    /// Offset of the source file name in the BTF string table
+    pub file_name_off: u32,
+    /// Offset of the source line text in the BTF string table
+    pub line_off: u32,
+    /// Line number (upper 22 bits) and column (lower 10 bits)
+    pub line_col: u32,
+}
+
+impl BpfLineInfo {
+    /// Size of the record as defined by the kernel ABI.
+    pub const SIZE: usize = 16;
+
+    /// Source line number.
+    pub const fn line(&self) -> u32 {
+        self.line_col >> 10
+    }
+
+    /// Source column number.
+    pub const fn column(&self) -> u32 {
+        self.line_col & 0x3ff
+    }
+
+    /// Parse a record from native-endian bytes.
+    ///
+    /// `data` may be longer than [`Self::SIZE`] when userspace uses a newer,
+    /// larger record layout; trailing bytes are ignored.
+    pub fn from_bytes(data: &[u8]) -> LoadResult<Self> {
+        let le = cfg!(target_endian = "little");
+        Self::from_bytes_endian(data, le)
+    }
+
+    fn from_bytes_endian(data: &[u8], le: bool) -> LoadResult<Self> {
+        Ok(Self {
+            insn_off: read_u32(data, 0, le)?,
+            file_name_off: read_u32(data, 4, le)?,
+            line_off: read_u32(data, 8, le)?,
+            line_col: read_u32(data, 12, le)?,
+        })
+    }
+}
+
+/// Line info records for one program section of a `.BTF.ext` blob.
+#[derive(Debug, Clone)]
+struct SectionLineInfo {
+    /// Offset of the section name in the BTF string table
+    sec_name_off: u32,
+    /// Records, with `insn_off` already converted to instruction indices
+    records: Vec<BpfLineInfo>,
+}
+
+/// Parsed `.BTF.ext` blob.
+#[derive(Debug, Clone, Default)]
+pub struct BtfExt {
+    /// Per-section line info
+    line_info: Vec<SectionLineInfo>,
+}
+
+impl BtfExt {
+    /// Parse a raw `.BTF.ext` blob.
+    pub fn parse(data: &[u8]) -> LoadResult<Self> {
+        let le = detect_endian(data)?;
+
+        let hdr_len = read_u32(data, 4, le)? as usize;
+        let line_info_off = read_u32(data, 16, le)? as usize;
+        let line_info_len = read_u32(data, 20, le)? as usize;
+
+        if line_info_len == 0 {
+            return Ok(Self::default());
+        }
+
+        let start = hdr_len
+            .checked_add(line_info_off)
+            .ok_or(LoadError::BtfError)?;
+        let end = start
+            .checked_add(line_info_len)
+            .ok_or(LoadError::BtfError)?;
+        let section = data.get(start..end).ok_or(LoadError::BtfError)?;
+
+        let rec_size = read_u32(section, 0, le)? as usize;
+        if rec_size < BpfLineInfo::SIZE {
+            return Err(LoadError::BtfError);
+        }
+
+        let mut line_info = Vec::new();
+        let mut offset = 4;
+        while offset < section.len() {
+            let sec_name_off = read_u32(section, offset, le)?;
+            let num_info = read_u32(section, offset + 4, le)? as usize;
+            offset += 8;
+
+            // The count is untrusted: it must fit in what is left
+            let remaining = section.len().saturating_sub(offset) / rec_size;
+            if num_info > remaining {
+                return Err(LoadError::BtfError);
+            }
+
+            let mut records = Vec::with_capacity(num_info);
+            for _ in 0..num_info {
+                let bytes = section
+                    .get(offset..offset + rec_size)
+                    .ok_or(LoadError::BtfError)?;
+                let mut record = BpfLineInfo::from_bytes_endian(bytes, le)?;
+                record.insn_off /= INSN_SIZE;
+                records.push(record);
+                offset += rec_size;
+            }
+
+            line_info.push(SectionLineInfo {
+                sec_name_off,
+                records,
+            });
+        }
+
+        Ok(Self { line_info })
+    }
+
+    /// Get the line info records for a program section.
+    ///
+    /// The returned records use instruction indices.
+    pub fn line_info_for(&self, btf: &Btf, section_name: &str) -> Option<&[BpfLineInfo]> {
+        self.line_info
+            .iter()
+            .find(|s| btf.string(s.sec_name_off) == Some(section_name))
+            .map(|s| s.records.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn btf_blob(strings: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; 24];
+        data[0..2].copy_from_slice(&BTF_MAGIC.to_le_bytes());
+        data[2] = 1; // version
+        data[4..8].copy_from_slice(&24u32.to_le_bytes()); // hdr_len
+        data[16..20].copy_from_slice(&0u32.to_le_bytes()); // str_off
+        data[20..24].copy_from_slice(&(strings.len() as u32).to_le_bytes());
+        data.extend_from_slice(strings);
+        data
+    }
+
+    #[test]
+    fn parse_btf_strings() {
+        let btf = Btf::parse(&btf_blob(b"\0prog.c\0x = 1;\0")).expect("valid BTF");
+        assert_eq!(btf.string(0), Some(""));
+        assert_eq!(btf.string(1), Some("prog.c"));
+        assert_eq!(btf.string(8), Some("x = 1;"));
+        assert_eq!(btf.string(100), None);
+    }
+
+    #[test]
+    fn reject_bad_magic() {
+        let mut blob = btf_blob(b"\0");
+        blob[0] = 0;
+        assert!(matches!(Btf::parse(&blob), Err(LoadError::BtfError)));
+    }
+
+    #[test]
+    fn parse_btf_ext_line_info() {
+        let btf = Btf::from_strings(b"\0xdp\0prog.c\0return 0;\0".to_vec());
+
+        let mut data = vec![0u8; 32];
+        data[0..2].copy_from_slice(&BTF_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&32u32.to_le_bytes()); // hdr_len
+        data[16..20].copy_from_slice(&0u32.to_le_bytes()); // line_info_off
+        data[20..24].copy_from_slice(&28u32.to_le_bytes()); // line_info_len
+
+        data.extend_from_slice(&16u32.to_le_bytes()); // rec_size
+        data.extend_from_slice(&1u32.to_le_bytes()); // sec_name_off ("xdp")
+        data.extend_from_slice(&1u32.to_le_bytes()); // num_info
+        data.extend_from_slice(&16u32.to_le_bytes()); // insn_off (bytes)
+        data.extend_from_slice(&5u32.to_le_bytes()); // file_name_off
+        data.extend_from_slice(&12u32.to_le_bytes()); // line_off
+        data.extend_from_slice(&((7u32 << 10) | 3).to_le_bytes()); // line_col
+
+        let ext = BtfExt::parse(&data).expect("valid BTF.ext");
+        let records = ext.line_info_for(&btf, "xdp").expect("section present");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].insn_off, 2);
+        assert_eq!(records[0].line(), 7);
+        assert_eq!(records[0].column(), 3);
+        assert!(ext.line_info_for(&btf, "socket").is_none());
+    }
+
+    #[test]
+    fn reject_btf_ext_count_beyond_section() {
+        let mut data = vec![0u8; 32];
+        data[0..2].copy_from_slice(&BTF_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&32u32.to_le_bytes()); // hdr_len
+        data[20..24].copy_from_slice(&12u32.to_le_bytes()); // line_info_len
+
+        data.extend_from_slice(&16u32.to_le_bytes()); // rec_size
+        data.extend_from_slice(&1u32.to_le_bytes()); // sec_name_off
+        data.extend_from_slice(&u32::MAX.to_le_bytes()); // num_info
+
+        assert!(matches!(BtfExt::parse(&data), Err(LoadError::BtfError)));
+    }
+}
@@ -22,7 +22,7 @@
 //! - ELF64 parsing for BPF objects
 //! - Multiple programs per object file
 //! - Map definitions and relocations
-//! - BTF parsing (optional, cloud profile only)
+//! - BTF line info for source-level error reports
 //! - License extraction
 //!
 //! # Usage
@@ -42,6 +42,7 @@
 
 extern crate alloc;
 
+mod btf;
 mod elf;
 mod error;
 mod object;
@@ -50,6 +51,7 @@ mod reloc;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
+pub use btf::{BTF_MAGIC, BpfLineInfo, Btf, BtfExt};
 pub use elf::{ElfParser, SectionType};
 pub use error::{LoadError, LoadResult};
 pub use object::{BpfObject, LoadedMap, LoadedProgram};
@@ -57,6 +59,7 @@ pub use reloc::Relocator;
 
 use crate::bytecode::insn::BpfInsn;
 use crate::bytecode::program::BpfProgType;
+use crate::debug::LineTable;
 use crate::maps::MapDef;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
@@ -119,10 +122,17 @@ impl<P: PhysicalProfile> BpfLoader<P> {
         // Extract maps
         let maps = self.load_maps(&mut parser)?;
 
+        // Extract BTF debug info, if the object was built with -g. It is
+        // optional, so malformed BTF only drops the line info.
+        let (btf, mut btf_error) = match self.load_btf(&parser) {
+            Ok(btf) => (btf, None),
+            Err(e) => (None, Some(e)),
+        };
+
         // Extract programs
-        let programs = self.load_programs(&mut parser, &maps)?;
+        let programs = self.load_programs(&mut parser, &maps, btf.as_ref(), &mut btf_error)?;
 
-        Ok(BpfObject::new(programs, maps, license))
+        Ok(BpfObject::new(programs, maps, license).with_btf_error(btf_error))
     }
 
     /// Load map definitions from the ELF file.
@@ -154,11 +164,27 @@ impl<P: PhysicalProfile> BpfLoader<P> {
         Ok(maps)
     }
 
+    /// Load the `.BTF` and `.BTF.ext` sections, if both are present.
+    fn load_btf(&self, parser: &ElfParser) -> LoadResult<Option<(Btf, BtfExt)>> {
+        let Some(btf_section) = parser.find_section(".BTF")? else {
+            return Ok(None);
+        };
+        let Some(ext_section) = parser.find_section(".BTF.ext")? else {
+            return Ok(None);
+        };
+
+        let btf = Btf::parse(parser.section_data(&btf_section)?)?;
+        let ext = BtfExt::parse(parser.section_data(&ext_section)?)?;
+        Ok(Some((btf, ext)))
+    }
+
     /// Load programs from the ELF file.
     fn load_programs(
         &self,
         parser: &mut ElfParser,
         maps: &[LoadedMap],
+        btf: Option<&(Btf, BtfExt)>,
+        btf_error: &mut Option<LoadError>,
     ) -> LoadResult<Vec<LoadedProgram<P>>> {
         let mut programs = Vec::new();
 
@@ -183,7 +209,21 @@ impl<P: PhysicalProfile> BpfLoader<P> {
             let mut relocator = Relocator::new(maps);
             let insns = relocator.relocate(&name, insns, parser)?;
 
-            programs.push(LoadedProgram::new(name, prog_type, insns));
+            let line_table = match btf {
+                Some((btf, ext)) => match ext.line_info_for(btf, &name) {
+                    Some(records) => match LineTable::from_btf(btf, records) {
+                        Ok(table) => Some(table),
+                        Err(e) => {
+                            btf_error.get_or_insert(e);
+                            None
+                        }
+                    },
+                    None => None,
+                },
+                None => None,
+            };
+
+            programs.push(LoadedProgram::new(name, prog_type, insns).with_line_table(line_table));
         }
 
         Ok(programs)
@@ -217,6 +257,8 @@ impl<P: PhysicalProfile> BpfLoader<P> {
             BpfProgType::SchedCls
         } else if name.starts_with("lwt_") {
             BpfProgType::LwtIn
+        } else if name.starts_with("seccomp") || name.starts_with("syscall_filter") {
+            BpfProgType::SyscallFilter
         } else {
             // Default to socket filter for unknown types (including struct_ops, lsm)
             BpfProgType::SocketFilter
@@ -342,6 +384,10 @@ mod tests {
             BpfLoader::<ActiveProfile>::section_to_prog_type("tracepoint/syscalls/sys_enter_write"),
             BpfProgType::Tracepoint
         );
+        assert_eq!(
+            BpfLoader::<ActiveProfile>::section_to_prog_type("seccomp"),
+            BpfProgType::SyscallFilter
+        );
     }
 
     #[test]
@@ -8,8 +8,10 @@ use alloc::string::String;
 use alloc::vec::Vec;
 use core::marker::PhantomData;
 
+use super::error::LoadError;
 use crate::bytecode::insn::BpfInsn;
 use crate::bytecode::program::BpfProgType;
+use crate::debug::LineTable;
 use crate::maps::MapDef;
 use crate::profile::{ActiveProfile, PhysicalProfile};
 
@@ -22,6 +24,8 @@ pub struct LoadedProgram<P: PhysicalProfile = ActiveProfile> {
     prog_type: BpfProgType,
     /// Program instructions
     insns: Vec<BpfInsn>,
+    /// Source line info from BTF, if present
+    line_table: Option<LineTable>,
     /// Profile marker
     _profile: PhantomData<P>,
 }
@@ -33,10 +37,22 @@ impl<P: PhysicalProfile> LoadedProgram<P> {
             name,
             prog_type,
             insns,
+            line_table: None,
             _profile: PhantomData,
         }
     }
 
+    /// Attach source line info.
+    pub fn with_line_table(mut self, line_table: Option<LineTable>) -> Self {
+        self.line_table = line_table;
+        self
+    }
+
+    /// Get the source line info, if the object carried BTF.
+    pub fn line_table(&self) -> Option<&LineTable> {
+        self.line_table.as_ref()
+    }
+
     /// Get the program name.
     pub fn name(&self) -> &str {
         &self.name
@@ -93,6 +109,8 @@ pub struct BpfObject<P: PhysicalProfile = ActiveProfile> {
     maps: Vec<LoadedMap>,
     /// License string
     license: Option<String>,
+    /// Why BTF line info was dropped, if it was malformed
+    btf_error: Option<LoadError>,
 }
 
 impl<P: PhysicalProfile> BpfObject<P> {
@@ -106,9 +124,21 @@ impl<P: PhysicalProfile> BpfObject<P> {
             programs,
             maps,
             license,
+            btf_error: None,
         }
     }
 
+    /// Record why BTF line info was dropped.
+    pub fn with_btf_error(mut self, btf_error: Option<LoadError>) -> Self {
+        self.btf_error = btf_error;
+        self
+    }
+
+    /// Why BTF line info was dropped, if the object had malformed BTF.
+    pub fn btf_error(&self) -> Option<&LoadError> {
+        self.btf_error.as_ref()
+    }
+
     /// Get all programs.
     pub fn programs(&self) -> &[LoadedProgram<P>] {
         &self.programs
@@ -197,6 +197,7 @@ impl<'a> Relocator<'a> {
             "bpf_set_hash" => Some(48),
             "bpf_setsockopt" => Some(49),
             "bpf_skb_adjust_room" => Some(50),
+            "bpf_get_stack" => Some(67),
             // Ring buffer helpers
             "bpf_ringbuf_output" => Some(130),
             "bpf_ringbuf_reserve" => Some(131),
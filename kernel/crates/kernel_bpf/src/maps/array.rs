@@ -179,6 +179,18 @@ impl<P: PhysicalProfile> BpfMap<P> for ArrayMap<P> {
         &self.def
     }
 
+    fn get_next_key(&self, key: Option<&[u8]>) -> MapResult<Vec<u8>> {
+        let next = match key.and_then(Self::parse_key) {
+            Some(index) if index < self.def.max_entries => index + 1,
+            _ => 0,
+        };
+        if next < self.def.max_entries {
+            Ok(next.to_ne_bytes().to_vec())
+        } else {
+            Err(MapError::KeyNotFound)
+        }
+    }
+
     // SAFETY: This method returns a raw pointer to the map value.
     // The caller must ensure that the pointer is not used after the map is modified or dropped.
     // We rely on the caller to maintain the safety invariants required by the BpfMap trait.
@@ -262,6 +274,20 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn array_map_get_next_key() {
+        let map = ArrayMap::<ActiveProfile>::with_entries(4, 2).expect("create map");
+        assert_eq!(map.get_next_key(None), Ok(0u32.to_ne_bytes().to_vec()));
+        assert_eq!(
+            map.get_next_key(Some(&0u32.to_ne_bytes())),
+            Ok(1u32.to_ne_bytes().to_vec())
+        );
+        assert_eq!(
+            map.get_next_key(Some(&1u32.to_ne_bytes())),
+            Err(MapError::KeyNotFound)
+        );
+    }
+
     #[cfg(feature = "cloud-profile")]
     #[test]
     fn array_map_resize() {
@@ -78,7 +78,6 @@ impl Bucket {
         self.state == BucketState::Empty
     }
 
-    #[allow(dead_code)]
     fn is_occupied(&self) -> bool {
         self.state == BucketState::Occupied
     }
@@ -239,6 +238,23 @@ impl HashStorage {
         Ok(())
     }
 
+    /// Get the key after `key` in bucket order, or the first key if `key`
+    /// is not in the map.
+    fn next_key(&self, key: Option<&[u8]>) -> Option<&[u8]> {
+        let start = match key {
+            Some(key) if key.len() == self.key_size => match self.find_bucket(key) {
+                (idx, true) => idx + 1,
+                (_, false) => 0,
+            },
+            _ => 0,
+        };
+
+        self.buckets[start..]
+            .iter()
+            .find(|b| b.is_occupied())
+            .map(|b| b.key.as_slice())
+    }
+
     /// Resize the hash map (cloud profile only).
     #[cfg(feature = "cloud-profile")]
     fn resize(&mut self, new_capacity: usize) {
@@ -368,6 +384,14 @@ impl<P: PhysicalProfile> BpfMap<P> for HashMap<P> {
         &self.def
     }
 
+    fn get_next_key(&self, key: Option<&[u8]>) -> MapResult<Vec<u8>> {
+        let guard = self.storage.read();
+        guard
+            .next_key(key)
+            .map(|k| k.to_vec())
+            .ok_or(MapError::KeyNotFound)
+    }
+
     /// # Safety
     /// This method returns a raw pointer to the map value. The caller must ensure
     /// that the pointer is not used after the map is modified or dropped.
@@ -550,6 +574,30 @@ mod tests {
             .expect("insert into deleted slot");
     }
 
+    #[test]
+    fn hash_map_get_next_key() {
+        let map = HashMap::<ActiveProfile>::with_sizes(4, 4, 16).expect("create map");
+        assert_eq!(map.get_next_key(None), Err(MapError::KeyNotFound));
+
+        for i in 0u32..5 {
+            map.update(&i.to_ne_bytes(), &i.to_ne_bytes(), 0)
+                .expect("insert");
+        }
+        map.delete(&2u32.to_ne_bytes()).expect("delete");
+
+        let mut keys = Vec::new();
+        let mut key = map.get_next_key(None).ok();
+        while let Some(k) = key {
+            key = map.get_next_key(Some(&k)).ok();
+            keys.push(u32::from_ne_bytes(k.try_into().unwrap()));
+        }
+        keys.sort_unstable();
+        assert_eq!(keys, [0, 1, 3, 4]);
+
+        // A missing key restarts the walk
+        assert!(map.get_next_key(Some(&2u32.to_ne_bytes())).is_ok());
+    }
+
     #[test]
     fn hash_map_invalid_sizes() {
         // Zero key size
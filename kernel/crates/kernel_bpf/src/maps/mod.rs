@@ -22,7 +22,9 @@ extern crate alloc;
 
 mod array;
 mod hash;
+mod perf_event;
 mod ringbuf;
+mod stack_trace;
 mod timeseries;
 
 #[cfg(feature = "embedded-profile")]
@@ -32,8 +34,16 @@ use alloc::sync::Arc;
 
 pub use array::ArrayMap;
 pub use hash::HashMap;
+pub use perf_event::{
+    BPF_F_CURRENT_CPU, PERF_PAGE_SIZE, PERF_RECORD_LOST, PERF_RECORD_SAMPLE, PerfEventArrayMap,
+    PerfRecord, PerfRing,
+};
 pub use ringbuf::{RingBufMap, RingBufReservation};
 use spin::RwLock;
+pub use stack_trace::{
+    BPF_F_FAST_STACK_CMP, BPF_F_REUSE_STACKID, BPF_F_SKIP_FIELD_MASK, BPF_F_USER_STACK,
+    MAX_STACK_DEPTH, StackTraceMap,
+};
 #[cfg(feature = "embedded-profile")]
 pub use static_pool::StaticPool;
 pub use timeseries::{TimeSeriesMap, TimeSeriesStats};
@@ -184,6 +194,15 @@ pub trait BpfMap<P: PhysicalProfile = ActiveProfile>: Send + Sync {
     /// Get the map definition.
     fn def(&self) -> &MapDef;
 
+    /// Get the key following `key`, for iterating over the map.
+    ///
+    /// With no `key`, or one that is not in the map, returns the first key.
+    /// Returns `KeyNotFound` after the last key, and `NotSupported` for maps
+    /// without keys to iterate.
+    fn get_next_key(&self, _key: Option<&[u8]>) -> MapResult<alloc::vec::Vec<u8>> {
+        Err(MapError::NotSupported)
+    }
+
     /// Look up a value by key and return a raw pointer.
     ///
     /// # Safety
@@ -193,6 +212,68 @@ pub trait BpfMap<P: PhysicalProfile = ActiveProfile>: Send + Sync {
         None
     }
 
+    /// Reserve a record of `size` bytes to be written in place.
+    ///
+    /// Only ring buffers support this; other maps return `None`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure that the map is not resized or deleted while the pointer is in
+    /// use, and must hand the pointer back to [`BpfMap::release_ptr`] exactly once.
+    unsafe fn reserve_ptr(&self, _size: usize) -> Option<*mut u8> {
+        None
+    }
+
+    /// Submit (or, with `discard`, drop) a record returned by [`BpfMap::reserve_ptr`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `reserve_ptr` on this map and not released yet.
+    unsafe fn release_ptr(&self, _ptr: *mut u8, _discard: bool) -> MapResult<()> {
+        Err(MapError::NotSupported)
+    }
+
+    /// Append a sample taken at `timestamp_ns`.
+    ///
+    /// Only time-series maps support this; other maps return `NotSupported`.
+    fn push_sample(&self, _timestamp_ns: u64, _value: &[u8]) -> MapResult<()> {
+        Err(MapError::NotSupported)
+    }
+
+    /// Compute statistics over the newest `n` samples.
+    ///
+    /// Only time-series maps support this; other maps return `NotSupported`.
+    /// Returns `KeyNotFound` if there are no samples.
+    fn sample_stats(&self, _n: usize) -> MapResult<TimeSeriesStats> {
+        Err(MapError::NotSupported)
+    }
+
+    /// Get the samples taken within `[start_ns, end_ns]`, oldest first.
+    ///
+    /// Only time-series maps support this; other maps return `NotSupported`.
+    fn samples_in_window(
+        &self,
+        _start_ns: u64,
+        _end_ns: u64,
+    ) -> MapResult<alloc::vec::Vec<(u64, alloc::vec::Vec<u8>)>> {
+        Err(MapError::NotSupported)
+    }
+
+    /// Write a sample to the perf ring of `cpu`.
+    ///
+    /// Only perf event arrays support this; other maps return `NotSupported`.
+    fn perf_output(&self, _cpu: u32, _data: &[u8]) -> MapResult<()> {
+        Err(MapError::NotSupported)
+    }
+
+    /// Store a call stack and return its ID.
+    ///
+    /// Only stack trace maps support this; other maps return `NotSupported`.
+    /// With `reuse`, a different stack with the same ID is replaced.
+    fn store_stack(&self, _ips: &[u64], _reuse: bool) -> MapResult<u32> {
+        Err(MapError::NotSupported)
+    }
+
     /// Resize the map (cloud profile only).
     ///
     /// This method is completely erased from embedded builds.
@@ -0,0 +1,559 @@
+//! Perf Event Array Map Implementation
+//!
+//! A perf event array holds one perf ring per CPU, which BPF programs write
+//! samples to with `bpf_perf_event_output`. Each ring is laid out like the
+//! mapping of a Linux perf event, so readers built on libbpf's `perf_buffer`
+//! can consume it with minimal porting.
+//!
+//! # Memory Layout
+//!
+//! ```text
+//! ┌──────────────────────┬───────────────────────────────────────┐
+//! │ Metadata page        │ Data pages (2^n)                      │
+//! │ perf_event_mmap_page │ ┌────────┬────────┬────────┬───────┐ │
+//! │   data_head  @ 1024  │ │ record │ record │ record │       │ │
+//! │   data_tail  @ 1032  │ └────────┴────────┴────────┴───────┘ │
+//! │   data_offset @ 1040 │      ▲                      ▲         │
+//! │   data_size  @ 1048  │    tail                   head        │
+//! └──────────────────────┴───────────────────────────────────────┘
+//! ```
+//!
+//! The producer advances `data_head`; the reader consumes records up to it
+//! and then advances `data_tail`. Both are free-running byte counters.
+//! Records may wrap around the end of the data pages, as on Linux.
+//!
+//! # Record Format
+//!
+//! Every record starts with a `perf_event_header`:
+//!
+//! ```text
+//! ┌──────────────────┬────────────────┬────────────────┐
+//! │ type (4 bytes)   │ misc (2 bytes) │ size (2 bytes) │
+//! └──────────────────┴────────────────┴────────────────┘
+//! ```
+//!
+//! - `PERF_RECORD_SAMPLE`: a `u32` size followed by that many bytes of raw
+//!   sample data, zero-padded so the record is 8-byte aligned.
+//! - `PERF_RECORD_LOST`: a `u64` id (always 0) and the `u64` number of
+//!   samples dropped because the ring was full.
+//!
+//! # Allocation
+//!
+//! As with a perf event, a CPU's ring is allocated when userspace first maps
+//! it ([`PerfEventArrayMap::open`]), sized by the mapping. Samples for CPUs
+//! without a ring are dropped.
+
+extern crate alloc;
+
+use alloc::alloc::{Layout, alloc_zeroed, dealloc};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use spin::{Mutex, Once};
+
+use super::{BpfMap, MapDef, MapError, MapResult, MapType};
+use crate::profile::{ActiveProfile, PhysicalProfile};
+
+/// Size of the metadata page and granularity of the data pages
+pub const PERF_PAGE_SIZE: usize = 4096;
+
+/// Record type of dropped samples
+pub const PERF_RECORD_LOST: u32 = 2;
+
+/// Record type of samples
+pub const PERF_RECORD_SAMPLE: u32 = 9;
+
+/// `bpf_perf_event_output` flags value selecting the current CPU's ring
+pub const BPF_F_CURRENT_CPU: u64 = 0xffff_ffff;
+
+/// `perf_event_mmap_page` field offsets
+mod meta {
+    pub const DATA_HEAD: usize = 1024;
+    pub const DATA_TAIL: usize = 1032;
+    pub const DATA_OFFSET: usize = 1040;
+    pub const DATA_SIZE: usize = 1048;
+}
+
+/// Size of a `perf_event_header`
+const HEADER_SIZE: usize = 8;
+
+/// Size of a lost record
+const LOST_SIZE: usize = HEADER_SIZE + 16;
+
+/// A record read back from a perf ring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PerfRecord {
+    /// Raw sample data, including padding
+    Sample(Vec<u8>),
+    /// Number of samples dropped
+    Lost(u64),
+}
+
+/// One CPU's perf ring: a metadata page followed by the data pages.
+pub struct PerfRing {
+    /// Start of the metadata page
+    base: NonNull<u8>,
+    /// Size of the data area, a power of two
+    data_size: usize,
+    /// Serializes producers
+    writer: Mutex<()>,
+    /// Samples dropped and not yet reported with a lost record
+    lost: AtomicU64,
+}
+
+impl PerfRing {
+    /// Allocate a ring with `data_size` bytes of data pages.
+    fn new(data_size: usize) -> MapResult<Self> {
+        let layout = Self::layout(data_size)?;
+        // SAFETY: The layout has a non-zero size.
+        let base = NonNull::new(unsafe { alloc_zeroed(layout) }).ok_or(MapError::OutOfMemory)?;
+
+        let ring = Self {
+            base,
+            data_size,
+            writer: Mutex::new(()),
+            lost: AtomicU64::new(0),
+        };
+        ring.meta(meta::DATA_OFFSET)
+            .store(PERF_PAGE_SIZE as u64, Ordering::Relaxed);
+        ring.meta(meta::DATA_SIZE)
+            .store(data_size as u64, Ordering::Relaxed);
+        Ok(ring)
+    }
+
+    fn layout(data_size: usize) -> MapResult<Layout> {
+        Layout::from_size_align(PERF_PAGE_SIZE + data_size, PERF_PAGE_SIZE)
+            .map_err(|_| MapError::OutOfMemory)
+    }
+
+    /// Get a `u64` field of the metadata page.
+    fn meta(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: All field offsets are 8-byte aligned and within the page,
+        // which lives as long as the ring. The fields are only accessed
+        // atomically.
+        unsafe { &*(self.base.as_ptr().add(offset) as *const AtomicU64) }
+    }
+
+    /// Start of the ring, to map it into a process.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.base.as_ptr()
+    }
+
+    /// Size of the ring, including the metadata page.
+    pub fn mmap_size(&self) -> usize {
+        PERF_PAGE_SIZE + self.data_size
+    }
+
+    /// Size of the data area.
+    pub fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    /// Number of samples dropped and not yet reported with a lost record.
+    pub fn lost_count(&self) -> u64 {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Copy the ring, metadata page first, starting at `offset`.
+    ///
+    /// Returns the number of bytes copied.
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.mmap_size().saturating_sub(offset));
+        if len == 0 {
+            return 0;
+        }
+        // SAFETY: The range is within the ring. Concurrent writes can only
+        // tear the copy, like those of a reader of the mapping.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.base.as_ptr().add(offset), buf.as_mut_ptr(), len);
+        }
+        len
+    }
+
+    /// Write a sample.
+    ///
+    /// If samples were dropped since the last one written, a lost record is
+    /// written first. Returns `MapFull` (and counts the sample as lost) if
+    /// the ring is full or another producer is writing to it, which happens
+    /// when a program interrupts one on the same CPU.
+    pub fn output(&self, data: &[u8]) -> MapResult<()> {
+        // The raw size includes the padding
+        let raw_size = (4 + data.len()).next_multiple_of(8) - 4;
+        let size = HEADER_SIZE + 4 + raw_size;
+        if size > usize::from(u16::MAX) || size > self.data_size {
+            return Err(MapError::InvalidValue);
+        }
+
+        let Some(_writer) = self.writer.try_lock() else {
+            self.lost.fetch_add(1, Ordering::Relaxed);
+            return Err(MapError::MapFull);
+        };
+
+        let mut head = self.meta(meta::DATA_HEAD).load(Ordering::Relaxed);
+        // The reader may write anything to data_tail; a bogus one makes the
+        // ring look full
+        let tail = self.meta(meta::DATA_TAIL).load(Ordering::Acquire);
+        let free = self
+            .data_size
+            .saturating_sub(head.wrapping_sub(tail) as usize);
+
+        let lost = self.lost.swap(0, Ordering::Relaxed);
+        let needed = if lost > 0 { LOST_SIZE + size } else { size };
+        if free < needed {
+            self.lost.fetch_add(lost + 1, Ordering::Relaxed);
+            return Err(MapError::MapFull);
+        }
+
+        if lost > 0 {
+            self.write_header(head, PERF_RECORD_LOST, LOST_SIZE);
+            self.write_at(head + 8, &0u64.to_ne_bytes());
+            self.write_at(head + 16, &lost.to_ne_bytes());
+            head += LOST_SIZE as u64;
+        }
+
+        self.write_header(head, PERF_RECORD_SAMPLE, size);
+        self.write_at(head + 8, &(raw_size as u32).to_ne_bytes());
+        self.write_at(head + 12, data);
+        self.write_at(
+            head + 12 + data.len() as u64,
+            &[0; 8][..raw_size - data.len()],
+        );
+
+        self.meta(meta::DATA_HEAD)
+            .store(head + size as u64, Ordering::Release);
+        Ok(())
+    }
+
+    /// Consume the oldest record, like a reader of the mapping would.
+    pub fn consume(&self) -> Option<PerfRecord> {
+        let head = self.meta(meta::DATA_HEAD).load(Ordering::Acquire);
+        let tail = self.meta(meta::DATA_TAIL).load(Ordering::Relaxed);
+        if head == tail {
+            return None;
+        }
+
+        let mut header = [0u8; HEADER_SIZE];
+        self.read_at(tail, &mut header);
+        let kind = u32::from_ne_bytes(header[0..4].try_into().ok()?);
+        let size = u16::from_ne_bytes(header[6..8].try_into().ok()?);
+
+        let mut body = [0u8; 8];
+        self.read_at(tail + 8, &mut body);
+        let record = if kind == PERF_RECORD_LOST {
+            self.read_at(tail + 16, &mut body);
+            PerfRecord::Lost(u64::from_ne_bytes(body))
+        } else {
+            let raw_size = u32::from_ne_bytes(body[0..4].try_into().ok()?);
+            let mut raw = alloc::vec![0u8; raw_size as usize];
+            self.read_at(tail + 12, &mut raw);
+            PerfRecord::Sample(raw)
+        };
+
+        self.meta(meta::DATA_TAIL)
+            .store(tail + u64::from(size), Ordering::Release);
+        Some(record)
+    }
+
+    fn write_header(&self, pos: u64, kind: u32, size: usize) {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(&kind.to_ne_bytes());
+        header[6..8].copy_from_slice(&(size as u16).to_ne_bytes());
+        self.write_at(pos, &header);
+    }
+
+    /// Copy `bytes` to the data area at ring position `pos`, wrapping
+    /// around its end.
+    fn write_at(&self, pos: u64, bytes: &[u8]) {
+        let offset = pos as usize & (self.data_size - 1);
+        let first = bytes.len().min(self.data_size - offset);
+        // SAFETY: Both parts are within the data area. Readers only look at
+        // bytes below data_head, which are not written again until they
+        // advance data_tail past them.
+        unsafe {
+            let data = self.base.as_ptr().add(PERF_PAGE_SIZE);
+            core::ptr::copy_nonoverlapping(bytes.as_ptr(), data.add(offset), first);
+            core::ptr::copy_nonoverlapping(bytes[first..].as_ptr(), data, bytes.len() - first);
+        }
+    }
+
+    /// Copy from the data area at ring position `pos`, wrapping around its
+    /// end.
+    fn read_at(&self, pos: u64, bytes: &mut [u8]) {
+        let offset = pos as usize & (self.data_size - 1);
+        let first = bytes.len().min(self.data_size - offset);
+        // SAFETY: Both parts are within the data area.
+        unsafe {
+            let data = self.base.as_ptr().add(PERF_PAGE_SIZE);
+            core::ptr::copy_nonoverlapping(data.add(offset), bytes.as_mut_ptr(), first);
+            core::ptr::copy_nonoverlapping(data, bytes[first..].as_mut_ptr(), bytes.len() - first);
+        }
+    }
+}
+
+impl Drop for PerfRing {
+    fn drop(&mut self) {
+        // SAFETY: The memory was allocated in new() with the same layout.
+        unsafe { dealloc(self.base.as_ptr(), Self::layout(self.data_size).unwrap()) };
+    }
+}
+
+// SAFETY: The ring memory is owned by the ring. The metadata fields are only
+// accessed atomically and the data area is only written under the writer lock.
+unsafe impl Send for PerfRing {}
+// SAFETY: See above.
+unsafe impl Sync for PerfRing {}
+
+/// Perf event array map implementation.
+///
+/// Holds one [`PerfRing`] per CPU, indexed by CPU number.
+pub struct PerfEventArrayMap<P: PhysicalProfile = ActiveProfile> {
+    /// Map definition
+    def: MapDef,
+    /// Ring of each CPU, once mapped
+    rings: Vec<Once<PerfRing>>,
+    /// Profile marker
+    _profile: PhantomData<fn() -> P>,
+}
+
+impl<P: PhysicalProfile> PerfEventArrayMap<P> {
+    /// Maximum data area size of a ring for embedded profile.
+    #[cfg(feature = "embedded-profile")]
+    const MAX_DATA_SIZE: usize = 64 * 1024; // 64 KB
+
+    /// Maximum data area size of a ring for cloud profile.
+    #[cfg(feature = "cloud-profile")]
+    const MAX_DATA_SIZE: usize = 256 * 1024 * 1024; // 256 MB
+
+    /// Create a perf event array for `cpus` CPUs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `cpus` is 0.
+    pub fn new(cpus: u32) -> MapResult<Self> {
+        if cpus == 0 {
+            return Err(MapError::InvalidValue);
+        }
+
+        Ok(Self {
+            def: MapDef::new(MapType::PerfEventArray, 4, 4, cpus),
+            rings: (0..cpus).map(|_| Once::new()).collect(),
+            _profile: PhantomData,
+        })
+    }
+
+    /// Get the ring of `cpu` for a mapping of `size` bytes, allocating it
+    /// on first use.
+    ///
+    /// `size` covers the metadata page and a power-of-two number of data
+    /// pages. Later mappings must have the same size.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidKey` for an unknown CPU, `InvalidValue` for a bad
+    /// size and `OutOfMemory` if the ring can't be allocated.
+    pub fn open(&self, cpu: u32, size: usize) -> MapResult<&PerfRing> {
+        let slot = self.rings.get(cpu as usize).ok_or(MapError::InvalidKey)?;
+
+        let data_size = size.saturating_sub(PERF_PAGE_SIZE);
+        if !data_size.is_multiple_of(PERF_PAGE_SIZE) || !data_size.is_power_of_two() {
+            return Err(MapError::InvalidValue);
+        }
+        if data_size > Self::MAX_DATA_SIZE {
+            return Err(MapError::OutOfMemory);
+        }
+
+        let ring = slot.try_call_once(|| PerfRing::new(data_size))?;
+        if ring.data_size != data_size {
+            return Err(MapError::InvalidValue);
+        }
+        Ok(ring)
+    }
+
+    /// Get the ring of `cpu`, if it was mapped.
+    pub fn ring(&self, cpu: u32) -> Option<&PerfRing> {
+        self.rings.get(cpu as usize)?.get()
+    }
+
+    /// Number of CPUs.
+    pub fn cpus(&self) -> u32 {
+        self.def.max_entries
+    }
+
+    /// Write a sample to the ring of `cpu`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidKey` for an unknown CPU, `KeyNotFound` if its ring
+    /// was not mapped, and otherwise see [`PerfRing::output`].
+    pub fn output(&self, cpu: u32, data: &[u8]) -> MapResult<()> {
+        let slot = self.rings.get(cpu as usize).ok_or(MapError::InvalidKey)?;
+        slot.get().ok_or(MapError::KeyNotFound)?.output(data)
+    }
+}
+
+impl<P: PhysicalProfile> BpfMap<P> for PerfEventArrayMap<P> {
+    fn lookup(&self, _key: &[u8]) -> Option<Vec<u8>> {
+        // Rings are read through their mapping
+        None
+    }
+
+    fn update(&self, _key: &[u8], _value: &[u8], _flags: u64) -> MapResult<()> {
+        // Rings are created by mapping them, not by storing perf event fds
+        Err(MapError::NotSupported)
+    }
+
+    fn delete(&self, _key: &[u8]) -> MapResult<()> {
+        Err(MapError::NotSupported)
+    }
+
+    fn def(&self) -> &MapDef {
+        &self.def
+    }
+
+    fn perf_output(&self, cpu: u32, data: &[u8]) -> MapResult<()> {
+        self.output(cpu, data)
+    }
+
+    #[cfg(feature = "cloud-profile")]
+    fn resize(&mut self, _new_max_entries: u32) -> MapResult<()> {
+        // The number of CPUs does not change
+        Err(MapError::NotSupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    const ONE_PAGE: usize = 2 * PERF_PAGE_SIZE;
+
+    fn meta(ring: &PerfRing, offset: usize) -> u64 {
+        let mut bytes = [0u8; 8];
+        ring.read(offset, &mut bytes);
+        u64::from_ne_bytes(bytes)
+    }
+
+    #[test]
+    fn open_validates_size() {
+        let map = PerfEventArrayMap::<ActiveProfile>::new(2).expect("create map");
+        assert!(map.ring(0).is_none());
+
+        assert_eq!(
+            map.open(0, PERF_PAGE_SIZE).err(),
+            Some(MapError::InvalidValue)
+        );
+        assert_eq!(
+            map.open(0, 4 * PERF_PAGE_SIZE).err(),
+            Some(MapError::InvalidValue)
+        );
+        assert_eq!(map.open(2, ONE_PAGE).err(), Some(MapError::InvalidKey));
+
+        let ring = map.open(1, ONE_PAGE).expect("open ring");
+        assert_eq!(ring.mmap_size(), ONE_PAGE);
+        assert_eq!(ring.as_ptr() as usize % PERF_PAGE_SIZE, 0);
+        assert_eq!(meta(ring, meta::DATA_OFFSET), PERF_PAGE_SIZE as u64);
+        assert_eq!(meta(ring, meta::DATA_SIZE), PERF_PAGE_SIZE as u64);
+
+        // A later mapping must match the first one
+        assert!(map.open(1, ONE_PAGE).is_ok());
+        assert_eq!(
+            map.open(1, 3 * PERF_PAGE_SIZE).err(),
+            Some(MapError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn output_sample_record() {
+        let map = PerfEventArrayMap::<ActiveProfile>::new(1).expect("create map");
+        assert_eq!(map.output(0, b"abc"), Err(MapError::KeyNotFound));
+
+        let ring = map.open(0, ONE_PAGE).expect("open ring");
+        map.output(0, b"hello").expect("output");
+
+        // header + u32 size + 5 bytes, padded to 24
+        assert_eq!(meta(ring, meta::DATA_HEAD), 24);
+        let mut record = [0u8; 24];
+        ring.read(PERF_PAGE_SIZE, &mut record);
+        assert_eq!(
+            u32::from_ne_bytes(record[0..4].try_into().unwrap()),
+            PERF_RECORD_SAMPLE
+        );
+        assert_eq!(u16::from_ne_bytes(record[6..8].try_into().unwrap()), 24);
+        assert_eq!(u32::from_ne_bytes(record[8..12].try_into().unwrap()), 12);
+        assert_eq!(&record[12..17], b"hello");
+
+        assert_eq!(
+            ring.consume(),
+            Some(PerfRecord::Sample(b"hello\0\0\0\0\0\0\0".to_vec()))
+        );
+        assert_eq!(meta(ring, meta::DATA_TAIL), 24);
+        assert_eq!(ring.consume(), None);
+    }
+
+    #[test]
+    fn output_wraps_around() {
+        let map = PerfEventArrayMap::<ActiveProfile>::new(1).expect("create map");
+        let ring = map.open(0, ONE_PAGE).expect("open ring");
+
+        // 1000-byte samples take 1016 bytes; the fifth one wraps
+        let samples: Vec<Vec<u8>> = (0..8u8).map(|i| vec![i; 1000]).collect();
+        for sample in &samples {
+            ring.output(sample).expect("output");
+            match ring.consume() {
+                Some(PerfRecord::Sample(raw)) => assert_eq!(&raw[..1000], &sample[..]),
+                other => panic!("unexpected record {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn full_ring_reports_lost_samples() {
+        let map = PerfEventArrayMap::<ActiveProfile>::new(1).expect("create map");
+        let ring = map.open(0, ONE_PAGE).expect("open ring");
+
+        // Four 1016-byte records fit in a page, the next two are dropped
+        for _ in 0..6 {
+            let _ = ring.output(&[1; 1000]);
+        }
+        assert_eq!(ring.lost_count(), 2);
+        assert_eq!(ring.output(&[2; 8]), Err(MapError::MapFull));
+        assert_eq!(ring.lost_count(), 3);
+
+        // Once there is room again, the drops are reported first
+        for _ in 0..4 {
+            assert!(matches!(ring.consume(), Some(PerfRecord::Sample(_))));
+        }
+        ring.output(&[2; 4]).expect("output");
+        assert_eq!(ring.consume(), Some(PerfRecord::Lost(3)));
+        assert_eq!(ring.consume(), Some(PerfRecord::Sample(vec![2, 2, 2, 2])));
+        assert_eq!(ring.lost_count(), 0);
+    }
+
+    #[test]
+    fn oversized_sample_rejected() {
+        let map = PerfEventArrayMap::<ActiveProfile>::new(1).expect("create map");
+        let ring = map.open(0, ONE_PAGE).expect("open ring");
+        assert_eq!(
+            ring.output(&[0; PERF_PAGE_SIZE]),
+            Err(MapError::InvalidValue)
+        );
+        assert_eq!(ring.lost_count(), 0);
+    }
+
+    #[test]
+    fn bpf_map_interface() {
+        let map = PerfEventArrayMap::<ActiveProfile>::new(2).expect("create map");
+        assert_eq!(map.def().map_type, MapType::PerfEventArray);
+        assert_eq!(map.cpus(), 2);
+
+        map.open(1, ONE_PAGE).expect("open ring");
+        let map: &dyn BpfMap<ActiveProfile> = &map;
+        assert!(map.perf_output(1, b"x").is_ok());
+        assert_eq!(map.perf_output(0, b"x"), Err(MapError::KeyNotFound));
+        assert_eq!(map.update(&[0; 4], &[0; 4], 0), Err(MapError::NotSupported));
+    }
+}
@@ -41,6 +41,15 @@
 //! └─────────────────────────────────────────────┘
 //! ```
 //!
+//! # In-Place Records
+//!
+//! `bpf_ringbuf_reserve` hands the program a pointer straight into the ring,
+//! so a record is written once instead of being built on the stack and
+//! copied. Records never straddle the end of the buffer: when one would, the
+//! remaining tail is filled with a discarded padding record and the record
+//! starts at offset 0. A reserved record is marked busy, which stops
+//! consumers at it until it is submitted or discarded.
+//!
 //! # Profile Differences
 //!
 //! | Feature       | Cloud          | Embedded       |
@@ -111,9 +120,9 @@ impl EventHeader {
     /// Total size of this event including header, 8-byte aligned
     fn total_size(&self) -> usize {
         let data_size = self.length as usize;
-        let total = EventHeader::SIZE + data_size;
+        let total = EventHeader::SIZE.saturating_add(data_size);
         // Round up to 8-byte alignment
-        (total + 7) & !7
+        total.saturating_add(7) & !7
     }
 }
 
@@ -252,13 +261,29 @@ impl<P: PhysicalProfile> RingBufMap<P> {
 
     /// Reserve space for writing an event.
     ///
-    /// Returns a reservation that must be submitted or discarded.
+    /// Returns a reservation that must be submitted or discarded. The
+    /// record's data area is contiguous in the buffer. Fails for empty
+    /// records and records that could never fit.
     pub fn reserve(&self, size: usize) -> Option<RingBufReservation> {
-        let total_size = EventHeader::SIZE + size;
-        let aligned_size = (total_size + 7) & !7;
+        if size == 0 || size > self.control.capacity.saturating_sub(EventHeader::SIZE) {
+            return None;
+        }
+        let total_size = EventHeader::SIZE.checked_add(size)?;
+        let aligned_size = total_size.checked_add(7)? & !7;
+
+        let mut buffer = self.data.lock();
+        let head = self.control.head.load(Ordering::Acquire);
+        let offset = self.control.wrap(head);
+
+        // Pad to the end of the buffer if the record would wrap
+        let padding = if offset + aligned_size > self.control.capacity {
+            self.control.capacity - offset
+        } else {
+            0
+        };
 
         // Check if there's enough space
-        if self.control.available_space() < aligned_size {
+        if self.control.available_space() < padding + aligned_size {
             self.dropped_events.fetch_add(1, Ordering::Relaxed);
 
             // In embedded profile, drop newest (this reservation)
@@ -271,12 +296,25 @@ impl<P: PhysicalProfile> RingBufMap<P> {
             return None;
         }
 
-        // Allocate space
-        let head = self
-            .control
+        if padding > 0 {
+            let pad = EventHeader {
+                length: (padding - EventHeader::SIZE) as u32,
+                flags: EventHeader::FLAG_DISCARD,
+            };
+            self.write_wrapped(&mut buffer, offset, &pad.as_bytes());
+        }
+
+        // Mark the record busy until it is submitted or discarded
+        let offset = self.control.wrap(head + padding as u64);
+        let header = EventHeader {
+            length: size as u32,
+            flags: EventHeader::FLAG_BUSY,
+        };
+        self.write_wrapped(&mut buffer, offset, &header.as_bytes());
+
+        self.control
             .head
-            .fetch_add(aligned_size as u64, Ordering::AcqRel);
-        let offset = self.control.wrap(head);
+            .store(head + (padding + aligned_size) as u64, Ordering::Release);
 
         Some(RingBufReservation {
             offset,
@@ -287,7 +325,8 @@ impl<P: PhysicalProfile> RingBufMap<P> {
 
     /// Submit data to a reservation.
     ///
-    /// This makes the event visible to consumers.
+    /// This copies `data` into the record and makes the event visible to
+    /// consumers. Unused bytes of the record are zeroed.
     pub fn submit(&self, reservation: &RingBufReservation, data: &[u8]) -> MapResult<()> {
         if data.len() > reservation.data_size {
             return Err(MapError::InvalidValue);
@@ -295,18 +334,73 @@ impl<P: PhysicalProfile> RingBufMap<P> {
 
         let mut buffer = self.data.lock();
 
-        // Write header
-        let header = EventHeader::new(data.len() as u32);
-        let header_bytes = header.as_bytes();
-        self.write_wrapped(&mut buffer, reservation.offset, &header_bytes);
-
-        // Write data
-        let data_offset = (reservation.offset + EventHeader::SIZE) & self.control.mask;
-        self.write_wrapped(&mut buffer, data_offset, data);
+        let data_offset = reservation.offset + EventHeader::SIZE;
+        let record = &mut buffer[data_offset..data_offset + reservation.data_size];
+        record[..data.len()].copy_from_slice(data);
+        record[data.len()..].fill(0);
 
+        self.finish(&mut buffer, reservation, 0);
         Ok(())
     }
 
+    /// Submit a reservation whose data was written in place.
+    pub fn commit(&self, reservation: RingBufReservation) {
+        let mut buffer = self.data.lock();
+        self.finish(&mut buffer, &reservation, 0);
+    }
+
+    /// Discard a reservation; consumers skip it.
+    pub fn discard(&self, reservation: RingBufReservation) {
+        let mut buffer = self.data.lock();
+        self.finish(&mut buffer, &reservation, EventHeader::FLAG_DISCARD);
+    }
+
+    /// Rewrite a reservation's header, clearing the busy flag.
+    fn finish(&self, buffer: &mut [u8], reservation: &RingBufReservation, flags: u32) {
+        let mut header = EventHeader::new(reservation.data_size as u32);
+        header.flags = flags;
+        self.write_wrapped(buffer, reservation.offset, &header.as_bytes());
+    }
+
+    /// Get a pointer to the data area of a reservation.
+    ///
+    /// The pointer stays valid until the reservation is submitted or
+    /// discarded, as long as the map is neither resized nor dropped.
+    pub fn reservation_ptr(&self, reservation: &RingBufReservation) -> *mut u8 {
+        let mut buffer = self.data.lock();
+        // SAFETY: reserve() keeps the data area inside the buffer.
+        unsafe {
+            buffer
+                .as_mut_ptr()
+                .add(reservation.offset + EventHeader::SIZE)
+        }
+    }
+
+    /// Recover a pending reservation from its data pointer.
+    ///
+    /// Returns `None` if `ptr` does not point at the data area of a busy
+    /// record in this buffer.
+    pub fn reservation_at(&self, ptr: *const u8) -> Option<RingBufReservation> {
+        let buffer = self.data.lock();
+        let base = buffer.as_ptr() as usize;
+        let data_offset = (ptr as usize).checked_sub(base)?;
+        let offset = data_offset.checked_sub(EventHeader::SIZE)?;
+        if data_offset >= self.control.capacity || offset % 8 != 0 {
+            return None;
+        }
+
+        let header = EventHeader::from_bytes(&buffer[offset..offset + EventHeader::SIZE])?;
+        if !header.is_busy() {
+            return None;
+        }
+
+        Some(RingBufReservation {
+            offset,
+            data_size: header.length as usize,
+            total_size: header.total_size(),
+        })
+    }
+
     /// Output data directly to the ring buffer.
     ///
     /// This is a convenience method combining reserve + submit.
@@ -452,6 +546,21 @@ impl<P: PhysicalProfile> BpfMap<P> for RingBufMap<P> {
         &self.def
     }
 
+    unsafe fn reserve_ptr(&self, size: usize) -> Option<*mut u8> {
+        let reservation = self.reserve(size)?;
+        Some(self.reservation_ptr(&reservation))
+    }
+
+    unsafe fn release_ptr(&self, ptr: *mut u8, discard: bool) -> MapResult<()> {
+        let reservation = self.reservation_at(ptr).ok_or(MapError::InvalidValue)?;
+        if discard {
+            self.discard(reservation);
+        } else {
+            self.commit(reservation);
+        }
+        Ok(())
+    }
+
     #[cfg(feature = "cloud-profile")]
     fn resize(&mut self, new_max_entries: u32) -> MapResult<()> {
         let new_size = new_max_entries as usize;
@@ -564,6 +673,56 @@ mod tests {
         assert_eq!(result, data);
     }
 
+    #[test]
+    fn ringbuf_reserve_in_place() {
+        let ringbuf = RingBufMap::<ActiveProfile>::new(4096).expect("create ringbuf");
+
+        let reservation = ringbuf.reserve(8).expect("reserve");
+        let ptr = ringbuf.reservation_ptr(&reservation);
+        // SAFETY: ptr points at the 8-byte data area of the reservation.
+        unsafe { ptr.cast::<u64>().write_unaligned(0x1234) };
+
+        // Busy records are not visible to consumers
+        assert!(ringbuf.poll().is_none());
+
+        let pending = ringbuf.reservation_at(ptr).expect("pending reservation");
+        ringbuf.commit(pending);
+        assert!(ringbuf.reservation_at(ptr).is_none());
+
+        let result = ringbuf.poll().expect("poll");
+        assert_eq!(result, 0x1234u64.to_ne_bytes());
+    }
+
+    #[test]
+    fn ringbuf_discard_is_skipped() {
+        let ringbuf = RingBufMap::<ActiveProfile>::new(4096).expect("create ringbuf");
+
+        let first = ringbuf.reserve(16).expect("reserve");
+        ringbuf.output(b"kept", 0).expect("output");
+        ringbuf.discard(first);
+
+        assert_eq!(ringbuf.poll().expect("poll"), b"kept");
+        assert!(ringbuf.poll().is_none());
+    }
+
+    #[test]
+    fn ringbuf_reserve_never_wraps() {
+        let ringbuf = RingBufMap::<ActiveProfile>::new(64).expect("create ringbuf");
+
+        // Leave 24 bytes at the end of the buffer
+        ringbuf.output(&[1u8; 32], 0).expect("output");
+        assert_eq!(ringbuf.poll().expect("poll"), [1u8; 32]);
+
+        // A 24-byte record (32 with header) does not fit in the tail, so it
+        // is placed at the start of the buffer behind a padding record
+        let reservation = ringbuf.reserve(24).expect("reserve");
+        assert_eq!(reservation.offset, 0);
+        ringbuf.submit(&reservation, &[2u8; 24]).expect("submit");
+
+        assert_eq!(ringbuf.poll().expect("poll"), [2u8; 24]);
+        assert!(ringbuf.is_empty());
+    }
+
     #[test]
     fn ringbuf_buffer_full() {
         // Very small buffer
@@ -592,4 +751,15 @@ mod tests {
         let result = RingBufMap::<ActiveProfile>::new(0);
         assert!(matches!(result, Err(MapError::InvalidValue)));
     }
+
+    #[test]
+    fn ringbuf_reserve_rejects_bad_sizes() {
+        let ringbuf = RingBufMap::<ActiveProfile>::new(64).expect("create ringbuf");
+
+        assert!(ringbuf.reserve(0).is_none());
+        assert!(ringbuf.reserve(64).is_none());
+        assert!(ringbuf.reserve(usize::MAX).is_none());
+        assert!(ringbuf.reserve(usize::MAX - 7).is_none());
+        assert!(ringbuf.is_empty());
+    }
 }
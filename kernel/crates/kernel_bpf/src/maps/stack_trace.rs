@@ -0,0 +1,339 @@
+//! Stack Trace Map Implementation
+//!
+//! A BPF stack trace map stores call stacks captured by `bpf_get_stackid`,
+//! deduplicated by ID. The ID of a stack is its hash modulo the number of
+//! buckets, so identical stacks always get the same ID and aggregation maps
+//! can key on it instead of the frames.
+//!
+//! Each bucket holds one stack. When a different stack hashes to an
+//! occupied bucket, the helper fails with `EEXIST` unless the program passes
+//! `BPF_F_REUSE_STACKID`, which replaces the old stack.
+//!
+//! # Memory Layout
+//!
+//! ```text
+//! key:   u32 stack ID
+//! value: u64 instruction pointers, innermost first, zero-padded to
+//!        value_size (= 8 * max depth)
+//! ```
+//!
+//! # Usage
+//!
+//! ```c
+//! // Kernel and user stacks of the interrupted code
+//! long kstack = bpf_get_stackid(ctx, &stacks, 0);
+//! long ustack = bpf_get_stackid(ctx, &stacks, BPF_F_USER_STACK);
+//! ```
+//!
+//! # Profile Differences
+//!
+//! | Feature       | Cloud          | Embedded       |
+//! |---------------|----------------|----------------|
+//! | Max buckets   | Up to 64K      | Up to 1K       |
+//! | Allocation    | At creation    | At creation    |
+//! | Resize        | Not supported  | **Erased**     |
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use spin::Mutex;
+
+use super::{BpfMap, MapDef, MapError, MapResult, MapType};
+use crate::profile::{ActiveProfile, PhysicalProfile};
+
+/// Number of leading frames to skip, in the low bits of the helper flags.
+pub const BPF_F_SKIP_FIELD_MASK: u64 = 0xff;
+
+/// Capture the user stack instead of the kernel stack.
+pub const BPF_F_USER_STACK: u64 = 1 << 8;
+
+/// Compare stacks by hash only (accepted for compatibility; stacks are
+/// always compared in full).
+pub const BPF_F_FAST_STACK_CMP: u64 = 1 << 9;
+
+/// Replace the stack of an occupied bucket instead of failing.
+pub const BPF_F_REUSE_STACKID: u64 = 1 << 10;
+
+/// Most frames a stack can hold.
+pub const MAX_STACK_DEPTH: usize = 127;
+
+/// One bucket, holding the stack whose ID is the bucket index.
+struct Bucket {
+    hash: u32,
+    /// Number of frames in `ips`, 0 for an empty bucket
+    nr: usize,
+    ips: Box<[u64]>,
+}
+
+/// Stack trace map implementation.
+///
+/// Buckets are allocated when the map is created; storing a stack never
+/// allocates, so programs can capture stacks from interrupt context.
+pub struct StackTraceMap<P: PhysicalProfile = ActiveProfile> {
+    /// Map definition
+    def: MapDef,
+    /// Buckets, a power of two of them
+    buckets: Vec<Mutex<Bucket>>,
+    /// Profile marker
+    _profile: PhantomData<fn() -> P>,
+}
+
+impl<P: PhysicalProfile> StackTraceMap<P> {
+    /// Maximum buckets for embedded profile.
+    #[cfg(feature = "embedded-profile")]
+    const MAX_ENTRIES: usize = 1024;
+
+    /// Maximum buckets for cloud profile.
+    #[cfg(feature = "cloud-profile")]
+    const MAX_ENTRIES: usize = 64 * 1024;
+
+    /// Create a new stack trace map.
+    ///
+    /// # Arguments
+    ///
+    /// * `value_size` - Size of a stack in bytes, 8 per frame
+    /// * `max_entries` - Number of stacks, rounded up to a power of two
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidValue` if the value size is not a multiple of 8 or
+    /// exceeds [`MAX_STACK_DEPTH`] frames, and `OutOfMemory` if there are
+    /// too many entries.
+    pub fn new(value_size: u32, max_entries: u32) -> MapResult<Self> {
+        let depth = value_size as usize / 8;
+        if depth == 0 || !value_size.is_multiple_of(8) || depth > MAX_STACK_DEPTH {
+            return Err(MapError::InvalidValue);
+        }
+
+        if max_entries == 0 {
+            return Err(MapError::InvalidValue);
+        }
+
+        let n_buckets = (max_entries as usize).next_power_of_two();
+        if n_buckets > Self::MAX_ENTRIES {
+            return Err(MapError::OutOfMemory);
+        }
+
+        // Check memory budget for embedded profile
+        #[cfg(feature = "embedded-profile")]
+        {
+            use crate::profile::MemoryStrategy;
+            let total_size = value_size as usize * n_buckets;
+            let budget = <P::MemoryStrategy as MemoryStrategy>::MEMORY_BUDGET;
+            if budget > 0 && total_size > budget {
+                return Err(MapError::OutOfMemory);
+            }
+        }
+
+        let buckets = (0..n_buckets)
+            .map(|_| {
+                Mutex::new(Bucket {
+                    hash: 0,
+                    nr: 0,
+                    ips: vec![0; depth].into_boxed_slice(),
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            def: MapDef::new(MapType::StackTrace, 4, value_size, n_buckets as u32),
+            buckets,
+            _profile: PhantomData,
+        })
+    }
+
+    /// Maximum number of frames per stack.
+    pub fn max_depth(&self) -> usize {
+        self.def.value_size as usize / 8
+    }
+
+    /// Store a stack and return its ID.
+    ///
+    /// Frames beyond [`max_depth`](Self::max_depth) are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidValue` for an empty stack, `KeyExists` if a different
+    /// stack occupies the bucket and `reuse` is not set, and `MapFull` if
+    /// the bucket is being accessed by code this call interrupted.
+    pub fn store(&self, ips: &[u64], reuse: bool) -> MapResult<u32> {
+        let ips = &ips[..ips.len().min(self.max_depth())];
+        if ips.is_empty() {
+            return Err(MapError::InvalidValue);
+        }
+
+        let hash = hash_stack(ips);
+        let id = hash as usize & (self.buckets.len() - 1);
+        let mut bucket = self.buckets[id].try_lock().ok_or(MapError::MapFull)?;
+
+        let stored = &bucket.ips[..bucket.nr];
+        if bucket.nr != 0 && !(bucket.hash == hash && stored == ips) && !reuse {
+            return Err(MapError::KeyExists);
+        }
+
+        bucket.hash = hash;
+        bucket.nr = ips.len();
+        bucket.ips[..ips.len()].copy_from_slice(ips);
+        Ok(id as u32)
+    }
+
+    /// Get the frames of the stack with ID `id`.
+    pub fn get(&self, id: u32) -> Option<Vec<u64>> {
+        let bucket = self.buckets.get(id as usize)?.lock();
+        (bucket.nr != 0).then(|| bucket.ips[..bucket.nr].to_vec())
+    }
+
+    fn parse_key(key: &[u8]) -> Option<u32> {
+        Some(u32::from_ne_bytes(key.try_into().ok()?))
+    }
+}
+
+/// FNV-1a over the frames.
+fn hash_stack(ips: &[u64]) -> u32 {
+    ips.iter()
+        .flat_map(|ip| ip.to_ne_bytes())
+        .fold(0x811c_9dc5_u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+}
+
+impl<P: PhysicalProfile> BpfMap<P> for StackTraceMap<P> {
+    fn lookup(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let ips = self.get(Self::parse_key(key)?)?;
+        let mut value = vec![0u8; self.def.value_size as usize];
+        for (chunk, ip) in value.as_chunks_mut::<8>().0.iter_mut().zip(ips) {
+            chunk.copy_from_slice(&ip.to_ne_bytes());
+        }
+        Some(value)
+    }
+
+    fn update(&self, _key: &[u8], _value: &[u8], _flags: u64) -> MapResult<()> {
+        // Stacks are only stored by programs
+        Err(MapError::NotSupported)
+    }
+
+    fn delete(&self, key: &[u8]) -> MapResult<()> {
+        let id = Self::parse_key(key).ok_or(MapError::InvalidKey)?;
+        let mut bucket = self
+            .buckets
+            .get(id as usize)
+            .ok_or(MapError::InvalidKey)?
+            .lock();
+        if bucket.nr == 0 {
+            return Err(MapError::KeyNotFound);
+        }
+        bucket.nr = 0;
+        Ok(())
+    }
+
+    fn def(&self) -> &MapDef {
+        &self.def
+    }
+
+    fn get_next_key(&self, key: Option<&[u8]>) -> MapResult<Vec<u8>> {
+        let start = match key.and_then(Self::parse_key) {
+            Some(id) if (id as usize) < self.buckets.len() => id as usize + 1,
+            _ => 0,
+        };
+        (start..self.buckets.len())
+            .find(|&id| self.buckets[id].lock().nr != 0)
+            .map(|id| (id as u32).to_ne_bytes().to_vec())
+            .ok_or(MapError::KeyNotFound)
+    }
+
+    fn store_stack(&self, ips: &[u64], reuse: bool) -> MapResult<u32> {
+        self.store(ips, reuse)
+    }
+
+    #[cfg(feature = "cloud-profile")]
+    fn resize(&mut self, _new_max_entries: u32) -> MapResult<()> {
+        // IDs are bucket indices, which a resize would invalidate
+        Err(MapError::NotSupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_bad_sizes() {
+        assert!(StackTraceMap::<ActiveProfile>::new(0, 16).is_err());
+        assert!(StackTraceMap::<ActiveProfile>::new(12, 16).is_err());
+        assert!(StackTraceMap::<ActiveProfile>::new(8 * 128, 16).is_err());
+        assert!(StackTraceMap::<ActiveProfile>::new(64, 0).is_err());
+    }
+
+    #[test]
+    fn rounds_buckets_to_power_of_two() {
+        let map = StackTraceMap::<ActiveProfile>::new(64, 100).unwrap();
+        assert_eq!(BpfMap::<ActiveProfile>::def(&map).max_entries, 128);
+        assert_eq!(map.max_depth(), 8);
+    }
+
+    #[test]
+    fn same_stack_same_id() {
+        let map = StackTraceMap::<ActiveProfile>::new(64, 64).unwrap();
+        let a = map.store(&[0x1000, 0x2000, 0x3000], false).unwrap();
+        let b = map.store(&[0x1000, 0x2000, 0x3000], false).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(map.get(a), Some(vec![0x1000, 0x2000, 0x3000]));
+    }
+
+    #[test]
+    fn collision_needs_reuse() {
+        // A single bucket makes every stack collide
+        let map = StackTraceMap::<ActiveProfile>::new(64, 1).unwrap();
+        let id = map.store(&[0x1000], false).unwrap();
+        assert_eq!(map.store(&[0x2000], false), Err(MapError::KeyExists));
+        assert_eq!(map.store(&[0x2000], true), Ok(id));
+        assert_eq!(map.get(id), Some(vec![0x2000]));
+    }
+
+    #[test]
+    fn truncates_to_depth() {
+        let map = StackTraceMap::<ActiveProfile>::new(16, 8).unwrap();
+        let id = map.store(&[1, 2, 3, 4], false).unwrap();
+        assert_eq!(map.get(id), Some(vec![1, 2]));
+        assert_eq!(map.store(&[], false), Err(MapError::InvalidValue));
+    }
+
+    #[test]
+    fn lookup_pads_and_delete_clears() {
+        let map = StackTraceMap::<ActiveProfile>::new(24, 8).unwrap();
+        let id = map.store(&[0xabcd], false).unwrap();
+        let key = id.to_ne_bytes();
+
+        let value = map.lookup(&key).unwrap();
+        assert_eq!(value.len(), 24);
+        assert_eq!(&value[..8], &0xabcd_u64.to_ne_bytes());
+        assert!(value[8..].iter().all(|&b| b == 0));
+
+        assert!(map.update(&key, &value, 0).is_err());
+        assert_eq!(map.delete(&key), Ok(()));
+        assert_eq!(map.lookup(&key), None);
+        assert_eq!(map.delete(&key), Err(MapError::KeyNotFound));
+    }
+
+    #[test]
+    fn get_next_key_skips_empty_buckets() {
+        let map = StackTraceMap::<ActiveProfile>::new(8, 64).unwrap();
+        assert_eq!(map.get_next_key(None), Err(MapError::KeyNotFound));
+
+        let mut ids = [
+            map.store(&[0x1000], false).unwrap(),
+            map.store(&[0x2000], false).unwrap(),
+        ];
+        ids.sort_unstable();
+
+        let first = map.get_next_key(None).unwrap();
+        assert_eq!(first, ids[0].to_ne_bytes());
+        let second = map.get_next_key(Some(&first)).unwrap();
+        assert_eq!(second, ids[1].to_ne_bytes());
+        assert_eq!(map.get_next_key(Some(&second)), Err(MapError::KeyNotFound));
+    }
+}
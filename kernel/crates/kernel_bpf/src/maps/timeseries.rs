@@ -31,11 +31,11 @@
 //! # Usage
 //!
 //! ```c
-//! // Push a new timestamped value
-//! bpf_timeseries_push(&ts_map, key, &value);
+//! // Push a new value, timestamped with kernel time
+//! bpf_timeseries_push(&ts_map, &value);
 //!
-//! // Query last N entries
-//! bpf_timeseries_query(&ts_map, key, &output, count);
+//! // Statistics over the last 10 values
+//! bpf_timeseries_stats(&ts_map, 10, &stats, sizeof(stats));
 //! ```
 //!
 //! # Profile Differences
@@ -390,7 +390,8 @@ impl<P: PhysicalProfile> TimeSeriesMap<P> {
 
     /// Compute basic statistics over the last N entries.
     ///
-    /// Returns (min, max, sum, count) for numeric values interpreted as i64.
+    /// Returns (min, max, sum, count) for numeric values interpreted as i64,
+    /// along with their mean and population variance.
     pub fn stats_last_n(&self, n: usize) -> Option<TimeSeriesStats> {
         let entries = self.get_last_n(n);
         if entries.is_empty() {
@@ -402,19 +403,33 @@ impl<P: PhysicalProfile> TimeSeriesMap<P> {
         let mut min_val = i64::MAX;
         let mut max_val = i64::MIN;
         let mut sum: i64 = 0;
+        let mut exact_sum: i128 = 0;
+
+        // Interpret first 8 bytes as i64 for statistics
+        let values = || {
+            entries
+                .iter()
+                .filter(|(_, value)| value.len() >= 8)
+                .map(|(_, value)| i64::from_ne_bytes(value[0..8].try_into().unwrap()))
+        };
 
-        for (ts, value) in &entries {
+        for (ts, _) in &entries {
             min_ts = min_ts.min(*ts);
             max_ts = max_ts.max(*ts);
-
-            // Interpret first 8 bytes as i64 for statistics
-            if value.len() >= 8 {
-                let val = i64::from_ne_bytes(value[0..8].try_into().unwrap());
-                min_val = min_val.min(val);
-                max_val = max_val.max(val);
-                sum = sum.saturating_add(val);
-            }
         }
+        for val in values() {
+            min_val = min_val.min(val);
+            max_val = max_val.max(val);
+            sum = sum.saturating_add(val);
+            exact_sum += i128::from(val);
+        }
+
+        // The mean lies between min and max, so it fits an i64
+        let mean = (exact_sum / entries.len() as i128) as i64;
+        let squares = values()
+            .map(|val| (i128::from(val) - i128::from(mean)).unsigned_abs().pow(2))
+            .fold(0u128, u128::saturating_add);
+        let variance = u64::try_from(squares / entries.len() as u128).unwrap_or(u64::MAX);
 
         Some(TimeSeriesStats {
             count: entries.len(),
@@ -423,6 +438,8 @@ impl<P: PhysicalProfile> TimeSeriesMap<P> {
             min_value: min_val,
             max_value: max_val,
             sum,
+            mean,
+            variance,
         })
     }
 }
@@ -442,6 +459,10 @@ pub struct TimeSeriesStats {
     pub max_value: i64,
     /// Sum of values
     pub sum: i64,
+    /// Mean value, rounded towards zero
+    pub mean: i64,
+    /// Population variance of values, rounded down
+    pub variance: u64,
 }
 
 impl TimeSeriesStats {
@@ -494,6 +515,18 @@ impl<P: PhysicalProfile> BpfMap<P> for TimeSeriesMap<P> {
         Err(MapError::NotSupported)
     }
 
+    fn push_sample(&self, timestamp_ns: u64, value: &[u8]) -> MapResult<()> {
+        self.push(timestamp_ns, value)
+    }
+
+    fn sample_stats(&self, n: usize) -> MapResult<TimeSeriesStats> {
+        self.stats_last_n(n).ok_or(MapError::KeyNotFound)
+    }
+
+    fn samples_in_window(&self, start_ns: u64, end_ns: u64) -> MapResult<Vec<(u64, Vec<u8>)>> {
+        Ok(self.get_in_window(start_ns, end_ns))
+    }
+
     fn def(&self) -> &MapDef {
         &self.def
     }
@@ -628,7 +661,15 @@ mod tests {
         assert_eq!(stats.max_value, 50);
         assert_eq!(stats.sum, 10 + 20 + 30 + 40 + 50);
         assert_eq!(stats.average(), 30.0);
+        assert_eq!(stats.mean, 30);
+        // Deviations -20, -10, 0, 10, 20
+        assert_eq!(stats.variance, (400 + 100 + 100 + 400) / 5);
         assert_eq!(stats.time_span_ns(), 4000);
+
+        // Only the newest two
+        let stats = map.stats_last_n(2).expect("stats");
+        assert_eq!((stats.min_value, stats.max_value), (40, 50));
+        assert_eq!((stats.mean, stats.variance), (45, 25));
     }
 
     #[test]
@@ -663,6 +704,14 @@ mod tests {
         let val = i64::from_ne_bytes(result[8..16].try_into().unwrap());
         assert_eq!(ts, 5000);
         assert_eq!(val, 42);
+
+        // Time-series operations via BpfMap trait
+        let map: &dyn BpfMap<ActiveProfile> = &map;
+        map.push_sample(6000, &50i64.to_ne_bytes()).expect("push");
+        assert_eq!(map.sample_stats(2).expect("stats").mean, 46);
+        let window = map.samples_in_window(5500, 7000).expect("window");
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].0, 6000);
     }
 
     #[cfg(feature = "cloud-profile")]
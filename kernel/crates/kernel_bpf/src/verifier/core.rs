@@ -11,7 +11,7 @@ use core::marker::PhantomData;
 
 use super::cfg::ControlFlowGraph;
 use super::error::{VerifyError, VerifyResult};
-use super::helpers::{HelperValidation, validate_helper_call};
+use super::helpers::{HelperValidation, check_stack_buffer, validate_helper_call};
 use super::state::{RegState, RegType, ScalarValue, StackSlot, VerifierState};
 use crate::bytecode::insn::BpfInsn;
 use crate::bytecode::opcode::{AluOp, OpcodeClass};
@@ -232,10 +232,12 @@ impl<P: PhysicalProfile> Verifier<P> {
                 } => {
                     // Verify both paths
                     let mut branch_state = state.clone();
+                    branch_state.refine_branch(insn, true);
                     branch_state.insn_idx = target;
                     branch_state.insn_processed += 1;
                     self.verify_path(insns, target, branch_state)?;
 
+                    state.refine_branch(insn, false);
                     state.insn_idx = fallthrough;
                     state.insn_processed += 1;
                 }
@@ -254,7 +256,7 @@ impl<P: PhysicalProfile> Verifier<P> {
                 return false;
             }
         }
-        true
+        s1.refs == s2.refs
     }
 
     /// Verify a single instruction.
@@ -273,6 +275,14 @@ impl<P: PhysicalProfile> Verifier<P> {
                     reg: Register::R0,
                 });
             }
+
+            // Every acquired reference must have been released
+            if let Some(acquired) = state.unreleased_ref() {
+                return Err(VerifyError::UnreleasedReference {
+                    insn_idx: acquired.insn_idx,
+                    exit_idx: idx,
+                });
+            }
             return Ok(InsnResult::Exit);
         }
 
@@ -293,15 +303,16 @@ impl<P: PhysicalProfile> Verifier<P> {
             return self.verify_jump(insn, state, idx);
         }
 
-        // Memory instructions
-        if insn.is_memory() {
-            self.verify_memory(insn, state, idx)?;
+        // Wide instruction (64-bit immediate load); checked before memory
+        // instructions since it shares the LD class
+        if insn.is_wide() {
+            self.verify_wide_load(insn, state, idx)?;
             return Ok(InsnResult::Continue);
         }
 
-        // Wide instruction (64-bit immediate load)
-        if insn.is_wide() {
-            self.verify_wide_load(insn, state, idx)?;
+        // Memory instructions
+        if insn.is_memory() {
+            self.verify_memory(insn, state, idx)?;
             return Ok(InsnResult::Continue);
         }
 
@@ -375,8 +386,8 @@ impl<P: PhysicalProfile> Verifier<P> {
             });
         }
 
-        // Update destination register to scalar
-        state.set_scalar(dst, Some(ScalarValue::unknown()));
+        // Update destination register
+        state.apply_alu(insn, dst, alu_op);
 
         Ok(())
     }
@@ -462,6 +473,36 @@ impl<P: PhysicalProfile> Verifier<P> {
         // Validate helper call using the registry
         match validate_helper_call(helper_id, &arg_types) {
             HelperValidation::Valid(sig) => {
+                check_stack_buffer(&sig, state, idx)?;
+
+                // Releasing helpers consume the reference held in R1
+                if sig.id.releases_ref() {
+                    let r1 = state.reg(Register::R1);
+                    let ref_obj_id = r1.ref_obj_id;
+                    if ref_obj_id == 0 || r1.ptr_offset != 0 || !state.release_ref(ref_obj_id) {
+                        return Err(VerifyError::HelperArgType {
+                            insn_idx: idx,
+                            helper_name: sig.id.name(),
+                            arg_idx: 0,
+                        });
+                    }
+                }
+
+                // Acquiring helpers need a constant size so that accesses
+                // through the returned pointer can be bounds-checked
+                let acquired_size = if sig.id.acquires_ref() {
+                    let size = state.reg(Register::R2).const_value().ok_or(
+                        VerifyError::HelperArgType {
+                            insn_idx: idx,
+                            helper_name: sig.id.name(),
+                            arg_idx: 1,
+                        },
+                    )?;
+                    Some(size)
+                } else {
+                    None
+                };
+
                 // Caller-saved registers are clobbered
                 for reg in [
                     Register::R0,
@@ -475,7 +516,13 @@ impl<P: PhysicalProfile> Verifier<P> {
                 }
 
                 // R0 contains return value based on helper signature
-                *state.reg_mut(Register::R0) = sig.ret.to_reg_state();
+                *state.reg_mut(Register::R0) = match acquired_size {
+                    Some(size) => {
+                        let ref_obj_id = state.acquire_ref(idx);
+                        RegState::ringbuf_sample_or_null(ref_obj_id, size)
+                    }
+                    None => sig.ret.to_reg_state(),
+                };
 
                 Ok(())
             }
@@ -560,6 +607,14 @@ impl<P: PhysicalProfile> Verifier<P> {
                     });
                 }
 
+                if let Some(offset) = src_state.out_of_bounds(insn.offset, size.size_bytes()) {
+                    return Err(VerifyError::OutOfBoundsAccess {
+                        insn_idx: idx,
+                        offset,
+                        size: size.size_bytes(),
+                    });
+                }
+
                 // Check stack bounds if stack pointer
                 if src_state.reg_type == RegType::PtrToStack
                     || src_state.reg_type == RegType::PtrToFp
@@ -611,6 +666,14 @@ impl<P: PhysicalProfile> Verifier<P> {
                     });
                 }
 
+                if let Some(offset) = dst_state.out_of_bounds(insn.offset, size.size_bytes()) {
+                    return Err(VerifyError::OutOfBoundsAccess {
+                        insn_idx: idx,
+                        offset,
+                        size: size.size_bytes(),
+                    });
+                }
+
                 // Update stack state if writing to stack
                 if dst_state.reg_type == RegType::PtrToStack
                     || dst_state.reg_type == RegType::PtrToFp
@@ -652,6 +715,14 @@ impl<P: PhysicalProfile> Verifier<P> {
                         reason: "cannot write to this pointer type",
                     });
                 }
+
+                if let Some(offset) = dst_state.out_of_bounds(insn.offset, size.size_bytes()) {
+                    return Err(VerifyError::OutOfBoundsAccess {
+                        insn_idx: idx,
+                        offset,
+                        size: size.size_bytes(),
+                    });
+                }
             }
 
             _ => {}
@@ -676,8 +747,13 @@ impl<P: PhysicalProfile> Verifier<P> {
             return Err(VerifyError::WriteToReadOnly { insn_idx: idx });
         }
 
-        // Result is scalar with known lower 32 bits
-        state.set_scalar(dst, Some(ScalarValue::unknown()));
+        // src_reg = BPF_PSEUDO_MAP_FD marks a map reference; anything else
+        // is a plain 64-bit scalar
+        if insn.src_reg() == 1 {
+            *state.reg_mut(dst) = RegState::map_ptr(insn.imm as u32);
+        } else {
+            state.set_scalar(dst, Some(ScalarValue::unknown()));
+        }
 
         Ok(())
     }
@@ -817,6 +893,102 @@ mod tests {
         assert!(matches!(result, Err(VerifyError::WriteToReadOnly { .. })));
     }
 
+    /// Reserve a 16-byte ring buffer record into R0 (instructions 0-4).
+    fn reserve_prologue() -> [BpfInsn; 5] {
+        let map = crate::bytecode::insn::WideInsn::ld_dw_imm(1, 0);
+        [
+            BpfInsn::new(map.insn.opcode, 1, 1, 0, 0), // r1 = map 0
+            map.next,
+            BpfInsn::mov64_imm(2, 16), // r2 = 16 (record size)
+            BpfInsn::mov64_imm(3, 0),  // r3 = 0 (flags)
+            BpfInsn::call(131),        // r0 = bpf_ringbuf_reserve(r1, r2, r3)
+        ]
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_ringbuf_reserve_submit() {
+        let mut insns = reserve_prologue().to_vec();
+        insns.extend([
+            BpfInsn::jeq_imm(0, 0, 5),       // if r0 == 0 goto exit
+            BpfInsn::mov64_reg(1, 0),        // r1 = r0
+            BpfInsn::new(0x7a, 1, 0, 8, 42), // *(u64 *)(r1 + 8) = 42
+            BpfInsn::mov64_imm(2, 0),        // r2 = 0
+            BpfInsn::call(132),              // bpf_ringbuf_submit(r1, r2)
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),
+        ]);
+
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_ringbuf_leak_on_one_path() {
+        let mut insns = reserve_prologue().to_vec();
+        insns.extend([
+            BpfInsn::jeq_imm(0, 0, 6), // if r0 == 0 goto exit
+            BpfInsn::mov64_reg(6, 0),  // r6 = r0
+            BpfInsn::jeq_imm(6, 1, 4), // if r6 == 1 goto exit (leaks)
+            BpfInsn::mov64_reg(1, 6),  // r1 = r6
+            BpfInsn::mov64_imm(2, 0),  // r2 = 0
+            BpfInsn::call(133),        // bpf_ringbuf_discard(r1, r2)
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),
+        ]);
+
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::UnreleasedReference { insn_idx: 4, .. })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_ringbuf_write_out_of_bounds() {
+        let mut insns = reserve_prologue().to_vec();
+        insns.extend([
+            BpfInsn::jeq_imm(0, 0, 5),       // if r0 == 0 goto exit
+            BpfInsn::new(0x7a, 0, 0, 12, 1), // *(u64 *)(r0 + 12) = 1 (past 16 bytes)
+            BpfInsn::mov64_reg(1, 0),
+            BpfInsn::mov64_imm(2, 0),
+            BpfInsn::call(132),
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),
+        ]);
+
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::OutOfBoundsAccess {
+                insn_idx: 6,
+                offset: 12,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_ringbuf_submit_unchecked_pointer() {
+        let mut insns = reserve_prologue().to_vec();
+        insns.extend([
+            BpfInsn::mov64_reg(1, 0), // r1 = r0 (not checked against NULL)
+            BpfInsn::mov64_imm(2, 0),
+            BpfInsn::call(132),
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),
+        ]);
+
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::HelperArgType { arg_idx: 0, .. })
+        ));
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
     fn verify_uninitialized_register() {
@@ -831,4 +1003,53 @@ mod tests {
             Err(VerifyError::UninitializedRegister { .. })
         ));
     }
+
+    /// `bpf_probe_read_user(fp - 16, size, r3)` with the given size.
+    fn probe_read_insns(buf_off: i32, size: i32) -> [BpfInsn; 7] {
+        [
+            BpfInsn::mov64_reg(1, 10),
+            BpfInsn::add64_imm(1, buf_off), // r1 = fp + buf_off
+            BpfInsn::mov64_imm(2, size),    // r2 = size
+            BpfInsn::mov64_imm(3, 0x1000),  // r3 = untrusted address
+            BpfInsn::call(12),              // bpf_probe_read_user(r1, r2, r3)
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),
+        ]
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_probe_read_stack_buffer() {
+        let insns = probe_read_insns(-16, 16);
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(result.is_ok(), "{result:?}");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_probe_read_buffer_past_frame() {
+        // 16 bytes starting at fp - 8 would run past the frame pointer
+        let insns = probe_read_insns(-8, 16);
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::OutOfBoundsAccess {
+                insn_idx: 4,
+                offset: -8,
+                size: 16
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // Slow under Miri due to large stack allocation (512KB for cloud profile)
+    fn verify_probe_read_unknown_size() {
+        let mut insns = probe_read_insns(-16, 16);
+        insns[2] = BpfInsn::mov64_reg(2, 1); // r2 = pointer, not a constant
+        let result = Verifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::HelperArgType { arg_idx: 1, .. })
+        ));
+    }
 }
@@ -124,6 +124,14 @@ pub enum VerifyError {
         insn_idx: usize,
     },
 
+    /// Acquired reference not released on some path to exit
+    UnreleasedReference {
+        /// Instruction that acquired the reference
+        insn_idx: usize,
+        /// Exit instruction reached while the reference was held
+        exit_idx: usize,
+    },
+
     // ========================================
     // Profile-specific violations
     // ========================================
@@ -195,6 +203,40 @@ pub enum VerifyError {
     },
 }
 
+impl VerifyError {
+    /// Index of the offending instruction, if the error refers to one.
+    pub fn insn_idx(&self) -> Option<usize> {
+        match self {
+            Self::InvalidOpcode { insn_idx, .. }
+            | Self::InvalidRegister { insn_idx, .. }
+            | Self::UninitializedRegister { insn_idx, .. }
+            | Self::OutOfBoundsAccess { insn_idx, .. }
+            | Self::InvalidMemoryAccess { insn_idx, .. }
+            | Self::UnreachableInstruction { insn_idx }
+            | Self::InfiniteLoop { insn_idx }
+            | Self::InvalidJump { insn_idx, .. }
+            | Self::InvalidHelper { insn_idx, .. }
+            | Self::HelperNotAvailable { insn_idx, .. }
+            | Self::HelperArgCount { insn_idx, .. }
+            | Self::HelperArgType { insn_idx, .. }
+            | Self::DivisionByZero { insn_idx }
+            | Self::UnreleasedReference { insn_idx, .. }
+            | Self::WriteToReadOnly { insn_idx }
+            | Self::MisalignedAccess { insn_idx, .. } => Some(*insn_idx),
+            #[cfg(feature = "embedded-profile")]
+            Self::InterruptUnsafe { insn_idx, .. }
+            | Self::DynamicAllocationAttempted { insn_idx }
+            | Self::UnboundedLoop { insn_idx } => Some(*insn_idx),
+            Self::NoExit
+            | Self::EmptyProgram
+            | Self::StackExceeded { .. }
+            | Self::InsnCountExceeded { .. } => None,
+            #[cfg(feature = "embedded-profile")]
+            Self::WcetExceeded { .. } => None,
+        }
+    }
+}
+
 impl fmt::Display for VerifyError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -296,6 +338,13 @@ impl fmt::Display for VerifyError {
             Self::DivisionByZero { insn_idx } => {
                 write!(f, "possible division by zero at instruction {}", insn_idx)
             }
+            Self::UnreleasedReference { insn_idx, exit_idx } => {
+                write!(
+                    f,
+                    "reference acquired at instruction {} not released before exit at instruction {}",
+                    insn_idx, exit_idx
+                )
+            }
             Self::StackExceeded { used, limit } => {
                 write!(f, "stack size {} exceeds limit {}", used, limit)
             }
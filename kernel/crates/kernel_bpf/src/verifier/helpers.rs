@@ -18,7 +18,9 @@
 //! - Cloud: All helpers available
 //! - Embedded: Restricted set (no dynamic allocation helpers)
 
-use super::state::{RegState, RegType};
+use super::error::{VerifyError, VerifyResult};
+use super::state::{RegState, RegType, StackSlot, VerifierState};
+use crate::bytecode::registers::Register;
 
 /// Helper function identifier.
 ///
@@ -56,6 +58,18 @@ pub enum HelperId {
     GetCurrentUidGid = 10,
     /// Get current process command name
     GetCurrentComm = 11,
+    /// Read from a user address (faults return an error)
+    ProbeReadUser = 12,
+    /// Read from a kernel address (faults return an error)
+    ProbeReadKernel = 13,
+
+    // ===== Perf Helpers (25-67) =====
+    /// Write a sample to a perf event array
+    PerfEventOutput = 25,
+    /// Store the current call stack in a stack trace map
+    GetStackid = 27,
+    /// Copy the current call stack into a buffer
+    GetStack = 67,
 
     // ===== Ring Buffer Helpers (130-140) =====
     /// Reserve space in ring buffer
@@ -72,7 +86,7 @@ pub enum HelperId {
     MotorEmergencyStop = 1000,
     /// Push value to time-series map
     TimeseriesPush = 1001,
-    /// Get last timestamp from sensor
+    /// Get timestamp of the latest sample of a sensor
     SensorLastTimestamp = 1002,
     /// Set GPIO pin state
     GpioSet = 1003,
@@ -80,10 +94,14 @@ pub enum HelperId {
     GpioGet = 1004,
     /// Write to PWM channel
     PwmWrite = 1005,
-    /// Read IIO sensor value
+    /// Read latest scaled value of an IIO channel
     IioRead = 1006,
     /// Send CAN message
     CanSend = 1007,
+    /// Arm a high-resolution timer
+    TimerStart = 1008,
+    /// Compute statistics over the newest values of a time series
+    TimeseriesStats = 1009,
 }
 
 impl HelperId {
@@ -101,6 +119,11 @@ impl HelperId {
             9 => Some(Self::GetCurrentPidTgid),
             10 => Some(Self::GetCurrentUidGid),
             11 => Some(Self::GetCurrentComm),
+            12 => Some(Self::ProbeReadUser),
+            13 => Some(Self::ProbeReadKernel),
+            25 => Some(Self::PerfEventOutput),
+            27 => Some(Self::GetStackid),
+            67 => Some(Self::GetStack),
             131 => Some(Self::RingbufReserve),
             132 => Some(Self::RingbufSubmit),
             133 => Some(Self::RingbufDiscard),
@@ -113,6 +136,8 @@ impl HelperId {
             1005 => Some(Self::PwmWrite),
             1006 => Some(Self::IioRead),
             1007 => Some(Self::CanSend),
+            1008 => Some(Self::TimerStart),
+            1009 => Some(Self::TimeseriesStats),
             _ => None,
         }
     }
@@ -131,6 +156,11 @@ impl HelperId {
             Self::GetCurrentPidTgid => "bpf_get_current_pid_tgid",
             Self::GetCurrentUidGid => "bpf_get_current_uid_gid",
             Self::GetCurrentComm => "bpf_get_current_comm",
+            Self::ProbeReadUser => "bpf_probe_read_user",
+            Self::ProbeReadKernel => "bpf_probe_read_kernel",
+            Self::PerfEventOutput => "bpf_perf_event_output",
+            Self::GetStackid => "bpf_get_stackid",
+            Self::GetStack => "bpf_get_stack",
             Self::RingbufReserve => "bpf_ringbuf_reserve",
             Self::RingbufSubmit => "bpf_ringbuf_submit",
             Self::RingbufDiscard => "bpf_ringbuf_discard",
@@ -143,6 +173,8 @@ impl HelperId {
             Self::PwmWrite => "bpf_pwm_write",
             Self::IioRead => "bpf_iio_read",
             Self::CanSend => "bpf_can_send",
+            Self::TimerStart => "bpf_timer_start",
+            Self::TimeseriesStats => "bpf_timeseries_stats",
         }
     }
 
@@ -165,18 +197,29 @@ impl HelperId {
 
             // Probe helpers - available but restricted
             Self::ProbeRead => true,
+            Self::ProbeReadUser => true,
+            Self::ProbeReadKernel => true,
 
             // Process helpers - available
             Self::GetCurrentPidTgid => true,
             Self::GetCurrentUidGid => true,
             Self::GetCurrentComm => true,
 
-            // Ring buffer - reserve disabled (dynamic alloc)
-            Self::RingbufReserve => false,
+            // Ring buffer - reserve carves records out of the preallocated
+            // ring, so no dynamic allocation is involved
+            Self::RingbufReserve => true,
             Self::RingbufSubmit => true,
             Self::RingbufDiscard => true,
             Self::RingbufOutput => true,
 
+            // Perf output - rings are allocated when userspace maps them,
+            // never by the program
+            Self::PerfEventOutput => true,
+
+            // Stack helpers - stack trace maps preallocate their buckets
+            Self::GetStackid => true,
+            Self::GetStack => true,
+
             // Robotics helpers - all available
             Self::MotorEmergencyStop => true,
             Self::TimeseriesPush => true,
@@ -186,6 +229,8 @@ impl HelperId {
             Self::PwmWrite => true,
             Self::IioRead => true,
             Self::CanSend => true,
+            Self::TimerStart => true,
+            Self::TimeseriesStats => true,
         }
     }
 
@@ -196,6 +241,16 @@ impl HelperId {
         true
     }
 
+    /// Check if this helper returns a reference that must be released.
+    pub const fn acquires_ref(&self) -> bool {
+        matches!(self, Self::RingbufReserve)
+    }
+
+    /// Check if this helper releases the reference passed in R1.
+    pub const fn releases_ref(&self) -> bool {
+        matches!(self, Self::RingbufSubmit | Self::RingbufDiscard)
+    }
+
     /// Check if helper is available in the current profile.
     pub const fn is_available(&self) -> bool {
         #[cfg(feature = "embedded-profile")]
@@ -232,6 +287,8 @@ pub enum ArgType {
     PtrToCtx,
     /// Any pointer type
     AnyPtr,
+    /// Any initialized value, e.g. an untrusted address to probe
+    Anything,
     /// Constant value (flags, etc.)
     Const,
     /// Ring buffer pointer
@@ -277,13 +334,15 @@ impl ArgType {
             }
             Self::PtrToCtx => matches!(reg_type, RegType::PtrToCtx),
             Self::AnyPtr => reg_type.is_pointer(),
+            Self::Anything => !matches!(reg_type, RegType::NotInit),
             Self::PtrToRingbuf => {
                 // Ring buffer map pointer
                 matches!(reg_type, RegType::ConstPtrToMap | RegType::PtrToMapValue)
             }
             Self::PtrToRingbufSample => {
-                // Reserved sample pointer (returned by ringbuf_reserve)
-                matches!(reg_type, RegType::PtrToMapValue)
+                // Reserved sample pointer (returned by ringbuf_reserve and
+                // checked against NULL)
+                matches!(reg_type, RegType::PtrToRingbufSample)
             }
         }
     }
@@ -342,6 +401,16 @@ impl HelperSignature {
     pub fn arg_count(&self) -> usize {
         self.args.len()
     }
+
+    /// Index of a stack buffer argument, if the helper writes to one.
+    ///
+    /// A stack buffer is a `PtrToStack` argument immediately followed by its
+    /// `MemSize`.
+    pub fn stack_buffer_arg(&self) -> Option<usize> {
+        self.args
+            .windows(2)
+            .position(|w| w == [ArgType::PtrToStack, ArgType::MemSize])
+    }
 }
 
 /// Get the signature for a helper function.
@@ -385,11 +454,13 @@ pub fn get_helper_signature(id: HelperId) -> HelperSignature {
         ),
 
         // Memory helpers
-        HelperId::ProbeRead => HelperSignature::new(
-            id,
-            &[ArgType::PtrToStack, ArgType::MemSize, ArgType::AnyPtr],
-            ReturnType::Integer,
-        ),
+        HelperId::ProbeRead | HelperId::ProbeReadUser | HelperId::ProbeReadKernel => {
+            HelperSignature::new(
+                id,
+                &[ArgType::PtrToStack, ArgType::MemSize, ArgType::Anything],
+                ReturnType::Integer,
+            )
+        }
 
         // Process helpers
         HelperId::GetCurrentPidTgid => HelperSignature::new(id, &[], ReturnType::Integer),
@@ -432,6 +503,36 @@ pub fn get_helper_signature(id: HelperId) -> HelperSignature {
             ReturnType::Integer,
         ),
 
+        // Perf helpers
+        HelperId::PerfEventOutput => HelperSignature::new(
+            id,
+            &[
+                ArgType::PtrToCtx,
+                ArgType::PtrToMap,
+                ArgType::Scalar,
+                ArgType::PtrToMem,
+                ArgType::MemSize,
+            ],
+            ReturnType::Integer,
+        ),
+
+        HelperId::GetStackid => HelperSignature::new(
+            id,
+            &[ArgType::PtrToCtx, ArgType::PtrToMap, ArgType::Scalar],
+            ReturnType::Integer,
+        ),
+
+        HelperId::GetStack => HelperSignature::new(
+            id,
+            &[
+                ArgType::PtrToCtx,
+                ArgType::PtrToStack,
+                ArgType::MemSize,
+                ArgType::Scalar,
+            ],
+            ReturnType::Integer,
+        ),
+
         // Robotics helpers
         HelperId::MotorEmergencyStop => {
             HelperSignature::new(id, &[ArgType::Scalar], ReturnType::Integer)
@@ -439,7 +540,7 @@ pub fn get_helper_signature(id: HelperId) -> HelperSignature {
 
         HelperId::TimeseriesPush => HelperSignature::new(
             id,
-            &[ArgType::PtrToMap, ArgType::PtrToMapKey, ArgType::PtrToMem],
+            &[ArgType::PtrToMap, ArgType::PtrToMem],
             ReturnType::Integer,
         ),
 
@@ -459,20 +560,85 @@ pub fn get_helper_signature(id: HelperId) -> HelperSignature {
             ReturnType::Integer,
         ),
 
-        HelperId::IioRead => HelperSignature::new(
+        HelperId::IioRead => {
+            HelperSignature::new(id, &[ArgType::Scalar, ArgType::Scalar], ReturnType::Integer)
+        }
+
+        HelperId::CanSend => HelperSignature::new(
             id,
-            &[ArgType::Scalar, ArgType::PtrToStack, ArgType::MemSize],
+            &[
+                ArgType::Scalar,
+                ArgType::Scalar,
+                ArgType::PtrToMem,
+                ArgType::MemSize,
+            ],
             ReturnType::Integer,
         ),
 
-        HelperId::CanSend => HelperSignature::new(
+        HelperId::TimerStart => {
+            HelperSignature::new(id, &[ArgType::Scalar, ArgType::Scalar], ReturnType::Integer)
+        }
+
+        HelperId::TimeseriesStats => HelperSignature::new(
             id,
-            &[ArgType::Scalar, ArgType::PtrToMem, ArgType::MemSize],
+            &[
+                ArgType::PtrToMap,
+                ArgType::Scalar,
+                ArgType::PtrToStack,
+                ArgType::MemSize,
+            ],
             ReturnType::Integer,
         ),
     }
 }
 
+/// Check the stack buffer argument of a helper call, if it has one.
+///
+/// The buffer size must be a known constant and the whole buffer must lie
+/// within the stack frame. Since the helper fills the buffer, its slots are
+/// marked as written.
+pub(crate) fn check_stack_buffer(
+    sig: &HelperSignature,
+    state: &mut VerifierState,
+    insn_idx: usize,
+) -> VerifyResult<()> {
+    let Some(arg_idx) = sig.stack_buffer_arg() else {
+        return Ok(());
+    };
+    let arg_error = |arg_idx| VerifyError::HelperArgType {
+        insn_idx,
+        helper_name: sig.id.name(),
+        arg_idx,
+    };
+
+    // Arguments are passed in R1-R5
+    let ptr_reg = Register::from_raw(arg_idx as u8 + 1).ok_or_else(|| arg_error(arg_idx))?;
+    let size_reg = Register::from_raw(arg_idx as u8 + 2).ok_or_else(|| arg_error(arg_idx + 1))?;
+
+    let ptr = state.reg(ptr_reg);
+    if !matches!(ptr.reg_type, RegType::PtrToStack | RegType::PtrToFp) {
+        return Err(arg_error(arg_idx));
+    }
+    let offset = ptr.ptr_offset;
+    let size = state
+        .reg(size_reg)
+        .const_value()
+        .ok_or_else(|| arg_error(arg_idx + 1))?;
+
+    if !state.stack.is_valid_region(offset, size) {
+        return Err(VerifyError::OutOfBoundsAccess {
+            insn_idx,
+            offset,
+            size: size as usize,
+        });
+    }
+
+    for i in 0..size as i64 {
+        let _ = state.stack.set(offset + i, StackSlot::Scalar);
+    }
+    Ok(())
+}
+
 /// Result of helper validation.
 #[derive(Debug, Clone)]
 pub enum HelperValidation {
@@ -595,6 +761,125 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn validate_perf_event_output() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::PtrToCtx; // R1 = ctx
+        args[1] = RegType::ConstPtrToMap; // R2 = perf event array
+        args[2] = RegType::Scalar; // R3 = flags
+        args[3] = RegType::PtrToStack; // R4 = data
+        args[4] = RegType::Scalar; // R5 = size
+        assert!(matches!(
+            validate_helper_call(25, &args),
+            HelperValidation::Valid(_)
+        ));
+
+        args[0] = RegType::Scalar;
+        assert!(matches!(
+            validate_helper_call(25, &args),
+            HelperValidation::ArgTypeMismatch { arg_idx: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn validate_get_stack() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::PtrToCtx; // R1 = ctx
+        args[1] = RegType::ConstPtrToMap; // R2 = stack trace map
+        args[2] = RegType::Scalar; // R3 = flags
+        assert!(matches!(
+            validate_helper_call(27, &args),
+            HelperValidation::Valid(_)
+        ));
+
+        args[1] = RegType::PtrToStack; // R2 = buf
+        args[2] = RegType::Scalar; // R3 = size
+        args[3] = RegType::Scalar; // R4 = flags
+        let HelperValidation::Valid(sig) = validate_helper_call(67, &args) else {
+            panic!("bpf_get_stack should validate");
+        };
+        assert_eq!(sig.stack_buffer_arg(), Some(1));
+    }
+
+    #[test]
+    fn validate_iio_read() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::Scalar; // R1 = device
+        args[1] = RegType::Scalar; // R2 = channel
+        assert!(matches!(
+            validate_helper_call(1006, &args),
+            HelperValidation::Valid(_)
+        ));
+
+        args[1] = RegType::PtrToStack;
+        assert!(matches!(
+            validate_helper_call(1006, &args),
+            HelperValidation::ArgTypeMismatch { arg_idx: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn validate_can_send() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::Scalar; // R1 = interface
+        args[1] = RegType::Scalar; // R2 = identifier
+        args[2] = RegType::PtrToStack; // R3 = payload
+        args[3] = RegType::Scalar; // R4 = length
+        assert!(matches!(
+            validate_helper_call(1007, &args),
+            HelperValidation::Valid(_)
+        ));
+
+        args[2] = RegType::Scalar;
+        assert!(matches!(
+            validate_helper_call(1007, &args),
+            HelperValidation::ArgTypeMismatch { arg_idx: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn validate_timer_start() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::Scalar; // R1 = timer
+        args[1] = RegType::Scalar; // R2 = delay
+        assert!(matches!(
+            validate_helper_call(1008, &args),
+            HelperValidation::Valid(_)
+        ));
+
+        args[1] = RegType::PtrToStack;
+        assert!(matches!(
+            validate_helper_call(1008, &args),
+            HelperValidation::ArgTypeMismatch { arg_idx: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn validate_timeseries_helpers() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::ConstPtrToMap; // R1 = map
+        args[1] = RegType::PtrToStack; // R2 = value
+        assert!(matches!(
+            validate_helper_call(1001, &args),
+            HelperValidation::Valid(_)
+        ));
+
+        args[1] = RegType::Scalar; // R2 = count
+        args[2] = RegType::PtrToStack; // R3 = stats
+        args[3] = RegType::Scalar; // R4 = size
+        let sig = match validate_helper_call(1009, &args) {
+            HelperValidation::Valid(sig) => sig,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(sig.stack_buffer_arg(), Some(2));
+
+        args[0] = RegType::Scalar;
+        assert!(matches!(
+            validate_helper_call(1009, &args),
+            HelperValidation::ArgTypeMismatch { arg_idx: 0, .. }
+        ));
+    }
+
     #[test]
     fn validate_unknown_helper() {
         let args = [RegType::NotInit; 5];
@@ -618,6 +903,25 @@ mod tests {
         assert!(ArgType::PtrToMemOrNull.is_compatible(RegType::PtrToStack));
     }
 
+    #[test]
+    fn ringbuf_submit_requires_checked_sample() {
+        let mut args = [RegType::NotInit; 5];
+        args[0] = RegType::PtrToRingbufSampleOrNull;
+        args[1] = RegType::Scalar;
+        assert!(matches!(
+            validate_helper_call(132, &args),
+            HelperValidation::ArgTypeMismatch { arg_idx: 0, .. }
+        ));
+
+        args[0] = RegType::PtrToRingbufSample;
+        assert!(matches!(
+            validate_helper_call(132, &args),
+            HelperValidation::Valid(_)
+        ));
+        assert!(HelperId::RingbufReserve.acquires_ref());
+        assert!(HelperId::RingbufDiscard.releases_ref());
+    }
+
     #[test]
     fn robotics_helpers_available() {
         // Robotics helpers should be defined
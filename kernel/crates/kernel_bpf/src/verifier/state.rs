@@ -9,6 +9,8 @@ extern crate alloc;
 use alloc::vec::Vec;
 use core::fmt;
 
+use crate::bytecode::insn::BpfInsn;
+use crate::bytecode::opcode::{AluOp, JmpOp, SourceType};
 use crate::bytecode::registers::Register;
 
 /// Type of value held in a register.
@@ -53,6 +55,12 @@ pub enum RegType {
 
     /// Null pointer
     NullPtr,
+
+    /// Reserved ring buffer record, possibly NULL (not yet checked)
+    PtrToRingbufSampleOrNull,
+
+    /// Reserved ring buffer record (acquired reference)
+    PtrToRingbufSample,
 }
 
 impl RegType {
@@ -72,6 +80,7 @@ impl RegType {
                 | Self::PtrToCtx
                 | Self::PtrToPacket
                 | Self::PtrToPacketMeta
+                | Self::PtrToRingbufSample
         )
     }
 
@@ -80,7 +89,7 @@ impl RegType {
     pub const fn can_write(&self) -> bool {
         matches!(
             self,
-            Self::PtrToStack | Self::PtrToMapValue | Self::PtrToPacket
+            Self::PtrToStack | Self::PtrToMapValue | Self::PtrToPacket | Self::PtrToRingbufSample
         )
     }
 }
@@ -99,6 +108,12 @@ pub struct RegState {
 
     /// For map pointers: map ID
     pub map_id: Option<u32>,
+
+    /// For acquired references: ID of the reference (0 if none)
+    pub ref_obj_id: u32,
+
+    /// For pointers to sized memory: size of the pointed-to region
+    pub mem_size: u64,
 }
 
 impl RegState {
@@ -109,6 +124,8 @@ impl RegState {
             scalar_value: None,
             ptr_offset: 0,
             map_id: None,
+            ref_obj_id: 0,
+            mem_size: 0,
         }
     }
 
@@ -119,6 +136,8 @@ impl RegState {
             scalar_value: value,
             ptr_offset: 0,
             map_id: None,
+            ref_obj_id: 0,
+            mem_size: 0,
         }
     }
 
@@ -129,6 +148,8 @@ impl RegState {
             scalar_value: None,
             ptr_offset: offset,
             map_id: None,
+            ref_obj_id: 0,
+            mem_size: 0,
         }
     }
 
@@ -139,6 +160,8 @@ impl RegState {
             scalar_value: None,
             ptr_offset: 0,
             map_id: None,
+            ref_obj_id: 0,
+            mem_size: 0,
         }
     }
 
@@ -149,6 +172,32 @@ impl RegState {
             scalar_value: None,
             ptr_offset: 0,
             map_id: None,
+            ref_obj_id: 0,
+            mem_size: 0,
+        }
+    }
+
+    /// Create a constant map pointer state (64-bit map load).
+    pub const fn map_ptr(map_id: u32) -> Self {
+        Self {
+            reg_type: RegType::ConstPtrToMap,
+            scalar_value: None,
+            ptr_offset: 0,
+            map_id: Some(map_id),
+            ref_obj_id: 0,
+            mem_size: 0,
+        }
+    }
+
+    /// Create a ring buffer record state as returned by `bpf_ringbuf_reserve`.
+    pub const fn ringbuf_sample_or_null(ref_obj_id: u32, mem_size: u64) -> Self {
+        Self {
+            reg_type: RegType::PtrToRingbufSampleOrNull,
+            scalar_value: None,
+            ptr_offset: 0,
+            map_id: None,
+            ref_obj_id,
+            mem_size,
         }
     }
 
@@ -157,6 +206,31 @@ impl RegState {
     pub fn is_init(&self) -> bool {
         !matches!(self.reg_type, RegType::NotInit)
     }
+
+    /// Get the value if this is a known constant scalar.
+    pub fn const_value(&self) -> Option<u64> {
+        match self.reg_type {
+            RegType::Scalar => self.scalar_value.and_then(|v| v.value),
+            _ => None,
+        }
+    }
+
+    /// Check an access through this pointer against the size of its region.
+    ///
+    /// Returns the offending offset if the access leaves the region. Only
+    /// sized regions such as ring buffer records are checked here; other
+    /// pointer types are checked elsewhere and always pass.
+    pub fn out_of_bounds(&self, insn_off: i16, size: usize) -> Option<i64> {
+        if self.reg_type != RegType::PtrToRingbufSample {
+            return None;
+        }
+        let offset = self.ptr_offset + insn_off as i64;
+        if offset < 0 || offset + size as i64 > self.mem_size as i64 {
+            Some(offset)
+        } else {
+            None
+        }
+    }
 }
 
 impl Default for RegState {
@@ -344,6 +418,15 @@ impl StackState {
         self.max_depth
     }
 
+    /// Check if the buffer `[offset, offset + size)` lies within the stack.
+    ///
+    /// Unlike [`Self::is_valid_access`], this describes a buffer handed to
+    /// a helper, which extends upwards from `offset` towards FP.
+    pub fn is_valid_region(&self, offset: i64, size: u64) -> bool {
+        let len = self.slots.len() as i64;
+        size > 0 && size <= len as u64 && offset >= -len && offset + size as i64 <= 0
+    }
+
     /// Check if access at offset with size is valid.
     pub fn is_valid_access(&self, offset: i64, size: usize) -> bool {
         // Stack access must be negative offset from FP
@@ -376,6 +459,18 @@ impl fmt::Debug for StackState {
     }
 }
 
+/// A reference acquired from a helper.
+///
+/// Every acquired reference must be released (for ring buffer records:
+/// submitted or discarded) on every path before the program exits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcquiredRef {
+    /// Reference ID, shared by every register holding the reference
+    pub id: u32,
+    /// Instruction that acquired the reference
+    pub insn_idx: usize,
+}
+
 /// Complete verifier state at a program point.
 #[derive(Clone)]
 pub struct VerifierState {
@@ -390,6 +485,12 @@ pub struct VerifierState {
 
     /// Number of instructions processed (for bounds checking)
     pub insn_processed: usize,
+
+    /// References acquired but not yet released
+    pub refs: Vec<AcquiredRef>,
+
+    /// Next reference ID to hand out
+    next_ref_id: u32,
 }
 
 impl VerifierState {
@@ -408,6 +509,8 @@ impl VerifierState {
             stack: StackState::new(stack_size),
             insn_idx: 0,
             insn_processed: 0,
+            refs: Vec::new(),
+            next_ref_id: 1,
         }
     }
 
@@ -431,6 +534,119 @@ impl VerifierState {
         self.regs[reg as usize] = RegState::scalar(value);
     }
 
+    /// Update the destination register of an ALU instruction.
+    ///
+    /// 64-bit register moves copy the source state so pointers survive being
+    /// moved between registers, immediate moves produce known constants, and
+    /// constant offsets applied to a stack or ring buffer record pointer are
+    /// tracked so accesses through it can be bounds-checked. Anything else
+    /// yields an unknown scalar.
+    pub fn apply_alu(&mut self, insn: &BpfInsn, dst: Register, alu_op: AluOp) {
+        let is64 = insn.is_alu64();
+        let result = match (alu_op, insn.source_type()) {
+            (AluOp::Mov, SourceType::Reg) if is64 => insn.src().map(|src| self.reg(src).clone()),
+            (AluOp::Mov, SourceType::Imm) => {
+                let value = if is64 {
+                    insn.imm as i64 as u64
+                } else {
+                    insn.imm as u32 as u64
+                };
+                Some(RegState::scalar(Some(ScalarValue::constant(value))))
+            }
+            (AluOp::Add | AluOp::Sub, SourceType::Imm)
+                if is64
+                    && matches!(
+                        self.reg(dst).reg_type,
+                        RegType::PtrToRingbufSample | RegType::PtrToFp | RegType::PtrToStack
+                    ) =>
+            {
+                let mut reg = self.reg(dst).clone();
+                if reg.reg_type == RegType::PtrToFp {
+                    reg.reg_type = RegType::PtrToStack;
+                }
+                if matches!(alu_op, AluOp::Add) {
+                    reg.ptr_offset += insn.imm as i64;
+                } else {
+                    reg.ptr_offset -= insn.imm as i64;
+                }
+                Some(reg)
+            }
+            _ => None,
+        };
+
+        *self.reg_mut(dst) =
+            result.unwrap_or_else(|| RegState::scalar(Some(ScalarValue::unknown())));
+    }
+
+    /// Acquire a new reference at the given instruction.
+    pub fn acquire_ref(&mut self, insn_idx: usize) -> u32 {
+        let id = self.next_ref_id;
+        self.next_ref_id += 1;
+        self.refs.push(AcquiredRef { id, insn_idx });
+        id
+    }
+
+    /// Release a reference.
+    ///
+    /// Every register still holding the reference becomes an unknown
+    /// scalar, so it can no longer be dereferenced or released again.
+    /// Returns `false` if the reference was not held.
+    pub fn release_ref(&mut self, id: u32) -> bool {
+        let Some(pos) = self.refs.iter().position(|r| r.id == id) else {
+            return false;
+        };
+        self.refs.remove(pos);
+
+        for reg in self.regs.iter_mut().filter(|r| r.ref_obj_id == id) {
+            *reg = RegState::scalar(Some(ScalarValue::unknown()));
+        }
+        true
+    }
+
+    /// Get the first reference that has not been released.
+    pub fn unreleased_ref(&self) -> Option<&AcquiredRef> {
+        self.refs.first()
+    }
+
+    /// Refine register types for one side of a conditional branch.
+    ///
+    /// A `jeq`/`jne` against zero on a maybe-NULL record pointer tells us
+    /// whether the reservation succeeded. On the NULL side nothing was
+    /// reserved, so the reference is dropped; on the other side every copy
+    /// of the pointer becomes a valid record pointer.
+    pub fn refine_branch(&mut self, insn: &BpfInsn, taken: bool) {
+        let Some(jmp_op) = insn.jmp_op() else {
+            return;
+        };
+        if !matches!(jmp_op, JmpOp::Jeq | JmpOp::Jne)
+            || !matches!(insn.source_type(), SourceType::Imm)
+            || insn.imm != 0
+        {
+            return;
+        }
+        let Some(dst) = insn.dst() else {
+            return;
+        };
+
+        let reg = self.reg(dst);
+        if reg.reg_type != RegType::PtrToRingbufSampleOrNull {
+            return;
+        }
+        let id = reg.ref_obj_id;
+        let is_null = matches!(jmp_op, JmpOp::Jeq) == taken;
+
+        if is_null {
+            self.refs.retain(|r| r.id != id);
+        }
+        for reg in self.regs.iter_mut().filter(|r| r.ref_obj_id == id) {
+            if is_null {
+                *reg = RegState::scalar(Some(ScalarValue::constant(0)));
+            } else {
+                reg.reg_type = RegType::PtrToRingbufSample;
+            }
+        }
+    }
+
     /// Advance to next instruction.
     pub fn advance(&mut self) {
         self.insn_idx += 1;
@@ -450,6 +666,7 @@ impl fmt::Debug for VerifierState {
             .field("insn_idx", &self.insn_idx)
             .field("insn_processed", &self.insn_processed)
             .field("stack_depth", &self.stack.max_depth())
+            .field("refs", &self.refs)
             .finish()
     }
 }
@@ -515,4 +732,33 @@ mod tests {
         assert!(!state.is_reg_init(Register::R0));
         assert!(!state.is_reg_init(Register::R2));
     }
+
+    #[test]
+    fn ringbuf_reference_tracking() {
+        let mut state = VerifierState::new_entry(512);
+        let id = state.acquire_ref(4);
+        *state.reg_mut(Register::R0) = RegState::ringbuf_sample_or_null(id, 16);
+        state.regs[Register::R6 as usize] = state.reg(Register::R0).clone();
+
+        // if r0 == 0 goto ...
+        let check = BpfInsn::jeq_imm(0, 0, 3);
+        let mut null_side = state.clone();
+        null_side.refine_branch(&check, true);
+        assert!(null_side.unreleased_ref().is_none());
+        assert_eq!(null_side.reg(Register::R6).const_value(), Some(0));
+
+        state.refine_branch(&check, false);
+        assert_eq!(
+            state.reg(Register::R6).reg_type,
+            RegType::PtrToRingbufSample
+        );
+        assert_eq!(state.unreleased_ref().map(|r| r.insn_idx), Some(4));
+        assert!(state.reg(Register::R0).out_of_bounds(8, 8).is_none());
+        assert_eq!(state.reg(Register::R0).out_of_bounds(12, 8), Some(12));
+
+        assert!(state.release_ref(id));
+        assert!(state.unreleased_ref().is_none());
+        assert!(!state.reg(Register::R6).reg_type.is_pointer());
+        assert!(!state.release_ref(id));
+    }
 }
@@ -50,6 +50,7 @@ use alloc::vec::Vec;
 use core::marker::PhantomData;
 
 use super::error::{VerifyError, VerifyResult};
+use super::helpers::{HelperId, check_stack_buffer, get_helper_signature};
 use super::state::{RegState, RegType, ScalarValue, StackSlot, VerifierState};
 use crate::bytecode::insn::BpfInsn;
 use crate::bytecode::opcode::{AluOp, OpcodeClass};
@@ -291,16 +292,8 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
         let mut state = self.current_state.take().ok_or(VerifyError::EmptyProgram)?;
         state.insn_idx = start_idx;
 
-        // Check if we've already processed this block with compatible state
-        if let Some(merged) = self.find_merge_point(start_idx) {
-            if self.states_compatible(&state, &merged.state) {
-                // Already verified with compatible state
-                return Ok(());
-            }
-            // Need to merge states
-            self.merge_state_at(start_idx, &state)?;
-            return Ok(());
-        }
+        // Pruning against merge points happens when blocks are queued, so
+        // every block popped from the worklist still needs to be processed.
 
         // Process instructions in this block
         loop {
@@ -353,7 +346,9 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
                     target,
                 } => {
                     // Add both paths to worklist
-                    let branch_state = state.clone();
+                    let mut branch_state = state.clone();
+                    branch_state.refine_branch(insn, true);
+                    state.refine_branch(insn, false);
 
                     // Check for back edge on target
                     if target <= idx {
@@ -480,13 +475,20 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
                 return false;
             }
         }
-        true
+        s1.refs == s2.refs
     }
 
     /// Conservatively merge two states.
     ///
     /// The result is the "widest" state that encompasses both inputs.
+    /// References held on either path stay held in the merged state.
     fn merge_states_conservative(target: &mut VerifierState, incoming: &VerifierState) {
+        for acquired in &incoming.refs {
+            if !target.refs.contains(acquired) {
+                target.refs.push(*acquired);
+            }
+        }
+
         for i in 0..Register::COUNT {
             let target_reg = &mut target.regs[i];
             let incoming_reg = &incoming.regs[i];
@@ -529,6 +531,14 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
                     reg: Register::R0,
                 });
             }
+
+            // Every acquired reference must have been released
+            if let Some(acquired) = state.unreleased_ref() {
+                return Err(VerifyError::UnreleasedReference {
+                    insn_idx: acquired.insn_idx,
+                    exit_idx: idx,
+                });
+            }
             return Ok(InsnResult::Exit);
         }
 
@@ -549,15 +559,16 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
             return self.verify_jump(insn, state, idx);
         }
 
-        // Memory instructions
-        if insn.is_memory() {
-            self.verify_memory(insn, state, idx)?;
+        // Wide instruction (64-bit immediate load); checked before memory
+        // instructions since it shares the LD class
+        if insn.is_wide() {
+            self.verify_wide_load(insn, state, idx)?;
             return Ok(InsnResult::Continue);
         }
 
-        // Wide instruction (64-bit immediate load)
-        if insn.is_wide() {
-            self.verify_wide_load(insn, state, idx)?;
+        // Memory instructions
+        if insn.is_memory() {
+            self.verify_memory(insn, state, idx)?;
             return Ok(InsnResult::Continue);
         }
 
@@ -628,8 +639,8 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
             });
         }
 
-        // Update destination register to scalar
-        state.set_scalar(dst, Some(ScalarValue::unknown()));
+        // Update destination register
+        state.apply_alu(insn, dst, alu_op);
 
         Ok(())
     }
@@ -708,6 +719,40 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
             });
         }
 
+        // Helpers that fill a stack buffer need it to fit in the frame
+        let helper = HelperId::from_raw(helper_id);
+        if let Some(helper) = helper {
+            check_stack_buffer(&get_helper_signature(helper), state, idx)?;
+        }
+
+        // Releasing helpers consume the record pointer held in R1
+        if let Some(helper) = helper.filter(HelperId::releases_ref) {
+            let r1 = state.reg(Register::R1);
+            let ref_obj_id = r1.ref_obj_id;
+            if r1.reg_type != RegType::PtrToRingbufSample
+                || r1.ptr_offset != 0
+                || !state.release_ref(ref_obj_id)
+            {
+                return Err(VerifyError::HelperArgType {
+                    insn_idx: idx,
+                    helper_name: helper.name(),
+                    arg_idx: 0,
+                });
+            }
+        }
+
+        // Acquiring helpers need a constant size for bounds checking
+        let acquired_size = match helper.filter(HelperId::acquires_ref) {
+            Some(helper) => Some(state.reg(Register::R2).const_value().ok_or(
+                VerifyError::HelperArgType {
+                    insn_idx: idx,
+                    helper_name: helper.name(),
+                    arg_idx: 1,
+                },
+            )?),
+            None => None,
+        };
+
         // Clobber caller-saved registers
         for reg in [
             Register::R0,
@@ -721,7 +766,13 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
         }
 
         // R0 contains return value
-        state.set_scalar(Register::R0, Some(ScalarValue::unknown()));
+        match acquired_size {
+            Some(size) => {
+                let ref_obj_id = state.acquire_ref(idx);
+                *state.reg_mut(Register::R0) = RegState::ringbuf_sample_or_null(ref_obj_id, size);
+            }
+            None => state.set_scalar(Register::R0, Some(ScalarValue::unknown())),
+        }
 
         Ok(())
     }
@@ -773,6 +824,14 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
                     });
                 }
 
+                if let Some(offset) = src_state.out_of_bounds(insn.offset, size.size_bytes()) {
+                    return Err(VerifyError::OutOfBoundsAccess {
+                        insn_idx: idx,
+                        offset,
+                        size: size.size_bytes(),
+                    });
+                }
+
                 if src_state.reg_type == RegType::PtrToStack
                     || src_state.reg_type == RegType::PtrToFp
                 {
@@ -820,6 +879,14 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
                     });
                 }
 
+                if let Some(offset) = dst_state.out_of_bounds(insn.offset, size.size_bytes()) {
+                    return Err(VerifyError::OutOfBoundsAccess {
+                        insn_idx: idx,
+                        offset,
+                        size: size.size_bytes(),
+                    });
+                }
+
                 if dst_state.reg_type == RegType::PtrToStack
                     || dst_state.reg_type == RegType::PtrToFp
                 {
@@ -858,6 +925,14 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
                         reason: "cannot write to this pointer type",
                     });
                 }
+
+                if let Some(offset) = dst_state.out_of_bounds(insn.offset, size.size_bytes()) {
+                    return Err(VerifyError::OutOfBoundsAccess {
+                        insn_idx: idx,
+                        offset,
+                        size: size.size_bytes(),
+                    });
+                }
             }
 
             _ => {}
@@ -882,7 +957,12 @@ impl<P: PhysicalProfile> StreamingVerifier<P> {
             return Err(VerifyError::WriteToReadOnly { insn_idx: idx });
         }
 
-        state.set_scalar(dst, Some(ScalarValue::unknown()));
+        // src_reg = BPF_PSEUDO_MAP_FD marks a map reference
+        if insn.src_reg() == 1 {
+            *state.reg_mut(dst) = RegState::map_ptr(insn.imm as u32);
+        } else {
+            state.set_scalar(dst, Some(ScalarValue::unknown()));
+        }
 
         Ok(())
     }
@@ -1038,6 +1118,54 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn verify_ringbuf_leak_after_branch() {
+        let map = crate::bytecode::insn::WideInsn::ld_dw_imm(1, 0);
+        let insns = [
+            BpfInsn::new(map.insn.opcode, 1, 1, 0, 0), // r1 = map 0
+            map.next,
+            BpfInsn::mov64_imm(2, 8),  // r2 = 8
+            BpfInsn::mov64_imm(3, 0),  // r3 = 0
+            BpfInsn::call(131),        // r0 = bpf_ringbuf_reserve(r1, r2, r3)
+            BpfInsn::jne_imm(0, 0, 2), // if r0 != 0 goto keep
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),                // NULL: nothing reserved, fine
+            BpfInsn::new(0x7a, 0, 0, 0, 7), // keep: *(u64 *)(r0 + 0) = 7
+            BpfInsn::exit(),                // leaks the record
+        ];
+
+        let result = StreamingVerifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::UnreleasedReference {
+                insn_idx: 4,
+                exit_idx: 9
+            })
+        ));
+    }
+
+    #[test]
+    fn verify_ringbuf_discard_on_every_path() {
+        let map = crate::bytecode::insn::WideInsn::ld_dw_imm(1, 0);
+        let insns = [
+            BpfInsn::new(map.insn.opcode, 1, 1, 0, 0), // r1 = map 0
+            map.next,
+            BpfInsn::mov64_imm(2, 8),       // r2 = 8
+            BpfInsn::mov64_imm(3, 0),       // r3 = 0
+            BpfInsn::call(131),             // r0 = bpf_ringbuf_reserve(r1, r2, r3)
+            BpfInsn::jeq_imm(0, 0, 4),      // if r0 == 0 goto out
+            BpfInsn::new(0x7a, 0, 0, 0, 7), // *(u64 *)(r0 + 0) = 7
+            BpfInsn::mov64_reg(1, 0),       // r1 = r0
+            BpfInsn::mov64_imm(2, 0),       // r2 = 0
+            BpfInsn::call(133),             // bpf_ringbuf_discard(r1, r2)
+            BpfInsn::mov64_imm(0, 0),       // out: r0 = 0
+            BpfInsn::exit(),
+        ];
+
+        let result = StreamingVerifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
     #[test]
     fn verify_forward_jump() {
         let insns = [
@@ -1050,4 +1178,32 @@ mod tests {
         let result = StreamingVerifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn verify_probe_read_stack_buffer() {
+        let insns = [
+            BpfInsn::mov64_reg(1, 10),
+            BpfInsn::add64_imm(1, -16),    // r1 = fp - 16
+            BpfInsn::mov64_imm(2, 32),     // r2 = 32 (too large)
+            BpfInsn::mov64_imm(3, 0x1000), // r3 = untrusted address
+            BpfInsn::call(13),             // bpf_probe_read_kernel(r1, r2, r3)
+            BpfInsn::mov64_imm(0, 0),
+            BpfInsn::exit(),
+        ];
+
+        let result = StreamingVerifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(matches!(
+            result,
+            Err(VerifyError::OutOfBoundsAccess {
+                insn_idx: 4,
+                offset: -16,
+                size: 32
+            })
+        ));
+
+        let mut insns = insns;
+        insns[2] = BpfInsn::mov64_imm(2, 16);
+        let result = StreamingVerifier::<ActiveProfile>::verify(BpfProgType::SocketFilter, &insns);
+        assert!(result.is_ok(), "{result:?}");
+    }
 }
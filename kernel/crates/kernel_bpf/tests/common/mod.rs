@@ -0,0 +1,196 @@
+//! Shared helpers for the integration tests
+//!
+//! The interpreter calls BPF helpers through symbols the kernel provides, so
+//! each test binary links these stubs in their place.
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ktime_get_ns() -> u64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_trace_printk(_fmt: *const u8, _len: u32) -> i32 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_prandom_u32() -> u32 {
+    4
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_smp_processor_id() -> u32 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_map_lookup_elem(_map_id: u32, _key: *const u8) -> *mut u8 {
+    core::ptr::null_mut()
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_map_update_elem(
+    _map_id: u32,
+    _key: *const u8,
+    _value: *const u8,
+    _flags: u64,
+) -> i32 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_map_delete_elem(_map_id: u32, _key: *const u8) -> i32 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_output(
+    _map_id: u32,
+    _data: *const u8,
+    _size: u64,
+    _flags: u64,
+) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_reserve(_map_id: u32, _size: u64, _flags: u64) -> *mut u8 {
+    core::ptr::null_mut()
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_submit(_data: *mut u8, _flags: u64) {}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_discard(_data: *mut u8, _flags: u64) {}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_current_pid_tgid() -> u64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_current_uid_gid() -> u64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_current_comm(_buf: *mut u8, _size: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_probe_read(_dst: *mut u8, _size: u32, _src: *const u8) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_probe_read_user(_dst: *mut u8, _size: u32, _src: *const u8) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_probe_read_kernel(_dst: *mut u8, _size: u32, _src: *const u8) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_gpio_read(_pin: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_gpio_write(_pin: u32, _value: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_pwm_write(_pwm_id: u32, _channel: u32, _duty: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_motor_emergency_stop(_reason: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_sensor_last_timestamp(_device: u32) -> u64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_iio_read(_device: u32, _channel: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_can_send(_iface: u32, _can_id: u32, _data: *const u8, _len: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_timer_start(_timer: u32, _delay_ns: u64) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_timeseries_push(_map_id: u32, _value: *const u8) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_timeseries_stats(_map_id: u32, _n: u32, _stats: *mut u8, _size: u32) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_perf_event_output(
+    _ctx: *const u8,
+    _map_id: u32,
+    _flags: u64,
+    _data: *const u8,
+    _size: u64,
+) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_stackid(_ctx: *const u8, _map_id: u32, _flags: u64) -> i64 {
+    0
+}
+
+// SAFETY: Test stub for BPF helper.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_stack(_ctx: *const u8, _buf: *mut u8, _size: u32, _flags: u64) -> i64 {
+    0
+}
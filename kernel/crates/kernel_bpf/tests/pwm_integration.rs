@@ -10,71 +10,7 @@ use kernel_bpf::bytecode::program::{BpfProgType, ProgramBuilder};
 use kernel_bpf::execution::{BpfContext, BpfExecutor, Interpreter};
 use kernel_bpf::profile::ActiveProfile;
 
-// Stubs for resolving linker errors during integration testing
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_ktime_get_ns() -> u64 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_trace_printk(_fmt: *const u8, _len: u32) -> i32 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_map_lookup_elem(_map_id: u32, _key: *const u8) -> *mut u8 {
-    core::ptr::null_mut()
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_map_update_elem(
-    _map_id: u32,
-    _key: *const u8,
-    _value: *const u8,
-    _flags: u64,
-) -> i32 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_map_delete_elem(_map_id: u32, _key: *const u8) -> i32 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_ringbuf_output(
-    _map_id: u32,
-    _data: *const u8,
-    _size: u64,
-    _flags: u64,
-) -> i64 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_gpio_read(_pin: u32) -> i64 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_gpio_write(_pin: u32, _value: u32) -> i64 {
-    0
-}
-
-// SAFETY: Test stub for BPF helper.
-#[unsafe(no_mangle)]
-pub extern "C" fn bpf_pwm_write(_pwm_id: u32, _channel: u32, _duty: u32) -> i64 {
-    0
-}
+mod common;
 
 /// Helper to create an interpreter
 fn interpreter() -> Interpreter<ActiveProfile> {
@@ -92,6 +28,11 @@ fn test_pwm_event_processing() {
         duty_ns: 250_000,     // 25% duty
         polarity: 0,
         enabled: 1,
+        old_period_ns: 0,
+        old_duty_ns: 0,
+        old_polarity: 0,
+        old_enabled: 0,
+        last_change: 0,
     };
 
     // Serialize event to byte slice
@@ -121,6 +62,11 @@ fn test_pwm_event_processing() {
     // - duty_ns: u32 (20)
     // - polarity: u32 (24)
     // - enabled: u32 (28)
+    // - old_period_ns: u32 (32)
+    // - old_duty_ns: u32 (36)
+    // - old_polarity: u32 (40)
+    // - old_enabled: u32 (44)
+    // - last_change: u64 (48)
 
     let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
         // R1 points to context (BpfContext)
@@ -161,6 +107,11 @@ fn test_pwm_event_filtering() {
         duty_ns: 500,
         polarity: 0,
         enabled: 1,
+        old_period_ns: 0,
+        old_duty_ns: 0,
+        old_polarity: 0,
+        old_enabled: 0,
+        last_change: 0,
     };
 
     // SAFETY: Creating a byte slice from a stack-allocated struct is safe for test data serialization.
@@ -195,3 +146,54 @@ fn test_pwm_event_filtering() {
     // Channel is 2, so should return 0
     assert_eq!(result, Ok(0));
 }
+
+#[test]
+fn test_pwm_rate_limit_veto() {
+    // Program: veto (return 1) if the duty cycle rises by more than 10% of
+    // the period in one change. Decreases are always allowed.
+    let program = ProgramBuilder::<ActiveProfile>::new(BpfProgType::SocketFilter)
+        .insn(BpfInsn::new(0x79, 4, 1, 0, 0)) // LDX_DW R4, [R1+0]
+        .insn(BpfInsn::new(0x61, 2, 4, 20, 0)) // R2 = duty_ns
+        .insn(BpfInsn::new(0x61, 3, 4, 36, 0)) // R3 = old_duty_ns
+        .insn(BpfInsn::new(0x61, 5, 4, 16, 0)) // R5 = period_ns
+        .insn(BpfInsn::new(0x1F, 2, 3, 0, 0)) // R2 -= R3
+        .insn(BpfInsn::new(0x37, 5, 0, 0, 10)) // R5 /= 10
+        .insn(BpfInsn::new(0x6D, 2, 5, 2, 0)) // JSGT R2, R5, +2
+        .insn(BpfInsn::mov64_imm(0, 0))
+        .insn(BpfInsn::exit())
+        .insn(BpfInsn::mov64_imm(0, 1))
+        .insn(BpfInsn::exit())
+        .build()
+        .expect("valid program");
+
+    let change = |old_duty_ns, duty_ns| PwmEvent {
+        timestamp: 2_000_000,
+        chip_id: 0,
+        channel: 1,
+        period_ns: 1_000_000,
+        duty_ns,
+        polarity: 0,
+        enabled: 1,
+        old_period_ns: 1_000_000,
+        old_duty_ns,
+        old_polarity: 0,
+        old_enabled: 1,
+        last_change: 1_000_000,
+    };
+
+    let interp = interpreter();
+    let run = |event: PwmEvent| {
+        // SAFETY: Creating a byte slice from a stack-allocated struct is safe for test data serialization.
+        let data = unsafe {
+            core::slice::from_raw_parts(
+                &event as *const _ as *const u8,
+                core::mem::size_of::<PwmEvent>(),
+            )
+        };
+        interp.execute(&program, &BpfContext::from_slice(data))
+    };
+
+    assert_eq!(run(change(200_000, 250_000)), Ok(0));
+    assert_eq!(run(change(200_000, 800_000)), Ok(1));
+    assert_eq!(run(change(800_000, 0)), Ok(0));
+}
@@ -1,4 +1,4 @@
-use kernel_vfs::{ReadError, Stat, StatError, WriteError};
+use kernel_vfs::{MmapError, ReadError, Stat, StatError, WriteError};
 
 mod block;
 pub use block::*;
@@ -13,4 +13,11 @@ pub trait DevFile: Send + Sync {
     fn read(&mut self, buf: &mut [u8], offset: usize) -> Result<usize, ReadError>;
     fn write(&mut self, buf: &[u8], offset: usize) -> Result<usize, WriteError>;
     fn stat(&mut self, stat: &mut Stat) -> Result<(), StatError>;
+
+    /// See [`FileSystem::mmap`](kernel_vfs::fs::FileSystem::mmap). Files
+    /// are not mappable unless they say otherwise.
+    fn mmap(&mut self, offset: usize, len: usize) -> Result<usize, MmapError> {
+        let _ = (offset, len);
+        Err(MmapError::NotMappable)
+    }
 }
@@ -6,7 +6,9 @@ use core::sync::atomic::Ordering::Relaxed;
 
 use kernel_vfs::fs::{FileSystem, FsHandle};
 use kernel_vfs::path::{AbsolutePath, ROOT};
-use kernel_vfs::{CloseError, FsError, OpenError, ReadError, Stat, StatError, WriteError};
+use kernel_vfs::{
+    CloseError, FsError, MmapError, OpenError, ReadError, Stat, StatError, WriteError,
+};
 use thiserror::Error;
 
 use crate::node::{DevDirectoryNode, DevFileNode, DevNode, DevNodeKind};
@@ -174,6 +176,10 @@ impl FileSystem for DevFs {
     fn stat(&mut self, handle: FsHandle, stat: &mut Stat) -> Result<(), StatError> {
         self.resolve_handle(handle)?.stat(stat)
     }
+
+    fn mmap(&mut self, handle: FsHandle, offset: usize, len: usize) -> Result<usize, MmapError> {
+        self.resolve_handle(handle)?.mmap(offset, len)
+    }
 }
 
 #[cfg(test)]
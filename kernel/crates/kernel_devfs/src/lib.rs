@@ -14,7 +14,7 @@ mod node;
 pub use fs::*;
 use kernel_vfs::fs::{FileSystem, FsHandle};
 use kernel_vfs::path::AbsolutePath;
-use kernel_vfs::{CloseError, OpenError, ReadError, Stat, StatError, WriteError};
+use kernel_vfs::{CloseError, MmapError, OpenError, ReadError, Stat, StatError, WriteError};
 
 #[derive(Clone)]
 pub struct ArcLockedDevFs {
@@ -68,4 +68,8 @@ impl FileSystem for ArcLockedDevFs {
     fn stat(&mut self, handle: FsHandle, stat: &mut Stat) -> Result<(), StatError> {
         self.inner.write().stat(handle, stat)
     }
+
+    fn mmap(&mut self, handle: FsHandle, offset: usize, len: usize) -> Result<usize, MmapError> {
+        self.inner.write().mmap(handle, offset, len)
+    }
 }
@@ -21,6 +21,12 @@ pub trait Mapping {
 pub enum CreateMappingError {
     LocationAlreadyMapped,
     OutOfMemory,
+    /// The file descriptor is not open.
+    BadFile,
+    /// The file does not support mapping.
+    NotMappable,
+    /// The mapped range is outside the file.
+    InvalidRange,
 }
 
 pub trait MemoryAccess {
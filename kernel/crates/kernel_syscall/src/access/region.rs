@@ -1,3 +1,7 @@
+use core::ffi::c_int;
+
+use kernel_abi::ProtFlags;
+
 use crate::UserspacePtr;
 use crate::access::{AllocationStrategy, CreateMappingError, Location};
 
@@ -25,6 +29,20 @@ pub trait MemoryRegionAccess {
         allocation_strategy: AllocationStrategy,
     ) -> Result<UserspacePtr<u8>, CreateMappingError>;
 
+    /// Maps `size` bytes of the file open at `fd`, starting at `offset`, and
+    /// tracks the mapping as a memory region in the process. The mapping is
+    /// shared: it shows the file's memory itself rather than a copy, and is
+    /// only writable if `prot` allows writes.
+    /// Returns the address of the created mapping.
+    fn create_and_track_file_mapping(
+        &self,
+        location: Location,
+        size: usize,
+        prot: ProtFlags,
+        fd: c_int,
+        offset: usize,
+    ) -> Result<UserspacePtr<u8>, CreateMappingError>;
+
     /// Adds a memory region to the process's memory region tracking.
     /// This makes the region available to other kernel components.
     fn add_memory_region(&self, region: Self::Region);
@@ -1,7 +1,10 @@
-use kernel_abi::{EINVAL, ENOMEM, Errno, MapFlags, ProtFlags};
+use kernel_abi::{EBADF, EINVAL, ENODEV, ENOMEM, Errno, MapFlags, ProtFlags};
 
 use crate::UserspacePtr;
-use crate::access::{AllocationStrategy, Location, MemoryRegionAccess};
+use crate::access::{AllocationStrategy, CreateMappingError, Location, MemoryRegionAccess};
+
+/// Granularity of file mapping offsets
+const PAGE_SIZE: usize = 4096;
 
 pub fn sys_mmap<Cx: MemoryRegionAccess>(
     cx: &Cx,
@@ -9,8 +12,8 @@ pub fn sys_mmap<Cx: MemoryRegionAccess>(
     len: usize,
     prot: i32,
     flags: i32,
-    _fd: i32,
-    _offset: usize,
+    fd: i32,
+    offset: usize,
 ) -> Result<usize, Errno> {
     // Validate size is non-zero
     if len == 0 {
@@ -19,11 +22,17 @@ pub fn sys_mmap<Cx: MemoryRegionAccess>(
 
     let flags = MapFlags::from_bits(flags).ok_or(EINVAL)?;
 
-    // For now, only support anonymous private mappings
-    if !flags.contains(MapFlags::ANONYMOUS) {
-        return Err(EINVAL);
-    }
-    if !flags.contains(MapFlags::PRIVATE) {
+    // For now, only support anonymous private mappings and shared file
+    // mappings; private file mappings would need copy-on-write
+    let anonymous = flags.contains(MapFlags::ANONYMOUS);
+    if anonymous {
+        if !flags.contains(MapFlags::PRIVATE) {
+            return Err(EINVAL);
+        }
+    } else if !flags.contains(MapFlags::SHARED)
+        || flags.contains(MapFlags::PRIVATE)
+        || !offset.is_multiple_of(PAGE_SIZE)
+    {
         return Err(EINVAL);
     }
 
@@ -54,12 +63,17 @@ pub fn sys_mmap<Cx: MemoryRegionAccess>(
 
     // Create the mapping and add it to the process's memory regions
     // The context is responsible for converting the mapping to a region
-    let mapped_addr = cx
-        .create_and_track_mapping(location, len, allocation_strategy)
-        .map_err(|e| match e {
-            crate::access::CreateMappingError::LocationAlreadyMapped => EINVAL,
-            crate::access::CreateMappingError::OutOfMemory => ENOMEM,
-        })?;
+    let mapped_addr = if anonymous {
+        cx.create_and_track_mapping(location, len, allocation_strategy)
+    } else {
+        cx.create_and_track_file_mapping(location, len, prot, fd, offset)
+    }
+    .map_err(|e| match e {
+        CreateMappingError::LocationAlreadyMapped | CreateMappingError::InvalidRange => EINVAL,
+        CreateMappingError::OutOfMemory => ENOMEM,
+        CreateMappingError::BadFile => EBADF,
+        CreateMappingError::NotMappable => ENODEV,
+    })?;
 
     Ok(mapped_addr.addr())
 }
@@ -68,8 +82,9 @@ pub fn sys_mmap<Cx: MemoryRegionAccess>(
 mod tests {
     use alloc::sync::Arc;
     use alloc::vec::Vec;
+    use core::ffi::c_int;
 
-    use kernel_abi::{EINVAL, MapFlags, ProtFlags};
+    use kernel_abi::{EBADF, EINVAL, MapFlags, ProtFlags};
     use spin::mutex::Mutex;
 
     use crate::UserspacePtr;
@@ -146,6 +161,21 @@ mod tests {
             Ok(ptr)
         }
 
+        fn create_and_track_file_mapping(
+            &self,
+            location: Location,
+            size: usize,
+            _prot: ProtFlags,
+            fd: c_int,
+            _offset: usize,
+        ) -> Result<UserspacePtr<u8>, CreateMappingError> {
+            // Only fd 3 is open, and mappable
+            if fd != 3 {
+                return Err(CreateMappingError::BadFile);
+            }
+            self.create_and_track_mapping(location, size, AllocationStrategy::Eager)
+        }
+
         fn add_memory_region(&self, _region: Self::Region) {
             // Just a placeholder for testing
         }
@@ -230,6 +260,36 @@ mod tests {
         assert_eq!(result, Err(EINVAL));
     }
 
+    #[test]
+    fn test_mmap_shared_file() {
+        let cx = Arc::new(TestMemoryAccess::new());
+        // SAFETY: creating a dummy pointer for testing purposes
+        let addr = unsafe { UserspacePtr::try_from_usize(0).unwrap() };
+        let prot = (ProtFlags::READ | ProtFlags::WRITE).bits();
+
+        let result = sys_mmap(&cx, addr, 4096, prot, MapFlags::SHARED.bits(), 3, 4096);
+        assert!(result.is_ok());
+
+        let result = sys_mmap(&cx, addr, 4096, prot, MapFlags::SHARED.bits(), 4, 0);
+        assert_eq!(result, Err(EBADF));
+
+        // Offsets must be page-aligned
+        let result = sys_mmap(&cx, addr, 4096, prot, MapFlags::SHARED.bits(), 3, 100);
+        assert_eq!(result, Err(EINVAL));
+
+        // Private file mappings are not supported
+        let result = sys_mmap(
+            &cx,
+            addr,
+            4096,
+            prot,
+            (MapFlags::SHARED | MapFlags::PRIVATE).bits(),
+            3,
+            0,
+        );
+        assert_eq!(result, Err(EINVAL));
+    }
+
     #[test]
     fn test_mmap_fixed() {
         let cx = Arc::new(TestMemoryAccess::new());
@@ -1,5 +1,5 @@
 use crate::path::AbsolutePath;
-use crate::{CloseError, OpenError, ReadError, Stat, StatError, WriteError};
+use crate::{CloseError, MmapError, OpenError, ReadError, Stat, StatError, WriteError};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct FsHandle(u64);
@@ -41,4 +41,19 @@ pub trait FileSystem: Send + Sync {
     fn write(&mut self, handle: FsHandle, buf: &[u8], offset: usize) -> Result<usize, WriteError>;
 
     fn stat(&mut self, handle: FsHandle, stat: &mut Stat) -> Result<(), StatError>;
+
+    /// Get the kernel address of `len` bytes of the file at the given
+    /// `handle`, starting at `offset`, so that they can be mapped into a
+    /// process. Writes through the mapping go straight to the file.
+    ///
+    /// The memory is page-aligned. A mapping does not keep the file open,
+    /// so the memory must stay allocated for as long as the kernel runs.
+    ///
+    /// # Errors
+    /// Returns [`MmapError::NotMappable`] if the file can't be mapped,
+    /// which is the default.
+    fn mmap(&mut self, handle: FsHandle, offset: usize, len: usize) -> Result<usize, MmapError> {
+        let _ = (handle, offset, len);
+        Err(MmapError::NotMappable)
+    }
 }
@@ -74,3 +74,19 @@ pub enum StatError {
         FsError,
     ),
 }
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Error)]
+pub enum MmapError {
+    #[error("{0}")]
+    FsError(
+        #[from]
+        #[source]
+        FsError,
+    ),
+    #[error("file is not mappable")]
+    NotMappable,
+    #[error("range is outside the file")]
+    InvalidRange,
+    #[error("out of memory")]
+    OutOfMemory,
+}
@@ -5,9 +5,9 @@ use core::ops::Deref;
 use spin::RwLock;
 
 use crate::fs::{FileSystem, FsHandle};
-use crate::path::AbsoluteOwnedPath;
+use crate::path::{AbsoluteOwnedPath, AbsolutePath};
 use crate::vfs::stat::Stat;
-use crate::{FsError, ReadError, StatError, WriteError};
+use crate::{FsError, MmapError, ReadError, StatError, WriteError};
 
 #[derive(Clone)]
 pub struct VfsNode {
@@ -61,6 +61,12 @@ impl VfsNode {
         }
     }
 
+    /// The path this node was opened at.
+    #[must_use]
+    pub fn path(&self) -> &AbsolutePath {
+        self.inner.path.as_ref()
+    }
+
     /// Reads up to `buf.len()` bytes from the file at the given
     /// `offset` into `buf` and returns the number of bytes read.
     ///
@@ -96,6 +102,17 @@ impl VfsNode {
         let mut guard = fs.write();
         guard.stat(self.fs_handle, stat)
     }
+
+    /// Get the kernel address of `len` bytes of the file, starting at
+    /// `offset`, to map them into a process.
+    ///
+    /// See [`FileSystem::mmap`] for more details.
+    pub fn mmap(&self, offset: usize, len: usize) -> Result<usize, MmapError> {
+        let fs = self.fs.upgrade().ok_or(FsError::FileSystemNotOpen)?;
+
+        let mut guard = fs.write();
+        guard.mmap(self.fs_handle, offset, len)
+    }
 }
 
 #[cfg(test)]
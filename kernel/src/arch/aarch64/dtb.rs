@@ -149,3 +149,50 @@ pub fn total_memory() -> usize {
     // SAFETY: Reading initialized static global.
     unsafe { DTB_INFO.total_memory }
 }
+
+/// MMIO device found in the device tree
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceNode {
+    pub base: usize,
+    pub size: usize,
+    /// GIC interrupt ID of the first interrupt, if any
+    pub irq: Option<u32>,
+}
+
+/// Find the first device compatible with one of `compatible`.
+pub fn find_compatible(compatible: &[&str]) -> Option<DeviceNode> {
+    let info = info();
+    if info.dtb_start == 0 {
+        return None;
+    }
+
+    // SAFETY: The DTB region was recorded by parse() and is reserved from the
+    // physical allocator, so it is still intact. Fdt::new rejects it if
+    // parsing failed and only the fallback size is known.
+    let dtb = unsafe { core::slice::from_raw_parts(info.dtb_start as *const u8, info.dtb_size) };
+    let fdt = Fdt::new(dtb).ok()?;
+    let node = fdt.find_compatible(compatible)?;
+    let reg = node.reg()?.next()?;
+    let irq = node.property("interrupts").and_then(|p| gic_irq(p.value));
+
+    Some(DeviceNode {
+        base: reg.starting_address as usize,
+        size: reg.size.unwrap_or(0),
+        irq,
+    })
+}
+
+/// Convert the first GIC interrupt specifier (type, number, flags) of an
+/// `interrupts` property to a GIC interrupt ID.
+fn gic_irq(value: &[u8]) -> Option<u32> {
+    let cell = |i: usize| {
+        let bytes = value.get(i * 4..i * 4 + 4)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?))
+    };
+
+    match (cell(0)?, cell(1)?) {
+        (0, spi) => Some(32 + spi),
+        (1, ppi) => Some(16 + ppi),
+        _ => None,
+    }
+}
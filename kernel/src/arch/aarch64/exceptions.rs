@@ -1,5 +1,12 @@
 use core::arch::asm;
 
+use kernel_bpf::attach::{
+    PAGE_FAULT_PROTECTION, PAGE_FAULT_USER, PAGE_FAULT_WRITE, PageFaultEvent, PtRegs,
+};
+
+use crate::bpf::{kprobe, tracepoint};
+use crate::mcore::context::ExecutionContext;
+
 /// Exception vector table
 #[repr(C, align(2048))]
 pub struct ExceptionVectorTable {
@@ -44,6 +51,33 @@ unsafe extern "C" {
     fn exception_vector_base();
 }
 
+/// Synchronous exception handler for exceptions taken from EL1
+///
+/// Called from the vector table with every register saved in `regs`, which
+/// is restored on return. Breakpoints go to kprobes, everything else to
+/// [`handle_sync_exception`].
+#[unsafe(no_mangle)]
+pub extern "C" fn handle_kernel_sync_exception(regs: &mut PtRegs) {
+    let esr: u64;
+    // SAFETY: Reading ESR is safe in an exception handler.
+    unsafe { asm!("mrs {}, esr_el1", out(reg) esr) };
+
+    let ec = (esr >> 26) & 0x3F;
+    if ec == 0x3C && kprobe::handle_breakpoint(regs) {
+        return;
+    }
+
+    handle_sync_exception();
+
+    // The handlers above work on the live registers; keep their changes
+    // (e.g. skipping an instruction) when the frame is restored.
+    // SAFETY: Reading exception registers is safe in an exception handler.
+    unsafe {
+        asm!("mrs {}, elr_el1", out(reg) regs.pc);
+        asm!("mrs {}, spsr_el1", out(reg) regs.pstate);
+    }
+}
+
 /// Synchronous exception handler
 ///
 /// # Safety
@@ -80,7 +114,7 @@ pub extern "C" fn handle_sync_exception() {
         }
         0x24 | 0x25 => {
             // Data abort from lower/same EL
-            handle_data_abort(elr, far, iss);
+            handle_data_abort(elr, far, iss, ec == 0x24);
         }
         _ => {
             panic!(
@@ -152,13 +186,34 @@ impl DataFaultCode {
     }
 }
 
-fn handle_data_abort(elr: u64, far: u64, iss: u64) {
+fn handle_data_abort(elr: u64, far: u64, iss: u64, from_user: bool) {
     let is_write = (iss & (1 << 6)) != 0; // WnR bit
     let _is_cm = (iss & (1 << 8)) != 0; // Cache maintenance
     let _is_s1ptw = (iss & (1 << 7)) != 0; // Stage 1 page table walk
 
     let fault_code = DataFaultCode::from_iss(iss);
 
+    tracepoint::PAGE_FAULT.emit(|| {
+        let mut flags = 0;
+        if is_write {
+            flags |= PAGE_FAULT_WRITE;
+        }
+        if from_user {
+            flags |= PAGE_FAULT_USER;
+        }
+        if fault_code.is_some_and(|code| code.is_permission_fault()) {
+            flags |= PAGE_FAULT_PROTECTION;
+        }
+        PageFaultEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            address: far,
+            ip: elr,
+            pid: ExecutionContext::try_load().map_or(0, |ctx| ctx.pid().as_u64()),
+            flags,
+            cpu: tracepoint::cpu(),
+        }
+    });
+
     log::debug!(
         "Data abort: PC={:#x}, addr={:#x}, write={}, dfsc={:?}",
         elr,
@@ -2,7 +2,8 @@
 //!
 //! This module handles interrupt initialization and dispatching for ARM64.
 //! It uses the GIC (Generic Interrupt Controller) for interrupt management
-//! and the ARM generic timer for scheduling.
+//! and the ARM generic timer for scheduling. The virtual timer is left to
+//! high-resolution timers ([`crate::hrtimer`]).
 //!
 //! # RP1 GPIO Interrupt Routing
 //!
@@ -19,8 +20,16 @@
 //!
 //! The RP1's GPIO Bank 0 generates internal IRQ 0, which routes through
 //! the RP1's interrupt controller to one of these PCIe lines.
+//!
+//! # PL061 GPIO Interrupt Routing
+//!
+//! On QEMU virt, the PL061 GPIO controller has a GIC SPI of its own, read
+//! from the device tree when the controller is initialized.
+
+use kernel_bpf::attach::{IrqEvent, PtRegs};
 
 use super::gic;
+use crate::bpf::tracepoint;
 
 /// Physical timer IRQ number (PPI 14 = IRQ 30)
 const TIMER_IRQ: u32 = gic::irq::TIMER_PHYS;
@@ -52,8 +61,13 @@ pub fn init() {
     {
         gic::enable_irq(RP1_GPIO_IRQ);
         gic::set_priority(RP1_GPIO_IRQ, 0x80);
+        super::platform::rpi5::gpio::init();
     }
 
+    // Discover the PL061 GPIO controller and enable its interrupt
+    #[cfg(feature = "virt")]
+    super::platform::virt::gpio::init();
+
     // Initialize and start the timer
     init_timer();
 
@@ -75,10 +89,11 @@ pub fn init() {
 /// # Safety
 ///
 /// This function is the IRQ exception handler entry point called from the vector table
-/// (via assembly stubs that save register state). It assumes the GIC is initialized
-/// and that it's safe to interact with hardware state. It must not unwind.
+/// (via assembly stubs that save the interrupted registers to `regs`). It assumes the
+/// GIC is initialized and that it's safe to interact with hardware state. It must not
+/// unwind.
 #[unsafe(no_mangle)]
-pub extern "C" fn handle_irq() {
+pub extern "C" fn handle_irq(regs: &PtRegs) {
     // Acknowledge the interrupt and get its ID
     let irq = gic::acknowledge();
 
@@ -90,38 +105,62 @@ pub extern "C" fn handle_irq() {
 
     log::trace!("Handling IRQ {}", irq);
 
+    let entered = tracepoint::IRQ_EXIT
+        .is_enabled()
+        .then(crate::time::get_kernel_time_ns);
+    tracepoint::IRQ_ENTRY.emit(|| IrqEvent {
+        timestamp: crate::time::get_kernel_time_ns(),
+        duration_ns: 0,
+        irq,
+        cpu: tracepoint::cpu(),
+    });
+
     // Dispatch based on IRQ number
     match irq {
-        TIMER_IRQ => handle_timer_interrupt(),
+        TIMER_IRQ => handle_timer_interrupt(regs),
+        gic::irq::TIMER_VIRT => crate::hrtimer::expire(),
         #[cfg(feature = "rpi5")]
         RP1_GPIO_IRQ => {
             crate::arch::aarch64::platform::rpi5::gpio::handle_interrupt();
         }
+        #[cfg(feature = "virt")]
+        irq if Some(irq) == super::platform::virt::gpio::irq() => {
+            super::platform::virt::gpio::handle_interrupt();
+        }
+        irq if crate::driver::uart::is_port_irq(irq) => crate::driver::uart::interrupt(irq),
         _ => {
             log::warn!("Unhandled IRQ: {}", irq);
         }
     }
 
+    tracepoint::IRQ_EXIT.emit(|| {
+        let timestamp = crate::time::get_kernel_time_ns();
+        IrqEvent {
+            timestamp,
+            duration_ns: entered.map_or(0, |entered| timestamp.saturating_sub(entered)),
+            irq,
+            cpu: tracepoint::cpu(),
+        }
+    });
+
     // Signal end of interrupt
     if irq != gic::irq::SPURIOUS {
         gic::end_of_interrupt(irq);
     }
 }
 
-/// Handle timer interrupt
-fn handle_timer_interrupt() {
+/// Handle timer interrupt of the code interrupted with `regs`
+fn handle_timer_interrupt(regs: &PtRegs) {
     log::trace!("Timer interrupt started");
     // Clear and reset timer for next interrupt
     clear_timer_interrupt();
     set_next_timer();
 
-    // Run BPF hooks (AttachType::Timer = 1)
-    if let Some(manager) = crate::BPF_MANAGER.get() {
-        log::trace!("Executing BPF timer hooks");
-        let ctx = kernel_bpf::execution::BpfContext::empty();
-        manager.lock().execute_hooks(1, &ctx);
-        log::trace!("BPF timer hooks executed");
-    }
+    // Run BPF hooks (AttachType::Timer = 1) on a sample of the interrupted code
+    log::trace!("Executing BPF timer hooks");
+    crate::bpf::attach::timer_tick(regs);
+    log::trace!("BPF timer hooks executed");
+    crate::driver::watchdog::tick();
 
     // Trigger scheduler tick (may cause context switch)
     log::trace!("Calling timer_tick");
@@ -79,3 +79,8 @@ impl Architecture for Aarch64 {
         shutdown::reboot()
     }
 }
+
+/// Run `f` with interrupts disabled on this CPU.
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    Aarch64::without_interrupts(f)
+}
@@ -12,6 +12,7 @@
 
 use super::memory_map::RP1_GPIO_BASE;
 use super::mmio::MmioReg;
+use crate::driver::gpio::GpioController;
 
 /// GPIO function select values
 ///
@@ -285,6 +286,54 @@ impl Rp1Gpio {
     }
 }
 
+impl GpioController for Rp1Gpio {
+    fn num_lines(&self) -> u32 {
+        Self::NUM_PINS.into()
+    }
+
+    fn read(&self, line: u32) -> bool {
+        Rp1Gpio::read(self, line as u8)
+    }
+
+    fn write(&self, line: u32, high: bool) {
+        if high {
+            self.set_high(line as u8);
+        } else {
+            self.set_low(line as u8);
+        }
+    }
+
+    fn toggle(&self, line: u32) {
+        Rp1Gpio::toggle(self, line as u8);
+    }
+
+    fn configure_output(&self, line: u32, initial_high: bool) {
+        Rp1Gpio::configure_output(self, line as u8, initial_high);
+    }
+
+    fn configure_input(&self, line: u32) {
+        Rp1Gpio::configure_input(self, line as u8);
+    }
+
+    fn enable_interrupt(&self, line: u32, rising: bool, falling: bool) {
+        Rp1Gpio::disable_interrupt(self, line as u8);
+        Rp1Gpio::enable_interrupt(self, line as u8, rising, falling);
+    }
+
+    fn disable_interrupt(&self, line: u32) {
+        Rp1Gpio::disable_interrupt(self, line as u8);
+    }
+}
+
+/// RP1 IO bank 0, registered as `gpiochip0`
+// SAFETY: The RP1 GPIO block is always mapped on RPi5.
+static RP1_GPIO: Rp1Gpio = unsafe { Rp1Gpio::new() };
+
+/// Register the RP1 GPIO bank with the GPIO layer.
+pub fn init() {
+    crate::driver::gpio::register(&RP1_GPIO);
+}
+
 /// Read the ARM generic timer counter for high-precision timestamps
 #[inline]
 fn read_timer_counter() -> u64 {
@@ -326,9 +375,7 @@ fn counter_to_ns(counter: u64) -> u64 {
 /// Called from the main IRQ handler when an RP1 GPIO interrupt fires.
 /// Scans all pins for pending events and invokes attached BPF programs.
 pub fn handle_interrupt() {
-    // SAFETY: We are in an interrupt handler, so we can access the GPIO hardware.
-    // The base address is correct for RPi5.
-    let gpio = unsafe { Rp1Gpio::new() };
+    let gpio = &RP1_GPIO;
 
     // Get timestamp at interrupt entry for accurate timing
     let timestamp = counter_to_ns(read_timer_counter());
@@ -362,32 +409,9 @@ pub fn handle_interrupt() {
             // 1. Clear interrupt FIRST to avoid missing edges
             gpio.clear_interrupt(pin);
 
-            // 2. Prepare BPF Context
-            let event = kernel_bpf::attach::GpioEvent {
-                timestamp,
-                chip_id: 0, // gpiochip0 (RP1 IO Bank 0)
-                line: pin as u32,
-                edge,
-                value,
-            };
-
-            // SAFETY: Transmuting struct to slice for read-only access
-            let slice = unsafe {
-                core::slice::from_raw_parts(
-                    &event as *const _ as *const u8,
-                    core::mem::size_of::<kernel_bpf::attach::GpioEvent>(),
-                )
-            };
-
-            let ctx = kernel_bpf::execution::BpfContext::from_slice(slice);
-
-            // 3. Invoke BPF hooks
-            if let Some(manager) = crate::BPF_MANAGER.get() {
-                // Execute all attached BPF programs
-                manager
-                    .lock()
-                    .execute_hooks(crate::bpf::ATTACH_TYPE_GPIO, &ctx);
-            }
+            // 2. Invoke BPF hooks attached to this line and edge
+            // (gpiochip0 is RP1 IO Bank 0)
+            crate::driver::gpio::dispatch_edge(0, pin as u32, edge, value, timestamp);
         }
     }
 }
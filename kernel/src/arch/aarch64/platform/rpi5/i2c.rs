@@ -0,0 +1,249 @@
+//! RP1 I2C Driver for Raspberry Pi 5
+//!
+//! The RP1 has seven Synopsys DesignWare I2C controllers. I2C1 is routed to
+//! header pins 3 (SDA, GPIO2) and 5 (SCL, GPIO3); with `dtparam=i2c_arm=on`
+//! the firmware selects that pin function, so this driver only programs the
+//! controller itself.
+//!
+//! Transfers are polled: bytes are pushed through the TX FIFO as data and
+//! read commands, and the controller generates the start, repeated start and
+//! stop conditions.
+
+use super::memory_map::{RP1_I2C0_BASE, RP1_I2C1_BASE};
+use super::mmio::MmioReg;
+use crate::driver::i2c::{I2cBus, I2cError};
+
+/// DesignWare I2C register offsets
+mod reg {
+    /// Control
+    pub const CON: usize = 0x00;
+    /// Target address
+    pub const TAR: usize = 0x04;
+    /// Data and command FIFO
+    pub const DATA_CMD: usize = 0x10;
+    /// Fast mode SCL high count
+    pub const FS_SCL_HCNT: usize = 0x1C;
+    /// Fast mode SCL low count
+    pub const FS_SCL_LCNT: usize = 0x20;
+    /// Interrupt mask
+    pub const INTR_MASK: usize = 0x30;
+    /// Raw interrupt status
+    pub const RAW_INTR_STAT: usize = 0x34;
+    /// Clear all interrupts (read to clear)
+    pub const CLR_INTR: usize = 0x40;
+    /// Enable
+    pub const ENABLE: usize = 0x6C;
+    /// Status
+    pub const STATUS: usize = 0x70;
+    /// RX FIFO level
+    pub const RXFLR: usize = 0x78;
+    /// Transmit abort source
+    pub const TX_ABRT_SOURCE: usize = 0x80;
+    /// Enable status
+    pub const ENABLE_STATUS: usize = 0x9C;
+}
+
+/// Control register bit fields
+mod con {
+    /// Controller mode
+    pub const MASTER_MODE: u32 = 1 << 0;
+    /// Fast mode (400 kHz)
+    pub const SPEED_FAST: u32 = 2 << 1;
+    /// Allow repeated start conditions
+    pub const RESTART_EN: u32 = 1 << 5;
+    /// Disable target mode
+    pub const SLAVE_DISABLE: u32 = 1 << 6;
+}
+
+/// Data and command register bit fields
+mod cmd {
+    /// Read a byte instead of writing one
+    pub const READ: u32 = 1 << 8;
+    /// Issue a stop after this byte
+    pub const STOP: u32 = 1 << 9;
+    /// Issue a repeated start before this byte
+    pub const RESTART: u32 = 1 << 10;
+}
+
+/// Status and interrupt bit fields
+mod status {
+    /// TX FIFO not full
+    pub const TFNF: u32 = 1 << 1;
+    /// Transfer aborted (raw interrupt status)
+    pub const TX_ABRT: u32 = 1 << 6;
+    /// Stop condition seen (raw interrupt status)
+    pub const STOP_DET: u32 = 1 << 9;
+    /// Arbitration lost (abort source)
+    pub const ABRT_LOST: u32 = 1 << 12;
+}
+
+/// Controller input clock
+const IC_CLK_HZ: u32 = 200_000_000;
+
+/// SCL frequency
+const SCL_HZ: u32 = 400_000;
+
+/// Polls of a status bit before a transfer is considered stuck
+///
+/// Generously above the ~25 us a byte takes at 400 kHz.
+const POLL_LIMIT: u32 = 1_000_000;
+
+/// RP1 DesignWare I2C Controller
+pub struct Rp1I2c {
+    base: usize,
+}
+
+impl Rp1I2c {
+    /// Create a driver for I2C0
+    ///
+    /// # Safety
+    ///
+    /// No other driver may access the I2C0 registers.
+    pub const unsafe fn i2c0() -> Self {
+        Self {
+            base: RP1_I2C0_BASE,
+        }
+    }
+
+    /// Create a driver for I2C1, the controller on the 40-pin header
+    ///
+    /// # Safety
+    ///
+    /// No other driver may access the I2C1 registers.
+    pub const unsafe fn i2c1() -> Self {
+        Self {
+            base: RP1_I2C1_BASE,
+        }
+    }
+
+    /// Configure the controller for 400 kHz polled operation.
+    pub fn init(&self) {
+        self.disable();
+
+        self.reg(reg::CON)
+            .write(con::MASTER_MODE | con::SPEED_FAST | con::RESTART_EN | con::SLAVE_DISABLE);
+
+        // Fast mode needs at least 0.6 us high and 1.3 us low, so split the
+        // period roughly 40/60.
+        let period = IC_CLK_HZ / SCL_HZ;
+        self.reg(reg::FS_SCL_HCNT).write(period * 2 / 5);
+        self.reg(reg::FS_SCL_LCNT).write(period * 3 / 5);
+
+        self.reg(reg::INTR_MASK).write(0);
+        let _ = self.reg(reg::CLR_INTR).read();
+    }
+
+    fn reg(&self, offset: usize) -> MmioReg<u32> {
+        // SAFETY: The base address is valid (checked at creation) and all
+        // offsets are within the register block.
+        unsafe { MmioReg::new(self.base + offset) }
+    }
+
+    fn enable(&self) {
+        self.reg(reg::ENABLE).write(1);
+    }
+
+    fn disable(&self) {
+        self.reg(reg::ENABLE).write(0);
+        for _ in 0..POLL_LIMIT {
+            if !self.reg(reg::ENABLE_STATUS).is_set(1) {
+                return;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Fail with the abort reason if the controller gave up on the transfer.
+    fn check_abort(&self) -> Result<(), I2cError> {
+        if !self.reg(reg::RAW_INTR_STAT).is_set(status::TX_ABRT) {
+            return Ok(());
+        }
+
+        let source = self.reg(reg::TX_ABRT_SOURCE).read();
+        // Reading CLR_INTR also releases the TX FIFO after an abort
+        let _ = self.reg(reg::CLR_INTR).read();
+        // Every other abort source is some form of missing acknowledge
+        if source & status::ABRT_LOST != 0 {
+            Err(I2cError::ArbitrationLost)
+        } else {
+            Err(I2cError::Nack)
+        }
+    }
+
+    /// Wait until `ready` holds, stopping on an abort.
+    fn wait(&self, ready: impl Fn(&Self) -> bool) -> Result<(), I2cError> {
+        for _ in 0..POLL_LIMIT {
+            self.check_abort()?;
+            if ready(self) {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(I2cError::Timeout)
+    }
+
+    fn push(&self, value: u32) -> Result<(), I2cError> {
+        self.wait(|s| s.reg(reg::STATUS).is_set(status::TFNF))?;
+        self.reg(reg::DATA_CMD).write(value);
+        Ok(())
+    }
+
+    fn run(&self, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+        let total = write.len() + read.len();
+
+        for (i, &byte) in write.iter().enumerate() {
+            let stop = if i + 1 == total { cmd::STOP } else { 0 };
+            self.push(u32::from(byte) | stop)?;
+        }
+
+        // Queue read commands while draining the RX FIFO, so reads longer
+        // than the FIFO depth do not stall.
+        let mut queued = 0;
+        let mut received = 0;
+        let mut polls = 0;
+        while received < read.len() {
+            self.check_abort()?;
+
+            if queued < read.len() && self.reg(reg::STATUS).is_set(status::TFNF) {
+                let mut value = cmd::READ;
+                if queued == 0 && !write.is_empty() {
+                    value |= cmd::RESTART;
+                }
+                if queued + 1 == read.len() {
+                    value |= cmd::STOP;
+                }
+                self.reg(reg::DATA_CMD).write(value);
+                queued += 1;
+            } else if self.reg(reg::RXFLR).read() > 0 {
+                read[received] = self.reg(reg::DATA_CMD).read() as u8;
+                received += 1;
+                polls = 0;
+            } else if polls == POLL_LIMIT {
+                return Err(I2cError::Timeout);
+            } else {
+                polls += 1;
+                core::hint::spin_loop();
+            }
+        }
+
+        self.wait(|s| s.reg(reg::RAW_INTR_STAT).is_set(status::STOP_DET))
+    }
+}
+
+impl I2cBus for Rp1I2c {
+    fn transfer(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+        if write.is_empty() && read.is_empty() {
+            return Ok(());
+        }
+
+        // The target address can only change while the controller is off
+        self.disable();
+        self.reg(reg::TAR).write(u32::from(addr & 0x7F));
+        let _ = self.reg(reg::CLR_INTR).read();
+        self.enable();
+
+        let result = self.run(write, read);
+        self.disable();
+        result
+    }
+}
@@ -24,6 +24,9 @@ pub const RP1_GPIO_OFFSET: usize = 0x000D_0000;
 /// RP1 internal offset for I2C0
 pub const RP1_I2C0_OFFSET: usize = 0x0007_0000;
 
+/// RP1 internal offset for I2C1
+pub const RP1_I2C1_OFFSET: usize = 0x0007_4000;
+
 /// RP1 internal offset for SPI0
 pub const RP1_SPI0_OFFSET: usize = 0x0005_0000;
 
@@ -48,12 +51,27 @@ pub const RP1_UART1_BASE: usize = rp1_peripheral_addr(RP1_UART1_OFFSET);
 /// GPIO base address
 pub const RP1_GPIO_BASE: usize = rp1_peripheral_addr(RP1_GPIO_OFFSET);
 
+/// I2C0 base address (DesignWare)
+pub const RP1_I2C0_BASE: usize = rp1_peripheral_addr(RP1_I2C0_OFFSET);
+
+/// I2C1 base address (DesignWare)
+pub const RP1_I2C1_BASE: usize = rp1_peripheral_addr(RP1_I2C1_OFFSET);
+
+/// SPI0 base address (DesignWare)
+pub const RP1_SPI0_BASE: usize = rp1_peripheral_addr(RP1_SPI0_OFFSET);
+
 /// PWM0 base address
 pub const RP1_PWM0_BASE: usize = rp1_peripheral_addr(RP1_PWM0_OFFSET);
 
 /// PWM1 base address
 pub const RP1_PWM1_BASE: usize = rp1_peripheral_addr(RP1_PWM1_OFFSET);
 
+/// Power management block with the watchdog (on BCM2712, not RP1)
+pub const BCM2712_PM_BASE: usize = 0x10_7D20_0000;
+
+/// RNG200 true random number generator base address (on BCM2712, not RP1)
+pub const BCM2712_RNG_BASE: usize = 0x10_7D20_8000;
+
 /// ARM GIC distributor base address (on BCM2712, not RP1)
 pub const GICD_BASE: usize = 0xFF84_1000;
 
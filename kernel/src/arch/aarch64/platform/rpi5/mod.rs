@@ -9,16 +9,23 @@
 //! - RP1 peripheral base: 0x1F00_0000_0000
 //! - UART0: 0x1F00_0030_0000
 //! - GPIO: 0x1F00_00D0_0000
+//! - I2C1: 0x1F00_0007_4000
+//! - SPI0: 0x1F00_0005_0000
 
 pub mod gpio;
+pub mod i2c;
 pub mod memory_map;
 pub mod mmio;
 pub mod pwm;
+pub mod spi;
+pub mod trng;
 pub mod uart;
+pub mod watchdog;
 
 use conquer_once::spin::Lazy;
 use pwm::Rp1Pwm;
 use spin::Mutex;
+use trng::Rng200;
 use uart::Rp1Uart;
 
 /// Global UART instance for debug output
@@ -48,6 +55,15 @@ pub static PWM1: Lazy<Mutex<Rp1Pwm>> = Lazy::new(|| {
     Mutex::new(pwm)
 });
 
+/// Global hardware random number generator instance
+pub static TRNG: Lazy<Mutex<Rng200>> = Lazy::new(|| {
+    // SAFETY: We initialize the TRNG driver for the RPi5 platform.
+    // This is called once by Lazy initialization.
+    let trng = unsafe { Rng200::new() };
+    trng.init();
+    Mutex::new(trng)
+});
+
 /// Initialize Raspberry Pi 5 platform
 ///
 /// This should be called early in boot to set up essential peripherals
@@ -2,15 +2,20 @@
 //!
 //! The RP1 chip has two PWM controllers (PWM0 and PWM1), each with two channels.
 //! This driver provides basic functionality to control frequency and duty cycle.
+//!
+//! Every state change is announced to the BPF programs attached to the
+//! channel before it is applied, with the old and the requested state. Any of
+//! them can veto the change, except for disabling a channel, which always
+//! goes through so a motor can always be stopped.
+
+use core::sync::atomic::{AtomicU64, Ordering};
 
-use kernel_bpf::attach::PwmEvent;
+use kernel_bpf::attach::{AttachEvent, PwmEvent};
 use kernel_bpf::execution::BpfContext;
 use spin::Mutex;
 
 use super::memory_map::{RP1_PWM0_BASE, RP1_PWM1_BASE};
 use super::mmio::MmioReg;
-use crate::BPF_MANAGER;
-use crate::bpf::ATTACH_TYPE_PWM;
 
 /// Global PWM0 instance
 // SAFETY: We initialize the PWM0 driver with the correct base address for RPi5.
@@ -104,9 +109,19 @@ mod sta {
     pub const STA1: u32 = 1 << 9;
 }
 
+/// State of a channel as programmed in the registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChannelState {
+    range: u32,
+    data: u32,
+    enabled: bool,
+}
+
 /// RP1 PWM Driver
 pub struct Rp1Pwm {
     base: usize,
+    /// Time of the last applied change of each channel
+    last_change: [AtomicU64; 2],
 }
 
 impl Rp1Pwm {
@@ -118,6 +133,7 @@ impl Rp1Pwm {
     pub const unsafe fn pwm0() -> Self {
         Self {
             base: RP1_PWM0_BASE,
+            last_change: [const { AtomicU64::new(0) }; 2],
         }
     }
 
@@ -129,6 +145,7 @@ impl Rp1Pwm {
     pub const unsafe fn pwm1() -> Self {
         Self {
             base: RP1_PWM1_BASE,
+            last_change: [const { AtomicU64::new(0) }; 2],
         }
     }
 
@@ -140,28 +157,37 @@ impl Rp1Pwm {
     }
 
     /// Enable a PWM channel
-    pub fn enable(&self, channel: u8) {
-        match channel {
-            1 => self.reg_ctl().modify(|v| v | ctl::PWEN1 | ctl::MSEN1),
-            2 => self.reg_ctl().modify(|v| v | ctl::PWEN2 | ctl::MSEN2),
-            _ => panic!("Invalid PWM channel: {}", channel),
-        }
-        self.trigger_event(channel, true);
+    ///
+    /// Returns `false` if an attached program vetoed the change.
+    pub fn enable(&self, channel: u8) -> bool {
+        let state = self.channel_state(channel);
+        self.change(
+            channel,
+            ChannelState {
+                enabled: true,
+                ..state
+            },
+        )
     }
 
     /// Disable a PWM channel
+    ///
+    /// Attached programs see the change but cannot veto it.
     pub fn disable(&self, channel: u8) {
-        match channel {
-            1 => self.reg_ctl().modify(|v| v & !ctl::PWEN1),
-            2 => self.reg_ctl().modify(|v| v & !ctl::PWEN2),
-            _ => panic!("Invalid PWM channel: {}", channel),
-        }
-        self.trigger_event(channel, false);
+        let state = self.channel_state(channel);
+        self.change(
+            channel,
+            ChannelState {
+                enabled: false,
+                ..state
+            },
+        );
     }
 
     /// Set the range (period) for a channel
     ///
     /// The actual frequency depends on the input clock (typically 125MHz or 100MHz on RP1).
+    /// Raises no PWM event.
     pub fn set_range(&self, channel: u8, range: u32) {
         match channel {
             1 => self.reg_rng1().write(range),
@@ -171,6 +197,8 @@ impl Rp1Pwm {
     }
 
     /// Set the data (duty cycle) for a channel
+    ///
+    /// Raises no PWM event.
     pub fn set_data(&self, channel: u8, data: u32) {
         match channel {
             1 => self.reg_dat1().write(data),
@@ -181,78 +209,153 @@ impl Rp1Pwm {
 
     /// Set frequency for a channel
     ///
-    /// This assumes a 125MHz input clock frequency for RP1 PWM.
-    pub fn set_frequency(&self, channel: u8, freq_hz: u32) {
+    /// This assumes a 125MHz input clock frequency for RP1 PWM. Returns
+    /// `false` if an attached program vetoed the change.
+    pub fn set_frequency(&self, channel: u8, freq_hz: u32) -> bool {
         if freq_hz == 0 {
-            return;
+            return true;
         }
         let clock = 125_000_000;
-        let range = clock / freq_hz;
-        self.set_range(channel, range);
-        self.trigger_event(channel, true);
+        let state = self.channel_state(channel);
+        self.change(
+            channel,
+            ChannelState {
+                range: clock / freq_hz,
+                ..state
+            },
+        )
     }
 
     /// Set duty cycle as a percentage (0-100)
-    pub fn set_duty_cycle(&self, channel: u8, percent: u32) {
-        let range = match channel {
-            1 => self.reg_rng1().read(),
-            2 => self.reg_rng2().read(),
-            _ => panic!("Invalid PWM channel: {}", channel),
-        };
+    ///
+    /// Returns `false` if an attached program vetoed the change.
+    pub fn set_duty_cycle(&self, channel: u8, percent: u32) -> bool {
+        let state = self.channel_state(channel);
+        let data = (state.range * percent.min(100)) / 100;
+        self.change(channel, ChannelState { data, ..state })
+    }
 
-        let data = (range * percent.min(100)) / 100;
-        self.set_data(channel, data);
-        self.trigger_event(channel, true);
+    fn channel_state(&self, channel: u8) -> ChannelState {
+        let ctl = self.reg_ctl().read();
+        match channel {
+            1 => ChannelState {
+                range: self.reg_rng1().read(),
+                data: self.reg_dat1().read(),
+                enabled: ctl & ctl::PWEN1 != 0,
+            },
+            2 => ChannelState {
+                range: self.reg_rng2().read(),
+                data: self.reg_dat2().read(),
+                enabled: ctl & ctl::PWEN2 != 0,
+            },
+            _ => panic!("Invalid PWM channel: {}", channel),
+        }
     }
 
-    // Helper to get period in nanoseconds
-    fn get_period_ns(&self, channel: u8) -> u32 {
-        let range = match channel {
-            1 => self.reg_rng1().read(),
-            2 => self.reg_rng2().read(),
-            _ => return 0,
+    /// Announce a change to the attached programs and apply it unless one
+    /// of them vetoes. Disabling cannot be vetoed.
+    fn change(&self, channel: u8, new: ChannelState) -> bool {
+        let old = self.channel_state(channel);
+        let last_change = &self.last_change[usize::from(channel - 1)];
+        let event = PwmEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            chip_id: self.chip_id(),
+            channel: u32::from(channel),
+            period_ns: ns(new.range),
+            duty_ns: ns(new.data),
+            polarity: 0, // Simplified for now
+            enabled: u32::from(new.enabled),
+            old_period_ns: ns(old.range),
+            old_duty_ns: ns(old.data),
+            old_polarity: 0,
+            old_enabled: u32::from(old.enabled),
+            last_change: last_change.load(Ordering::Relaxed),
         };
-        // Period = range / (125MHz) => range * 8ns
-        range * 8
-    }
 
-    // Helper to get duty cycle in nanoseconds
-    fn get_duty_ns(&self, channel: u8) -> u32 {
-        let data = match channel {
-            1 => self.reg_dat1().read(),
-            2 => self.reg_dat2().read(),
-            _ => return 0,
+        // Serialize event to byte slice for context
+        // SAFETY: We are creating a slice from a local struct reference. The pointer is valid
+        // and the size is correct. The lifetime is bound to the scope of this function.
+        let data = unsafe {
+            core::slice::from_raw_parts(
+                &event as *const _ as *const u8,
+                core::mem::size_of::<PwmEvent>(),
+            )
         };
-        // Duty = data * 8ns
-        data * 8
+
+        let ctx = BpfContext::from_slice(data);
+        let vetoed = crate::bpf::hooks::veto_hooks(AttachEvent::Pwm(&event), &ctx);
+        if vetoed && new.enabled {
+            log::warn!(
+                "PWM{} channel {}: change to {}/{} ns (enabled={}) vetoed",
+                event.chip_id,
+                channel,
+                event.duty_ns,
+                event.period_ns,
+                new.enabled
+            );
+            return false;
+        }
+
+        self.apply(channel, old, new);
+        last_change.store(event.timestamp, Ordering::Relaxed);
+        true
     }
 
-    // Trigger BPF event
-    fn trigger_event(&self, channel: u8, enabled: bool) {
-        if let Some(manager) = BPF_MANAGER.get() {
-            let event = PwmEvent {
-                timestamp: crate::time::get_kernel_time_ns(),
-                chip_id: if self.base == RP1_PWM0_BASE { 0 } else { 1 },
-                channel: channel as u32,
-                period_ns: self.get_period_ns(channel),
-                duty_ns: self.get_duty_ns(channel),
-                polarity: 0, // Simplified for now
-                enabled: if enabled { 1 } else { 0 },
+    fn apply(&self, channel: u8, old: ChannelState, new: ChannelState) {
+        if new.range != old.range {
+            self.set_range(channel, new.range);
+        }
+        if new.data != old.data {
+            self.set_data(channel, new.data);
+        }
+        if new.enabled != old.enabled {
+            let bits = match channel {
+                1 => ctl::PWEN1 | ctl::MSEN1,
+                _ => ctl::PWEN2 | ctl::MSEN2,
             };
+            if new.enabled {
+                self.reg_ctl().modify(|v| v | bits);
+            } else {
+                // Leave the M/S mode bit alone
+                self.reg_ctl()
+                    .modify(|v| v & !(bits & (ctl::PWEN1 | ctl::PWEN2)));
+            }
+        }
+    }
 
-            // Serialize event to byte slice for context
-            // SAFETY: We are creating a slice from a local struct reference. The pointer is valid
-            // and the size is correct. The lifetime is bound to the scope of this function.
-            let data = unsafe {
-                core::slice::from_raw_parts(
-                    &event as *const _ as *const u8,
-                    core::mem::size_of::<PwmEvent>(),
-                )
-            };
+    fn chip_id(&self) -> u32 {
+        if self.base == RP1_PWM0_BASE { 0 } else { 1 }
+    }
 
-            let ctx = BpfContext::from_slice(data);
-            manager.lock().execute_hooks(ATTACH_TYPE_PWM, &ctx);
+    /// Drive a channel to `percent` duty cycle without taking the controller
+    /// lock, disabling it at 0%
+    ///
+    /// Only for the emergency stop, which must work from interrupt context
+    /// even while the code it interrupted holds the lock. Raises no PWM event.
+    /// Returns `false` for an invalid controller or channel.
+    pub fn force_duty(pwm_id: u32, channel: u32, percent: u32) -> bool {
+        let base = match pwm_id {
+            0 => RP1_PWM0_BASE,
+            1 => RP1_PWM1_BASE,
+            _ => return false,
+        };
+        let (range, data, enable) = match channel {
+            1 => (reg::RNG1, reg::DAT1, ctl::PWEN1),
+            2 => (reg::RNG2, reg::DAT2, ctl::PWEN2),
+            _ => return false,
+        };
+
+        // SAFETY: The base address is a PWM controller and the offsets are
+        // within its register block. A lock holder racing with these writes
+        // can only leave the channel in a state it could have set anyway, and
+        // the actuator layer re-applies the safe state after such writes.
+        let at = |offset| unsafe { MmioReg::<u32>::new(base + offset) };
+        let range = at(range).read();
+        at(data).write(range * percent.min(100) / 100);
+        if percent == 0 {
+            at(reg::CTL).clear_bits(enable);
         }
+        true
     }
 
     // Register accessors
@@ -292,3 +395,8 @@ impl Rp1Pwm {
         unsafe { MmioReg::new(self.base + reg::DAT2) }
     }
 }
+
+/// Convert counter ticks of the 125 MHz clock to nanoseconds
+fn ns(ticks: u32) -> u32 {
+    ticks.saturating_mul(8)
+}
@@ -0,0 +1,183 @@
+//! RP1 SPI Driver for Raspberry Pi 5
+//!
+//! The RP1 has Synopsys DesignWare SSI controllers. SPI0 is routed to header
+//! pins 19 (MOSI, GPIO10), 21 (MISO, GPIO9), 23 (SCLK, GPIO11), 24 (CE0,
+//! GPIO8) and 26 (CE1, GPIO7); with `dtparam=spi=on` the firmware selects
+//! that pin function, so this driver only programs the controller itself.
+//!
+//! Transfers are polled and full duplex: the controller clocks in a byte for
+//! every byte clocked out, so reads send zeros and the bytes received while
+//! writing are discarded. The controller drops chip select whenever its TX
+//! FIFO runs empty, so the FIFO is refilled as soon as a byte is received.
+
+use super::memory_map::RP1_SPI0_BASE;
+use super::mmio::MmioReg;
+use crate::driver::spi::{SpiController, SpiError};
+
+/// DesignWare SSI register offsets
+mod reg {
+    /// Control 0
+    pub const CTRLR0: usize = 0x00;
+    /// Enable
+    pub const SSIENR: usize = 0x08;
+    /// Chip select enable
+    pub const SER: usize = 0x10;
+    /// Clock divider
+    pub const BAUDR: usize = 0x14;
+    /// Status
+    pub const SR: usize = 0x28;
+    /// Interrupt mask
+    pub const IMR: usize = 0x2C;
+    /// Data FIFO
+    pub const DR: usize = 0x60;
+}
+
+/// Control 0 register bit fields
+mod ctrlr0 {
+    /// 8-bit frames, in the data frame size field of controllers without
+    /// 32-bit frame support
+    pub const DFS_8: u32 = 7;
+    /// Data frame size field of controllers with 32-bit frame support
+    pub const DFS32_MASK: u32 = 0x1F << 16;
+    /// 8-bit frames, in `DFS32_MASK`
+    pub const DFS32_8: u32 = 7 << 16;
+}
+
+/// Status register bit fields
+mod status {
+    /// Transfer in progress
+    pub const BUSY: u32 = 1 << 0;
+    /// TX FIFO not full
+    pub const TFNF: u32 = 1 << 1;
+    /// RX FIFO not empty
+    pub const RFNE: u32 = 1 << 3;
+}
+
+/// Controller input clock
+const SSI_CLK_HZ: u32 = 200_000_000;
+
+/// SCLK frequency, within what common SPI peripherals accept
+const SCLK_HZ: u32 = 1_000_000;
+
+/// Number of native chip selects
+const CHIP_SELECTS: u8 = 2;
+
+/// Bytes clocked out ahead of those received
+///
+/// Below the smallest FIFO depth of the controller, so the RX FIFO never
+/// overflows.
+const IN_FLIGHT: usize = 8;
+
+/// Polls of a status bit before a transfer is considered stuck
+///
+/// Generously above the 8 us a byte takes at 1 MHz.
+const POLL_LIMIT: u32 = 1_000_000;
+
+/// RP1 DesignWare SPI Controller
+pub struct Rp1Spi {
+    base: usize,
+}
+
+impl Rp1Spi {
+    /// Create a driver for SPI0, the controller on the 40-pin header
+    ///
+    /// # Safety
+    ///
+    /// No other driver may access the SPI0 registers.
+    pub const unsafe fn spi0() -> Self {
+        Self {
+            base: RP1_SPI0_BASE,
+        }
+    }
+
+    /// Configure the controller for mode 0 with 8-bit frames at 1 MHz.
+    pub fn init(&self) {
+        self.reg(reg::SSIENR).write(0);
+        self.reg(reg::SER).write(0);
+        self.reg(reg::IMR).write(0);
+
+        // Controllers with 32-bit frame support keep the frame size in a
+        // wider field; find out which one this is by writing all ones.
+        self.reg(reg::CTRLR0).write(u32::MAX);
+        let ctrlr0 = if self.reg(reg::CTRLR0).is_set(ctrlr0::DFS32_MASK) {
+            ctrlr0::DFS32_8
+        } else {
+            ctrlr0::DFS_8
+        };
+        // Motorola frame format, mode 0, transmit and receive
+        self.reg(reg::CTRLR0).write(ctrlr0);
+
+        // The divider must be even
+        self.reg(reg::BAUDR)
+            .write((SSI_CLK_HZ / SCLK_HZ).next_multiple_of(2));
+    }
+
+    fn reg(&self, offset: usize) -> MmioReg<u32> {
+        // SAFETY: The base address is valid (checked at creation) and all
+        // offsets are within the register block.
+        unsafe { MmioReg::new(self.base + offset) }
+    }
+
+    fn run(&self, write: &[u8], read: &mut [u8]) -> Result<(), SpiError> {
+        let total = write.len() + read.len();
+        let mut sent = 0;
+        let mut received = 0;
+        let mut polls = 0;
+
+        while received < total {
+            let status = self.reg(reg::SR).read();
+            if sent < total && sent - received < IN_FLIGHT && status & status::TFNF != 0 {
+                let byte = write.get(sent).copied().unwrap_or(0);
+                self.reg(reg::DR).write(u32::from(byte));
+                sent += 1;
+            } else if status & status::RFNE != 0 {
+                let byte = self.reg(reg::DR).read() as u8;
+                if let Some(slot) = received
+                    .checked_sub(write.len())
+                    .and_then(|i| read.get_mut(i))
+                {
+                    *slot = byte;
+                }
+                received += 1;
+                polls = 0;
+            } else if polls == POLL_LIMIT {
+                return Err(SpiError::Timeout);
+            } else {
+                polls += 1;
+                core::hint::spin_loop();
+            }
+        }
+
+        for _ in 0..POLL_LIMIT {
+            if !self.reg(reg::SR).is_set(status::BUSY) {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(SpiError::Timeout)
+    }
+}
+
+impl SpiController for Rp1Spi {
+    fn chip_selects(&self) -> u8 {
+        CHIP_SELECTS
+    }
+
+    fn transfer(&mut self, cs: u8, write: &[u8], read: &mut [u8]) -> Result<(), SpiError> {
+        if cs >= CHIP_SELECTS {
+            return Err(SpiError::InvalidChipSelect);
+        }
+        if write.is_empty() && read.is_empty() {
+            return Ok(());
+        }
+
+        // Chip select is asserted while the controller is enabled and has
+        // data to send; disabling it also flushes both FIFOs.
+        self.reg(reg::SER).write(1 << cs);
+        self.reg(reg::SSIENR).write(1);
+        let result = self.run(write, read);
+        self.reg(reg::SSIENR).write(0);
+        self.reg(reg::SER).write(0);
+        result
+    }
+}
@@ -0,0 +1,119 @@
+//! BCM2712 RNG200 Driver for Raspberry Pi 5
+//!
+//! The RNG200 is a true random number generator on the BCM2712 SoC itself
+//! (not on RP1). It fills a FIFO with 32-bit words of entropy; the FIFO
+//! count register reports how many words are ready.
+//!
+//! Register layout follows Linux `drivers/char/hw_random/iproc-rng200.c`.
+
+use super::memory_map::BCM2712_RNG_BASE;
+use super::mmio::MmioReg;
+
+/// RNG200 register offsets
+mod reg {
+    /// Control register
+    pub const CTRL: usize = 0x00;
+    /// RNG soft reset register
+    pub const RNG_SOFT_RESET: usize = 0x04;
+    /// RBG soft reset register
+    pub const RBG_SOFT_RESET: usize = 0x08;
+    /// FIFO data register
+    pub const FIFO_DATA: usize = 0x20;
+    /// FIFO count register
+    pub const FIFO_COUNT: usize = 0x24;
+}
+
+/// Control register bit fields
+mod ctrl {
+    /// Random bit generator enable field
+    pub const RBGEN_MASK: u32 = 0x1FFF;
+    /// Enable value for the generator
+    pub const RBGEN_ENABLE: u32 = 0x1;
+}
+
+/// FIFO count register: number of words available
+const FIFO_COUNT_MASK: u32 = 0xFF;
+
+/// How often to poll the FIFO before giving up
+///
+/// The generator produces a word every few microseconds once warmed up.
+const READ_RETRIES: usize = 10_000;
+
+/// BCM2712 RNG200 Driver
+pub struct Rng200 {
+    base: usize,
+}
+
+impl Rng200 {
+    /// Create a new RNG200 instance
+    ///
+    /// # Safety
+    ///
+    /// Must be called only once.
+    pub const unsafe fn new() -> Self {
+        Self {
+            base: BCM2712_RNG_BASE,
+        }
+    }
+
+    /// Initialize the generator
+    ///
+    /// The firmware usually leaves the generator running. If it does not,
+    /// reset it and enable it.
+    pub fn init(&self) {
+        if self.reg_ctrl().read() & ctrl::RBGEN_MASK == ctrl::RBGEN_ENABLE {
+            return;
+        }
+
+        self.reg_ctrl().clear_bits(ctrl::RBGEN_MASK);
+        self.reg_rng_soft_reset().write(1);
+        self.reg_rbg_soft_reset().write(1);
+        self.reg_rng_soft_reset().write(0);
+        self.reg_rbg_soft_reset().write(0);
+        self.reg_ctrl()
+            .modify(|v| (v & !ctrl::RBGEN_MASK) | ctrl::RBGEN_ENABLE);
+    }
+
+    /// Read one 32-bit word of entropy
+    ///
+    /// Returns `None` if the FIFO stays empty.
+    pub fn read(&self) -> Option<u32> {
+        for _ in 0..READ_RETRIES {
+            if self.reg_fifo_count().read() & FIFO_COUNT_MASK != 0 {
+                return Some(self.reg_fifo_data().read());
+            }
+            core::hint::spin_loop();
+        }
+        None
+    }
+
+    fn reg_ctrl(&self) -> MmioReg<u32> {
+        // SAFETY: The base address is valid for the RNG200 peripheral.
+        // The offset reg::CTRL is within the bounds of the RNG200's register space.
+        unsafe { MmioReg::new(self.base + reg::CTRL) }
+    }
+
+    fn reg_rng_soft_reset(&self) -> MmioReg<u32> {
+        // SAFETY: The base address is valid for the RNG200 peripheral.
+        // The offset reg::RNG_SOFT_RESET is within the bounds of the RNG200's register space.
+        unsafe { MmioReg::new(self.base + reg::RNG_SOFT_RESET) }
+    }
+
+    fn reg_rbg_soft_reset(&self) -> MmioReg<u32> {
+        // SAFETY: The base address is valid for the RNG200 peripheral.
+        // The offset reg::RBG_SOFT_RESET is within the bounds of the RNG200's register space.
+        unsafe { MmioReg::new(self.base + reg::RBG_SOFT_RESET) }
+    }
+
+    fn reg_fifo_data(&self) -> MmioReg<u32> {
+        // SAFETY: The base address is valid for the RNG200 peripheral.
+        // The offset reg::FIFO_DATA is within the bounds of the RNG200's register space.
+        unsafe { MmioReg::new(self.base + reg::FIFO_DATA) }
+    }
+
+    fn reg_fifo_count(&self) -> MmioReg<u32> {
+        // SAFETY: The base address is valid for the RNG200 peripheral.
+        // The offset reg::FIFO_COUNT is within the bounds of the RNG200's register space.
+        unsafe { MmioReg::new(self.base + reg::FIFO_COUNT) }
+    }
+}
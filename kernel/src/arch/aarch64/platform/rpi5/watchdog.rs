@@ -0,0 +1,111 @@
+//! BCM2712 PM Watchdog Driver for Raspberry Pi 5
+//!
+//! The power management block of the BCM2712 has a 20-bit watchdog counter
+//! ticking at 64 kHz, so timeouts go up to about 16 seconds. When it reaches
+//! zero the chip does a full reset. Every register write must carry the PM
+//! password in its top byte or it is ignored.
+//!
+//! Register layout follows Linux `drivers/watchdog/bcm2835_wdt.c`.
+
+use super::memory_map::BCM2712_PM_BASE;
+use super::mmio::MmioReg;
+use crate::driver::watchdog::Watchdog;
+
+/// PM register offsets
+mod reg {
+    /// Reset control
+    pub const RSTC: usize = 0x1C;
+    /// Watchdog counter
+    pub const WDOG: usize = 0x24;
+}
+
+/// Must be in the top byte of every write
+const PASSWORD: u32 = 0x5A00_0000;
+
+/// Watchdog counter bits
+const WDOG_TIME_MASK: u32 = 0x000F_FFFF;
+
+/// Reset configuration field of RSTC
+const RSTC_WRCFG_MASK: u32 = 0x0000_0030;
+
+/// Full reset on watchdog expiry
+const RSTC_WRCFG_FULL_RESET: u32 = 0x0000_0020;
+
+/// Stop the watchdog
+const RSTC_RESET: u32 = 0x0000_0102;
+
+/// Counter ticks per second
+const TICKS_PER_SEC: u64 = 1 << 16;
+
+/// Counter ticks before reset when resetting on purpose
+const RESET_TICKS: u32 = 10;
+
+/// BCM2712 PM Watchdog
+pub struct Bcm2712Watchdog {
+    base: usize,
+    /// Counter reload value, 0 while stopped
+    ticks: u32,
+}
+
+impl Bcm2712Watchdog {
+    /// Create a driver for the PM watchdog
+    ///
+    /// # Safety
+    ///
+    /// No other driver may access the PM watchdog registers.
+    pub const unsafe fn new() -> Self {
+        Self {
+            base: BCM2712_PM_BASE,
+            ticks: 0,
+        }
+    }
+
+    fn reg(&self, offset: usize) -> MmioReg<u32> {
+        // SAFETY: The base address is the PM block and all offsets are
+        // within it.
+        unsafe { MmioReg::new(self.base + offset) }
+    }
+
+    /// Load the counter and enable reset on expiry.
+    fn load(&self, ticks: u32) {
+        self.reg(reg::WDOG)
+            .write(PASSWORD | (ticks & WDOG_TIME_MASK));
+        let rstc = self.reg(reg::RSTC).read();
+        self.reg(reg::RSTC)
+            .write(PASSWORD | (rstc & !RSTC_WRCFG_MASK) | RSTC_WRCFG_FULL_RESET);
+    }
+}
+
+impl Watchdog for Bcm2712Watchdog {
+    fn name(&self) -> &'static str {
+        "bcm2712-pm"
+    }
+
+    fn max_timeout_ms(&self) -> u32 {
+        (u64::from(WDOG_TIME_MASK) * 1000 / TICKS_PER_SEC) as u32
+    }
+
+    fn start(&mut self, timeout_ms: u32) {
+        self.ticks =
+            (u64::from(timeout_ms) * TICKS_PER_SEC / 1000).min(u64::from(WDOG_TIME_MASK)) as u32;
+        self.load(self.ticks);
+    }
+
+    fn pet(&mut self) {
+        if self.ticks != 0 {
+            self.load(self.ticks);
+        }
+    }
+
+    fn stop(&mut self) {
+        self.reg(reg::RSTC).write(PASSWORD | RSTC_RESET);
+        self.ticks = 0;
+    }
+
+    fn reset(&mut self) -> ! {
+        self.load(RESET_TICKS);
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+}
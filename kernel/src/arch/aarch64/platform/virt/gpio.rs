@@ -0,0 +1,189 @@
+//! ARM PrimeCell PL061 GPIO Driver for QEMU virt
+//!
+//! The virt machine has one PL061 with 8 lines, described in the device tree
+//! as `arm,pl061`. Line 3 is wired to QEMU's power button, so
+//! `system_powerdown` in the QEMU monitor raises a rising edge on it; this is
+//! what lets GPIO-triggered BPF programs run under QEMU.
+//!
+//! The PL061 raises a single interrupt for all lines. It latches which lines
+//! fired but not which edge, so the edge is derived from the level after the
+//! event and the configured sense.
+
+use conquer_once::spin::OnceCell;
+
+use super::mmio::MmioReg;
+use crate::arch::aarch64::{dtb, gic};
+use crate::driver::gpio::GpioController;
+
+/// PL061 register offsets
+mod reg {
+    /// Data register; address bits 9:2 mask which lines are accessed
+    pub const DATA: usize = 0x000;
+    /// Direction (1 = output)
+    pub const DIR: usize = 0x400;
+    /// Interrupt sense (1 = level, 0 = edge)
+    pub const IS: usize = 0x404;
+    /// Interrupt on both edges
+    pub const IBE: usize = 0x408;
+    /// Interrupt event (1 = rising edge, 0 = falling edge)
+    pub const IEV: usize = 0x40C;
+    /// Interrupt mask (1 = enabled)
+    pub const IE: usize = 0x410;
+    /// Masked interrupt status
+    pub const MIS: usize = 0x418;
+    /// Interrupt clear (write 1 to clear)
+    pub const IC: usize = 0x41C;
+}
+
+/// PL061 GPIO Driver
+pub struct Pl061 {
+    base: usize,
+    irq: u32,
+}
+
+impl Pl061 {
+    /// Number of GPIO lines
+    pub const NUM_LINES: u32 = 8;
+
+    /// Create a driver for the PL061 at `base`
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the address of a PL061 register block, and no other
+    /// driver may access it.
+    pub const unsafe fn new(base: usize, irq: u32) -> Self {
+        Self { base, irq }
+    }
+
+    /// Mask all interrupts and clear pending ones.
+    pub fn reset(&self) {
+        self.reg(reg::IE).write(0);
+        self.reg(reg::IC).write(0xFF);
+    }
+
+    /// GIC interrupt ID of this controller
+    pub fn irq(&self) -> u32 {
+        self.irq
+    }
+
+    fn reg(&self, offset: usize) -> MmioReg<u32> {
+        // SAFETY: The base address is valid (checked at creation) and all
+        // offsets are within the 4 KiB register block.
+        unsafe { MmioReg::new(self.base + offset) }
+    }
+
+    /// Data register view that only accesses `line`
+    fn data(&self, line: u32) -> MmioReg<u32> {
+        self.reg(reg::DATA + (1 << (line + 2)))
+    }
+
+    fn set_bit(&self, offset: usize, line: u32, set: bool) {
+        let reg = self.reg(offset);
+        if set {
+            reg.set_bits(1 << line);
+        } else {
+            reg.clear_bits(1 << line);
+        }
+    }
+
+    /// Get the edge that raised a pending interrupt on `line`: 1 for rising,
+    /// 2 for falling.
+    fn pending_edge(&self, line: u32, high: bool) -> u32 {
+        let rising = if self.reg(reg::IBE).is_set(1 << line) {
+            high
+        } else {
+            self.reg(reg::IEV).is_set(1 << line)
+        };
+        if rising { 1 } else { 2 }
+    }
+}
+
+impl GpioController for Pl061 {
+    fn num_lines(&self) -> u32 {
+        Self::NUM_LINES
+    }
+
+    fn read(&self, line: u32) -> bool {
+        self.data(line).read() != 0
+    }
+
+    fn write(&self, line: u32, high: bool) {
+        self.data(line).write(if high { 0xFF } else { 0 });
+    }
+
+    fn configure_output(&self, line: u32, initial_high: bool) {
+        self.write(line, initial_high);
+        self.set_bit(reg::DIR, line, true);
+    }
+
+    fn configure_input(&self, line: u32) {
+        self.set_bit(reg::DIR, line, false);
+    }
+
+    fn enable_interrupt(&self, line: u32, rising: bool, falling: bool) {
+        self.set_bit(reg::IE, line, false);
+        self.set_bit(reg::IS, line, false);
+        self.set_bit(reg::IBE, line, rising && falling);
+        self.set_bit(reg::IEV, line, rising);
+        self.reg(reg::IC).write(1 << line);
+        self.set_bit(reg::IE, line, rising || falling);
+    }
+
+    fn disable_interrupt(&self, line: u32) {
+        self.set_bit(reg::IE, line, false);
+    }
+}
+
+static PL061: OnceCell<Pl061> = OnceCell::uninit();
+
+/// Find the PL061 in the device tree, route its interrupt and register it
+/// with the GPIO layer.
+///
+/// Must run after the GIC is initialized.
+pub fn init() {
+    let Some(node) = dtb::find_compatible(&["arm,pl061"]) else {
+        log::info!("No PL061 GPIO in device tree");
+        return;
+    };
+    let Some(irq) = node.irq else {
+        log::warn!("PL061 GPIO at {:#x} has no interrupt", node.base);
+        return;
+    };
+
+    // SAFETY: The device tree describes a PL061 at this address, and this is
+    // the only driver for it.
+    let gpio = PL061.get_or_init(|| unsafe { Pl061::new(node.base, irq) });
+    gpio.reset();
+
+    gic::set_priority(irq, 0x80);
+    gic::enable_irq(irq);
+    crate::driver::gpio::register(gpio);
+
+    log::info!("PL061 GPIO at {:#x} (irq={})", node.base, irq);
+}
+
+/// GIC interrupt ID of the PL061, once initialized
+pub fn irq() -> Option<u32> {
+    PL061.get().map(Pl061::irq)
+}
+
+/// Handle GPIO interrupt
+///
+/// Called from the main IRQ handler when the PL061 interrupt fires.
+/// Runs the BPF programs attached to every line with a pending edge.
+pub fn handle_interrupt() {
+    let Some(gpio) = PL061.get() else {
+        return;
+    };
+    let timestamp = crate::time::get_kernel_time_ns();
+
+    // Clear first so edges arriving while hooks run are not lost
+    let pending = gpio.reg(reg::MIS).read() & 0xFF;
+    gpio.reg(reg::IC).write(pending);
+
+    for line in (0..Pl061::NUM_LINES).filter(|l| pending & (1 << l) != 0) {
+        let high = gpio.read(line);
+        let edge = gpio.pending_edge(line, high);
+        crate::driver::gpio::dispatch_edge(0, line, edge, u32::from(high), timestamp);
+    }
+}
@@ -1,5 +1,6 @@
 //! QEMU virt platform support
 
+pub mod gpio;
 pub mod mmio;
 pub mod uart;
 
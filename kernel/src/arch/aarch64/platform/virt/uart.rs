@@ -18,6 +18,8 @@ mod reg {
     pub const LCRH: usize = 0x2C;
     /// Control Register
     pub const CR: usize = 0x30;
+    /// Interrupt Mask Set/Clear Register
+    pub const IMSC: usize = 0x38;
     /// Interrupt Clear Register
     pub const ICR: usize = 0x44;
 }
@@ -27,7 +29,6 @@ mod fr {
     /// Transmit FIFO full
     pub const TXFF: u32 = 1 << 5;
     /// Receive FIFO empty
-    #[allow(dead_code)]
     pub const RXFE: u32 = 1 << 4;
     /// UART busy transmitting
     pub const BUSY: u32 = 1 << 3;
@@ -41,6 +42,14 @@ mod lcrh {
     pub const WLEN_8: u32 = 0b11 << 5;
 }
 
+/// Interrupt bits (IMSC, ICR)
+mod int {
+    /// Receive FIFO reached its trigger level
+    pub const RX: u32 = 1 << 4;
+    /// Receive timeout: data in the FIFO, but no more arriving
+    pub const RT: u32 = 1 << 6;
+}
+
 /// Control Register bits
 mod cr {
     /// UART enable
@@ -100,6 +109,30 @@ impl PL011Uart {
         self.reg_dr().write(c as u32);
     }
 
+    /// Try to receive a byte (non-blocking)
+    ///
+    /// Returns `Some(byte)` if data is available, `None` otherwise.
+    pub fn try_getc(&self) -> Option<u8> {
+        if self.reg_fr().is_set(fr::RXFE) {
+            None
+        } else {
+            Some((self.reg_dr().read() & 0xFF) as u8)
+        }
+    }
+
+    /// Enable or disable the receive interrupts.
+    ///
+    /// Both fire until the receive FIFO is drained with [`Self::try_getc`].
+    pub fn set_rx_interrupt(&mut self, enabled: bool) {
+        let imsc = self.reg_imsc();
+        if enabled {
+            self.reg_icr().write(int::RX | int::RT);
+            imsc.set_bits(int::RX | int::RT);
+        } else {
+            imsc.clear_bits(int::RX | int::RT);
+        }
+    }
+
     // Register accessors
     fn reg_dr(&self) -> MmioReg<u32> {
         unsafe { MmioReg::new(self.base + reg::DR) }
@@ -117,6 +150,10 @@ impl PL011Uart {
         unsafe { MmioReg::new(self.base + reg::CR) }
     }
 
+    fn reg_imsc(&self) -> MmioReg<u32> {
+        unsafe { MmioReg::new(self.base + reg::IMSC) }
+    }
+
     fn reg_icr(&self) -> MmioReg<u32> {
         unsafe { MmioReg::new(self.base + reg::ICR) }
     }
@@ -5,6 +5,9 @@ use core::fmt::{Debug, Formatter};
 use core::mem::transmute;
 use core::sync::atomic::Ordering::Relaxed;
 
+use kernel_bpf::attach::{
+    IrqEvent, PAGE_FAULT_PROTECTION, PAGE_FAULT_USER, PAGE_FAULT_WRITE, PageFaultEvent, PtRegs,
+};
 use kernel_memapi::{Guarded, Location, MemoryApi, UserAccessible};
 use log::{error, warn};
 use x86_64::PrivilegeLevel;
@@ -15,6 +18,7 @@ use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, Pag
 
 use crate::UsizeExt;
 use crate::arch::gdt;
+use crate::bpf::{kprobe, tracepoint};
 use crate::mcore::context::ExecutionContext;
 use crate::mcore::mtask::process::mem::MemoryRegion;
 use crate::mcore::mtask::task::FxArea;
@@ -28,6 +32,10 @@ pub enum InterruptIndex {
     Timer = 0x20,
     /// 49
     LapicErr = 0x31,
+    /// 64
+    HrTimer = 0x40,
+    /// 65
+    Serial = 0x41,
     Syscall = 0x80,
     /// 255
     Spurious = 0xff,
@@ -56,8 +64,6 @@ pub fn create_idt() -> InterruptDescriptorTable {
             .set_stack_index(gdt::PAGE_FAULT_IST_INDEX);
     }
 
-    idt.debug.set_handler_fn(debug_handler);
-    idt.breakpoint.set_handler_fn(breakpoint_handler);
     idt.device_not_available
         .set_handler_fn(device_not_available_handler);
 
@@ -70,8 +76,9 @@ pub fn create_idt() -> InterruptDescriptorTable {
     idt.stack_segment_fault
         .set_handler_fn(stack_segment_fault_handler);
 
-    idt[InterruptIndex::Timer.as_u8()].set_handler_fn(timer_interrupt_handler);
     idt[InterruptIndex::LapicErr.as_u8()].set_handler_fn(lapic_err_interrupt_handler);
+    idt[InterruptIndex::HrTimer.as_u8()].set_handler_fn(hrtimer_interrupt_handler);
+    idt[InterruptIndex::Serial.as_u8()].set_handler_fn(serial_interrupt_handler);
     idt[InterruptIndex::Spurious.as_u8()].set_handler_fn(spurious_interrupt_handler);
 
     // SAFETY: Setting up the syscall handler with the correct privilege level and interrupt handling.
@@ -86,6 +93,25 @@ pub fn create_idt() -> InterruptDescriptorTable {
             .disable_interrupts(true);
     }
 
+    // SAFETY: The debug, breakpoint and timer handlers are wrapped to save
+    // all registers and return with iretq, see wrap_pt_regs!.
+    unsafe {
+        idt.debug.set_handler_fn(transmute::<
+            *mut fn(),
+            extern "x86-interrupt" fn(InterruptStackFrame),
+        >(debug_handler as *mut fn()));
+        idt.breakpoint.set_handler_fn(transmute::<
+            *mut fn(),
+            extern "x86-interrupt" fn(InterruptStackFrame),
+        >(breakpoint_handler as *mut fn()));
+        idt[InterruptIndex::Timer.as_u8()].set_handler_fn(transmute::<
+            *mut fn(),
+            extern "x86-interrupt" fn(InterruptStackFrame),
+        >(
+            timer_interrupt_handler as *mut fn()
+        ));
+    }
+
     idt
 }
 
@@ -129,6 +155,67 @@ macro_rules! wrap {
 
 wrap!(syscall_handler_impl => syscall_handler);
 
+/// Wrap the handler of an exception without error code, or an interrupt,
+/// that needs every register, such as for kprobes and timer sampling. The
+/// registers are pushed below the interrupt frame so that both form a
+/// [`PtRegs`], which the handler gets and may modify.
+macro_rules! wrap_pt_regs {
+    ($fn:ident => $w:ident) => {
+        #[allow(clippy::missing_safety_doc)]
+        // SAFETY: This is a naked function used as an interrupt wrapper.
+        // It manually saves/restores registers and calls the handler.
+        // It is only called by the CPU via the IDT.
+        #[unsafe(naked)]
+        pub unsafe extern "sysv64" fn $w() {
+            core::arch::naked_asm!(
+                "push -1", // orig_rax
+                "push rdi",
+                "push rsi",
+                "push rdx",
+                "push rcx",
+                "push rax",
+                "push r8",
+                "push r9",
+                "push r10",
+                "push r11",
+                "push rbx",
+                "push rbp",
+                "push r12",
+                "push r13",
+                "push r14",
+                "push r15",
+                "mov rdi, rsp", // Arg #1: PtRegs
+                "sub rsp, 8",   // 16-byte align for the call
+                "cld",
+                "call {}",
+                "add rsp, 8",
+                "pop r15",
+                "pop r14",
+                "pop r13",
+                "pop r12",
+                "pop rbp",
+                "pop rbx",
+                "pop r11",
+                "pop r10",
+                "pop r9",
+                "pop r8",
+                "pop rax",
+                "pop rcx",
+                "pop rdx",
+                "pop rsi",
+                "pop rdi",
+                "add rsp, 8", // orig_rax
+                "iretq",
+                sym $fn
+            );
+        }
+    };
+}
+
+wrap_pt_regs!(breakpoint_handler_impl => breakpoint_handler);
+wrap_pt_regs!(debug_handler_impl => debug_handler);
+wrap_pt_regs!(timer_interrupt_handler_impl => timer_interrupt_handler);
+
 #[repr(align(8), C)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct SyscallRegisters {
@@ -161,7 +248,18 @@ pub extern "sysv64" fn syscall_handler_impl(
     regs.rax = result as usize; // save result
 }
 
-extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+extern "sysv64" fn timer_interrupt_handler_impl(regs: &PtRegs) {
+    let irq = u32::from(InterruptIndex::Timer.as_u8());
+    let entered = tracepoint::IRQ_EXIT
+        .is_enabled()
+        .then(crate::time::get_kernel_time_ns);
+    tracepoint::IRQ_ENTRY.emit(|| IrqEvent {
+        timestamp: crate::time::get_kernel_time_ns(),
+        duration_ns: 0,
+        irq,
+        cpu: tracepoint::cpu(),
+    });
+
     // 1. Acknowledge interrupt first
     // SAFETY: We are acknowledging the interrupt to the LAPIC.
     // Safe because we are in an interrupt handler.
@@ -169,13 +267,22 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
         end_of_interrupt();
     }
 
-    // 2. Run BPF hooks (AttachType::Timer = 1)
-    if let Some(manager) = crate::BPF_MANAGER.get() {
-        // We use an empty context for timer for now, or could pass time?
-        let ctx = kernel_bpf::execution::BpfContext::empty();
-        // unsafe { crate::serial_print!("."); }
-        manager.lock().execute_hooks(1, &ctx);
-    }
+    // 2. Run BPF hooks (AttachType::Timer = 1) on a sample of the
+    // interrupted code
+    crate::bpf::attach::timer_tick(regs);
+    crate::driver::watchdog::tick();
+
+    // The handler ends here for tracing, as rescheduling may not return to it
+    // until the task runs again
+    tracepoint::IRQ_EXIT.emit(|| {
+        let timestamp = crate::time::get_kernel_time_ns();
+        IrqEvent {
+            timestamp,
+            duration_ns: entered.map_or(0, |entered| timestamp.saturating_sub(entered)),
+            irq,
+            cpu: tracepoint::cpu(),
+        }
+    });
 
     // 3. Schedule next task
     let ctx = ExecutionContext::load();
@@ -186,6 +293,66 @@ extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFr
     }
 }
 
+extern "x86-interrupt" fn hrtimer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let irq = u32::from(InterruptIndex::HrTimer.as_u8());
+    let entered = tracepoint::IRQ_EXIT
+        .is_enabled()
+        .then(crate::time::get_kernel_time_ns);
+    tracepoint::IRQ_ENTRY.emit(|| IrqEvent {
+        timestamp: crate::time::get_kernel_time_ns(),
+        duration_ns: 0,
+        irq,
+        cpu: tracepoint::cpu(),
+    });
+
+    crate::hrtimer::expire();
+
+    tracepoint::IRQ_EXIT.emit(|| {
+        let timestamp = crate::time::get_kernel_time_ns();
+        IrqEvent {
+            timestamp,
+            duration_ns: entered.map_or(0, |entered| timestamp.saturating_sub(entered)),
+            irq,
+            cpu: tracepoint::cpu(),
+        }
+    });
+
+    // SAFETY: We are acknowledging the interrupt to the LAPIC.
+    unsafe {
+        end_of_interrupt();
+    }
+}
+
+extern "x86-interrupt" fn serial_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    let irq = u32::from(InterruptIndex::Serial.as_u8());
+    let entered = tracepoint::IRQ_EXIT
+        .is_enabled()
+        .then(crate::time::get_kernel_time_ns);
+    tracepoint::IRQ_ENTRY.emit(|| IrqEvent {
+        timestamp: crate::time::get_kernel_time_ns(),
+        duration_ns: 0,
+        irq,
+        cpu: tracepoint::cpu(),
+    });
+
+    crate::driver::uart::interrupt(irq);
+
+    tracepoint::IRQ_EXIT.emit(|| {
+        let timestamp = crate::time::get_kernel_time_ns();
+        IrqEvent {
+            timestamp,
+            duration_ns: entered.map_or(0, |entered| timestamp.saturating_sub(entered)),
+            irq,
+            cpu: tracepoint::cpu(),
+        }
+    });
+
+    // SAFETY: We are acknowledging the interrupt to the LAPIC.
+    unsafe {
+        end_of_interrupt();
+    }
+}
+
 extern "x86-interrupt" fn lapic_err_interrupt_handler(stack_frame: InterruptStackFrame) {
     panic!("EXCEPTION: LAPIC ERROR\n{:#?}", stack_frame);
 }
@@ -227,6 +394,26 @@ extern "x86-interrupt" fn page_fault_handler(
     error_code: PageFaultErrorCode,
 ) {
     let accessed_address = Cr2::read().ok();
+    tracepoint::PAGE_FAULT.emit(|| {
+        let mut flags = 0;
+        if error_code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {
+            flags |= PAGE_FAULT_WRITE;
+        }
+        if error_code.contains(PageFaultErrorCode::USER_MODE) {
+            flags |= PAGE_FAULT_USER;
+        }
+        if error_code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {
+            flags |= PAGE_FAULT_PROTECTION;
+        }
+        PageFaultEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            address: accessed_address.map_or(0, |addr| addr.as_u64()),
+            ip: stack_frame.instruction_pointer.as_u64(),
+            pid: ExecutionContext::try_load().map_or(0, |ctx| ctx.pid().as_u64()),
+            flags,
+            cpu: tracepoint::cpu(),
+        }
+    });
 
     // if we know the address...
     if let Some(addr) = accessed_address {
@@ -277,7 +464,7 @@ extern "x86-interrupt" fn page_fault_handler(
                         // TODO: allocate new physical page, map it and add it to the lazy memory
                         // region
                     }
-                    MemoryRegion::Mapped(_mapped_memory_region) => {
+                    MemoryRegion::Mapped(_) | MemoryRegion::Shared(_) => {
                         error!(
                             "invalid memory access in process '{}' task '{}', terminating...",
                             process.name(),
@@ -327,16 +514,24 @@ extern "x86-interrupt" fn stack_segment_fault_handler(
     panic!("EXCEPTION: STACK SEGMENT FAULT:\nerror code: {error_code:#?}\n{stack_frame:#?}");
 }
 
-extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    warn!("BREAKPOINT:\n{stack_frame:#?}");
+extern "sysv64" fn breakpoint_handler_impl(regs: &mut PtRegs) {
+    if kprobe::handle_breakpoint(regs) {
+        return;
+    }
+
+    warn!("BREAKPOINT:\n{regs:#x?}");
     warn!("halting...");
     loop {
         hlt();
     }
 }
 
-extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
-    warn!("DEBUG:\n{stack_frame:#?}");
+extern "sysv64" fn debug_handler_impl(regs: &mut PtRegs) {
+    if kprobe::handle_debug(regs) {
+        return;
+    }
+
+    warn!("DEBUG:\n{regs:#x?}");
     let dr6_flags = Dr6::read();
     warn!("DR6 flags: {dr6_flags:#?}");
     let dr7_flags = Dr7::read();
@@ -60,3 +60,8 @@ impl Architecture for Riscv64 {
         shutdown::reboot()
     }
 }
+
+/// Run `f` with interrupts disabled on this CPU.
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    Riscv64::without_interrupts(f)
+}
@@ -15,6 +15,20 @@ pub trait Architecture {
     /// Check if interrupts are enabled
     fn are_interrupts_enabled() -> bool;
 
+    /// Run `f` with interrupts disabled on this CPU, restoring the previous
+    /// state afterwards
+    fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+        let were_enabled = Self::are_interrupts_enabled();
+        if were_enabled {
+            Self::disable_interrupts();
+        }
+        let res = f();
+        if were_enabled {
+            Self::enable_interrupts();
+        }
+        res
+    }
+
     /// Wait for an interrupt (halt until interrupt)
     fn wait_for_interrupt();
 
@@ -1,3 +1,4 @@
+pub use x86_64::instructions::interrupts::without_interrupts;
 use x86_64::instructions::port::Port;
 
 pub fn shutdown() -> ! {
@@ -11,3 +12,15 @@ pub fn shutdown() -> ! {
         x86_64::instructions::hlt();
     }
 }
+
+pub fn reboot() -> ! {
+    let mut port = Port::new(0x64);
+    // SAFETY: We are pulsing the CPU reset line through the keyboard
+    // controller, which QEMU and PC hardware implement.
+    unsafe {
+        port.write(0xFE_u8);
+    }
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
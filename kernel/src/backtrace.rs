@@ -175,13 +175,19 @@ enum Kind {
     },
 }
 
-struct ReturnAddressIterator {
+/// Walks the frame pointer chain of the kernel stack, yielding return
+/// addresses.
+///
+/// Each frame starts with the caller's frame pointer followed by the return
+/// address, on x86_64 (`rbp`) and aarch64 (`x29`) alike. The walk ends at a
+/// null or misaligned frame pointer, or one that does not move up the stack.
+pub(crate) struct ReturnAddressIterator {
     current_bp: *const usize,
 }
 
 impl ReturnAddressIterator {
     #[cfg(target_arch = "x86_64")]
-    pub fn new() -> Self {
+    pub(crate) fn new() -> Self {
         let mut current_bp: *const usize;
         // SAFETY: We are reading the base pointer (rbp) register to start walking the stack.
         // This is safe as we just read the register value.
@@ -194,33 +200,63 @@ impl ReturnAddressIterator {
         Self { current_bp }
     }
 
-    #[cfg(not(target_arch = "x86_64"))]
-    pub fn new() -> Self {
-        // Backtrace not implemented for non-x86_64
+    #[cfg(target_arch = "aarch64")]
+    pub(crate) fn new() -> Self {
+        let mut current_bp: *const usize;
+        // SAFETY: We are reading the frame pointer (x29) register to start walking the stack.
+        unsafe {
+            core::arch::asm!(
+            "mov {bp}, x29",
+            bp = out(reg) current_bp,
+            );
+        }
+        Self { current_bp }
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub(crate) fn new() -> Self {
+        // Backtrace not implemented for this architecture
         Self {
             current_bp: core::ptr::null(),
         }
     }
+
+    /// Walk the frames of the code whose frame pointer is `bp`, such as
+    /// interrupted code.
+    ///
+    /// # Safety
+    /// `bp` must be null or the frame pointer of a kernel stack that stays
+    /// alive while the iterator is used.
+    pub(crate) unsafe fn from_frame_pointer(bp: usize) -> Self {
+        Self {
+            current_bp: bp as *const usize,
+        }
+    }
 }
 
 impl Iterator for ReturnAddressIterator {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_bp.is_null() {
+        if self.current_bp.is_null() || !self.current_bp.is_aligned() {
             return None;
         }
 
         let current_bp = self.current_bp;
         // SAFETY: We assume the base pointer points to a valid stack frame where
         // the first word is the previous base pointer and the second is the return address.
-        // This is the standard x86_64 stack frame layout.
+        // This is the standard frame layout with frame pointers enabled.
         let next_bp = unsafe { *current_bp };
         // SAFETY: We are reading the return address from the stack frame (offset 1 usize).
         // The pointer arithmetic is valid because current_bp is a valid stack pointer.
         let instruction_pointer = unsafe { *(current_bp.add(1)) };
 
-        self.current_bp = next_bp as *const usize;
+        // Callers' frames lie further up the stack; anything else would loop
+        self.current_bp = if next_bp > current_bp as usize {
+            next_bp as *const usize
+        } else {
+            core::ptr::null()
+        };
         if instruction_pointer == 0 {
             None
         } else {
@@ -0,0 +1,467 @@
+//! Attach point resolution
+//!
+//! `BPF_PROG_ATTACH` names an attach type by its `ATTACH_TYPE_*` number and a
+//! target within it through the `key`/`value` pair. This module turns such a
+//! request into a [`kernel_bpf::attach`] configuration and attach point, wiring
+//! up the hardware that delivers the events where the board has any.
+//!
+//! Timer ticks, syscall entry and exit and watchdog liveness checks have no
+//! counterpart in the crate, so their attach points live here. Syscall
+//! filters are not attach points; see [`super::syscall_filter`].
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use kernel_bpf::attach::{
+    AttachConfig, AttachError, AttachEvent, AttachId, AttachPoint, AttachResult, AttachType,
+    AttachedPrograms, BusAttach, CanAttach, EventFilter, Framing, GpioAttach, GpioEdge,
+    HrTimerAttach, IioAttach, IioChannel, KprobeAttach, KprobeType, PerfEventData, PtRegs,
+    PwmAttach, SerialAttach, TracepointAttach, syscall_selected,
+};
+use kernel_bpf::bytecode::program::BpfProgram;
+use kernel_bpf::execution::BpfContext;
+use kernel_bpf::profile::ActiveProfile;
+
+use super::{
+    ATTACH_TYPE_CAN, ATTACH_TYPE_GPIO, ATTACH_TYPE_HRTIMER, ATTACH_TYPE_I2C, ATTACH_TYPE_IIO,
+    ATTACH_TYPE_KPROBE, ATTACH_TYPE_KRETPROBE, ATTACH_TYPE_PWM, ATTACH_TYPE_SERIAL,
+    ATTACH_TYPE_SPI, ATTACH_TYPE_SYSCALL, ATTACH_TYPE_SYSCALL_EXIT, ATTACH_TYPE_SYSCALL_FILTER,
+    ATTACH_TYPE_TIMER, ATTACH_TYPE_TRACEPOINT, ATTACH_TYPE_WATCHDOG, hooks, kprobe, stack,
+    tracepoint,
+};
+use crate::driver::gpio::{self, GpioIrq};
+use crate::driver::uart::UartRx;
+use crate::hrtimer::{self, HrTimerSlot};
+use crate::syscall::validation::read_userspace_slice;
+
+/// Perf event name of the periodic timer tick
+const TIMER_EVENT: &str = "cpu-clock";
+
+/// Nominal period of the timer tick, which runs at 100 Hz
+const TIMER_PERIOD_NS: u64 = 10_000_000;
+
+/// Target name of the watchdog liveness check
+const WATCHDOG_EVENT: &str = "watchdog:liveness";
+
+/// Longest `category:name` accepted for a tracepoint
+const TRACEPOINT_NAME_MAX: u64 = 64;
+
+/// Longest function name accepted for a kprobe
+const KPROBE_NAME_MAX: u64 = 256;
+
+/// Set of syscall numbers a program is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallSet {
+    /// Bit `n` selects syscall `n`
+    mask: u64,
+}
+
+impl SyscallSet {
+    /// All syscalls, including those numbered 64 and above.
+    pub const ALL: Self = Self { mask: u64::MAX };
+
+    /// Create a set from a bitmask of syscall numbers. An empty mask selects
+    /// all syscalls.
+    pub const fn from_mask(mask: u64) -> Self {
+        if mask == 0 { Self::ALL } else { Self { mask } }
+    }
+
+    pub fn contains(&self, nr: u64) -> bool {
+        syscall_selected(self.mask, nr)
+    }
+}
+
+/// Resolve the `key`/`value` pair of a `BPF_PROG_ATTACH` request.
+///
+/// - GPIO: `key` = chip << 32 | line, `value` = edge mask (1 = rising,
+///   2 = falling, 0 or 3 = both)
+/// - IIO: `key` = device, `value` = channel id
+/// - PWM: `key` = controller, `value` = channel
+/// - CAN: `key` = interface, `value` = mask << 32 | identifier; frames with
+///   `frame_id & mask == identifier & mask` match, so a zero value matches
+///   every frame
+/// - Serial: `key` = port, `value` = framing mode << 16 | argument; modes
+///   are 0 = delimiter (argument = the byte), 1 = fixed length (argument =
+///   1-256 bytes), 2 = SLIP and 3 = COBS. A port has one framing at a time.
+/// - I2C, SPI: `key` = bus << 32 | target address or chip select, with
+///   `0xffff_ffff` matching any; `value` = 0 for every transaction, or
+///   1 << 16 | last << 8 | first for those whose first byte written, the
+///   register, is in `first..=last`
+/// - Syscall, syscall exit: `value` = bitmask of syscall numbers, 0 for all
+/// - Tracepoint: `key` = pointer to `category:name` in userspace, `value` =
+///   its length
+/// - Kprobe, kretprobe: `key` = pointer to the demangled function name in
+///   userspace (e.g. `kernel::syscall::dispatch_syscall`), `value` = its
+///   length
+/// - High-resolution timer: `key` = timer number, `value` = phase << 32 |
+///   period, both in nanoseconds; a zero period makes a one-shot timer,
+///   armed by its programs with `bpf_timer_start`
+/// - Timer, watchdog: no target
+///
+/// Returns the configuration identifying the attach point, and a fresh
+/// attach point to use if none exists for it yet.
+pub fn resolve(
+    attach_type: u32,
+    key: u64,
+    value: u64,
+) -> AttachResult<(AttachConfig, Box<dyn AttachPoint<ActiveProfile>>)> {
+    let invalid = || AttachError::InvalidTarget(format!("{}:{:#x}:{:#x}", attach_type, key, value));
+
+    match attach_type {
+        ATTACH_TYPE_TIMER => {
+            let config = AttachConfig {
+                attach_type: AttachType::PerfEvent,
+                target: TIMER_EVENT.into(),
+                flags: 0,
+            };
+            Ok((config, Box::new(TimerAttach::new())))
+        }
+        ATTACH_TYPE_GPIO => {
+            let chip_id = (key >> 32) as u32;
+            let line = key as u32;
+            let edge = GpioEdge::from_flags(value as u32);
+            let config = AttachConfig::gpio_event(&format!("gpiochip{}", chip_id), line, edge);
+            Ok((config, Box::new(gpio_attach(chip_id, line, edge)?)))
+        }
+        ATTACH_TYPE_IIO => {
+            let device = format!("iio:device{}", key);
+            let channel = u32::try_from(value)
+                .ok()
+                .and_then(IioChannel::from_id)
+                .ok_or_else(invalid)?
+                .name();
+            let config = AttachConfig::iio_sensor(&device, &channel);
+            Ok((
+                config,
+                Box::new(IioAttach::<ActiveProfile>::new(&device, &channel)?),
+            ))
+        }
+        ATTACH_TYPE_PWM => {
+            let chip = format!("pwmchip{}", key);
+            let channel = u32::try_from(value).map_err(|_| invalid())?;
+            let config = AttachConfig::pwm_observe(&chip, channel);
+            Ok((
+                config,
+                Box::new(PwmAttach::<ActiveProfile>::new(&chip, channel)?),
+            ))
+        }
+        ATTACH_TYPE_CAN => {
+            let iface = format!("can{}", key);
+            let (id, mask) = (value as u32, (value >> 32) as u32);
+            let config = AttachConfig::can_bus(&iface, id, mask);
+            Ok((
+                config,
+                Box::new(CanAttach::<ActiveProfile>::new(&iface, id, mask)?),
+            ))
+        }
+        ATTACH_TYPE_SERIAL => {
+            let port = u32::try_from(key).map_err(|_| invalid())?;
+            let framing = u32::try_from(value)
+                .ok()
+                .and_then(Framing::from_flags)
+                .ok_or_else(invalid)?;
+            let name = format!("uart{}", port);
+            let config = AttachConfig::serial(&name, framing);
+            let point = SerialAttach::<ActiveProfile>::new(&name, framing)?
+                .with_hardware(Box::new(UartRx::new(port, framing)));
+            Ok((config, Box::new(point)))
+        }
+        ATTACH_TYPE_I2C | ATTACH_TYPE_SPI => {
+            let (bus, addr) = ((key >> 32) as u32, key as u32);
+            let regs = bus_regs(value).ok_or_else(invalid)?;
+            let (config, point) = if attach_type == ATTACH_TYPE_I2C {
+                let name = format!("i2c{}", bus);
+                (
+                    AttachConfig::i2c(&name, addr, regs.clone()),
+                    BusAttach::<ActiveProfile>::i2c(&name, addr, regs)?,
+                )
+            } else {
+                let name = format!("spi{}", bus);
+                (
+                    AttachConfig::spi(&name, addr, regs.clone()),
+                    BusAttach::<ActiveProfile>::spi(&name, addr, regs)?,
+                )
+            };
+            Ok((config, Box::new(point)))
+        }
+        ATTACH_TYPE_SYSCALL => {
+            let syscalls = SyscallSet::from_mask(value);
+            let config = AttachConfig {
+                attach_type: AttachType::RawTracepoint,
+                target: format!("raw_syscalls:sys_enter:{:#x}", syscalls.mask),
+                flags: 0,
+            };
+            Ok((config, Box::new(SyscallAttach::new(syscalls))))
+        }
+        ATTACH_TYPE_SYSCALL_EXIT => {
+            let syscalls = SyscallSet::from_mask(value);
+            let config = AttachConfig {
+                attach_type: AttachType::RawTracepoint,
+                target: format!("raw_syscalls:sys_exit:{:#x}", syscalls.mask),
+                flags: 0,
+            };
+            Ok((config, Box::new(SyscallAttach::exit(syscalls))))
+        }
+        ATTACH_TYPE_TRACEPOINT => {
+            if value > TRACEPOINT_NAME_MAX {
+                return Err(invalid());
+            }
+            let target =
+                read_userspace_slice(key as usize, value as usize).map_err(|_| invalid())?;
+            let target = core::str::from_utf8(&target).map_err(|_| invalid())?;
+            let (category, name) = target.split_once(':').ok_or_else(invalid)?;
+            let tp = tracepoint::lookup(category, name)
+                .ok_or_else(|| AttachError::InvalidTarget(target.into()))?;
+            let config = AttachConfig::tracepoint(category, name);
+            Ok((
+                config,
+                Box::new(TracepointAttach::<ActiveProfile>::new(category, name)?.with_id(tp.id())),
+            ))
+        }
+        ATTACH_TYPE_KPROBE | ATTACH_TYPE_KRETPROBE => {
+            if value > KPROBE_NAME_MAX {
+                return Err(invalid());
+            }
+            let name = read_userspace_slice(key as usize, value as usize).map_err(|_| invalid())?;
+            let name = core::str::from_utf8(&name).map_err(|_| invalid())?;
+            let sym = kprobe::lookup(name)?;
+            let (config, probe_type) = if attach_type == ATTACH_TYPE_KPROBE {
+                (AttachConfig::kprobe(name), KprobeType::Entry)
+            } else {
+                (AttachConfig::kretprobe(name), KprobeType::Return)
+            };
+            let point = KprobeAttach::<ActiveProfile>::new(name, probe_type)?
+                .with_address(sym.addr)
+                .with_hardware(Box::new(kprobe::Breakpoint::new(sym.addr, probe_type)));
+            Ok((config, Box::new(point)))
+        }
+        ATTACH_TYPE_HRTIMER => {
+            let timer = u32::try_from(key)
+                .ok()
+                .filter(|&timer| (timer as usize) < hrtimer::MAX_TIMERS)
+                .ok_or_else(invalid)?;
+            let (period_ns, phase_ns) = (value & 0xffff_ffff, value >> 32);
+            let config = AttachConfig::hrtimer(timer, period_ns, phase_ns);
+            let point = HrTimerAttach::<ActiveProfile>::new(timer, period_ns, phase_ns)?
+                .with_hardware(Box::new(HrTimerSlot::new(timer, period_ns, phase_ns)));
+            Ok((config, Box::new(point)))
+        }
+        ATTACH_TYPE_WATCHDOG => {
+            let config = AttachConfig {
+                attach_type: AttachType::Watchdog,
+                target: WATCHDOG_EVENT.into(),
+                flags: 0,
+            };
+            Ok((config, Box::new(WatchdogAttach::new())))
+        }
+        // Filters are installed on the calling process by sys_bpf and can
+        // never be detached
+        ATTACH_TYPE_SYSCALL_FILTER => Err(AttachError::PermissionDenied),
+        _ => Err(invalid()),
+    }
+}
+
+/// Decode the register range of an I2C or SPI attach request.
+fn bus_regs(value: u64) -> Option<RangeInclusive<u8>> {
+    match value >> 16 {
+        0 if value == 0 => Some(0..=u8::MAX),
+        1 => Some(value as u8..=(value >> 8) as u8),
+        _ => None,
+    }
+}
+
+/// Create a GPIO attach point that enables the line's edge interrupt on the
+/// board's GPIO controller. Without a controller the attach point never
+/// fires.
+fn gpio_attach(chip_id: u32, line: u32, edge: GpioEdge) -> AttachResult<GpioAttach> {
+    let attach = GpioAttach::new(&format!("gpiochip{}", chip_id), line, edge)?;
+    if gpio::chip(chip_id).is_none() {
+        return Ok(attach);
+    }
+
+    let irq = GpioIrq::new(chip_id, line, edge).ok_or(AttachError::ResourceNotFound)?;
+    Ok(attach.with_hardware(Box::new(irq)))
+}
+
+/// Periodic timer tick attach point
+pub struct TimerAttach {
+    attached: AttachedPrograms,
+}
+
+impl TimerAttach {
+    pub fn new() -> Self {
+        Self {
+            attached: AttachedPrograms::new(),
+        }
+    }
+}
+
+impl Default for TimerAttach {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttachPoint<ActiveProfile> for TimerAttach {
+    fn attach_type(&self) -> AttachType {
+        AttachType::PerfEvent
+    }
+
+    fn target(&self) -> &str {
+        TIMER_EVENT
+    }
+
+    fn attach(&mut self, program: &BpfProgram<ActiveProfile>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        EventFilter::Timer
+    }
+}
+
+/// Run the programs attached to the timer tick.
+///
+/// They get a [`PerfEventData`] sample of the code the tick interrupted,
+/// whose registers `regs` are, and stack helpers walk its stacks.
+pub fn timer_tick(regs: &PtRegs) {
+    let sample = PerfEventData::new(*regs, TIMER_PERIOD_NS);
+    stack::with_regs(regs, || {
+        hooks::execute_hooks(
+            AttachEvent::Timer,
+            &BpfContext::from_slice(sample.as_bytes()),
+        );
+    });
+}
+
+/// Syscall entry or exit attach point, filtered by syscall number
+pub struct SyscallAttach {
+    syscalls: SyscallSet,
+    /// Fire on exit rather than entry
+    exit: bool,
+    attached: AttachedPrograms,
+}
+
+impl SyscallAttach {
+    /// Create an attach point firing on entry to `syscalls`.
+    pub fn new(syscalls: SyscallSet) -> Self {
+        Self {
+            syscalls,
+            exit: false,
+            attached: AttachedPrograms::new(),
+        }
+    }
+
+    /// Create an attach point firing on return from `syscalls`.
+    pub fn exit(syscalls: SyscallSet) -> Self {
+        Self {
+            exit: true,
+            ..Self::new(syscalls)
+        }
+    }
+}
+
+impl AttachPoint<ActiveProfile> for SyscallAttach {
+    fn attach_type(&self) -> AttachType {
+        AttachType::RawTracepoint
+    }
+
+    fn target(&self) -> &str {
+        if self.exit {
+            "raw_syscalls:sys_exit"
+        } else {
+            "raw_syscalls:sys_enter"
+        }
+    }
+
+    fn attach(&mut self, program: &BpfProgram<ActiveProfile>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        let mask = self.syscalls.mask;
+        if self.exit {
+            EventFilter::SyscallExit { mask }
+        } else {
+            EventFilter::Syscall { mask }
+        }
+    }
+}
+
+/// Watchdog liveness attach point
+///
+/// Programs attached here decide whether the watchdog is petted; see
+/// [`crate::driver::watchdog`].
+pub struct WatchdogAttach {
+    attached: AttachedPrograms,
+}
+
+impl WatchdogAttach {
+    pub fn new() -> Self {
+        Self {
+            attached: AttachedPrograms::new(),
+        }
+    }
+}
+
+impl Default for WatchdogAttach {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttachPoint<ActiveProfile> for WatchdogAttach {
+    fn attach_type(&self) -> AttachType {
+        AttachType::Watchdog
+    }
+
+    fn target(&self) -> &str {
+        WATCHDOG_EVENT
+    }
+
+    fn attach(&mut self, program: &BpfProgram<ActiveProfile>) -> AttachResult<AttachId> {
+        self.attached.attach(self.attach_type(), program)
+    }
+
+    fn detach(&mut self, id: AttachId) -> AttachResult<()> {
+        self.attached.detach(id)
+    }
+
+    fn is_attached(&self, id: AttachId) -> bool {
+        self.attached.contains(id)
+    }
+
+    fn attached_ids(&self) -> Vec<AttachId> {
+        self.attached.ids()
+    }
+
+    fn event_filter(&self) -> EventFilter {
+        EventFilter::Watchdog
+    }
+}
@@ -1,3 +1,16 @@
+use kernel_abi::{BpfTimeSeriesStats, CanFrame};
+use kernel_bpf::maps::{
+    BPF_F_CURRENT_CPU, BPF_F_FAST_STACK_CMP, BPF_F_REUSE_STACKID, BPF_F_SKIP_FIELD_MASK,
+    BPF_F_USER_STACK, MAX_STACK_DEPTH, MapError,
+};
+
+use super::stack;
+use crate::driver::actuator::{self, Output};
+use crate::driver::iio::IIO_MANAGER;
+use crate::driver::{can, gpio};
+use crate::hrtimer;
+use crate::mcore::context::ExecutionContext;
+use crate::syscall::validation::read_nofault;
 use crate::time::get_kernel_time_ns;
 
 /// BPF helper: Get kernel time in nanoseconds
@@ -11,9 +24,38 @@ pub extern "C" fn bpf_ktime_get_ns() -> u64 {
     get_kernel_time_ns()
 }
 
+/// BPF helper: Get a pseudo-random number
+///
+/// Returns a value from this CPU's PRNG, which is seeded from the hardware
+/// RNG. Not suitable for cryptographic use.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It only touches the
+/// per-CPU generator state.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_prandom_u32() -> u32 {
+    ExecutionContext::load().prng().next_u32()
+}
+
+/// BPF helper: Get the current CPU index
+///
+/// Returns the logical CPU index used by the scheduler, suitable for
+/// sharding per-CPU data in maps.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It only reads the
+/// per-CPU execution context.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_smp_processor_id() -> u32 {
+    ExecutionContext::load().cpu_id() as u32
+}
+
 /// BPF helper: Read GPIO pin value
 ///
-/// Returns 1 if pin is high, 0 if low, -1 on error (invalid pin).
+/// Returns 1 if pin is high, 0 if low, -1 on error (invalid pin or no GPIO
+/// controller).
 ///
 /// # Safety
 ///
@@ -21,29 +63,19 @@ pub extern "C" fn bpf_ktime_get_ns() -> u64 {
 /// but validates inputs (pin numbers) to prevent invalid access.
 #[unsafe(no_mangle)]
 pub extern "C" fn bpf_gpio_read(pin: u32) -> i64 {
-    #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
-    {
-        if pin >= 28 {
-            return -1;
-        }
-        // SAFETY: Creating a temporary GPIO interface to access hardware registers.
-        // Safe because we are on RPi5 (checked by feature) and access is stateless/exclusive.
-        let gpio = unsafe { crate::arch::aarch64::platform::rpi5::gpio::Rp1Gpio::new() };
-        if gpio.read(pin as u8) { 1 } else { 0 }
-    }
-    #[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
-    {
-        let _ = pin;
-        -1
+    match gpio::line(pin) {
+        Some(gpio) => i64::from(gpio.read(pin)),
+        None => -1,
     }
 }
 
 /// BPF helper: Write GPIO pin value
 ///
 /// Sets output pin high (value != 0) or low (value == 0).
-/// Returns 0 on success, -1 on error (invalid pin).
+/// Returns 0 on success, -1 on error (invalid pin or no GPIO controller).
 ///
 /// Note: Pin must be configured as output first via syscall.
+/// Fails while an emergency stop holds the pin in its safe state.
 ///
 /// # Safety
 ///
@@ -51,25 +83,10 @@ pub extern "C" fn bpf_gpio_read(pin: u32) -> i64 {
 /// but validates inputs (pin numbers) to prevent invalid access.
 #[unsafe(no_mangle)]
 pub extern "C" fn bpf_gpio_write(pin: u32, value: u32) -> i64 {
-    #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
-    {
-        if pin >= 28 {
-            return -1;
-        }
-        // SAFETY: Creating a temporary GPIO interface to access hardware registers.
-        // Safe because we are on RPi5 (checked by feature) and access is stateless/exclusive.
-        let gpio = unsafe { crate::arch::aarch64::platform::rpi5::gpio::Rp1Gpio::new() };
-        if value != 0 {
-            gpio.set_high(pin as u8);
-        } else {
-            gpio.set_low(pin as u8);
-        }
-        0
-    }
-    #[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
-    {
-        let _ = (pin, value);
-        -1
+    match gpio::line(pin) {
+        Some(gpio) => actuator::write_guarded(gpio_output(pin), || gpio.write(pin, value != 0))
+            .map_or(-1, |()| 0),
+        None => -1,
     }
 }
 
@@ -84,22 +101,14 @@ pub extern "C" fn bpf_gpio_write(pin: u32, value: u32) -> i64 {
 /// but validates inputs (pin numbers) to prevent invalid access.
 #[unsafe(no_mangle)]
 pub extern "C" fn bpf_gpio_toggle(pin: u32) -> i64 {
-    #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
-    {
-        if pin >= 28 {
-            return -1;
-        }
-        // SAFETY: Creating a temporary GPIO interface to access hardware registers.
-        // Safe because we are on RPi5 (checked by feature) and access is stateless/exclusive.
-        let gpio = unsafe { crate::arch::aarch64::platform::rpi5::gpio::Rp1Gpio::new() };
-        gpio.toggle(pin as u8);
-        // Return new value
-        if gpio.read(pin as u8) { 1 } else { 0 }
-    }
-    #[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
-    {
-        let _ = pin;
-        -1
+    match gpio::line(pin) {
+        Some(gpio) => actuator::write_guarded(gpio_output(pin), || {
+            gpio.toggle(pin);
+            // Return new value
+            i64::from(gpio.read(pin))
+        })
+        .unwrap_or(-1),
+        None => -1,
     }
 }
 
@@ -114,24 +123,20 @@ pub extern "C" fn bpf_gpio_toggle(pin: u32) -> i64 {
 /// but validates inputs (pin numbers) to prevent invalid access.
 #[unsafe(no_mangle)]
 pub extern "C" fn bpf_gpio_set_output(pin: u32, initial_high: u32) -> i64 {
-    #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
-    {
-        if pin >= 28 {
-            return -1;
-        }
-        // SAFETY: Creating a temporary GPIO interface to access hardware registers.
-        // Safe because we are on RPi5 (checked by feature) and access is stateless/exclusive.
-        let gpio = unsafe { crate::arch::aarch64::platform::rpi5::gpio::Rp1Gpio::new() };
-        gpio.configure_output(pin as u8, initial_high != 0);
-        0
-    }
-    #[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
-    {
-        let _ = (pin, initial_high);
-        -1
+    match gpio::line(pin) {
+        Some(gpio) => actuator::write_guarded(gpio_output(pin), || {
+            gpio.configure_output(pin, initial_high != 0)
+        })
+        .map_or(-1, |()| 0),
+        None => -1,
     }
 }
 
+/// Actuator output of a `gpiochip0` line
+fn gpio_output(pin: u32) -> Output {
+    Output::Gpio { chip: 0, line: pin }
+}
+
 /// BPF helper: Write to PWM channel
 ///
 /// Arguments:
@@ -139,7 +144,9 @@ pub extern "C" fn bpf_gpio_set_output(pin: u32, initial_high: u32) -> i64 {
 /// - channel: 1 or 2
 /// - duty_percent: 0-100
 ///
-/// Returns 0 on success, -1 on error.
+/// Returns 0 on success, -1 on error, while the controller is busy or while
+/// an emergency stop holds the channel in its safe state, and -2 if a program
+/// attached to the channel vetoed the change.
 ///
 /// # Safety
 ///
@@ -155,18 +162,24 @@ pub extern "C" fn bpf_pwm_write(pwm_id: u32, channel: u32, duty_percent: u32) ->
             return -1;
         }
 
-        match pwm_id {
-            0 => {
-                let pwm = PWM0.lock();
-                pwm.set_duty_cycle(channel as u8, duty_percent);
-                0
-            }
-            1 => {
-                let pwm = PWM1.lock();
-                pwm.set_duty_cycle(channel as u8, duty_percent);
-                0
-            }
-            _ => -1,
+        let pwm = match pwm_id {
+            0 => &PWM0,
+            1 => &PWM1,
+            _ => return -1,
+        };
+        let output = Output::Pwm {
+            controller: pwm_id,
+            channel,
+        };
+        // A PWM hook calling this helper runs with the controller locked
+        let write = || {
+            pwm.try_lock()
+                .map(|pwm| pwm.set_duty_cycle(channel as u8, duty_percent))
+        };
+        match actuator::write_guarded(output, write) {
+            Ok(Some(true)) => 0,
+            Ok(Some(false)) => -2,
+            Ok(None) | Err(_) => -1,
         }
     }
     #[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
@@ -176,6 +189,176 @@ pub extern "C" fn bpf_pwm_write(pwm_id: u32, channel: u32, duty_percent: u32) ->
     }
 }
 
+/// BPF helper: Emergency stop all actuators
+///
+/// Drives every registered actuator to its safe state and latches, so later
+/// writes to them fail until a privileged process re-arms. `reason` is an
+/// application defined code reported in the safety event. Always returns 0.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It never blocks, so it
+/// is safe to call from any hook context.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_motor_emergency_stop(reason: u32) -> i64 {
+    actuator::emergency_stop(kernel_abi::ESTOP_SOURCE_BPF, reason as i32);
+    0
+}
+
+/// BPF helper: Send a CAN frame
+///
+/// Arguments:
+/// - iface: interface number (0 for can0)
+/// - can_id: identifier, with `CAN_EFF_FLAG` for a 29-bit identifier
+/// - data: payload
+/// - len: payload length, 0-8
+///
+/// Returns 0 once the frame is queued for transmission, and -1 for an invalid
+/// interface or frame, or while the interface or its transmit buffer is busy.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. The verifier ensures
+/// `data` is readable for `len` bytes. It never blocks, so it is safe to call
+/// from any hook context.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_can_send(iface: u32, can_id: u32, data: *const u8, len: u32) -> i64 {
+    let len = len as usize;
+    if data.is_null() || len > kernel_abi::CAN_MAX_DLEN {
+        return -1;
+    }
+
+    let mut frame = CanFrame {
+        can_id,
+        len: len as u8,
+        ..CanFrame::default()
+    };
+    // SAFETY: Verifier ensures valid memory access for data
+    frame.data[..len].copy_from_slice(unsafe { core::slice::from_raw_parts(data, len) });
+
+    can::try_send(iface, &frame).map_or(-1, |()| 0)
+}
+
+/// BPF helper: Arm a high-resolution timer
+///
+/// Fires timer `timer` once `delay_ns` from now: a one-shot timer fires once,
+/// a periodic timer continues its period from there.
+///
+/// Returns 0, or -1 if no programs are attached to the timer.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It never blocks, so it
+/// is safe to call from any hook context, including the timer's own programs.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_timer_start(timer: u32, delay_ns: u64) -> i64 {
+    hrtimer::start(timer, delay_ns).map_or(-1, |()| 0)
+}
+
+/// BPF helper: Append a sample to a time-series map
+///
+/// The sample is `value_size` bytes at `value`, timestamped with the kernel
+/// time. Once the map is full, the oldest sample is overwritten.
+///
+/// Returns 0, or -1 if the map does not exist or is not a time-series map.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures value is valid.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_timeseries_push(map_id: u32, value: *const u8) -> i64 {
+    let Some(table) = super::hooks::read() else {
+        return -1;
+    };
+    let Some(map) = table.map(map_id) else {
+        return -1;
+    };
+
+    let value_size = map.def().value_size as usize;
+    // SAFETY: Verifier ensures valid memory access for value
+    let value = unsafe { core::slice::from_raw_parts(value, value_size) };
+    map.push_sample(get_kernel_time_ns(), value)
+        .map_or(-1, |()| 0)
+}
+
+/// BPF helper: Compute statistics over the newest samples of a time-series map
+///
+/// Writes a [`BpfTimeSeriesStats`] over the newest `n` samples to `stats`,
+/// truncated to `size` bytes.
+///
+/// Returns 0, or -1 if the map does not exist, is not a time-series map or
+/// has no samples.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures stats points to
+/// `size` writable bytes.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_timeseries_stats(map_id: u32, n: u32, stats: *mut u8, size: u32) -> i64 {
+    let Some(table) = super::hooks::read() else {
+        return -1;
+    };
+    let Some(sample_stats) = table
+        .map(map_id)
+        .and_then(|map| map.sample_stats(n as usize).ok())
+    else {
+        return -1;
+    };
+
+    let out = BpfTimeSeriesStats {
+        count: sample_stats.count as u64,
+        min: sample_stats.min_value,
+        max: sample_stats.max_value,
+        mean: sample_stats.mean,
+        variance: sample_stats.variance,
+        first_ns: sample_stats.min_timestamp_ns,
+        last_ns: sample_stats.max_timestamp_ns,
+    };
+    let len = (size as usize).min(core::mem::size_of::<BpfTimeSeriesStats>());
+    // SAFETY: BpfTimeSeriesStats is repr(C) plain data, and the verifier
+    // ensures stats points to `size` writable bytes.
+    unsafe { core::ptr::copy_nonoverlapping(&out as *const _ as *const u8, stats, len) };
+    0
+}
+
+/// BPF helper: Read the latest sample of an IIO channel
+///
+/// Returns the scaled value in millionths of the channel unit (e.g. µm/s² for
+/// an accelerometer), or `i64::MIN` if the device or channel does not exist
+/// or has not produced a sample yet.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It only reads the IIO
+/// sample cache and never blocks.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_iio_read(device: u32, channel: u32) -> i64 {
+    IIO_MANAGER
+        .get()
+        .and_then(|m| m.latest(device, channel))
+        .unwrap_or(i64::MIN)
+}
+
+/// BPF helper: Get the timestamp of the latest sample of an IIO device
+///
+/// Returns the timestamp in nanoseconds, or 0 if the device does not exist or
+/// has not produced a sample yet.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It only reads the IIO
+/// sample cache and never blocks.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_sensor_last_timestamp(device: u32) -> u64 {
+    IIO_MANAGER
+        .get()
+        .and_then(|m| m.last_timestamp(device))
+        .unwrap_or(0)
+}
+
 /// # Safety
 ///
 /// This function is an entry point for BPF programs. The verifier ensures that the
@@ -200,15 +383,13 @@ pub extern "C" fn bpf_trace_printk(fmt: *const u8, _size: u32) -> i32 {
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[unsafe(no_mangle)]
 pub extern "C" fn bpf_map_lookup_elem(map_id: u32, key_ptr: *const u8) -> *mut u8 {
-    use crate::BPF_MANAGER;
-    if let Some(manager) = BPF_MANAGER.get() {
-        let manager = manager.lock();
-        if let Some(def) = manager.get_map_def(map_id) {
-            let key_size = def.key_size as usize;
+    if let Some(table) = super::hooks::read() {
+        if let Some(map) = table.map(map_id) {
+            let key_size = map.def().key_size as usize;
             // SAFETY: Verifier ensures valid memory access for key_ptr
             let key = unsafe { core::slice::from_raw_parts(key_ptr, key_size) };
-            // SAFETY: Manager lock ensures map stability
-            if let Some(ptr) = unsafe { manager.map_lookup_ptr(map_id, key) } {
+            // SAFETY: Maps are never removed, so the value outlives the snapshot
+            if let Some(ptr) = unsafe { map.lookup_ptr(key) } {
                 return ptr;
             }
         }
@@ -228,19 +409,17 @@ pub extern "C" fn bpf_map_update_elem(
     value_ptr: *const u8,
     flags: u64,
 ) -> i32 {
-    use crate::BPF_MANAGER;
-    if let Some(manager) = BPF_MANAGER.get() {
-        let manager = manager.lock();
-        if let Some(def) = manager.get_map_def(map_id) {
-            let key_size = def.key_size as usize;
-            let value_size = def.value_size as usize;
+    if let Some(table) = super::hooks::read() {
+        if let Some(map) = table.map(map_id) {
+            let key_size = map.def().key_size as usize;
+            let value_size = map.def().value_size as usize;
 
             // SAFETY: Verifier ensures valid memory access for key_ptr
             let key = unsafe { core::slice::from_raw_parts(key_ptr, key_size) };
             // SAFETY: Verifier ensures valid memory access for value_ptr
             let value = unsafe { core::slice::from_raw_parts(value_ptr, value_size) };
 
-            if manager.map_update(map_id, key, value, flags).is_ok() {
+            if map.update(key, value, flags).is_ok() {
                 return 0;
             }
         }
@@ -255,14 +434,12 @@ pub extern "C" fn bpf_map_update_elem(
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
 #[unsafe(no_mangle)]
 pub extern "C" fn bpf_map_delete_elem(map_id: u32, key_ptr: *const u8) -> i32 {
-    use crate::BPF_MANAGER;
-    if let Some(manager) = BPF_MANAGER.get() {
-        let manager = manager.lock();
-        if let Some(def) = manager.get_map_def(map_id) {
-            let key_size = def.key_size as usize;
+    if let Some(table) = super::hooks::read() {
+        if let Some(map) = table.map(map_id) {
+            let key_size = map.def().key_size as usize;
             // SAFETY: Verifier ensures valid memory access for key_ptr
             let key = unsafe { core::slice::from_raw_parts(key_ptr, key_size) };
-            if manager.map_delete(map_id, key).is_ok() {
+            if map.delete(key).is_ok() {
                 return 0;
             }
         }
@@ -293,20 +470,350 @@ pub extern "C" fn bpf_ringbuf_output(
     data_size: u64,
     flags: u64,
 ) -> i64 {
-    use crate::BPF_MANAGER;
-
     if data_ptr.is_null() {
         return -1;
     }
 
-    if let Some(manager) = BPF_MANAGER.get() {
-        let manager = manager.lock();
-        // SAFETY: Verifier ensures valid memory access for data_ptr
-        let data = unsafe { core::slice::from_raw_parts(data_ptr, data_size as usize) };
+    if let Some(table) = super::hooks::read() {
+        if let Some(map) = table.map(map_id) {
+            // SAFETY: Verifier ensures valid memory access for data_ptr
+            let data = unsafe { core::slice::from_raw_parts(data_ptr, data_size as usize) };
 
-        if manager.ringbuf_output(map_id, data, flags).is_ok() {
-            return 0;
+            // Ring buffer maps use update() with empty key to output data
+            if map.update(&[], data, flags).is_ok() {
+                return 0;
+            }
         }
     }
     -1
 }
+
+/// BPF helper: reserve space in a ring buffer map.
+///
+/// Returns a pointer to a record of `size` bytes that the program fills in
+/// place, then passes to `bpf_ringbuf_submit` or `bpf_ringbuf_discard`.
+///
+/// # Arguments
+/// * `map_id` - The ring buffer map ID
+/// * `size` - Size of the record in bytes
+/// * `flags` - Reserved for future use (pass 0)
+///
+/// # Returns
+/// Pointer to the record, or NULL if the ring buffer is full.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_reserve(map_id: u32, size: u64, flags: u64) -> *mut u8 {
+    let _ = flags;
+
+    if let Some(table) = super::hooks::read() {
+        if let Some(map) = table.map(map_id) {
+            // SAFETY: The record is tracked for the running program, and
+            // `hooks::run` discards it if the program does not release it.
+            if let Some(ptr) = unsafe { map.reserve_ptr(size as usize) } {
+                if super::hooks::track_record(ptr) {
+                    return ptr;
+                }
+                // SAFETY: ptr was just reserved and never handed out
+                let _ = unsafe { map.release_ptr(ptr, true) };
+            }
+        }
+    }
+    core::ptr::null_mut()
+}
+
+/// BPF helper: submit a reserved ring buffer record.
+///
+/// Makes the record visible to consumers.
+///
+/// Pointers that are not an open record of the running program are ignored.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_submit(data_ptr: *mut u8, flags: u64) {
+    let _ = flags;
+    ringbuf_release(data_ptr, false);
+}
+
+/// BPF helper: discard a reserved ring buffer record.
+///
+/// The record is skipped by consumers.
+///
+/// Pointers that are not an open record of the running program are ignored.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_ringbuf_discard(data_ptr: *mut u8, flags: u64) {
+    let _ = flags;
+    ringbuf_release(data_ptr, true);
+}
+
+fn ringbuf_release(data_ptr: *mut u8, discard: bool) {
+    if data_ptr.is_null() {
+        return;
+    }
+
+    if !super::hooks::untrack_record(data_ptr) {
+        return;
+    }
+    if let Some(table) = super::hooks::read() {
+        // SAFETY: data_ptr is an open record of the running program, and
+        // untracking it ensures it is released only once.
+        let _ = unsafe { table.ringbuf_release(data_ptr, discard) };
+    }
+}
+
+/// BPF helper: Write a sample to a perf event array
+///
+/// `flags` selects the CPU ring; `BPF_F_CURRENT_CPU` picks the ring of the
+/// CPU the program runs on. The sample is dropped (and counted as lost) if
+/// the ring is full or userspace has not mapped it yet.
+///
+/// Returns 0, or -1 if the map is not a perf event array, the flags are
+/// invalid or the sample was dropped.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures data points to
+/// `size` readable bytes.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_perf_event_output(
+    _ctx: *const u8,
+    map_id: u32,
+    flags: u64,
+    data: *const u8,
+    size: u64,
+) -> i64 {
+    if flags > BPF_F_CURRENT_CPU || data.is_null() {
+        return -1;
+    }
+    let cpu = if flags == BPF_F_CURRENT_CPU {
+        ExecutionContext::load().cpu_id() as u32
+    } else {
+        flags as u32
+    };
+
+    let Some(table) = super::hooks::read() else {
+        return -1;
+    };
+    let Some(map) = table.map(map_id) else {
+        return -1;
+    };
+
+    // SAFETY: Verifier ensures valid memory access for data
+    let data = unsafe { core::slice::from_raw_parts(data, size as usize) };
+    map.perf_output(cpu, data).map_or(-1, |()| 0)
+}
+
+/// BPF helper: Store the current call stack in a stack trace map
+///
+/// The low 8 bits of `flags` are the number of innermost frames to skip.
+/// `BPF_F_USER_STACK` captures the user stack instead of the kernel stack,
+/// and `BPF_F_REUSE_STACKID` replaces a different stack that has the same
+/// ID. See [`super::stack`] for where stacks are walked from.
+///
+/// Returns the stack ID, or a negative errno: `EFAULT` if there is no stack
+/// to capture, `EEXIST` if the ID is taken by another stack, `EBUSY` if the
+/// bucket is in use by the interrupted code and `EINVAL` for bad flags or a
+/// map that is not a stack trace map.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_stackid(_ctx: *const u8, map_id: u32, flags: u64) -> i64 {
+    const VALID: u64 =
+        BPF_F_SKIP_FIELD_MASK | BPF_F_USER_STACK | BPF_F_FAST_STACK_CMP | BPF_F_REUSE_STACKID;
+    if flags & !VALID != 0 {
+        return -(isize::from(kernel_abi::EINVAL) as i64);
+    }
+    let Some(table) = super::hooks::read() else {
+        return -(isize::from(kernel_abi::EINVAL) as i64);
+    };
+    let Some(map) = table.map(map_id) else {
+        return -(isize::from(kernel_abi::EINVAL) as i64);
+    };
+
+    let mut ips = [0; MAX_STACK_DEPTH];
+    let n = stack::capture(
+        flags & BPF_F_USER_STACK != 0,
+        (flags & BPF_F_SKIP_FIELD_MASK) as usize,
+        &mut ips,
+    );
+    if n == 0 {
+        return -(isize::from(kernel_abi::EFAULT) as i64);
+    }
+
+    match map.store_stack(&ips[..n], flags & BPF_F_REUSE_STACKID != 0) {
+        Ok(id) => i64::from(id),
+        Err(e) => {
+            let errno = match e {
+                MapError::KeyExists => kernel_abi::EEXIST,
+                MapError::MapFull => kernel_abi::EBUSY,
+                _ => kernel_abi::EINVAL,
+            };
+            -(isize::from(errno) as i64)
+        }
+    }
+}
+
+/// BPF helper: Copy the current call stack into a buffer
+///
+/// Writes the instruction pointers of the stack, innermost first, as `u64`s
+/// to `buf` and zeroes the rest of it. `flags` are those of
+/// [`bpf_get_stackid`] without `BPF_F_REUSE_STACKID`.
+///
+/// Returns the number of bytes written, or a negative errno: `EFAULT` if
+/// there is no stack to capture and `EINVAL` for bad flags.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures buf points to
+/// `size` writable bytes.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_stack(_ctx: *const u8, buf: *mut u8, size: u32, flags: u64) -> i64 {
+    if buf.is_null() {
+        return -(isize::from(kernel_abi::EFAULT) as i64);
+    }
+    // SAFETY: Verifier ensures buf is valid for size bytes
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, size as usize) };
+    buf.fill(0);
+
+    if flags & !(BPF_F_SKIP_FIELD_MASK | BPF_F_USER_STACK) != 0 {
+        return -(isize::from(kernel_abi::EINVAL) as i64);
+    }
+
+    let mut ips = [0; MAX_STACK_DEPTH];
+    let depth = (buf.len() / 8).min(MAX_STACK_DEPTH);
+    let n = stack::capture(
+        flags & BPF_F_USER_STACK != 0,
+        (flags & BPF_F_SKIP_FIELD_MASK) as usize,
+        &mut ips[..depth],
+    );
+    if n == 0 {
+        return -(isize::from(kernel_abi::EFAULT) as i64);
+    }
+
+    for (chunk, ip) in buf.as_chunks_mut::<8>().0.iter_mut().zip(&ips[..n]) {
+        chunk.copy_from_slice(&ip.to_ne_bytes());
+    }
+    (n * 8) as i64
+}
+
+/// BPF helper: Get current process and task ID
+///
+/// Returns `tgid << 32 | pid`, where the thread group ID is the ID of the
+/// current process and the pid is the ID of the current task, as scheduled
+/// on this CPU.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It only reads the
+/// per-CPU scheduler state.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_current_pid_tgid() -> u64 {
+    let task = ExecutionContext::load().current_task();
+    let tgid = task.process().pid().as_u64();
+    let pid = task.id().as_u64();
+    (tgid << 32) | (pid & 0xFFFF_FFFF)
+}
+
+/// BPF helper: Get current user and group ID
+///
+/// Returns `gid << 32 | uid`. The kernel has no user accounts yet, so every
+/// process runs as root and this is always 0.
+///
+/// # Safety
+///
+/// This function is an entry point for BPF programs. It does not access
+/// memory.
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_current_uid_gid() -> u64 {
+    0
+}
+
+/// BPF helper: Get current process name
+///
+/// Copies the name of the current process into `buf`, truncating it to
+/// `size - 1` bytes and always NUL-terminating it.
+/// Returns 0 on success, -1 on error (null or empty buffer).
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures buf points to
+/// `size` bytes of writable stack.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_get_current_comm(buf: *mut u8, size: u32) -> i64 {
+    if buf.is_null() || size == 0 {
+        return -1;
+    }
+
+    // SAFETY: Verifier ensures buf is valid for size bytes
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf, size as usize) };
+    let name = ExecutionContext::load().current_process().name().as_bytes();
+    let len = name.len().min(buf.len() - 1);
+
+    buf[..len].copy_from_slice(&name[..len]);
+    buf[len..].fill(0);
+    0
+}
+
+/// BPF helper: Read memory from a user address
+///
+/// Copies `size` bytes from `src` in the current process into `dst`.
+/// Returns 0 on success, -EFAULT if the source is not mapped user memory.
+/// On failure `dst` is zeroed, so the program never sees stale stack data.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures dst points to
+/// `size` bytes of writable stack; `src` is untrusted and validated here.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_probe_read_user(dst: *mut u8, size: u32, src: *const u8) -> i64 {
+    probe_read(dst, size, src, true)
+}
+
+/// BPF helper: Read memory from a kernel address
+///
+/// Copies `size` bytes from `src` in the kernel address space into `dst`.
+/// Returns 0 on success, -EFAULT if the source is not mapped kernel memory.
+/// On failure `dst` is zeroed.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures dst points to
+/// `size` bytes of writable stack; `src` is untrusted and validated here.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_probe_read_kernel(dst: *mut u8, size: u32, src: *const u8) -> i64 {
+    probe_read(dst, size, src, false)
+}
+
+/// BPF helper: Read memory from a user or kernel address
+///
+/// Legacy variant that picks the address space from the address itself.
+///
+/// # Safety
+///
+/// Called from verified BPF programs. The verifier ensures dst points to
+/// `size` bytes of writable stack; `src` is untrusted and validated here.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[unsafe(no_mangle)]
+pub extern "C" fn bpf_probe_read(dst: *mut u8, size: u32, src: *const u8) -> i64 {
+    // SAFETY: The pointer is only used to classify the address, never dereferenced.
+    let user = unsafe { kernel_syscall::UserspacePtr::<u8>::try_from_usize(src as usize) }.is_ok();
+    probe_read(dst, size, src, user)
+}
+
+fn probe_read(dst: *mut u8, size: u32, src: *const u8, user: bool) -> i64 {
+    if dst.is_null() {
+        return -(isize::from(kernel_abi::EFAULT) as i64);
+    }
+
+    // SAFETY: Verifier ensures dst is valid for size bytes
+    let dst = unsafe { core::slice::from_raw_parts_mut(dst, size as usize) };
+
+    match read_nofault(src as usize, dst, user) {
+        Ok(()) => 0,
+        Err(e) => {
+            dst.fill(0);
+            -(isize::from(e) as i64)
+        }
+    }
+}
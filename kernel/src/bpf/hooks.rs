@@ -0,0 +1,301 @@
+//! Lock-free hook dispatch
+//!
+//! Hooks run from timer and device interrupts and at syscall entry, any of
+//! which can interrupt code holding the [`BpfManager`](super::BpfManager)
+//! lock. Dispatch therefore never takes that lock. Instead the manager
+//! publishes an immutable [`HookTable`] whenever its programs, maps or
+//! attachments change, and hooks and helpers read the latest table through
+//! [`Rcu`].
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::Relaxed;
+
+use kernel_bpf::attach::{AttachEvent, EventFilter};
+use kernel_bpf::bytecode::program::BpfProgram;
+use kernel_bpf::debug::LineTable;
+#[cfg(not(target_arch = "aarch64"))]
+use kernel_bpf::execution::Interpreter;
+use kernel_bpf::execution::{BpfContext, BpfError, BpfFault};
+use kernel_bpf::maps::BpfMap;
+use kernel_bpf::profile::ActiveProfile;
+
+use crate::mcore::context::ExecutionContext;
+use crate::rcu::{Rcu, RcuReadGuard};
+
+/// Per-CPU record slots, more than the kernel brings up
+const CPU_SLOTS: usize = 64;
+
+/// Ring buffer records a CPU can have open, across all nested runs
+const MAX_OPEN_RECORDS: usize = 16;
+
+static TABLE: Rcu<HookTable> = Rcu::new();
+
+static OPEN: [OpenRecords; CPU_SLOTS] = [const { OpenRecords::new() }; CPU_SLOTS];
+
+/// Ring buffer records reserved on a CPU and not released yet.
+///
+/// Only touched by the owning CPU with interrupts masked. Runs nest (a
+/// kprobe can fire inside a helper), so the records form a stack and each
+/// run owns the records from `base` up.
+struct OpenRecords {
+    ptrs: [AtomicUsize; MAX_OPEN_RECORDS],
+    len: AtomicUsize,
+    /// Start of the innermost run's records
+    base: AtomicUsize,
+}
+
+impl OpenRecords {
+    const fn new() -> Self {
+        Self {
+            ptrs: [const { AtomicUsize::new(0) }; MAX_OPEN_RECORDS],
+            len: AtomicUsize::new(0),
+            base: AtomicUsize::new(0),
+        }
+    }
+}
+
+fn open_records() -> &'static OpenRecords {
+    let cpu = ExecutionContext::try_load().map_or(0, |ctx| ctx.cpu_id());
+    &OPEN[cpu % CPU_SLOTS]
+}
+
+/// Remember a record reserved by the running program, so it is discarded
+/// if the program exits without releasing it.
+///
+/// Returns `false` if too many records are open.
+pub(super) fn track_record(ptr: *mut u8) -> bool {
+    let open = open_records();
+    let len = open.len.load(Relaxed);
+    if len == MAX_OPEN_RECORDS {
+        return false;
+    }
+    open.ptrs[len].store(ptr as usize, Relaxed);
+    open.len.store(len + 1, Relaxed);
+    true
+}
+
+/// Forget a record the running program releases.
+///
+/// Returns `false` if the running program has no such open record.
+pub(super) fn untrack_record(ptr: *mut u8) -> bool {
+    let open = open_records();
+    let len = open.len.load(Relaxed);
+    let base = open.base.load(Relaxed);
+    let Some(idx) = (base..len).find(|&i| open.ptrs[i].load(Relaxed) == ptr as usize) else {
+        return false;
+    };
+    open.ptrs[idx].store(open.ptrs[len - 1].load(Relaxed), Relaxed);
+    open.len.store(len - 1, Relaxed);
+    true
+}
+
+/// Discard the records the innermost run left open and return to the
+/// enclosing run's records.
+fn discard_open_records(base: usize, outer_base: usize) {
+    let open = open_records();
+    let len = open.len.load(Relaxed);
+    if len > base {
+        log::warn!(
+            "BPF program exited with {} ring buffer record(s) open, discarding",
+            len - base
+        );
+        if let Some(table) = read() {
+            for slot in &open.ptrs[base..len] {
+                let ptr = slot.load(Relaxed) as *mut u8;
+                // SAFETY: the record was reserved by this run and not
+                // released, as releasing untracks it.
+                let _ = unsafe { table.ringbuf_release(ptr, true) };
+            }
+        }
+    }
+    open.len.store(base, Relaxed);
+    open.base.store(outer_base, Relaxed);
+}
+
+/// A program attached to the events passing `filter`
+pub(super) struct Hook {
+    pub(super) filter: EventFilter,
+    pub(super) prog_id: u32,
+    pub(super) program: Arc<BpfProgram<ActiveProfile>>,
+    pub(super) lines: Option<Arc<LineTable>>,
+}
+
+/// Snapshot of everything hooks and helpers need at run time
+pub struct HookTable {
+    hooks: Vec<Hook>,
+    /// All maps, indexed by map id
+    maps: Vec<Arc<dyn BpfMap<ActiveProfile>>>,
+}
+
+impl HookTable {
+    pub(super) fn new(hooks: Vec<Hook>, maps: Vec<Arc<dyn BpfMap<ActiveProfile>>>) -> Self {
+        Self { hooks, maps }
+    }
+
+    pub fn map(&self, map_id: u32) -> Option<&dyn BpfMap<ActiveProfile>> {
+        self.maps.get(map_id as usize).map(|m| &**m)
+    }
+
+    /// Submit or discard a ring buffer record.
+    ///
+    /// The helpers only receive the record pointer, so the owning map is
+    /// found by asking each map to release it.
+    ///
+    /// # Safety
+    /// `ptr` must come from [`BpfMap::reserve_ptr`] and not have been
+    /// released yet.
+    pub unsafe fn ringbuf_release(&self, ptr: *mut u8, discard: bool) -> Result<(), BpfError> {
+        for map in &self.maps {
+            // SAFETY: caller guarantees ptr is a live reservation; maps that
+            // do not own it reject it without touching the memory.
+            if unsafe { map.release_ptr(ptr, discard) }.is_ok() {
+                return Ok(());
+            }
+        }
+        Err(BpfError::NotLoaded)
+    }
+}
+
+/// Replace the table used by hooks and helpers, enabling the tracepoints
+/// it has programs for.
+///
+/// The previous table is freed once no CPU is still using it.
+pub(super) fn publish(table: HookTable) {
+    super::tracepoint::update(|id| {
+        table
+            .hooks
+            .iter()
+            .any(|h| h.filter == EventFilter::Tracepoint { id })
+    });
+    TABLE.publish(Box::new(table));
+}
+
+/// Get the current table, or `None` before the first publish.
+///
+/// Never blocks. The table stays valid until the guard is dropped, even if a
+/// newer one is published in the meantime.
+pub fn read() -> Option<RcuReadGuard<'static, HookTable>> {
+    TABLE.read()
+}
+
+/// Run the programs attached to the target that raised `event`.
+///
+/// Safe to call from interrupt handlers.
+pub fn execute_hooks(event: AttachEvent<'_>, ctx: &BpfContext) {
+    let Some(table) = read() else {
+        return;
+    };
+
+    for hook in table.hooks.iter().filter(|h| h.filter.matches(&event)) {
+        let prog_id = hook.prog_id;
+        match run(&hook.program, ctx) {
+            Ok(res) => {
+                if matches!(event, AttachEvent::Iio(_)) {
+                    log::info!("IIO BPF Hook [id={}] returned: {}", prog_id, res);
+                } else if matches!(event, AttachEvent::Syscall(_)) {
+                    // Log only interesting syscalls or just debug info
+                    // For demo purposes, we log everything if it returns non-zero
+                    if res != 0 {
+                        log::info!("Syscall Trace [id={}] syscall_nr: {}", prog_id, res);
+                    }
+                }
+            }
+            Err(fault) => {
+                let empty = LineTable::new();
+                let lines = hook.lines.as_deref().unwrap_or(&empty);
+                log::error!(
+                    "BPF Hook [id={}] failed: {}",
+                    prog_id,
+                    lines.annotate(&fault, fault.insn_idx)
+                );
+            }
+        }
+    }
+}
+
+/// Run the programs attached to the target that raised `event` as checks.
+///
+/// Returns `None` if no program is attached, otherwise whether every program
+/// ran without faulting and returned non-zero. Safe to call from interrupt
+/// handlers.
+pub fn check_hooks(event: AttachEvent<'_>, ctx: &BpfContext) -> Option<bool> {
+    let table = read()?;
+
+    let mut passed = None;
+    for hook in table.hooks.iter().filter(|h| h.filter.matches(&event)) {
+        let ok = match run(&hook.program, ctx) {
+            Ok(res) => res != 0,
+            Err(fault) => {
+                let empty = LineTable::new();
+                let lines = hook.lines.as_deref().unwrap_or(&empty);
+                log::error!(
+                    "BPF Hook [id={}] failed: {}",
+                    hook.prog_id,
+                    lines.annotate(&fault, fault.insn_idx)
+                );
+                false
+            }
+        };
+        passed = Some(passed.unwrap_or(true) && ok);
+    }
+    passed
+}
+
+/// Run the programs attached to the target that raised `event` as vetoes.
+///
+/// Returns whether any program returned non-zero. Programs that fault are
+/// logged and do not veto. Safe to call from interrupt handlers.
+pub fn veto_hooks(event: AttachEvent<'_>, ctx: &BpfContext) -> bool {
+    let Some(table) = read() else {
+        return false;
+    };
+
+    let mut vetoed = false;
+    for hook in table.hooks.iter().filter(|h| h.filter.matches(&event)) {
+        match run(&hook.program, ctx) {
+            Ok(res) => vetoed |= res != 0,
+            Err(fault) => {
+                let empty = LineTable::new();
+                let lines = hook.lines.as_deref().unwrap_or(&empty);
+                log::error!(
+                    "BPF Hook [id={}] failed: {}",
+                    hook.prog_id,
+                    lines.annotate(&fault, fault.insn_idx)
+                );
+            }
+        }
+    }
+    vetoed
+}
+
+/// Run a program with the executor of this architecture.
+///
+/// Interrupts are masked while it runs, as its helpers take the same map
+/// locks as programs run from interrupts. Ring buffer records the program
+/// leaves open, on exit or on a fault, are discarded.
+pub fn run(program: &BpfProgram<ActiveProfile>, ctx: &BpfContext) -> Result<u64, BpfFault> {
+    crate::arch::without_interrupts(|| {
+        let open = open_records();
+        let base = open.len.load(Relaxed);
+        let outer_base = open.base.swap(base, Relaxed);
+
+        #[cfg(target_arch = "aarch64")]
+        let result = {
+            use kernel_bpf::execution::{Arm64JitExecutor, BpfExecutor};
+            let executor = Arm64JitExecutor::<ActiveProfile>::new();
+            executor.execute(program, ctx).map_err(BpfFault::from)
+        };
+
+        #[cfg(not(target_arch = "aarch64"))]
+        let result = {
+            let interpreter = Interpreter::<ActiveProfile>::new();
+            interpreter.run(program, ctx)
+        };
+
+        discard_open_records(base, outer_base);
+        result
+    })
+}
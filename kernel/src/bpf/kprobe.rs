@@ -0,0 +1,785 @@
+//! Dynamic kernel probes
+//!
+//! A kprobe runs BPF programs when a kernel function is entered, a kretprobe
+//! when it returns. Functions are found by name in the kernel symbol table
+//! ([`ksyms`]) and probed by replacing their first instruction with a
+//! breakpoint: `int3` on x86_64, `brk` on aarch64. The breakpoint trap hands
+//! over the full register state as a [`PtRegs`], which programs receive as
+//! their context.
+//!
+//! After the programs ran, the replaced instruction still has to execute:
+//!
+//! - x86_64 puts it back, single-steps it with the trap flag set and
+//!   re-inserts the `int3` from the debug exception. Interrupts stay masked
+//!   for the step. Other CPUs entering the function during the step miss the
+//!   probe.
+//! - aarch64 runs a copy of it in an out-of-line slot followed by a second
+//!   `brk`, whose trap resumes behind the probe. `adr` and `adrp` are
+//!   simulated instead, since they depend on where they run; functions
+//!   starting with other PC-relative instructions, branches or exclusive
+//!   accesses cannot be probed.
+//!
+//! A kretprobe swaps the return address at entry for a trampoline holding
+//! another breakpoint, whose trap runs the programs and resumes at the real
+//! return address. Return addresses are kept in a pool of
+//! [`KRETPROBE_INSTANCES`] slots; calls made while it is full are not probed.
+//! Each slot belongs to the task that made the call, and a task's calls
+//! return in reverse order, so the trampoline resumes the task's latest
+//! call. Slots of tasks that exit inside a probed function are freed by
+//! [`release_task`].
+//!
+//! The trap path runs without locks or allocation. Functions it calls cannot
+//! be probed and are refused by [`is_blacklisted`]. Probes hit while the CPU
+//! is already running kprobe programs, such as in the programs' helpers, are
+//! stepped over without running programs and counted as [`missed`].
+
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64};
+
+use kernel_bpf::attach::{
+    AttachError, AttachEvent, AttachHardware, AttachResult, KprobeType, PtRegs,
+};
+use kernel_bpf::execution::BpfContext;
+use spin::Mutex;
+
+use crate::ksyms::{self, Symbol};
+use crate::mcore::context::ExecutionContext;
+
+/// Most probed functions over the lifetime of the kernel
+pub const MAX_SITES: usize = 128;
+
+/// Most kretprobe calls in flight at once
+pub const KRETPROBE_INSTANCES: usize = 256;
+
+/// Functions the trap path runs before it guards against recursion, which
+/// therefore cannot be probed: path prefixes ending in `::`, and unmangled
+/// names.
+const BLACKLIST: &[&str] = &[
+    "kernel::bpf::kprobe::",
+    "kernel::mcore::context::",
+    "kernel::mcore::mtask::scheduler::Scheduler::current_task",
+    "kernel::mcore::mtask::task::Task::id",
+    "kernel::mcore::mtask::task::id::TaskId::as_u64",
+    "kernel::arch::idt::",
+    "kernel::arch::aarch64::exceptions::",
+    "x86_64::registers::",
+    "core::",
+    "compiler_builtins::",
+    "handle_sync_exception",
+    "handle_kernel_sync_exception",
+    "handle_irq",
+    "handle_invalid_exception",
+    "memcpy",
+    "memmove",
+    "memset",
+    "memcmp",
+];
+
+/// Per-CPU state slots, more than the kernel brings up
+const CPU_SLOTS: usize = 64;
+
+/// Site flag: entry programs are attached
+const SITE_ENTRY: u32 = 1 << 0;
+
+/// Site flag: return programs are attached
+const SITE_RETURN: u32 = 1 << 1;
+
+/// A probed function. Sites are never freed, so the trap path can use them
+/// without synchronizing with registration.
+struct Site {
+    /// Function address, 0 for a free site
+    addr: AtomicU64,
+    /// Instruction the breakpoint replaces (its first byte on x86_64)
+    insn: AtomicU32,
+    /// `SITE_*` flags; the breakpoint is armed while any is set
+    flags: AtomicU32,
+}
+
+impl Site {
+    const fn new() -> Self {
+        Self {
+            addr: AtomicU64::new(0),
+            insn: AtomicU32::new(0),
+            flags: AtomicU32::new(0),
+        }
+    }
+}
+
+/// Per-CPU trap state
+struct CpuState {
+    /// Set while kprobe programs run
+    running: AtomicBool,
+    /// Function whose first instruction is being stepped, 0 if none
+    step_addr: AtomicU64,
+    /// Interrupt mask bit to restore after the step
+    step_irq: AtomicU64,
+}
+
+impl CpuState {
+    const fn new() -> Self {
+        Self {
+            running: AtomicBool::new(false),
+            step_addr: AtomicU64::new(0),
+            step_irq: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Return address saved by a kretprobe
+struct Instance {
+    /// Stack pointer at function entry, 0 for a free slot
+    frame: AtomicU64,
+    /// [`current_task`] of the caller, 0 while the slot is claimed or freed
+    task: AtomicU64,
+    /// Call order; the task's latest call has the highest depth
+    depth: AtomicU64,
+    /// Address the function returns to
+    ret: AtomicU64,
+    /// Function address
+    func: AtomicU64,
+}
+
+impl Instance {
+    const fn new() -> Self {
+        Self {
+            frame: AtomicU64::new(0),
+            task: AtomicU64::new(0),
+            depth: AtomicU64::new(0),
+            ret: AtomicU64::new(0),
+            func: AtomicU64::new(0),
+        }
+    }
+
+    fn release(&self) {
+        self.task.store(0, Relaxed);
+        self.frame.store(0, Release);
+    }
+}
+
+static SITES: [Site; MAX_SITES] = [const { Site::new() }; MAX_SITES];
+
+static CPUS: [CpuState; CPU_SLOTS] = [const { CpuState::new() }; CPU_SLOTS];
+
+static INSTANCES: [Instance; KRETPROBE_INSTANCES] =
+    [const { Instance::new() }; KRETPROBE_INSTANCES];
+
+/// Serializes registration
+static REGISTER: Mutex<()> = Mutex::new(());
+
+static MISSED: AtomicU64 = AtomicU64::new(0);
+
+/// Source of [`Instance::depth`]
+static DEPTH: AtomicU64 = AtomicU64::new(0);
+
+/// The breakpoint of a kprobe attach point, armed while programs are
+/// attached
+pub struct Breakpoint {
+    addr: u64,
+    probe_type: KprobeType,
+}
+
+impl Breakpoint {
+    pub fn new(addr: u64, probe_type: KprobeType) -> Self {
+        Self { addr, probe_type }
+    }
+}
+
+impl AttachHardware for Breakpoint {
+    fn enable(&mut self) -> AttachResult<()> {
+        register(self.addr, site_flag(self.probe_type))
+    }
+
+    fn disable(&mut self) {
+        unregister(self.addr, site_flag(self.probe_type));
+    }
+}
+
+fn site_flag(probe_type: KprobeType) -> u32 {
+    match probe_type {
+        KprobeType::Entry => SITE_ENTRY,
+        KprobeType::Return => SITE_RETURN,
+    }
+}
+
+/// Find a function that can be probed by name.
+pub fn lookup(name: &str) -> AttachResult<&'static Symbol> {
+    let sym = ksyms::lookup(name).ok_or(AttachError::ResourceNotFound)?;
+    if is_blacklisted(&sym.name) {
+        return Err(AttachError::PermissionDenied);
+    }
+    Ok(sym)
+}
+
+/// Check if the function named `name` must not be probed.
+pub fn is_blacklisted(name: &str) -> bool {
+    let path = name.trim_start_matches('<');
+    BLACKLIST.iter().any(|entry| {
+        if entry.ends_with("::") {
+            path.starts_with(entry)
+        } else {
+            path == *entry
+        }
+    })
+}
+
+/// Number of probe hits and kretprobe calls that ran no programs, because
+/// they were hit from kprobe programs, the kretprobe pool was full or the
+/// call never returned through the trampoline.
+pub fn missed() -> u64 {
+    MISSED.load(Relaxed)
+}
+
+fn register(addr: u64, flag: u32) -> AttachResult<()> {
+    crate::arch::without_interrupts(|| {
+        let _guard = REGISTER.lock();
+        let site = match SITES.iter().find(|s| s.addr.load(Relaxed) == addr) {
+            Some(site) => site,
+            None => {
+                let (idx, site) = SITES
+                    .iter()
+                    .enumerate()
+                    .find(|(_, s)| s.addr.load(Relaxed) == 0)
+                    .ok_or(AttachError::TooManyAttachments)?;
+                site.insn.store(arch::prepare(idx, addr)?, Relaxed);
+                site.addr.store(addr, Release);
+                site
+            }
+        };
+        if site.flags.fetch_or(flag, Relaxed) == 0 {
+            arch::arm(addr);
+        }
+        Ok(())
+    })
+}
+
+fn unregister(addr: u64, flag: u32) {
+    crate::arch::without_interrupts(|| {
+        let _guard = REGISTER.lock();
+        let Some(site) = SITES.iter().find(|s| s.addr.load(Relaxed) == addr) else {
+            return;
+        };
+        if site.flags.fetch_and(!flag, Relaxed) == flag {
+            arch::disarm(addr, site.insn.load(Relaxed));
+        }
+    });
+}
+
+/// Tag of the running task for [`Instance::task`]: its id plus one
+fn current_task() -> u64 {
+    ExecutionContext::try_load().map_or(0, |ctx| ctx.current_task().id().as_u64()) + 1
+}
+
+fn cpu_state() -> &'static CpuState {
+    let cpu = ExecutionContext::try_load().map_or(0, |ctx| ctx.cpu_id());
+    &CPUS[cpu % CPU_SLOTS]
+}
+
+fn site(addr: u64) -> Option<(usize, &'static Site)> {
+    SITES
+        .iter()
+        .enumerate()
+        .find(|(_, s)| s.addr.load(Acquire) == addr)
+}
+
+/// Handle a breakpoint trap, returning whether it was a kprobe's.
+///
+/// Runs the programs of the probe that was hit and arranges for execution
+/// to continue as if the breakpoint was not there.
+pub fn handle_breakpoint(regs: &mut PtRegs) -> bool {
+    let addr = arch::trap_addr(regs);
+    if addr == arch::trampoline() {
+        return kretprobe_return(regs);
+    }
+    // Out-of-line steps end in a breakpoint of their own
+    #[cfg(target_arch = "aarch64")]
+    if arch::finish_step(regs) {
+        return true;
+    }
+
+    let Some((idx, site)) = site(addr) else {
+        return false;
+    };
+    regs.set_ip(addr);
+
+    // Without flags the probe was disarmed after this trap was taken, or
+    // re-armed by a late step on another CPU; the step then leaves the
+    // instruction in place. A repeated hit while stepping this probe on this
+    // CPU comes from a concurrent re-arm and was already handled.
+    let flags = site.flags.load(Relaxed);
+    let cpu = cpu_state();
+    if flags != 0 && cpu.step_addr.load(Relaxed) != addr {
+        if cpu.running.swap(true, Relaxed) {
+            MISSED.fetch_add(1, Relaxed);
+        } else {
+            if flags & SITE_ENTRY != 0 {
+                run(addr, KprobeType::Entry, regs);
+            }
+            if flags & SITE_RETURN != 0 {
+                kretprobe_entry(addr, regs);
+            }
+            cpu.running.store(false, Relaxed);
+        }
+    }
+
+    arch::step(idx, addr, site.insn.load(Relaxed), regs, cpu);
+    true
+}
+
+/// Handle a debug trap, returning whether it ended a kprobe single-step.
+pub fn handle_debug(regs: &mut PtRegs) -> bool {
+    arch::finish_step(regs)
+}
+
+fn run(addr: u64, probe_type: KprobeType, regs: &PtRegs) {
+    let snapshot = *regs;
+    // SAFETY: PtRegs is plain data; the slice only lives while the programs
+    // run.
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            &snapshot as *const PtRegs as *const u8,
+            core::mem::size_of::<PtRegs>(),
+        )
+    };
+    super::stack::with_regs(regs, || {
+        super::hooks::execute_hooks(
+            AttachEvent::Kprobe { addr, probe_type },
+            &BpfContext::from_slice(slice),
+        );
+    });
+}
+
+/// Divert the return of the function being entered to the trampoline.
+fn kretprobe_entry(addr: u64, regs: &mut PtRegs) {
+    let ret = arch::return_address(regs);
+    // Tail call from a function whose return is already diverted
+    if ret == arch::trampoline() {
+        MISSED.fetch_add(1, Relaxed);
+        return;
+    }
+
+    let frame = regs.sp();
+    let Some(instance) = INSTANCES
+        .iter()
+        .find(|i| i.frame.compare_exchange(0, frame, Acquire, Relaxed).is_ok())
+    else {
+        MISSED.fetch_add(1, Relaxed);
+        return;
+    };
+    instance.ret.store(ret, Relaxed);
+    instance.func.store(addr, Relaxed);
+    instance.depth.store(DEPTH.fetch_add(1, Relaxed), Relaxed);
+    instance.task.store(current_task(), Release);
+    arch::set_return_address(regs, arch::trampoline());
+}
+
+/// The task's latest call among those passing `filter`
+fn latest_instance(task: u64, filter: impl Fn(&Instance) -> bool) -> Option<&'static Instance> {
+    INSTANCES
+        .iter()
+        .filter(|i| i.task.load(Acquire) == task && filter(i))
+        .max_by_key(|i| i.depth.load(Relaxed))
+}
+
+/// Run the return programs of a function that returned to the trampoline
+/// and resume at its real return address.
+///
+/// Returns `false`, leaving the trap unhandled, if the task has no call to
+/// return to.
+fn kretprobe_return(regs: &mut PtRegs) -> bool {
+    let task = current_task();
+    let frame = arch::entry_frame(regs);
+
+    let instance = latest_instance(task, |i| i.frame.load(Relaxed) == frame).or_else(|| {
+        log::warn!(
+            "kretprobe trampoline reached from unknown frame {:#x}, resuming latest call",
+            frame
+        );
+        latest_instance(task, |_| true)
+    });
+    let Some(instance) = instance else {
+        log::error!(
+            "kretprobe trampoline reached from frame {:#x} without a pending call",
+            frame
+        );
+        return false;
+    };
+
+    // Later calls of the task left without passing the trampoline
+    let depth = instance.depth.load(Relaxed);
+    while let Some(stale) = latest_instance(task, |i| i.depth.load(Relaxed) > depth) {
+        log::warn!(
+            "kretprobe call at frame {:#x} never returned, dropping it",
+            stale.frame.load(Relaxed)
+        );
+        stale.release();
+        MISSED.fetch_add(1, Relaxed);
+    }
+
+    let ret = instance.ret.load(Relaxed);
+    let func = instance.func.load(Relaxed);
+    instance.release();
+
+    regs.set_ip(ret);
+    arch::returned(regs, ret);
+
+    let cpu = cpu_state();
+    if cpu.running.swap(true, Relaxed) {
+        MISSED.fetch_add(1, Relaxed);
+    } else {
+        run(func, KprobeType::Return, regs);
+        cpu.running.store(false, Relaxed);
+    }
+    true
+}
+
+/// Free the kretprobe slots of an exited task, whose probed calls will never
+/// return.
+pub fn release_task(tid: u64) {
+    let task = tid + 1;
+    for instance in INSTANCES.iter().filter(|i| i.task.load(Acquire) == task) {
+        instance.release();
+        MISSED.fetch_add(1, Relaxed);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    use core::sync::atomic::Ordering::Relaxed;
+
+    use kernel_bpf::attach::{AttachError, AttachResult, PtRegs};
+    use x86_64::registers::control::{Cr0, Cr0Flags};
+
+    use super::{CpuState, SITES};
+
+    const INT3: u8 = 0xcc;
+
+    /// Trap flag
+    const RFLAGS_TF: u64 = 1 << 8;
+
+    /// Interrupt enable flag
+    const RFLAGS_IF: u64 = 1 << 9;
+
+    /// Write a byte of kernel text.
+    ///
+    /// # Safety
+    /// `addr` must be the first byte of an instruction, and interrupts must
+    /// be disabled.
+    unsafe fn poke(addr: u64, byte: u8) {
+        let cr0 = Cr0::read();
+        // SAFETY: Kernel text is mapped read-only; lifting write protection
+        // for one byte with interrupts disabled affects nothing else. A
+        // single byte write is atomic for instruction fetch on other CPUs.
+        unsafe {
+            Cr0::write(cr0 - Cr0Flags::WRITE_PROTECT);
+            core::ptr::write_volatile(addr as *mut u8, byte);
+            Cr0::write(cr0);
+        }
+    }
+
+    pub fn prepare(_site: usize, addr: u64) -> AttachResult<u32> {
+        // SAFETY: addr is the start of a kernel function.
+        let byte = unsafe { core::ptr::read_volatile(addr as *const u8) };
+        if byte == INT3 {
+            return Err(AttachError::ResourceBusy);
+        }
+        Ok(u32::from(byte))
+    }
+
+    pub fn arm(addr: u64) {
+        // SAFETY: Called with interrupts disabled on a function start.
+        unsafe { poke(addr, INT3) };
+    }
+
+    pub fn disarm(addr: u64, insn: u32) {
+        // SAFETY: Called with interrupts disabled on a function start.
+        unsafe { poke(addr, insn as u8) };
+    }
+
+    /// `int3` traps after itself
+    pub fn trap_addr(regs: &PtRegs) -> u64 {
+        regs.rip - 1
+    }
+
+    /// Put the instruction back and single-step it with interrupts masked.
+    pub fn step(_site: usize, addr: u64, insn: u32, regs: &mut PtRegs, cpu: &CpuState) {
+        if cpu.step_addr.load(Relaxed) != addr {
+            cpu.step_irq.store(regs.rflags & RFLAGS_IF, Relaxed);
+            cpu.step_addr.store(addr, Relaxed);
+        }
+        disarm(addr, insn);
+        regs.rip = addr;
+        regs.rflags = (regs.rflags | RFLAGS_TF) & !RFLAGS_IF;
+    }
+
+    /// Re-arm the probe after its instruction was stepped.
+    pub fn finish_step(regs: &mut PtRegs) -> bool {
+        let cpu = super::cpu_state();
+        let addr = cpu.step_addr.load(Relaxed);
+        if addr == 0 || regs.rflags & RFLAGS_TF == 0 {
+            return false;
+        }
+
+        let armed = SITES
+            .iter()
+            .find(|s| s.addr.load(Relaxed) == addr)
+            .is_some_and(|s| s.flags.load(Relaxed) != 0);
+        if armed {
+            arm(addr);
+        }
+        regs.rflags = (regs.rflags & !RFLAGS_TF) | cpu.step_irq.load(Relaxed);
+        cpu.step_addr.store(0, Relaxed);
+        true
+    }
+
+    pub fn return_address(regs: &PtRegs) -> u64 {
+        // SAFETY: At function entry the stack pointer points at the return
+        // address pushed by `call`.
+        unsafe { *(regs.rsp as *const u64) }
+    }
+
+    pub fn set_return_address(regs: &mut PtRegs, addr: u64) {
+        // SAFETY: See return_address.
+        unsafe { *(regs.rsp as *mut u64) = addr };
+    }
+
+    /// Stack pointer at entry of the function that just returned
+    pub fn entry_frame(regs: &PtRegs) -> u64 {
+        regs.rsp - 8
+    }
+
+    pub fn returned(_regs: &mut PtRegs, _ret: u64) {}
+
+    pub fn trampoline() -> u64 {
+        kretprobe_trampoline as usize as u64
+    }
+
+    /// Target of diverted returns; its `int3` is handled as a kretprobe hit.
+    #[unsafe(naked)]
+    unsafe extern "C" fn kretprobe_trampoline() {
+        core::arch::naked_asm!("int3", "ud2");
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use core::sync::atomic::AtomicU64;
+    use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+    use kernel_bpf::attach::{AttachError, AttachResult, PtRegs};
+
+    use super::{CpuState, MAX_SITES, SITES};
+    use crate::arch::aarch64::cpu::aarch64_jit_sync_cache;
+    use crate::bpf::jit_memory::aarch64::bpf_jit_alloc_exec;
+
+    /// `brk` immediate of kprobes
+    pub const BRK_KPROBE: u32 = 0x004;
+
+    /// `brk` immediate ending an out-of-line step
+    pub const BRK_STEP: u32 = 0x005;
+
+    /// `brk` immediate of the kretprobe trampoline
+    pub const BRK_KRETPROBE: u32 = 0x006;
+
+    /// PSTATE IRQ mask bit
+    const PSTATE_I: u64 = 1 << 7;
+
+    /// Bytes per out-of-line slot: the instruction and a `brk`
+    const SLOT_SIZE: u64 = 8;
+
+    /// Out-of-line slots, one per site, allocated on first use
+    static SLOTS: AtomicU64 = AtomicU64::new(0);
+
+    const fn brk(imm: u32) -> u32 {
+        0xd420_0000 | (imm << 5)
+    }
+
+    /// Write an instruction of kernel text.
+    ///
+    /// # Safety
+    /// `addr` must be the address of an instruction in writable text.
+    unsafe fn poke(addr: u64, insn: u32) {
+        // SAFETY: Guaranteed by the caller; aligned 32-bit writes are single
+        // copy atomic for instruction fetch.
+        unsafe {
+            core::ptr::write_volatile(addr as *mut u32, insn);
+            aarch64_jit_sync_cache(addr as usize, 4);
+        }
+    }
+
+    /// Whether `insn` reads the PC or changes control flow, so it cannot run
+    /// out of line (ADR and ADRP excluded; they are simulated)
+    fn needs_pc(insn: u32) -> bool {
+        insn & 0x7c00_0000 == 0x1400_0000 // B, BL
+            || insn & 0xff00_0010 == 0x5400_0000 // B.cond
+            || insn & 0x7e00_0000 == 0x3400_0000 // CBZ, CBNZ
+            || insn & 0x7e00_0000 == 0x3600_0000 // TBZ, TBNZ
+            || insn & 0x3b00_0000 == 0x1800_0000 // LDR (literal)
+            || insn & 0xfe00_0000 == 0xd600_0000 // BR, BLR, RET, ERET
+            || insn & 0xff00_0000 == 0xd400_0000 // SVC, HVC, SMC, BRK
+            || insn & 0x3f00_0000 == 0x0800_0000 // Exclusives
+    }
+
+    fn is_adr(insn: u32) -> bool {
+        insn & 0x1f00_0000 == 0x1000_0000
+    }
+
+    fn slots() -> AttachResult<u64> {
+        let base = SLOTS.load(Acquire);
+        if base != 0 {
+            return Ok(base);
+        }
+        // SAFETY: Returns fresh executable memory or null.
+        let base = unsafe { bpf_jit_alloc_exec(MAX_SITES * SLOT_SIZE as usize) } as u64;
+        if base == 0 {
+            return Err(AttachError::HardwareError);
+        }
+        SLOTS.store(base, Release);
+        Ok(base)
+    }
+
+    pub fn prepare(site: usize, addr: u64) -> AttachResult<u32> {
+        // SAFETY: addr is the start of a kernel function.
+        let insn = unsafe { core::ptr::read_volatile(addr as *const u32) };
+        if needs_pc(insn) {
+            return Err(AttachError::InvalidTarget(alloc::format!(
+                "{:#x}: first instruction {:#010x} cannot be stepped out of line",
+                addr,
+                insn
+            )));
+        }
+
+        let slot = slots()? + site as u64 * SLOT_SIZE;
+        // SAFETY: The slot belongs to this site and is not executed until
+        // the site is armed.
+        unsafe {
+            core::ptr::write_volatile(slot as *mut u32, insn);
+            core::ptr::write_volatile((slot + 4) as *mut u32, brk(BRK_STEP));
+            aarch64_jit_sync_cache(slot as usize, SLOT_SIZE as usize);
+        }
+        Ok(insn)
+    }
+
+    pub fn arm(addr: u64) {
+        // SAFETY: addr is a function start; kernel text is mapped writable.
+        unsafe { poke(addr, brk(BRK_KPROBE)) };
+    }
+
+    pub fn disarm(addr: u64, insn: u32) {
+        // SAFETY: See arm.
+        unsafe { poke(addr, insn) };
+    }
+
+    /// `brk` traps on itself
+    pub fn trap_addr(regs: &PtRegs) -> u64 {
+        regs.pc
+    }
+
+    /// Simulate ADR/ADRP, or run the instruction in the site's slot with
+    /// IRQs masked.
+    pub fn step(site: usize, addr: u64, insn: u32, regs: &mut PtRegs, cpu: &CpuState) {
+        if is_adr(insn) {
+            let imm = ((((insn >> 5) & 0x7_ffff) << 2) | ((insn >> 29) & 0x3)) as u64;
+            let imm = ((imm << 43) as i64 >> 43) as u64; // sign-extend 21 bits
+            let value = if insn & (1 << 31) != 0 {
+                (addr & !0xfff).wrapping_add(imm << 12)
+            } else {
+                addr.wrapping_add(imm)
+            };
+            let rd = (insn & 0x1f) as usize;
+            if rd != 31 {
+                regs.regs[rd] = value;
+            }
+            regs.pc = addr + 4;
+            return;
+        }
+
+        cpu.step_irq.store(regs.pstate & PSTATE_I, Relaxed);
+        cpu.step_addr.store(addr, Relaxed);
+        regs.pc = SLOTS.load(Acquire) + site as u64 * SLOT_SIZE;
+        regs.pstate |= PSTATE_I;
+    }
+
+    /// Resume behind the probe if the trap is the `brk` ending a slot.
+    pub fn finish_step(regs: &mut PtRegs) -> bool {
+        let base = SLOTS.load(Acquire);
+        let end = base + MAX_SITES as u64 * SLOT_SIZE;
+        if base == 0 || !(base..end).contains(&regs.pc) || (regs.pc - base) % SLOT_SIZE != 4 {
+            return false;
+        }
+
+        let site = ((regs.pc - base) / SLOT_SIZE) as usize;
+        let cpu = super::cpu_state();
+        regs.pc = SITES[site].addr.load(Relaxed) + 4;
+        regs.pstate = (regs.pstate & !PSTATE_I) | cpu.step_irq.load(Relaxed);
+        cpu.step_addr.store(0, Relaxed);
+        true
+    }
+
+    pub fn return_address(regs: &PtRegs) -> u64 {
+        regs.regs[30]
+    }
+
+    pub fn set_return_address(regs: &mut PtRegs, addr: u64) {
+        regs.regs[30] = addr;
+    }
+
+    /// Stack pointer at entry of the function that just returned
+    pub fn entry_frame(regs: &PtRegs) -> u64 {
+        regs.sp
+    }
+
+    pub fn returned(regs: &mut PtRegs, ret: u64) {
+        regs.regs[30] = ret;
+    }
+
+    pub fn trampoline() -> u64 {
+        kretprobe_trampoline as usize as u64
+    }
+
+    /// Target of diverted returns; its `brk` is handled as a kretprobe hit.
+    #[unsafe(naked)]
+    unsafe extern "C" fn kretprobe_trampoline() {
+        core::arch::naked_asm!("brk #{imm}", "udf #0", imm = const BRK_KRETPROBE);
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    use kernel_bpf::attach::{AttachError, AttachResult, AttachType, PtRegs};
+
+    use super::CpuState;
+
+    pub fn prepare(_site: usize, _addr: u64) -> AttachResult<u32> {
+        Err(AttachError::NotSupported(AttachType::Kprobe))
+    }
+
+    pub fn arm(_addr: u64) {}
+
+    pub fn disarm(_addr: u64, _insn: u32) {}
+
+    pub fn trap_addr(regs: &PtRegs) -> u64 {
+        regs.ip()
+    }
+
+    pub fn step(_site: usize, _addr: u64, _insn: u32, _regs: &mut PtRegs, _cpu: &CpuState) {}
+
+    pub fn finish_step(_regs: &mut PtRegs) -> bool {
+        false
+    }
+
+    pub fn return_address(_regs: &PtRegs) -> u64 {
+        0
+    }
+
+    pub fn set_return_address(_regs: &mut PtRegs, _addr: u64) {}
+
+    pub fn entry_frame(regs: &PtRegs) -> u64 {
+        regs.sp()
+    }
+
+    pub fn returned(_regs: &mut PtRegs, _ret: u64) {}
+
+    pub fn trampoline() -> u64 {
+        0
+    }
+}
@@ -1,29 +1,67 @@
+pub mod attach;
 pub mod helpers;
+pub mod hooks;
 pub mod jit_memory;
+pub mod kprobe;
+pub mod perf;
+pub mod stack;
+pub mod syscall_filter;
+pub mod tracepoint;
 
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::fmt::Write;
 
+use kernel_bpf::attach::{
+    AttachConfig, AttachError, AttachId, AttachManager, AttachPoint, AttachResult,
+};
 use kernel_bpf::bytecode::insn::BpfInsn;
 use kernel_bpf::bytecode::program::BpfProgram;
-use kernel_bpf::execution::{BpfContext, BpfError, BpfExecutor};
-#[cfg(not(target_arch = "aarch64"))]
-use kernel_bpf::execution::Interpreter;
-use kernel_bpf::loader::BpfLoader;
-use kernel_bpf::maps::{ArrayMap, BpfMap, HashMap as BpfHashMap, RingBufMap};
+use kernel_bpf::debug::{self, LineTable};
+use kernel_bpf::execution::{BpfContext, BpfError, BpfFault};
+use kernel_bpf::loader::{BpfLoader, Btf};
+use kernel_bpf::maps::{
+    ArrayMap, BpfMap, HashMap as BpfHashMap, PerfEventArrayMap, RingBufMap, StackTraceMap,
+    TimeSeriesMap,
+};
 use kernel_bpf::profile::ActiveProfile;
+use kernel_bpf::verifier::StreamingVerifier;
 
 pub const ATTACH_TYPE_TIMER: u32 = 1;
 pub const ATTACH_TYPE_GPIO: u32 = 2;
 pub const ATTACH_TYPE_PWM: u32 = 3;
 pub const ATTACH_TYPE_IIO: u32 = 4;
 pub const ATTACH_TYPE_SYSCALL: u32 = 5;
+pub const ATTACH_TYPE_WATCHDOG: u32 = 6;
+pub const ATTACH_TYPE_CAN: u32 = 7;
+pub const ATTACH_TYPE_TRACEPOINT: u32 = 8;
+pub const ATTACH_TYPE_KPROBE: u32 = 9;
+pub const ATTACH_TYPE_KRETPROBE: u32 = 10;
+pub const ATTACH_TYPE_SYSCALL_EXIT: u32 = 11;
+pub const ATTACH_TYPE_SYSCALL_FILTER: u32 = 12;
+pub const ATTACH_TYPE_HRTIMER: u32 = 13;
+pub const ATTACH_TYPE_SERIAL: u32 = 14;
+pub const ATTACH_TYPE_I2C: u32 = 15;
+pub const ATTACH_TYPE_SPI: u32 = 16;
 
+/// Owner of loaded programs, maps and attachments.
+///
+/// Every change is published to [`hooks`] as a new snapshot, which is what
+/// hooks and helpers run against; neither takes the manager lock.
 pub struct BpfManager {
-    programs: Vec<BpfProgram<ActiveProfile>>,
-    attachments: BTreeMap<u32, Vec<u32>>,
-    maps: Vec<Box<dyn BpfMap<ActiveProfile>>>,
+    programs: Vec<Arc<BpfProgram<ActiveProfile>>>,
+    /// Live attach points
+    attach_points: AttachManager<ActiveProfile>,
+    /// Program attached through each attachment, keyed by attach id
+    attached_programs: BTreeMap<u32, u32>,
+    maps: Vec<Arc<dyn BpfMap<ActiveProfile>>>,
+    /// Source line tables, keyed by program id
+    line_tables: BTreeMap<u32, Arc<LineTable>>,
+    /// BTF blobs loaded via BPF_BTF_LOAD, indexed by BTF id
+    btfs: Vec<Btf>,
 }
 
 impl Default for BpfManager {
@@ -36,14 +74,20 @@ impl BpfManager {
     pub fn new() -> Self {
         Self {
             programs: Vec::new(),
-            attachments: BTreeMap::new(),
+            attach_points: AttachManager::new(),
+            attached_programs: BTreeMap::new(),
             maps: Vec::new(),
+            line_tables: BTreeMap::new(),
+            btfs: Vec::new(),
         }
     }
 
     pub fn load_program(&mut self, elf_bytes: &[u8]) -> Result<u32, BpfError> {
         let mut loader = BpfLoader::<ActiveProfile>::new();
         let obj = loader.load(elf_bytes).map_err(|_| BpfError::NotLoaded)?;
+        if let Some(e) = obj.btf_error() {
+            log::warn!("Dropped malformed BTF line info: {}", e);
+        }
 
         if let Some(loaded_prog) = obj.programs().first() {
             let bpf_prog = BpfProgram::new(
@@ -53,75 +97,161 @@ impl BpfManager {
             )
             .map_err(|_| BpfError::InvalidInstruction)?;
 
-            let id = self.programs.len() as u32;
-            self.programs.push(bpf_prog);
-            Ok(id)
+            let line_table = loaded_prog.line_table().cloned();
+            Ok(self.add_program(bpf_prog, line_table))
         } else {
             Err(BpfError::NotLoaded)
         }
     }
 
-    pub fn load_raw_program(&mut self, insns: Vec<BpfInsn>) -> Result<u32, BpfError> {
+    pub fn load_raw_program(
+        &mut self,
+        insns: Vec<BpfInsn>,
+        line_table: Option<LineTable>,
+    ) -> Result<u32, BpfError> {
         let bpf_prog =
             BpfProgram::new(kernel_bpf::bytecode::program::BpfProgType::Unspec, insns, 0)
                 .map_err(|_| BpfError::InvalidInstruction)?;
 
+        Ok(self.add_program(bpf_prog, line_table))
+    }
+
+    fn add_program(
+        &mut self,
+        program: BpfProgram<ActiveProfile>,
+        line_table: Option<LineTable>,
+    ) -> u32 {
         let id = self.programs.len() as u32;
-        self.programs.push(bpf_prog);
+        self.programs.push(Arc::new(program));
+        if let Some(table) = line_table.filter(|t| !t.is_empty()) {
+            self.line_tables.insert(id, Arc::new(table));
+        }
+        self.publish();
+        id
+    }
+
+    /// Load a BTF blob and return its id.
+    ///
+    /// Only the string table is kept; it is what line info records refer to.
+    pub fn load_btf(&mut self, data: &[u8]) -> Result<u32, BpfError> {
+        let btf = Btf::parse(data).map_err(|_| BpfError::InvalidInstruction)?;
+        let id = self.btfs.len() as u32;
+        self.btfs.push(btf);
         Ok(id)
     }
 
-    pub fn attach(&mut self, attach_type: u32, prog_id: u32) -> Result<(), BpfError> {
-        if prog_id as usize >= self.programs.len() {
-            return Err(BpfError::NotLoaded);
-        }
+    pub fn btf(&self, btf_id: u32) -> Option<&Btf> {
+        self.btfs.get(btf_id as usize)
+    }
 
-        let list = self.attachments.entry(attach_type).or_default();
-        if !list.contains(&prog_id) {
-            list.push(prog_id);
-        }
-        Ok(())
+    /// Run the verifier over a loaded program and describe what it rejects.
+    ///
+    /// Findings are annotated with the source line of the offending
+    /// instruction when line info was supplied at load time. Programs are
+    /// not rejected here: the streaming verifier does not yet track pointer
+    /// arithmetic, so its findings are advisory.
+    pub fn verifier_report(&self, prog_id: u32) -> Option<String> {
+        let program = self.programs.get(prog_id as usize)?;
+        let err =
+            StreamingVerifier::<ActiveProfile>::verify(program.prog_type(), program.instructions())
+                .err()?;
+
+        let empty = LineTable::new();
+        let lines = self.line_tables.get(&prog_id).map_or(&empty, |t| &**t);
+
+        let mut report = String::new();
+        let _ = write!(report, "{}", lines.annotate(&err, err.insn_idx()));
+        Some(report)
     }
 
-    pub fn execute(&self, program_id: u32, ctx: &BpfContext) -> Result<u64, BpfError> {
-        let program = self
-            .programs
-            .get(program_id as usize)
-            .ok_or(BpfError::NotLoaded)?;
+    /// Render a program as text, interleaving source lines and JIT code.
+    pub fn dump_program(&self, prog_id: u32) -> Option<String> {
+        let program = self.programs.get(prog_id as usize)?;
+        let lines = self.line_tables.get(&prog_id).map(|t| &**t);
+        let mut out = String::new();
 
         #[cfg(target_arch = "aarch64")]
         {
-            use kernel_bpf::execution::Arm64JitExecutor;
-            let executor = Arm64JitExecutor::<ActiveProfile>::new();
-            executor.execute(program, ctx)
+            use kernel_bpf::execution::Arm64JitCompiler;
+            let compiled = Arm64JitCompiler::<ActiveProfile>::new()
+                .compile(program)
+                .ok();
+            let jit = compiled.as_ref().map(|c| debug::JitImage {
+                code: c.code(),
+                insn_offsets: c.insn_offsets(),
+            });
+            debug::dump_program(&mut out, program.instructions(), lines, jit).ok()?;
         }
 
         #[cfg(not(target_arch = "aarch64"))]
-        {
-            let interpreter = Interpreter::<ActiveProfile>::new();
-            interpreter.execute(program, ctx)
-        }
+        debug::dump_program(&mut out, program.instructions(), lines, None).ok()?;
+
+        Some(out)
     }
 
-    pub fn execute_hooks(&self, attach_type: u32, ctx: &BpfContext) {
-        if let Some(progs) = self.attachments.get(&attach_type) {
-            for prog_id in progs {
-                match self.execute(*prog_id, ctx) {
-                    Ok(res) => {
-                        if attach_type == ATTACH_TYPE_IIO {
-                            log::info!("IIO BPF Hook [id={}] returned: {}", prog_id, res);
-                        } else if attach_type == ATTACH_TYPE_SYSCALL {
-                            // Log only interesting syscalls or just debug info
-                            // For demo purposes, we log everything if it returns non-zero
-                            if res != 0 {
-                                log::info!("Syscall Trace [id={}] syscall_nr: {}", prog_id, res);
-                            }
-                        }
-                    }
-                    Err(e) => log::error!("BPF Hook [id={}] failed: {:?}", prog_id, e),
-                }
-            }
-        }
+    /// Attach a program, using `point` if no attach point exists for
+    /// `config` yet.
+    pub fn attach(
+        &mut self,
+        config: &AttachConfig,
+        point: Box<dyn AttachPoint<ActiveProfile>>,
+        prog_id: u32,
+    ) -> AttachResult<AttachId> {
+        let program = self
+            .programs
+            .get(prog_id as usize)
+            .ok_or(AttachError::ResourceNotFound)?;
+
+        let id = self
+            .attach_points
+            .attach_with(config, program, |_| Ok(point))?;
+        self.attached_programs.insert(id.0, prog_id);
+        self.publish();
+        Ok(id)
+    }
+
+    /// Detach a program from the attach point described by `config`.
+    pub fn detach(&mut self, config: &AttachConfig, prog_id: u32) -> AttachResult<()> {
+        let id = self
+            .attach_points
+            .attached_with(config)
+            .find(|id| self.attached_programs.get(&id.0) == Some(&prog_id))
+            .ok_or(AttachError::ResourceNotFound)?;
+
+        self.attach_points.detach(id)?;
+        self.attached_programs.remove(&id.0);
+        self.publish();
+        Ok(())
+    }
+
+    pub fn program(&self, prog_id: u32) -> Option<Arc<BpfProgram<ActiveProfile>>> {
+        self.programs.get(prog_id as usize).cloned()
+    }
+
+    pub fn execute(&self, program_id: u32, ctx: &BpfContext) -> Result<u64, BpfFault> {
+        let program = self
+            .programs
+            .get(program_id as usize)
+            .ok_or(BpfError::NotLoaded)?;
+        hooks::run(program, ctx)
+    }
+
+    /// Publish the current programs, maps and attachments to hooks.
+    fn publish(&self) {
+        let hooks = self
+            .attach_points
+            .hooks()
+            .filter_map(|(id, filter)| {
+                let prog_id = *self.attached_programs.get(&id.0)?;
+                Some(hooks::Hook {
+                    filter,
+                    prog_id,
+                    program: self.programs.get(prog_id as usize)?.clone(),
+                    lines: self.line_tables.get(&prog_id).cloned(),
+                })
+            })
+            .collect();
+        hooks::publish(hooks::HookTable::new(hooks, self.maps.clone()));
     }
 
     // --- Map operations ---
@@ -133,36 +263,64 @@ impl BpfManager {
         value_size: u32,
         max_entries: u32,
     ) -> Result<u32, BpfError> {
-        let map: Box<dyn BpfMap<ActiveProfile>> = match map_type {
+        let id = self.maps.len() as u32;
+        let map: Arc<dyn BpfMap<ActiveProfile>> = match map_type {
             1 => {
                 // Hash map
-                Box::new(
+                Arc::new(
                     BpfHashMap::<ActiveProfile>::with_sizes(key_size, value_size, max_entries)
                         .map_err(|_| BpfError::OutOfMemory)?,
                 )
             }
             2 => {
                 // Array map
-                Box::new(
+                Arc::new(
                     ArrayMap::<ActiveProfile>::with_entries(value_size, max_entries)
                         .map_err(|_| BpfError::OutOfMemory)?,
                 )
             }
+            4 => {
+                // Perf event array - one ring per CPU, max_entries 0 means all CPUs
+                let cpus = match max_entries {
+                    0 => crate::mcore::cpu_count() as u32,
+                    n => n,
+                };
+                let map = Arc::new(
+                    PerfEventArrayMap::<ActiveProfile>::new(cpus)
+                        .map_err(|_| BpfError::OutOfMemory)?,
+                );
+                perf::register_files(id, &map);
+                map
+            }
+            7 => {
+                // Stack trace map - value_size is 8 bytes per frame
+                Arc::new(
+                    StackTraceMap::<ActiveProfile>::new(value_size, max_entries)
+                        .map_err(|_| BpfError::OutOfMemory)?,
+                )
+            }
             27 => {
                 // Ring buffer map - max_entries is the buffer size (must be power of 2)
-                Box::new(
+                Arc::new(
                     RingBufMap::<ActiveProfile>::new(max_entries as usize)
                         .map_err(|_| BpfError::OutOfMemory)?,
                 )
             }
+            100 => {
+                // Time-series map - max_entries is the number of samples kept
+                Arc::new(
+                    TimeSeriesMap::<ActiveProfile>::new(value_size, max_entries)
+                        .map_err(|_| BpfError::OutOfMemory)?,
+                )
+            }
             _ => {
                 log::warn!("Unsupported map type: {}", map_type);
                 return Err(BpfError::InvalidInstruction);
             }
         };
 
-        let id = self.maps.len() as u32;
         self.maps.push(map);
+        self.publish();
         log::info!(
             "Created map id={} type={} key_size={} value_size={} max_entries={}",
             id,
@@ -174,19 +332,13 @@ impl BpfManager {
         Ok(id)
     }
 
-    pub fn map_lookup(&self, map_id: u32, key: &[u8]) -> Option<Vec<u8>> {
-        self.maps.get(map_id as usize)?.lookup(key)
-    }
+    // Maps take spin locks that programs run from interrupts take too, so
+    // every map operation runs with interrupts masked to keep an interrupt
+    // on this CPU from spinning on a lock it interrupted.
 
-    /// Look up a value by key and return a raw pointer.
-    ///
-    /// # Safety
-    /// The caller must ensure the map will not be resized or deleted while the
-    /// pointer is in use. The returned pointer is only valid while the map lock
-    /// is held by the caller.
-    pub unsafe fn map_lookup_ptr(&self, map_id: u32, key: &[u8]) -> Option<*mut u8> {
-        // SAFETY: caller ensures map will not be resized or deleted while pointer is in use
-        unsafe { self.maps.get(map_id as usize)?.lookup_ptr(key) }
+    pub fn map_lookup(&self, map_id: u32, key: &[u8]) -> Option<Vec<u8>> {
+        let map = self.maps.get(map_id as usize)?;
+        crate::arch::without_interrupts(|| map.lookup(key))
     }
 
     pub fn map_update(
@@ -197,28 +349,38 @@ impl BpfManager {
         flags: u64,
     ) -> Result<(), BpfError> {
         let map = self.maps.get(map_id as usize).ok_or(BpfError::NotLoaded)?;
-        map.update(key, value, flags)
+        crate::arch::without_interrupts(|| map.update(key, value, flags))
             .map_err(|_| BpfError::OutOfMemory)
     }
 
     pub fn map_delete(&self, map_id: u32, key: &[u8]) -> Result<(), BpfError> {
         let map = self.maps.get(map_id as usize).ok_or(BpfError::NotLoaded)?;
-        map.delete(key).map_err(|_| BpfError::NotLoaded)
+        crate::arch::without_interrupts(|| map.delete(key)).map_err(|_| BpfError::NotLoaded)
     }
 
-    pub fn get_map_def(&self, map_id: u32) -> Option<&kernel_bpf::maps::MapDef> {
-        self.maps.get(map_id as usize).map(|m| m.def())
+    /// Get the key following `key`, or the first key without one.
+    ///
+    /// Returns `None` after the last key and for maps that cannot be
+    /// iterated.
+    pub fn map_get_next_key(&self, map_id: u32, key: Option<&[u8]>) -> Option<Vec<u8>> {
+        let map = self.maps.get(map_id as usize)?;
+        crate::arch::without_interrupts(|| map.get_next_key(key)).ok()
     }
 
-    /// Output data to a ring buffer map.
-    ///
-    /// This is used by the bpf_ringbuf_output helper. For ringbuf maps,
-    /// the key is ignored and value is the event data.
-    pub fn ringbuf_output(&self, map_id: u32, data: &[u8], flags: u64) -> Result<(), BpfError> {
+    /// Get the samples of a time-series map taken within `[start_ns, end_ns]`,
+    /// oldest first.
+    pub fn map_query_window(
+        &self,
+        map_id: u32,
+        start_ns: u64,
+        end_ns: u64,
+    ) -> Result<Vec<(u64, Vec<u8>)>, BpfError> {
         let map = self.maps.get(map_id as usize).ok_or(BpfError::NotLoaded)?;
+        crate::arch::without_interrupts(|| map.samples_in_window(start_ns, end_ns))
+            .map_err(|_| BpfError::NotLoaded)
+    }
 
-        // Ring buffer maps use update() with empty key to output data
-        map.update(&[], data, flags)
-            .map_err(|_| BpfError::OutOfMemory)
+    pub fn get_map_def(&self, map_id: u32) -> Option<&kernel_bpf::maps::MapDef> {
+        self.maps.get(map_id as usize).map(|m| m.def())
     }
 }
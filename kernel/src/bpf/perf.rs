@@ -0,0 +1,70 @@
+//! Device files for perf event array rings.
+//!
+//! Every CPU slot of a perf event array gets a file `/dev/perf<map>.<cpu>`.
+//! Mapping the file with `MAP_SHARED` allocates the ring of that CPU (one
+//! metadata page plus a power-of-two number of data pages, as on Linux) and
+//! maps the kernel memory straight into the caller, so samples written by
+//! `bpf_perf_event_output` need no copy. Reading the file returns the same
+//! bytes for consumers that do not map it.
+
+use alloc::format;
+use alloc::sync::Arc;
+
+use kernel_bpf::maps::{MapError, PerfEventArrayMap};
+use kernel_bpf::profile::ActiveProfile;
+use kernel_devfs::DevFile;
+use kernel_vfs::path::AbsoluteOwnedPath;
+use kernel_vfs::{MmapError, ReadError, Stat, StatError, WriteError};
+
+use crate::file::devfs::devfs;
+
+/// Register the ring files of every CPU of a new perf event array.
+pub fn register_files(map_id: u32, map: &Arc<PerfEventArrayMap<ActiveProfile>>) {
+    for cpu in 0..map.cpus() {
+        let path = AbsoluteOwnedPath::try_from(format!("/perf{map_id}.{cpu}").as_ref()).unwrap();
+        let map = map.clone();
+        if let Err(e) = devfs().write().register_file(path.as_ref(), move || {
+            Ok(PerfFile {
+                map: map.clone(),
+                cpu,
+            })
+        }) {
+            log::warn!("perf{}.{}: no device file ({})", map_id, cpu, e);
+        }
+    }
+}
+
+struct PerfFile {
+    map: Arc<PerfEventArrayMap<ActiveProfile>>,
+    cpu: u32,
+}
+
+impl DevFile for PerfFile {
+    fn read(&mut self, buf: &mut [u8], offset: usize) -> Result<usize, ReadError> {
+        let ring = self.map.ring(self.cpu).ok_or(ReadError::EndOfFile)?;
+        match ring.read(offset, buf) {
+            0 if !buf.is_empty() => Err(ReadError::EndOfFile),
+            n => Ok(n),
+        }
+    }
+
+    fn write(&mut self, _: &[u8], _: usize) -> Result<usize, WriteError> {
+        Err(WriteError::NotWritable)
+    }
+
+    fn stat(&mut self, stat: &mut Stat) -> Result<(), StatError> {
+        stat.size = self.map.ring(self.cpu).map_or(0, |ring| ring.mmap_size());
+        Ok(())
+    }
+
+    fn mmap(&mut self, offset: usize, len: usize) -> Result<usize, MmapError> {
+        if offset != 0 {
+            return Err(MmapError::InvalidRange);
+        }
+        let ring = self.map.open(self.cpu, len).map_err(|e| match e {
+            MapError::OutOfMemory => MmapError::OutOfMemory,
+            _ => MmapError::InvalidRange,
+        })?;
+        Ok(ring.as_ptr() as usize)
+    }
+}
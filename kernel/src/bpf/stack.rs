@@ -0,0 +1,123 @@
+//! Call stack capture for `bpf_get_stackid` and `bpf_get_stack`
+//!
+//! Hooks that interrupt code, such as kprobes, publish the interrupted
+//! registers for the programs they run with [`with_regs`]. Stacks are walked
+//! from those registers. Without them, the kernel stack is walked from the
+//! helper itself and there is no user stack.
+//!
+//! Kernel frames are followed with the backtrace unwinder. User frames are
+//! followed through frame pointers as well, but a frame is only read if it
+//! lies within the current task's user stack, so a corrupt chain ends the
+//! walk instead of faulting.
+
+use core::ptr;
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::Ordering::Relaxed;
+
+use kernel_bpf::attach::PtRegs;
+
+use crate::backtrace::ReturnAddressIterator;
+use crate::mcore::context::ExecutionContext;
+
+/// Per-CPU slots, indexed by CPU id modulo this
+const CPU_SLOTS: usize = 64;
+
+static REGS: [AtomicPtr<PtRegs>; CPU_SLOTS] =
+    [const { AtomicPtr::new(ptr::null_mut()) }; CPU_SLOTS];
+
+fn slot() -> &'static AtomicPtr<PtRegs> {
+    let cpu = ExecutionContext::try_load().map_or(0, |ctx| ctx.cpu_id());
+    &REGS[cpu % CPU_SLOTS]
+}
+
+/// Run `f` with `regs` published as the interrupted registers of this CPU.
+///
+/// Must be called with interrupts disabled, as from an interrupt handler.
+pub fn with_regs<R>(regs: &PtRegs, f: impl FnOnce() -> R) -> R {
+    let slot = slot();
+    let prev = slot.swap(ptr::from_ref(regs).cast_mut(), Relaxed);
+    let result = f();
+    slot.store(prev, Relaxed);
+    result
+}
+
+/// Capture the kernel stack, or with `user` the user stack, into `ips`,
+/// innermost frame first and without the first `skip` frames.
+///
+/// Returns the number of frames written.
+pub fn capture(user: bool, skip: usize, ips: &mut [u64]) -> usize {
+    // SAFETY: The slot only holds registers while `with_regs` borrows them,
+    // and this CPU is inside `with_regs` until the helper returns.
+    let regs = unsafe { slot().load(Relaxed).as_ref() };
+
+    match (user, regs) {
+        (false, Some(regs)) if regs.user_mode() => 0,
+        (false, Some(regs)) => {
+            // SAFETY: The registers are those of interrupted kernel code,
+            // whose stack outlives the programs run from the interrupt.
+            let frames = unsafe { ReturnAddressIterator::from_frame_pointer(regs.fp() as usize) };
+            fill(
+                core::iter::once(regs.ip()).chain(frames.map(|ip| ip as u64)),
+                skip,
+                ips,
+            )
+        }
+        (false, None) => fill(ReturnAddressIterator::new().map(|ip| ip as u64), skip, ips),
+        (true, Some(regs)) if regs.user_mode() => {
+            let task = ExecutionContext::load().current_task();
+            let Some(ustack) = task.ustack().try_read() else {
+                return 0;
+            };
+            let Some(ustack) = ustack.as_ref() else {
+                return 0;
+            };
+            let frames = UserFrames {
+                fp: regs.fp(),
+                bottom: ustack.start().as_u64(),
+                top: ustack.start().as_u64() + ustack.len() as u64,
+            };
+            fill(core::iter::once(regs.ip()).chain(frames), skip, ips)
+        }
+        (true, _) => 0,
+    }
+}
+
+fn fill(frames: impl Iterator<Item = u64>, skip: usize, ips: &mut [u64]) -> usize {
+    let mut n = 0;
+    for (slot, ip) in ips.iter_mut().zip(frames.skip(skip)) {
+        *slot = ip;
+        n += 1;
+    }
+    n
+}
+
+/// Walks the frame pointer chain of a user stack, yielding return addresses.
+struct UserFrames {
+    fp: u64,
+    /// Bounds of the user stack
+    bottom: u64,
+    top: u64,
+}
+
+impl Iterator for UserFrames {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let fp = self.fp;
+        if fp < self.bottom || fp.saturating_add(16) > self.top || !fp.is_multiple_of(8) {
+            return None;
+        }
+
+        // SAFETY: The frame lies within the user stack of the current task,
+        // which is mapped in the current address space for as long as the
+        // task lives.
+        let (next_fp, ip) = unsafe {
+            let frame = fp as *const u64;
+            (frame.read_volatile(), frame.add(1).read_volatile())
+        };
+
+        // A chain that does not move up the stack is corrupt
+        self.fp = if next_fp > fp { next_fp } else { 0 };
+        (ip != 0).then_some(ip)
+    }
+}
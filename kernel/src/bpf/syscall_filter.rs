@@ -0,0 +1,158 @@
+//! Per-process syscall filters
+//!
+//! A process restricts the syscalls it may make by installing filter
+//! programs with `BPF_PROG_ATTACH` and [`ATTACH_TYPE_SYSCALL_FILTER`]. Before
+//! a syscall runs, every filter of the calling process receives its
+//! [`SyscallTraceContext`] and returns a `SYSCALL_FILTER_*` verdict from
+//! [`kernel_abi`]; the verdict with the lowest action is enforced.
+//!
+//! Like seccomp, filters form a chain: installing one extends the chain of
+//! the calling process, and spawned children start with their parent's
+//! chain. Filters are never removed, so a process and its descendants can
+//! only lose syscalls.
+//!
+//! [`ATTACH_TYPE_SYSCALL_FILTER`]: super::ATTACH_TYPE_SYSCALL_FILTER
+
+use alloc::sync::Arc;
+
+use kernel_abi::{
+    EINVAL, EOPNOTSUPP, Errno, SYSCALL_FILTER_ACTION, SYSCALL_FILTER_ALLOW, SYSCALL_FILTER_DATA,
+    SYSCALL_FILTER_ERRNO, SYSCALL_FILTER_KILL, SYSCALL_FILTER_TRACE, syscall_filter_errno,
+};
+use kernel_bpf::attach::SyscallFilterEvent;
+use kernel_bpf::bytecode::program::{BpfProgType, BpfProgram};
+use kernel_bpf::execution::{BpfContext, SyscallTraceContext};
+use kernel_bpf::profile::ActiveProfile;
+
+use super::{hooks, tracepoint};
+use crate::BPF_MANAGER;
+use crate::mcore::context::ExecutionContext;
+
+/// Exit code of a process killed by its filters, as a shell would report
+/// death by `SIGSYS`
+pub const KILL_EXIT_CODE: i32 = 128 + 31;
+
+/// What to do with a syscall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Run it
+    Allow,
+    /// Fail with the errno without running it
+    Errno(Errno),
+    /// Kill the calling process
+    Kill,
+}
+
+/// A filter program and the filters installed before it
+pub struct SyscallFilter {
+    prog_id: u32,
+    program: Arc<BpfProgram<ActiveProfile>>,
+    prev: Option<Arc<SyscallFilter>>,
+}
+
+impl SyscallFilter {
+    /// Run every filter of the chain, newest first, and return the verdict
+    /// with the lowest action.
+    ///
+    /// Filters that fault or return an unknown action kill the process.
+    fn evaluate(&self, ctx: &SyscallTraceContext) -> u32 {
+        // SAFETY: SyscallTraceContext is plain data; the slice only lives
+        // while the filters run.
+        let slice = unsafe {
+            core::slice::from_raw_parts(
+                ctx as *const _ as *const u8,
+                core::mem::size_of::<SyscallTraceContext>(),
+            )
+        };
+        let bpf_ctx = BpfContext::from_slice(slice);
+
+        let mut verdict = SYSCALL_FILTER_ALLOW;
+        let mut filter = Some(self);
+        while let Some(f) = filter {
+            let ret = match hooks::run(&f.program, &bpf_ctx) {
+                Ok(ret) => ret as u32,
+                Err(fault) => {
+                    log::error!("syscall filter [id={}] failed: {}", f.prog_id, fault);
+                    SYSCALL_FILTER_KILL
+                }
+            };
+            let ret = match ret & SYSCALL_FILTER_ACTION {
+                SYSCALL_FILTER_KILL | SYSCALL_FILTER_ERRNO | SYSCALL_FILTER_TRACE
+                | SYSCALL_FILTER_ALLOW => ret,
+                _ => SYSCALL_FILTER_KILL,
+            };
+            if ret & SYSCALL_FILTER_ACTION < verdict & SYSCALL_FILTER_ACTION {
+                verdict = ret;
+            }
+            filter = f.prev.as_deref();
+        }
+        verdict
+    }
+}
+
+/// Install program `prog_id` as a syscall filter of the calling process.
+///
+/// Only untyped programs and `SyscallFilter` programs are accepted. Fails
+/// with `EOPNOTSUPP` where a killed process cannot be terminated yet.
+pub fn install(prog_id: u32) -> Result<(), Errno> {
+    if cfg!(not(any(target_arch = "x86_64", target_arch = "aarch64"))) {
+        return Err(EOPNOTSUPP);
+    }
+
+    let program = BPF_MANAGER
+        .get()
+        .and_then(|manager| manager.lock().program(prog_id))
+        .ok_or(EINVAL)?;
+    if !matches!(
+        program.prog_type(),
+        BpfProgType::Unspec | BpfProgType::SyscallFilter
+    ) {
+        return Err(EINVAL);
+    }
+
+    let process = ExecutionContext::load().current_process();
+    let mut chain = process.syscall_filter().write();
+    let prev = chain.take();
+    *chain = Some(Arc::new(SyscallFilter {
+        prog_id,
+        program,
+        prev,
+    }));
+    log::info!(
+        "process {} installed syscall filter {}",
+        process.pid(),
+        prog_id
+    );
+    Ok(())
+}
+
+/// Decide what to do with the syscall `ctx` describes, made by the current
+/// process.
+pub fn check(ctx: &SyscallTraceContext) -> Verdict {
+    let Some(process) = ExecutionContext::try_load().map(|cx| cx.current_process()) else {
+        return Verdict::Allow;
+    };
+    // Run the filters without holding the lock, so a filter being installed
+    // concurrently does not stall the syscall
+    let Some(chain) = process.syscall_filter().read().clone() else {
+        return Verdict::Allow;
+    };
+
+    let verdict = chain.evaluate(ctx);
+    let data = verdict & SYSCALL_FILTER_DATA;
+    match verdict & SYSCALL_FILTER_ACTION {
+        SYSCALL_FILTER_ALLOW => Verdict::Allow,
+        SYSCALL_FILTER_TRACE => {
+            tracepoint::SYSCALL_FILTER_TRACE.emit(|| SyscallFilterEvent {
+                timestamp: crate::time::get_kernel_time_ns(),
+                pid: process.pid().as_u64(),
+                syscall_nr: ctx.syscall_nr,
+                data,
+                _reserved: 0,
+            });
+            Verdict::Allow
+        }
+        SYSCALL_FILTER_ERRNO => Verdict::Errno(syscall_filter_errno(verdict)),
+        _ => Verdict::Kill,
+    }
+}
@@ -0,0 +1,195 @@
+//! Static kernel tracepoints
+//!
+//! A tracepoint marks a place in the kernel where BPF programs can observe
+//! what it is doing. Each one is declared once in the registry below with a
+//! `category:name` and the context struct its programs receive, and hit with
+//! [`Tracepoint::emit`]:
+//!
+//! ```ignore
+//! tracepoint::TASK_EXIT.emit(|| TaskEvent { timestamp, pid, tid });
+//! ```
+//!
+//! A tracepoint is enabled while programs are attached to it, which
+//! [`hooks`](super::hooks) updates on every publish. Hitting a disabled
+//! tracepoint costs a relaxed load and a branch to a cold function, which the
+//! compiler lays out as not taken; the context is not even built.
+
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use kernel_bpf::attach::{
+    AttachEvent, BlockRqEvent, IrqEvent, PageFaultEvent, SchedSwitchEvent, SyscallFilterEvent,
+    TaskEvent, VFS_PATH_LEN, VfsIoEvent, VfsOpenEvent,
+};
+use kernel_bpf::execution::BpfContext;
+
+use crate::mcore::context::ExecutionContext;
+
+/// Name and state of a tracepoint, independent of its context type
+pub struct TracepointDesc {
+    id: u32,
+    category: &'static str,
+    name: &'static str,
+    enabled: AtomicBool,
+}
+
+impl TracepointDesc {
+    /// Number carried by `AttachEvent::Tracepoint`, the index in the registry
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn category(&self) -> &'static str {
+        self.category
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether programs are attached
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+}
+
+/// A tracepoint whose programs receive a `T`
+///
+/// `T` must be a `repr(C)` struct of plain data, as programs read it as
+/// bytes.
+pub struct Tracepoint<T> {
+    desc: TracepointDesc,
+    _ctx: PhantomData<fn(&T)>,
+}
+
+impl<T: Copy> Tracepoint<T> {
+    const fn new(id: u32, category: &'static str, name: &'static str) -> Self {
+        Self {
+            desc: TracepointDesc {
+                id,
+                category,
+                name,
+                enabled: AtomicBool::new(false),
+            },
+            _ctx: PhantomData,
+        }
+    }
+
+    /// Whether programs are attached, for call sites that must prepare
+    /// before the tracepoint is hit (e.g. take a start time).
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.desc.is_enabled()
+    }
+
+    /// Hit the tracepoint, running the attached programs on the context
+    /// `ctx` builds. `ctx` is only called if programs are attached.
+    ///
+    /// Safe to call from interrupt handlers.
+    #[inline(always)]
+    pub fn emit(&self, ctx: impl FnOnce() -> T) {
+        if self.desc.is_enabled() {
+            self.run(ctx);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn run(&self, ctx: impl FnOnce() -> T) {
+        let ctx = ctx();
+        // SAFETY: T is plain data (see the type docs); the slice only lives
+        // while the programs run.
+        let slice = unsafe {
+            core::slice::from_raw_parts(&ctx as *const T as *const u8, core::mem::size_of::<T>())
+        };
+        super::hooks::execute_hooks(
+            AttachEvent::Tracepoint { id: self.desc.id },
+            &BpfContext::from_slice(slice),
+        );
+    }
+}
+
+/// Declare the kernel's tracepoints, numbered in order, and the registry
+/// listing them.
+macro_rules! tracepoints {
+    ($($(#[$attr:meta])* $ident:ident = $category:literal : $name:literal => $ctx:ty;)*) => {
+        tracepoints!(@define 0; $($(#[$attr])* $ident = $category : $name => $ctx;)*);
+
+        /// Every tracepoint, indexed by id
+        static REGISTRY: &[&TracepointDesc] = &[$(&$ident.desc),*];
+    };
+    (@define $id:expr;) => {};
+    (@define $id:expr; $(#[$attr:meta])* $ident:ident = $category:literal : $name:literal => $ctx:ty; $($rest:tt)*) => {
+        $(#[$attr])*
+        pub static $ident: Tracepoint<$ctx> = Tracepoint::new($id, $category, $name);
+
+        tracepoints!(@define $id + 1; $($rest)*);
+    };
+}
+
+tracepoints! {
+    /// The CPU switched tasks
+    SCHED_SWITCH = "sched":"sched_switch" => SchedSwitchEvent;
+    /// A task was created
+    TASK_CREATE = "sched":"task_create" => TaskEvent;
+    /// A task is exiting
+    TASK_EXIT = "sched":"task_exit" => TaskEvent;
+    /// A page fault is being handled
+    PAGE_FAULT = "mm":"page_fault" => PageFaultEvent;
+    /// A process opened a file
+    VFS_OPEN = "vfs":"vfs_open" => VfsOpenEvent;
+    /// A process read from a file
+    VFS_READ = "vfs":"vfs_read" => VfsIoEvent;
+    /// A process wrote to a file
+    VFS_WRITE = "vfs":"vfs_write" => VfsIoEvent;
+    /// A request was issued to a block device
+    BLOCK_RQ_ISSUE = "block":"block_rq_issue" => BlockRqEvent;
+    /// A block device request completed
+    BLOCK_RQ_COMPLETE = "block":"block_rq_complete" => BlockRqEvent;
+    /// An interrupt handler started
+    IRQ_ENTRY = "irq":"irq_entry" => IrqEvent;
+    /// An interrupt handler finished
+    IRQ_EXIT = "irq":"irq_exit" => IrqEvent;
+    /// A syscall filter returned a trace verdict
+    SYSCALL_FILTER_TRACE = "syscalls":"filter_trace" => SyscallFilterEvent;
+}
+
+/// Find a tracepoint by category and name.
+pub fn lookup(category: &str, name: &str) -> Option<&'static TracepointDesc> {
+    REGISTRY
+        .iter()
+        .copied()
+        .find(|tp| tp.category == category && tp.name == name)
+}
+
+/// Iterate over all tracepoints in id order.
+pub fn all() -> impl Iterator<Item = &'static TracepointDesc> {
+    REGISTRY.iter().copied()
+}
+
+/// Enable exactly the tracepoints `attached` reports programs for.
+pub(super) fn update(attached: impl Fn(u32) -> bool) {
+    for tp in REGISTRY {
+        tp.enabled.store(attached(tp.id), Ordering::Relaxed);
+    }
+}
+
+/// CPU hitting a tracepoint, 0 before per-CPU state is set up
+pub fn cpu() -> u32 {
+    ExecutionContext::try_load().map_or(0, |ctx| ctx.cpu_id() as u32)
+}
+
+/// Build the `vfs_open` context, keeping the first `VFS_PATH_LEN` bytes of
+/// the path.
+pub fn vfs_open_event(pid: u64, fd: i32, path: &str) -> VfsOpenEvent {
+    let mut event = VfsOpenEvent {
+        timestamp: crate::time::get_kernel_time_ns(),
+        pid,
+        fd,
+        path_len: path.len() as u32,
+        path: [0; VFS_PATH_LEN],
+    };
+    let len = path.len().min(VFS_PATH_LEN);
+    event.path[..len].copy_from_slice(&path.as_bytes()[..len]);
+    event
+}
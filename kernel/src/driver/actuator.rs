@@ -0,0 +1,262 @@
+//! Actuator registry and emergency stop
+//!
+//! Outputs that move something, PWM channels driving motors and GPIO lines
+//! enabling motor drivers, are registered here together with their safe
+//! state. [`emergency_stop`] drives every registered actuator to its safe
+//! state and latches: writes to registered actuators are refused until a
+//! privileged process re-arms with [`rearm`].
+//!
+//! The stop is usable from any context a BPF hook runs in, including
+//! interrupt handlers, so it takes no locks: the registry is read through
+//! [`Rcu`] and the latch is a set of atomics. Every stop is recorded as a
+//! safety event that userspace reads with `SYS_ESTOP_STATUS`.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
+
+use kernel_abi::{SAFETY_ACTION_MOTOR_STOP, SAFETY_EMERGENCY_STOP, SAFETY_EVENT_TYPE, SafetyEvent};
+use spin::Mutex;
+use thiserror::Error;
+
+use super::gpio;
+use crate::rcu::Rcu;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum ActuatorError {
+    #[error("emergency stop is active")]
+    Stopped,
+    #[error("no such output")]
+    NoSuchOutput,
+    #[error("output is already registered")]
+    AlreadyRegistered,
+    #[error("invalid safe state")]
+    InvalidSafeState,
+}
+
+/// An output that can move something
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// PWM channel of a PWM controller
+    Pwm { controller: u32, channel: u32 },
+    /// GPIO line, typically the enable input of a motor driver
+    Gpio { chip: u32, line: u32 },
+}
+
+/// A registered actuator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Actuator {
+    pub output: Output,
+    /// Duty cycle in percent for PWM outputs, where 0 also disables the
+    /// channel, and the level for GPIO outputs
+    pub safe_state: u32,
+}
+
+impl Actuator {
+    /// Drive the output to its safe state. Never blocks.
+    fn make_safe(&self) -> bool {
+        match self.output {
+            Output::Pwm {
+                controller,
+                channel,
+            } => force_pwm(controller, channel, self.safe_state),
+            Output::Gpio { chip, line } => {
+                match gpio::chip(chip).filter(|c| line < c.num_lines()) {
+                    Some(chip) => {
+                        chip.configure_output(line, self.safe_state != 0);
+                        true
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
+fn force_pwm(controller: u32, channel: u32, percent: u32) -> bool {
+    crate::arch::aarch64::platform::rpi5::pwm::Rp1Pwm::force_duty(controller, channel, percent)
+}
+
+#[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
+fn force_pwm(_controller: u32, _channel: u32, _percent: u32) -> bool {
+    false
+}
+
+/// Registered actuators, as read by the stop path
+static ACTUATORS: Rcu<Vec<Actuator>> = Rcu::new();
+
+/// Serializes registration
+static REGISTRY: Mutex<Vec<Actuator>> = Mutex::new(Vec::new());
+
+/// Set while an emergency stop is latched
+static STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// Safety event of the latched stop
+static STOP_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+static STOP_SOURCE: AtomicU32 = AtomicU32::new(0);
+static STOP_REASON: AtomicI32 = AtomicI32::new(0);
+static STOP_CPU: AtomicU32 = AtomicU32::new(0);
+static STOP_PID: AtomicU32 = AtomicU32::new(0);
+
+/// Register an actuator.
+///
+/// The output is driven to its safe state right away if a stop is latched.
+pub fn register(actuator: Actuator) -> Result<(), ActuatorError> {
+    match actuator.output {
+        Output::Pwm { .. } if actuator.safe_state > 100 => {
+            return Err(ActuatorError::InvalidSafeState);
+        }
+        Output::Gpio { .. } if actuator.safe_state > 1 => {
+            return Err(ActuatorError::InvalidSafeState);
+        }
+        _ => {}
+    }
+
+    let mut registry = REGISTRY.lock();
+    if registry.iter().any(|a| a.output == actuator.output) {
+        return Err(ActuatorError::AlreadyRegistered);
+    }
+    // Checks that the output exists, and keeps a latched stop in force
+    if is_stopped() {
+        if !actuator.make_safe() {
+            return Err(ActuatorError::NoSuchOutput);
+        }
+    } else if !exists(actuator.output) {
+        return Err(ActuatorError::NoSuchOutput);
+    }
+
+    registry.push(actuator);
+    ACTUATORS.publish(Box::new(registry.clone()));
+    log::info!(
+        "Registered actuator {:?} (safe state {})",
+        actuator.output,
+        actuator.safe_state
+    );
+    Ok(())
+}
+
+fn exists(output: Output) -> bool {
+    match output {
+        Output::Pwm {
+            controller,
+            channel,
+        } => {
+            cfg!(all(target_arch = "aarch64", feature = "rpi5"))
+                && controller < 2
+                && (1..=2).contains(&channel)
+        }
+        Output::Gpio { chip, line } => gpio::chip(chip).is_some_and(|c| line < c.num_lines()),
+    }
+}
+
+/// Whether an emergency stop is latched
+pub fn is_stopped() -> bool {
+    STOPPED.load(Ordering::Acquire)
+}
+
+/// Drive every registered actuator to its safe state and latch.
+///
+/// `source` and `reason` are recorded in the safety event of the first stop
+/// since the last re-arm. Never blocks, so it is safe to call from interrupt
+/// handlers and BPF helpers.
+pub fn emergency_stop(source: u32, reason: i32) {
+    let first = !STOPPED.swap(true, Ordering::AcqRel);
+    if first {
+        STOP_TIMESTAMP.store(crate::time::get_kernel_time_ns(), Ordering::Relaxed);
+        STOP_SOURCE.store(source, Ordering::Relaxed);
+        STOP_REASON.store(reason, Ordering::Relaxed);
+        let (cpu, pid) = current_cpu_and_pid();
+        STOP_CPU.store(cpu, Ordering::Relaxed);
+        STOP_PID.store(pid, Ordering::Relaxed);
+    }
+
+    // Repeated stops re-apply the safe states in case something raced
+    make_all_safe();
+
+    if first {
+        log::error!(
+            "EMERGENCY STOP (source={}, reason={}): all actuators in safe state",
+            source,
+            reason
+        );
+    }
+}
+
+fn make_all_safe() {
+    if let Some(actuators) = ACTUATORS.read() {
+        for actuator in actuators.iter() {
+            if !actuator.make_safe() {
+                log::error!("Actuator {:?} could not be made safe", actuator.output);
+            }
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn current_cpu_and_pid() -> (u32, u32) {
+    use crate::mcore::context::ExecutionContext;
+
+    match ExecutionContext::try_load() {
+        Some(ctx) => (ctx.cpu_id() as u32, ctx.pid().as_u64() as u32),
+        None => (0, 0),
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn current_cpu_and_pid() -> (u32, u32) {
+    (0, 0)
+}
+
+/// Release a latched emergency stop.
+///
+/// Actuators stay in their safe state until they are written again. Returns
+/// `false` if no stop was latched.
+pub fn rearm() -> bool {
+    let was_stopped = STOPPED.swap(false, Ordering::AcqRel);
+    if was_stopped {
+        log::warn!("Emergency stop re-armed");
+    }
+    was_stopped
+}
+
+/// Get the safety event of the latched stop, if any.
+pub fn status() -> Option<SafetyEvent> {
+    if !is_stopped() {
+        return None;
+    }
+    Some(SafetyEvent {
+        timestamp_ns: STOP_TIMESTAMP.load(Ordering::Relaxed),
+        event_type: SAFETY_EVENT_TYPE,
+        cpu: STOP_CPU.load(Ordering::Relaxed),
+        pid: STOP_PID.load(Ordering::Relaxed),
+        _reserved: 0,
+        safety_type: SAFETY_EMERGENCY_STOP,
+        source_id: STOP_SOURCE.load(Ordering::Relaxed),
+        value: STOP_REASON.load(Ordering::Relaxed),
+        action: SAFETY_ACTION_MOTOR_STOP,
+    })
+}
+
+/// Write to an output unless it is a registered actuator and a stop is
+/// latched.
+///
+/// A stop can land while `write` runs; the safe state is then re-applied
+/// afterwards, so the write cannot undo the stop.
+pub fn write_guarded<R>(output: Output, write: impl FnOnce() -> R) -> Result<R, ActuatorError> {
+    let registered = || {
+        ACTUATORS
+            .read()
+            .is_some_and(|actuators| actuators.iter().any(|a| a.output == output))
+    };
+
+    if is_stopped() && registered() {
+        return Err(ActuatorError::Stopped);
+    }
+    let result = write();
+    if is_stopped() && registered() {
+        make_all_safe();
+        return Err(ActuatorError::Stopped);
+    }
+    Ok(result)
+}
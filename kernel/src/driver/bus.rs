@@ -0,0 +1,344 @@
+//! I2C and SPI bus transactions
+//!
+//! I2C controllers are registered as buses `i2c0`, `i2c1`, ... and SPI
+//! controllers as `spi0`, `spi1`, ... in registration order. Every transfer
+//! on them goes through this module, whether it comes from a kernel driver
+//! holding an [`I2cAdapter`] or [`SpiDevice`], or from a userspace driver
+//! through `/dev/i2cN` or `/dev/spiN`, and is checked by the BPF programs
+//! attached to its bus:
+//!
+//! - Before anything is sent, the programs see the bytes to write. If one
+//!   returns non-zero, the transfer fails with `Rejected` and the device is
+//!   never addressed.
+//! - Once the transfer completes, the programs see the bytes read. If one
+//!   returns non-zero, the transfer fails with `Rejected` and the bytes are
+//!   zeroed, so the driver never acts on them.
+//!
+//! Programs attach by bus, target address or chip select, and register
+//! range, the register being the first byte written; a program that rejects
+//! every event without `BUS_EVENT_READ` makes a register range read-only.
+//!
+//! Userspace writes a [`BusTransfer`] header followed by the bytes to write
+//! to the device file; the next read returns the bytes read in that
+//! transfer.
+//!
+//! The buses are:
+//!
+//! - aarch64 rpi5: the header I2C1 and SPI0 controllers of the RP1
+//! - every board: a software I2C bus with an MPU-6050 model, and a
+//!   [`LoopbackSpi`] controller, so bus programs can be tested without
+//!   hardware
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use conquer_once::spin::OnceCell;
+use kernel_abi::{BUS_TRANSFER_MAX, BusTransfer};
+use kernel_bpf::attach::{AttachEvent, BusEvent};
+use kernel_bpf::execution::BpfContext;
+use kernel_devfs::DevFile;
+use kernel_vfs::path::AbsoluteOwnedPath;
+use kernel_vfs::{ReadError, Stat, StatError, WriteError};
+use spin::Mutex;
+use thiserror::Error;
+
+use super::i2c::{I2cBus, I2cError, SoftI2cBus};
+use super::mpu6050::{self, Mpu6050Model};
+use super::spi::{LoopbackSpi, SpiBus, SpiController, SpiError};
+use crate::file::devfs::devfs;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum BusError {
+    #[error("too many buses")]
+    TooManyBuses,
+}
+
+/// Number of buses of each kind that can be registered
+const MAX_BUSES: usize = 4;
+
+/// Registered I2C controllers, indexed by bus number
+///
+/// Slots are only ever filled, so transfers find a bus without taking a
+/// lock other than the controller's own.
+static I2C_BUSES: [OnceCell<Mutex<Box<dyn I2cBus>>>; MAX_BUSES] =
+    [const { OnceCell::uninit() }; MAX_BUSES];
+
+static NUM_I2C_BUSES: AtomicUsize = AtomicUsize::new(0);
+
+/// Registered SPI controllers, indexed by bus number
+static SPI_BUSES: [OnceCell<Mutex<Box<dyn SpiController>>>; MAX_BUSES] =
+    [const { OnceCell::uninit() }; MAX_BUSES];
+
+static NUM_SPI_BUSES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    I2c,
+    Spi,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::I2c => "i2c",
+            Self::Spi => "spi",
+        }
+    }
+}
+
+/// Register a controller as the next `i2cN` bus and create `/dev/i2cN`.
+/// Returns the bus number.
+pub fn register_i2c(bus: Box<dyn I2cBus>) -> Result<u32, BusError> {
+    let index = NUM_I2C_BUSES.fetch_add(1, Ordering::Relaxed);
+    let slot = I2C_BUSES.get(index).ok_or(BusError::TooManyBuses)?;
+    slot.init_once(|| Mutex::new(bus));
+    Ok(register_file(Kind::I2c, index as u32))
+}
+
+/// Register a controller as the next `spiN` bus and create `/dev/spiN`.
+/// Returns the bus number.
+pub fn register_spi(controller: Box<dyn SpiController>) -> Result<u32, BusError> {
+    let index = NUM_SPI_BUSES.fetch_add(1, Ordering::Relaxed);
+    let slot = SPI_BUSES.get(index).ok_or(BusError::TooManyBuses)?;
+    slot.init_once(|| Mutex::new(controller));
+    Ok(register_file(Kind::Spi, index as u32))
+}
+
+fn register_file(kind: Kind, bus: u32) -> u32 {
+    let name = format!("{}{}", kind.name(), bus);
+    let path = AbsoluteOwnedPath::try_from(format!("/{name}").as_ref()).unwrap();
+    if let Err(e) = devfs().write().register_file(path.as_ref(), move || {
+        Ok(BusFile {
+            kind,
+            bus,
+            read: Vec::new(),
+        })
+    }) {
+        log::warn!("{}: no device file ({})", name, e);
+    }
+
+    log::info!("Registered bus {}", name);
+    bus
+}
+
+/// Get the number of registered I2C buses.
+pub fn num_i2c_buses() -> u32 {
+    NUM_I2C_BUSES.load(Ordering::Relaxed).min(MAX_BUSES) as u32
+}
+
+/// Run the programs attached to a transaction. Returns whether one of them
+/// rejected it.
+fn rejected(kind: Kind, event: &BusEvent) -> bool {
+    // SAFETY: BusEvent is repr(C) plain data; the slice only lives while
+    // the programs run.
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            event as *const _ as *const u8,
+            core::mem::size_of::<BusEvent>(),
+        )
+    };
+    let ctx = BpfContext::from_slice(slice);
+    let event = match kind {
+        Kind::I2c => AttachEvent::I2c(event),
+        Kind::Spi => AttachEvent::Spi(event),
+    };
+    crate::bpf::hooks::veto_hooks(event, &ctx)
+}
+
+/// Run a transfer with its write and read checked by BPF programs, failing
+/// with `reject` if a program rejects either.
+fn checked<E>(
+    kind: Kind,
+    bus: u32,
+    addr: u8,
+    write: &[u8],
+    read: &mut [u8],
+    reject: E,
+    transfer: impl FnOnce(&[u8], &mut [u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    let reg = write.first().copied();
+    let addr = u32::from(addr);
+
+    if !write.is_empty() {
+        let now = crate::time::get_kernel_time_ns();
+        if rejected(kind, &BusEvent::new(now, bus, addr, reg, false, write)) {
+            return Err(reject);
+        }
+    }
+
+    transfer(write, read)?;
+
+    if !read.is_empty() {
+        let now = crate::time::get_kernel_time_ns();
+        if rejected(kind, &BusEvent::new(now, bus, addr, reg, true, read)) {
+            read.fill(0);
+            return Err(reject);
+        }
+    }
+    Ok(())
+}
+
+/// Write `write` then read into `read` from the target at `addr` on an I2C
+/// bus, checked by the programs attached to it.
+pub fn i2c_transfer(bus: u32, addr: u8, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+    let controller = I2C_BUSES
+        .get(bus as usize)
+        .and_then(OnceCell::get)
+        .ok_or(I2cError::NoSuchBus)?;
+    checked(
+        Kind::I2c,
+        bus,
+        addr,
+        write,
+        read,
+        I2cError::Rejected,
+        |write, read| controller.lock().transfer(addr, write, read),
+    )
+}
+
+/// Clock out `write` then clock in `read` with chip select `cs` on an SPI
+/// bus, checked by the programs attached to it.
+pub fn spi_transfer(bus: u32, cs: u8, write: &[u8], read: &mut [u8]) -> Result<(), SpiError> {
+    let controller = SPI_BUSES
+        .get(bus as usize)
+        .and_then(OnceCell::get)
+        .ok_or(SpiError::NoSuchBus)?;
+    checked(
+        Kind::Spi,
+        bus,
+        cs,
+        write,
+        read,
+        SpiError::Rejected,
+        |write, read| {
+            let mut controller = controller.lock();
+            if cs >= controller.chip_selects() {
+                return Err(SpiError::InvalidChipSelect);
+            }
+            controller.transfer(cs, write, read)
+        },
+    )
+}
+
+/// A registered I2C bus, for kernel drivers
+pub struct I2cAdapter {
+    bus: u32,
+}
+
+impl I2cAdapter {
+    pub fn new(bus: u32) -> Self {
+        Self { bus }
+    }
+}
+
+impl I2cBus for I2cAdapter {
+    fn transfer(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<(), I2cError> {
+        i2c_transfer(self.bus, addr, write, read)
+    }
+}
+
+/// One device on a registered SPI bus, for kernel drivers
+pub struct SpiDevice {
+    bus: u32,
+    cs: u8,
+}
+
+impl SpiDevice {
+    pub fn new(bus: u32, cs: u8) -> Self {
+        Self { bus, cs }
+    }
+}
+
+impl SpiBus for SpiDevice {
+    fn transfer(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), SpiError> {
+        spi_transfer(self.bus, self.cs, write, read)
+    }
+}
+
+/// Register the board's buses
+pub fn init() {
+    #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
+    {
+        use crate::arch::aarch64::platform::rpi5::i2c::Rp1I2c;
+        use crate::arch::aarch64::platform::rpi5::spi::Rp1Spi;
+
+        // SAFETY: This is the only driver for I2C1.
+        let i2c = unsafe { Rp1I2c::i2c1() };
+        i2c.init();
+        if let Err(e) = register_i2c(Box::new(i2c)) {
+            log::warn!("I2C1 registration failed: {}", e);
+        }
+
+        // SAFETY: This is the only driver for SPI0.
+        let spi = unsafe { Rp1Spi::spi0() };
+        spi.init();
+        if let Err(e) = register_spi(Box::new(spi)) {
+            log::warn!("SPI0 registration failed: {}", e);
+        }
+    }
+
+    let mut soft = SoftI2cBus::new();
+    soft.attach(mpu6050::DEFAULT_ADDR, Box::new(Mpu6050Model::new()));
+    if let Err(e) = register_i2c(Box::new(soft)) {
+        log::warn!("Software I2C bus registration failed: {}", e);
+    }
+    if let Err(e) = register_spi(Box::new(LoopbackSpi::new())) {
+        log::warn!("Loopback SPI registration failed: {}", e);
+    }
+}
+
+/// Size of the header of a transfer written to a bus device file
+const HEADER_SIZE: usize = core::mem::size_of::<BusTransfer>();
+
+/// `/dev/i2cN`, `/dev/spiN`: each write is one transfer, a [`BusTransfer`]
+/// header followed by the bytes to write; reads return the bytes read by
+/// the last transfer
+struct BusFile {
+    kind: Kind,
+    bus: u32,
+    /// Bytes read by the last transfer, not yet returned
+    read: Vec<u8>,
+}
+
+impl DevFile for BusFile {
+    fn read(&mut self, buf: &mut [u8], _: usize) -> Result<usize, ReadError> {
+        let len = self.read.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.read[..len]);
+        self.read.clear();
+        Ok(len)
+    }
+
+    fn write(&mut self, buf: &[u8], _: usize) -> Result<usize, WriteError> {
+        if buf.len() < HEADER_SIZE {
+            return Err(WriteError::WriteFailed);
+        }
+        // SAFETY: buf holds at least HEADER_SIZE bytes and any bit pattern
+        // is a BusTransfer; the read is unaligned because buf may be.
+        let header = unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const BusTransfer) };
+        let write = &buf[HEADER_SIZE..];
+        let addr = u8::try_from(header.addr).map_err(|_| WriteError::WriteFailed)?;
+        let read_len = header.read_len as usize;
+        if write.len() > BUS_TRANSFER_MAX || read_len > BUS_TRANSFER_MAX {
+            return Err(WriteError::WriteFailed);
+        }
+
+        let mut read = vec![0; read_len];
+        let result = match self.kind {
+            Kind::I2c => i2c_transfer(self.bus, addr, write, &mut read).is_ok(),
+            Kind::Spi => spi_transfer(self.bus, addr, write, &mut read).is_ok(),
+        };
+        if !result {
+            self.read.clear();
+            return Err(WriteError::WriteFailed);
+        }
+        self.read = read;
+        Ok(buf.len())
+    }
+
+    fn stat(&mut self, stat: &mut Stat) -> Result<(), StatError> {
+        stat.size = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+//! CAN bus subsystem
+//!
+//! Controller drivers implement [`CanController`] and are registered as
+//! interfaces `can0`, `can1`, ... in registration order. A polling task
+//! drains every controller's receive buffers; each received frame first runs
+//! the BPF programs attached to its interface and identifier, and is queued
+//! for userspace unless one of them returns non-zero.
+//!
+//! Userspace sends and receives SocketCAN-style [`CanFrame`]s, either with
+//! the `SYS_CAN_SEND`/`SYS_CAN_RECV` syscalls or by writing and reading
+//! whole frames on `/dev/canN`. BPF programs send with `bpf_can_send`.
+//!
+//! Every board gets a [`VirtualCan`] loopback interface, so CAN programs can
+//! be tested without a transceiver. The [`Mcp2515`](super::mcp2515::Mcp2515)
+//! driver talks through [`SpiBus`](super::spi::SpiBus) and is registered by
+//! boards with an SPI controller driver.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use conquer_once::spin::OnceCell;
+use kernel_abi::{
+    CAN_EFF_FLAG, CAN_EFF_MASK, CAN_ERR_FLAG, CAN_MAX_DLEN, CAN_RTR_FLAG, CAN_SFF_MASK, CanFrame,
+};
+use kernel_bpf::attach::{AttachEvent, CAN_EVENT_EXTENDED, CAN_EVENT_RTR, CanEvent};
+use kernel_bpf::execution::BpfContext;
+use kernel_devfs::DevFile;
+use kernel_vfs::path::AbsoluteOwnedPath;
+use kernel_vfs::{ReadError, Stat, StatError, WriteError};
+use spin::Mutex;
+use thiserror::Error;
+
+use super::spi::SpiError;
+use crate::file::devfs::devfs;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::process::Process;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::scheduler::global::GlobalTaskQueue;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::task::Task;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum CanError {
+    #[error("no such interface")]
+    NoSuchInterface,
+    #[error("too many interfaces")]
+    TooManyInterfaces,
+    #[error("invalid frame")]
+    InvalidFrame,
+    #[error("invalid controller configuration")]
+    InvalidConfig,
+    #[error("transmit buffer full")]
+    TxBusy,
+    #[error("controller is bus-off")]
+    BusOff,
+    #[error("interface busy")]
+    Busy,
+    #[error("spi: {0}")]
+    Spi(#[from] SpiError),
+}
+
+/// A CAN controller.
+pub trait CanController: Send {
+    /// Queue a frame for transmission. The frame has been validated.
+    fn send(&mut self, frame: &CanFrame) -> Result<(), CanError>;
+
+    /// Take the oldest received frame, if any.
+    fn recv(&mut self) -> Result<Option<CanFrame>, CanError>;
+}
+
+/// Number of interfaces that can be registered
+const MAX_INTERFACES: usize = 4;
+
+/// Frames queued for userspace per interface; the oldest are dropped when
+/// full
+const RX_QUEUE_LEN: usize = 64;
+
+struct Interface {
+    controller: Mutex<Box<dyn CanController>>,
+    /// Received frames not yet read by userspace
+    rx: Mutex<VecDeque<CanFrame>>,
+}
+
+/// Registered interfaces, indexed by interface number
+///
+/// Slots are only ever filled, so senders and the receive path find an
+/// interface without taking a lock.
+static INTERFACES: [OnceCell<Interface>; MAX_INTERFACES] =
+    [const { OnceCell::uninit() }; MAX_INTERFACES];
+
+static NUM_INTERFACES: AtomicUsize = AtomicUsize::new(0);
+
+fn interface(iface: u32) -> Result<&'static Interface, CanError> {
+    INTERFACES
+        .get(iface as usize)
+        .and_then(OnceCell::get)
+        .ok_or(CanError::NoSuchInterface)
+}
+
+/// Register a controller as the next `canN` interface and create
+/// `/dev/canN`. Returns the interface number.
+pub fn register(controller: Box<dyn CanController>) -> Result<u32, CanError> {
+    let index = NUM_INTERFACES.fetch_add(1, Ordering::Relaxed);
+    let slot = INTERFACES.get(index).ok_or(CanError::TooManyInterfaces)?;
+    slot.init_once(|| Interface {
+        controller: Mutex::new(controller),
+        rx: Mutex::new(VecDeque::with_capacity(RX_QUEUE_LEN)),
+    });
+
+    let iface = index as u32;
+    let path = AbsoluteOwnedPath::try_from(format!("/can{iface}").as_ref()).unwrap();
+    if let Err(e) = devfs()
+        .write()
+        .register_file(path.as_ref(), move || Ok(CanFile { iface }))
+    {
+        log::warn!("can{}: no device file ({})", iface, e);
+    }
+
+    log::info!("Registered CAN interface can{}", iface);
+    Ok(iface)
+}
+
+/// Check that a frame is a valid classic data or remote frame.
+fn validate(frame: &CanFrame) -> Result<(), CanError> {
+    let id_mask = if frame.can_id & CAN_EFF_FLAG != 0 {
+        CAN_EFF_MASK
+    } else {
+        CAN_SFF_MASK
+    };
+    let id = frame.can_id & !(CAN_EFF_FLAG | CAN_RTR_FLAG);
+    let error_frame = frame.can_id & CAN_ERR_FLAG != 0;
+    if error_frame || id & !id_mask != 0 || usize::from(frame.len) > CAN_MAX_DLEN {
+        return Err(CanError::InvalidFrame);
+    }
+    Ok(())
+}
+
+/// Transmit a frame on an interface.
+pub fn send(iface: u32, frame: &CanFrame) -> Result<(), CanError> {
+    validate(frame)?;
+    interface(iface)?.controller.lock().send(frame)
+}
+
+/// Transmit a frame on an interface without waiting for it.
+///
+/// Fails with [`CanError::Busy`] if the interface is in use, such as by the
+/// code a BPF program interrupted. Never blocks.
+pub fn try_send(iface: u32, frame: &CanFrame) -> Result<(), CanError> {
+    validate(frame)?;
+    interface(iface)?
+        .controller
+        .try_lock()
+        .ok_or(CanError::Busy)?
+        .send(frame)
+}
+
+/// Take the oldest received frame of an interface.
+pub fn recv(iface: u32) -> Result<Option<CanFrame>, CanError> {
+    Ok(interface(iface)?.rx.lock().pop_front())
+}
+
+/// Run the BPF programs attached to a received frame, then queue it for
+/// userspace unless a program dropped it.
+fn dispatch(iface: u32, interface: &Interface, frame: CanFrame, timestamp: u64) {
+    let mut flags = 0;
+    if frame.can_id & CAN_EFF_FLAG != 0 {
+        flags |= CAN_EVENT_EXTENDED;
+    }
+    if frame.can_id & CAN_RTR_FLAG != 0 {
+        flags |= CAN_EVENT_RTR;
+    }
+    let event = CanEvent {
+        timestamp,
+        iface,
+        can_id: frame.can_id & CAN_EFF_MASK,
+        flags,
+        dlc: u32::from(frame.len),
+        data: frame.data,
+    };
+
+    // SAFETY: CanEvent is repr(C) plain data; the slice only lives while the
+    // programs run.
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            &event as *const _ as *const u8,
+            core::mem::size_of::<CanEvent>(),
+        )
+    };
+    let ctx = BpfContext::from_slice(slice);
+    if crate::bpf::hooks::veto_hooks(AttachEvent::Can(&event), &ctx) {
+        return;
+    }
+
+    let mut rx = interface.rx.lock();
+    if rx.len() == RX_QUEUE_LEN {
+        rx.pop_front();
+    }
+    rx.push_back(frame);
+}
+
+/// Drain the receive buffers of every interface.
+fn poll() {
+    let count = NUM_INTERFACES.load(Ordering::Relaxed).min(MAX_INTERFACES);
+    for iface in 0..count as u32 {
+        let Ok(interface) = interface(iface) else {
+            continue;
+        };
+        loop {
+            // The controller is unlocked while programs run, so they can send
+            let received = interface.controller.lock().recv();
+            match received {
+                Ok(Some(frame)) => {
+                    dispatch(iface, interface, frame, crate::time::get_kernel_time_ns())
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("can{}: receive failed: {}", iface, e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// CAN receive task entry point
+///
+/// Polls once per timer tick, so a controller must buffer the frames that
+/// arrive within a tick.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+extern "C" fn can_rx_task(_arg: *mut c_void) {
+    loop {
+        poll();
+
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            core::arch::asm!("hlt")
+        };
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            core::arch::asm!("wfi")
+        };
+    }
+}
+
+/// Register the board's CAN interfaces and start receiving
+pub fn init() {
+    if let Err(e) = register(Box::new(VirtualCan::new())) {
+        log::warn!("Virtual CAN registration failed: {}", e);
+        return;
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    {
+        let task = Task::create_new(Process::root(), can_rx_task, core::ptr::null_mut())
+            .expect("failed to create CAN receive task");
+        GlobalTaskQueue::enqueue(Box::pin(task));
+
+        log::info!("Started CAN receive task");
+    }
+}
+
+/// Loopback CAN interface
+///
+/// Every frame sent is received back, like a controller in loopback mode or
+/// a Linux `vcan` device with echo.
+#[derive(Default)]
+pub struct VirtualCan {
+    frames: VecDeque<CanFrame>,
+}
+
+impl VirtualCan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CanController for VirtualCan {
+    fn send(&mut self, frame: &CanFrame) -> Result<(), CanError> {
+        if self.frames.len() == RX_QUEUE_LEN {
+            return Err(CanError::TxBusy);
+        }
+        self.frames.push_back(*frame);
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<CanFrame>, CanError> {
+        Ok(self.frames.pop_front())
+    }
+}
+
+/// `/dev/canN`: reads return whole received frames, writes send whole
+/// frames
+struct CanFile {
+    iface: u32,
+}
+
+const FRAME_SIZE: usize = core::mem::size_of::<CanFrame>();
+
+impl DevFile for CanFile {
+    fn read(&mut self, buf: &mut [u8], _: usize) -> Result<usize, ReadError> {
+        let mut read = 0;
+        for chunk in buf.as_chunks_mut::<FRAME_SIZE>().0 {
+            let Some(frame) = recv(self.iface).map_err(|_| ReadError::ReadFailed)? else {
+                break;
+            };
+            // SAFETY: CanFrame is repr(C) plain data, viewed as bytes only
+            // for the copy.
+            let bytes =
+                unsafe { core::slice::from_raw_parts(&frame as *const _ as *const u8, FRAME_SIZE) };
+            chunk.copy_from_slice(bytes);
+            read += FRAME_SIZE;
+        }
+        Ok(read)
+    }
+
+    fn write(&mut self, buf: &[u8], _: usize) -> Result<usize, WriteError> {
+        if buf.len() % FRAME_SIZE != 0 {
+            return Err(WriteError::WriteFailed);
+        }
+        for chunk in buf.as_chunks::<FRAME_SIZE>().0 {
+            // SAFETY: chunk holds FRAME_SIZE bytes and any bit pattern is a
+            // CanFrame; the read is unaligned because chunk may be.
+            let frame = unsafe { core::ptr::read_unaligned(chunk.as_ptr() as *const CanFrame) };
+            send(self.iface, &frame).map_err(|_| WriteError::WriteFailed)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn stat(&mut self, stat: &mut Stat) -> Result<(), StatError> {
+        stat.size = 0;
+        Ok(())
+    }
+}
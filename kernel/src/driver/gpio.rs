@@ -0,0 +1,164 @@
+//! GPIO controller abstraction
+//!
+//! Board GPIO drivers implement [`GpioController`] and register themselves as
+//! `gpiochip0` during interrupt setup. The BPF GPIO helpers and GPIO attach
+//! points only go through this module, so they work on any board that has a
+//! driver: the RP1 on Raspberry Pi 5 and the PL061 on QEMU virt.
+
+use alloc::collections::BTreeMap;
+
+use conquer_once::spin::OnceCell;
+use kernel_bpf::attach::{AttachEvent, AttachHardware, AttachResult, GpioEdge, GpioEvent};
+use kernel_bpf::execution::BpfContext;
+use spin::Mutex;
+
+/// A bank of GPIO lines.
+///
+/// Callers check `line < num_lines()` before calling any other method.
+pub trait GpioController: Send + Sync {
+    /// Number of lines on this controller
+    fn num_lines(&self) -> u32;
+
+    /// Read the level of a line. Returns `true` if high.
+    fn read(&self, line: u32) -> bool;
+
+    /// Drive an output line high or low.
+    fn write(&self, line: u32, high: bool);
+
+    /// Invert an output line.
+    fn toggle(&self, line: u32) {
+        self.write(line, !self.read(line));
+    }
+
+    /// Configure a line as output and set its initial level.
+    fn configure_output(&self, line: u32, initial_high: bool);
+
+    /// Configure a line as input.
+    fn configure_input(&self, line: u32);
+
+    /// Raise an interrupt on the given edges of a line, replacing any edges
+    /// enabled before.
+    fn enable_interrupt(&self, line: u32, rising: bool, falling: bool);
+
+    /// Stop raising interrupts for a line.
+    fn disable_interrupt(&self, line: u32);
+}
+
+static CHIP0: OnceCell<&'static dyn GpioController> = OnceCell::uninit();
+
+/// Register the board's GPIO controller as `gpiochip0`.
+pub fn register(chip: &'static dyn GpioController) {
+    CHIP0.init_once(|| chip);
+}
+
+/// Get a registered GPIO controller by chip number.
+pub fn chip(chip_id: u32) -> Option<&'static dyn GpioController> {
+    match chip_id {
+        0 => CHIP0.get().copied(),
+        _ => None,
+    }
+}
+
+/// Get the controller of `gpiochip0` if `line` exists on it.
+pub fn line(line: u32) -> Option<&'static dyn GpioController> {
+    chip(0).filter(|c| line < c.num_lines())
+}
+
+/// Run the BPF programs attached to an edge of a GPIO line.
+///
+/// `edge` is 1 for rising and 2 for falling, and `value` the level after the
+/// edge. Called from GPIO interrupt handlers.
+pub fn dispatch_edge(chip_id: u32, line: u32, edge: u32, value: u32, timestamp: u64) {
+    let event = GpioEvent {
+        timestamp,
+        chip_id,
+        line,
+        edge,
+        value,
+    };
+
+    // SAFETY: Transmuting struct to slice for read-only access
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            &event as *const _ as *const u8,
+            core::mem::size_of::<GpioEvent>(),
+        )
+    };
+
+    let ctx = BpfContext::from_slice(slice);
+    crate::bpf::hooks::execute_hooks(AttachEvent::Gpio(&event), &ctx);
+}
+
+/// Number of attach points using the rising and falling edge interrupts of
+/// each line, keyed by chip and line
+static EDGE_USERS: Mutex<BTreeMap<(u32, u32), [u8; 2]>> = Mutex::new(BTreeMap::new());
+
+/// Edge interrupt of one GPIO line, enabled while BPF programs are attached
+///
+/// Several attach points may share a line with different edges, so the
+/// interrupt enables are reference counted per edge.
+pub struct GpioIrq {
+    chip: &'static dyn GpioController,
+    chip_id: u32,
+    line: u32,
+    edge: GpioEdge,
+}
+
+impl GpioIrq {
+    /// Returns `None` if the chip is not registered or has no such line.
+    pub fn new(chip_id: u32, line: u32, edge: GpioEdge) -> Option<Self> {
+        let chip = chip(chip_id).filter(|c| line < c.num_lines())?;
+        Some(Self {
+            chip,
+            chip_id,
+            line,
+            edge,
+        })
+    }
+
+    fn edges(&self) -> [bool; 2] {
+        [
+            matches!(self.edge, GpioEdge::Rising | GpioEdge::Both),
+            matches!(self.edge, GpioEdge::Falling | GpioEdge::Both),
+        ]
+    }
+}
+
+impl AttachHardware for GpioIrq {
+    fn enable(&mut self) -> AttachResult<()> {
+        let mut users = EDGE_USERS.lock();
+        let counts = users.entry((self.chip_id, self.line)).or_default();
+        for (count, used) in counts.iter_mut().zip(self.edges()) {
+            *count += u8::from(used);
+        }
+
+        self.chip.configure_input(self.line);
+        self.chip
+            .enable_interrupt(self.line, counts[0] > 0, counts[1] > 0);
+        log::info!(
+            "gpiochip{} line {} interrupt enabled (rising={}, falling={})",
+            self.chip_id,
+            self.line,
+            counts[0] > 0,
+            counts[1] > 0
+        );
+        Ok(())
+    }
+
+    fn disable(&mut self) {
+        let mut users = EDGE_USERS.lock();
+        let key = (self.chip_id, self.line);
+        let counts = users.entry(key).or_default();
+        for (count, used) in counts.iter_mut().zip(self.edges()) {
+            *count -= u8::from(used);
+        }
+
+        if counts.iter().any(|&c| c > 0) {
+            self.chip
+                .enable_interrupt(self.line, counts[0] > 0, counts[1] > 0);
+        } else {
+            self.chip.disable_interrupt(self.line);
+            users.remove(&key);
+        }
+    }
+}
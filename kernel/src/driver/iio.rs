@@ -3,55 +3,188 @@
 //! This module provides the interface for IIO sensors (accelerometers, gyroscopes, etc.)
 //! and integrates them with the BPF subsystem.
 //!
-//! In the future, this will interface with actual I2C/SPI drivers.
-//! For now, it provides the mechanism to inject sensor events and trigger BPF hooks.
+//! Sensors are I2C drivers that produce timestamped, scaled [`IioEvent`]s.
+//! The on-board IMU is an MPU-6050, probed on each registered I2C bus in
+//! turn: on Raspberry Pi 5 the header I2C bus comes first, and everywhere
+//! else, or if no chip answers, the model of it on the software I2C bus
+//! stands in, so BPF programs see the same event stream in QEMU as on
+//! hardware. Its transfers go through the [bus layer](super::bus), where
+//! I2C programs can check them.
+//!
+//! The manager also keeps the latest sample of every channel, so BPF programs
+//! that are not attached to a sensor, such as timer-driven control loops, can
+//! read it with `bpf_iio_read` and `bpf_sensor_last_timestamp`.
 
-use alloc::vec::Vec;
-#[cfg(target_arch = "x86_64")]
 use alloc::boxed::Box;
-#[cfg(target_arch = "x86_64")]
+use alloc::vec::Vec;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 use core::ffi::c_void;
-use spin::Mutex;
-use conquer_once::spin::OnceCell;
+use core::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
 
+use conquer_once::spin::OnceCell;
 use kernel_bpf::attach::{IioChannel, IioEvent};
 use kernel_bpf::execution::BpfContext;
-#[cfg(target_arch = "x86_64")]
-use crate::mcore::mtask::task::Task;
-#[cfg(target_arch = "x86_64")]
+use spin::Mutex;
+
+use super::bus::{self, I2cAdapter};
+use super::i2c::I2cError;
+use super::mpu6050::{self, Mpu6050};
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 use crate::mcore::mtask::process::Process;
-#[cfg(target_arch = "x86_64")]
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 use crate::mcore::mtask::scheduler::global::GlobalTaskQueue;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::task::Task;
 
 /// Global IIO manager instance
-pub static IIO_MANAGER: OnceCell<Mutex<IioManager>> = OnceCell::uninit();
+pub static IIO_MANAGER: OnceCell<IioManager> = OnceCell::uninit();
 
 /// Initialize the IIO subsystem
 pub fn init() {
-    IIO_MANAGER.init_once(|| Mutex::new(IioManager::new()));
+    IIO_MANAGER.init_once(IioManager::new);
+}
+
+/// Number of devices whose latest samples are cached
+const MAX_CACHED_DEVICES: usize = 8;
+
+/// Number of channels cached per device
+const MAX_CACHED_CHANNELS: usize = 16;
+
+/// Marks an unused cache slot
+const NO_ID: u32 = u32::MAX;
+
+/// Marks a channel without a sample yet
+const NO_SAMPLE: i64 = i64::MIN;
+
+/// Latest value of one channel
+struct ChannelSample {
+    channel: AtomicU32,
+    /// Scaled value in millionths of the channel unit
+    value: AtomicI64,
+}
+
+/// Latest samples of one device
+///
+/// Written by the device's driver and read by BPF helpers, which may run in
+/// interrupt context, so it is made of atomics instead of being behind a
+/// lock. A reader racing with an update may see channels from two
+/// consecutive samples.
+struct SampleCache {
+    device_id: AtomicU32,
+    timestamp: AtomicU64,
+    channels: [ChannelSample; MAX_CACHED_CHANNELS],
+}
+
+impl SampleCache {
+    const fn new() -> Self {
+        Self {
+            device_id: AtomicU32::new(NO_ID),
+            timestamp: AtomicU64::new(0),
+            channels: [const {
+                ChannelSample {
+                    channel: AtomicU32::new(NO_ID),
+                    value: AtomicI64::new(NO_SAMPLE),
+                }
+            }; MAX_CACHED_CHANNELS],
+        }
+    }
+
+    fn channel(&self, channel: u32) -> Option<&ChannelSample> {
+        self.channels
+            .iter()
+            .find(|c| c.channel.load(Ordering::Relaxed) == channel)
+    }
 }
 
 /// Manages IIO devices and event dispatch
 pub struct IioManager {
-    /// Simulated devices for now
-    devices: Vec<IioDevice>,
+    /// Registered sensors
+    devices: Mutex<Vec<IioDevice>>,
+    /// Latest samples, one slot per registered device
+    samples: [SampleCache; MAX_CACHED_DEVICES],
+}
+
+impl Default for IioManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl IioManager {
     pub fn new() -> Self {
         Self {
-            devices: Vec::new(),
+            devices: Mutex::new(Vec::new()),
+            samples: [const { SampleCache::new() }; MAX_CACHED_DEVICES],
         }
     }
 
-    pub fn register_device(&mut self, device: IioDevice) {
-        self.devices.push(device);
+    pub fn register_device(&self, device: IioDevice) {
+        let mut devices = self.devices.lock();
+
+        match self
+            .samples
+            .iter()
+            .find(|s| s.device_id.load(Ordering::Relaxed) == NO_ID)
+        {
+            Some(cache) => {
+                let ids = device.channels.iter().filter_map(IioChannel::id);
+                for (slot, id) in cache.channels.iter().zip(ids) {
+                    slot.channel.store(id, Ordering::Relaxed);
+                }
+                cache.device_id.store(device.id, Ordering::Release);
+            }
+            None => ::log::warn!(
+                "IIO sample cache full, device {} can only be read through events",
+                device.id
+            ),
+        }
+
+        devices.push(device);
+    }
+
+    fn cache(&self, device_id: u32) -> Option<&SampleCache> {
+        self.samples
+            .iter()
+            .find(|s| s.device_id.load(Ordering::Acquire) == device_id)
+    }
+
+    /// Get the latest scaled value of a channel, in millionths of its unit.
+    ///
+    /// Returns `None` if the device or channel is unknown or has no sample
+    /// yet. Never blocks, so it is safe to call from BPF helpers.
+    pub fn latest(&self, device_id: u32, channel: u32) -> Option<i64> {
+        let value = self
+            .cache(device_id)?
+            .channel(channel)?
+            .value
+            .load(Ordering::Relaxed);
+        (value != NO_SAMPLE).then_some(value)
+    }
+
+    /// Get the timestamp of the latest sample of a device.
+    ///
+    /// Returns `None` if the device is unknown or has no sample yet. Never
+    /// blocks.
+    pub fn last_timestamp(&self, device_id: u32) -> Option<u64> {
+        let timestamp = self.cache(device_id)?.timestamp.load(Ordering::Acquire);
+        (timestamp != 0).then_some(timestamp)
     }
 
     /// Dispatch an IIO event to BPF hooks
     ///
-    /// This is called by hardware drivers (or simulation) when new data is available.
+    /// This is called by sensor drivers when new data is available. The
+    /// sample cache is updated first, so hooks reading other channels see
+    /// this sample too.
     pub fn dispatch_event(&self, event: IioEvent) {
+        if let Some(cache) = self.cache(event.device_id) {
+            if let Some(slot) = cache.channel(event.channel) {
+                let scaled =
+                    (i64::from(event.value) + i64::from(event.offset)) * i64::from(event.scale);
+                slot.value.store(scaled, Ordering::Relaxed);
+            }
+            cache.timestamp.store(event.timestamp, Ordering::Release);
+        }
+
         // Create BPF context from the event
         // SAFETY: We are creating a slice from a stack-allocated struct.
         // The slice is only used within this scope to create the BpfContext.
@@ -65,11 +198,7 @@ impl IioManager {
         let ctx = BpfContext::from_slice(slice);
 
         // Execute BPF hooks
-        if let Some(manager) = crate::BPF_MANAGER.get() {
-            manager
-                .lock()
-                .execute_hooks(crate::bpf::ATTACH_TYPE_IIO, &ctx);
-        }
+        crate::bpf::hooks::execute_hooks(kernel_bpf::attach::AttachEvent::Iio(&event), &ctx);
     }
 }
 
@@ -95,60 +224,101 @@ impl IioDevice {
     }
 }
 
-/// Simulation task entry point
-#[cfg(target_arch = "x86_64")]
-extern "C" fn iio_simulation_task(_arg: *mut c_void) {
-    let mut counter = 0;
-    loop {
-        // Generate simulated accelerometer data
-        let event = IioEvent {
-            timestamp: 0, // In a real system, we'd use a timer
-            device_id: 0,
-            channel: 0, // AccelX
-            value: counter,
-            scale: 1_000_000,
-            offset: 0,
-        };
+/// IIO device id of the on-board IMU
+const IMU_DEVICE_ID: u32 = 0;
+
+/// IMU sample rate
+///
+/// The sampling task sleeps in timer ticks, so rates above the 100 Hz tick
+/// rate are not reached.
+const IMU_RATE_HZ: u32 = 100;
+
+/// Scheduler timer tick rate
+const TICK_HZ: u32 = 100;
 
-        if let Some(manager_lock) = IIO_MANAGER.get() {
-            let manager = manager_lock.lock();
-            manager.dispatch_event(event);
+/// Find the IMU on the first I2C bus it answers on. Hardware buses are
+/// registered before the software one, so a real sensor is preferred over
+/// the model.
+fn probe_imu() -> Result<Mpu6050, I2cError> {
+    let mut result = Err(I2cError::NoSuchBus);
+    for index in 0..bus::num_i2c_buses() {
+        let adapter = Box::new(I2cAdapter::new(index));
+        result = Mpu6050::probe(adapter, mpu6050::DEFAULT_ADDR, IMU_DEVICE_ID);
+        match &result {
+            Ok(_) => break,
+            Err(e) => ::log::info!("No MPU-6050 on i2c{} ({})", index, e),
         }
+    }
+    result
+}
+
+/// IMU sampling task entry point
+///
+/// `arg` is a `Box<Mpu6050>` turned into a raw pointer.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+extern "C" fn imu_sampling_task(arg: *mut c_void) {
+    // SAFETY: init_sensors passes ownership of a boxed Mpu6050.
+    let mut imu = unsafe { Box::from_raw(arg as *mut Mpu6050) };
+    let ticks = (TICK_HZ / IMU_RATE_HZ).max(1);
 
-        counter = (counter + 1) % 1000;
+    loop {
+        match imu.poll(crate::time::get_kernel_time_ns()) {
+            Ok(Some(events)) => {
+                if let Some(manager) = IIO_MANAGER.get() {
+                    for event in events {
+                        manager.dispatch_event(event);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => ::log::warn!("MPU-6050 read failed: {}", e),
+        }
 
-        // Simple delay - wait for a few interrupts
-        for _ in 0..100 {
+        for _ in 0..ticks {
             #[cfg(target_arch = "x86_64")]
-            unsafe { core::arch::asm!("hlt") };
+            unsafe {
+                core::arch::asm!("hlt")
+            };
             #[cfg(target_arch = "aarch64")]
-            unsafe { core::arch::asm!("wfi") };
+            unsafe {
+                core::arch::asm!("wfi")
+            };
         }
     }
 }
 
-/// Initialize a simulated accelerometer for testing
-pub fn init_simulated_device() {
-    if let Some(manager_lock) = IIO_MANAGER.get() {
-        let mut manager = manager_lock.lock();
+/// Probe the sensors, register them as IIO devices and start sampling
+pub fn init_sensors() {
+    let Some(manager) = IIO_MANAGER.get() else {
+        return;
+    };
 
-        let mut accel = IioDevice::new(0, "simulated-accel");
-        accel.add_channel(IioChannel::AccelX);
-        accel.add_channel(IioChannel::AccelY);
-        accel.add_channel(IioChannel::AccelZ);
-
-        manager.register_device(accel);
+    let mut imu = match probe_imu() {
+        Ok(imu) => imu,
+        Err(e) => {
+            ::log::warn!("MPU-6050 probe failed: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = imu.configure(IMU_RATE_HZ) {
+        ::log::warn!("MPU-6050 configuration failed: {}", e);
+        return;
+    }
 
-        ::log::info!("Initialized simulated IIO accelerometer (id=0)");
+    manager.register_device(imu.iio_device());
+    ::log::info!(
+        "Initialized MPU-6050 IMU (id={}, {} Hz)",
+        IMU_DEVICE_ID,
+        IMU_RATE_HZ
+    );
 
-        // Spawn simulation task
-        #[cfg(target_arch = "x86_64")]
-        {
-            let task = Task::create_new(Process::root(), iio_simulation_task, core::ptr::null_mut())
-                .expect("failed to create IIO simulation task");
-            GlobalTaskQueue::enqueue(Box::pin(task));
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    {
+        let arg = Box::into_raw(Box::new(imu)) as *mut c_void;
+        let task = Task::create_new(Process::root(), imu_sampling_task, arg)
+            .expect("failed to create IMU sampling task");
+        GlobalTaskQueue::enqueue(Box::pin(task));
 
-            ::log::info!("Started IIO simulation background task");
-        }
+        ::log::info!("Started IMU sampling task");
     }
 }
@@ -0,0 +1,243 @@
+//! Microchip MCP2515 CAN Controller Driver
+//!
+//! The MCP2515 is a stand-alone classic CAN controller on SPI, found on most
+//! Raspberry Pi CAN HATs next to an MCP2551 or TJA1050 transceiver. The
+//! driver uses one transmit buffer and both receive buffers with rollover
+//! and all acceptance filters open; identifier filtering happens in the
+//! kernel, where BPF programs can see it.
+
+use alloc::boxed::Box;
+
+use kernel_abi::{CAN_EFF_FLAG, CAN_EFF_MASK, CAN_RTR_FLAG, CAN_SFF_MASK, CanFrame};
+
+use super::can::{CanController, CanError};
+use super::spi::{SpiBus, SpiError};
+
+/// SPI instructions
+mod op {
+    pub const RESET: u8 = 0xC0;
+    pub const READ: u8 = 0x03;
+    pub const WRITE: u8 = 0x02;
+    pub const BIT_MODIFY: u8 = 0x05;
+    pub const READ_STATUS: u8 = 0xA0;
+    /// Load TX buffer 0 starting at TXB0SIDH
+    pub const LOAD_TX0: u8 = 0x40;
+    /// Request to send TX buffer 0
+    pub const RTS_TX0: u8 = 0x81;
+    /// Read RX buffer 0 starting at RXB0SIDH, clears RX0IF
+    pub const READ_RX0: u8 = 0x90;
+    /// Read RX buffer 1 starting at RXB1SIDH, clears RX1IF
+    pub const READ_RX1: u8 = 0x94;
+}
+
+/// Register addresses
+mod reg {
+    pub const CANSTAT: u8 = 0x0E;
+    pub const CANCTRL: u8 = 0x0F;
+    pub const CNF3: u8 = 0x28;
+    pub const CNF2: u8 = 0x29;
+    pub const CNF1: u8 = 0x2A;
+    pub const CANINTE: u8 = 0x2B;
+    pub const EFLG: u8 = 0x2D;
+    pub const TXB0CTRL: u8 = 0x30;
+    pub const RXB0CTRL: u8 = 0x60;
+    pub const RXB1CTRL: u8 = 0x70;
+}
+
+/// Operation mode field of CANCTRL (REQOP) and CANSTAT (OPMOD)
+const MODE_MASK: u8 = 0xE0;
+const MODE_NORMAL: u8 = 0x00;
+const MODE_LOOPBACK: u8 = 0x40;
+const MODE_CONFIG: u8 = 0x80;
+
+/// Receive buffer full interrupts (CANINTE, READ_STATUS)
+const RX0IF: u8 = 1 << 0;
+const RX1IF: u8 = 1 << 1;
+
+/// Transmit request pending (TXBnCTRL)
+const TXREQ: u8 = 1 << 3;
+
+/// Transmitter in bus-off state (EFLG)
+const TXBO: u8 = 1 << 5;
+
+/// Receive any message (RXBnCTRL.RXM)
+const RXM_ANY: u8 = 0x60;
+
+/// Roll over into RXB1 when RXB0 is full (RXB0CTRL)
+const BUKT: u8 = 1 << 2;
+
+/// Extended identifier enable (SIDL)
+const EXIDE: u8 = 1 << 3;
+
+/// Standard frame remote request received (SIDL)
+const SRR: u8 = 1 << 4;
+
+/// Remote transmission request (DLC)
+const RTR: u8 = 1 << 6;
+
+/// Time quanta per bit: sync 1 + propagation 3 + phase 1 8 + phase 2 4,
+/// sampling at 75%
+const TQ_PER_BIT: u32 = 16;
+
+/// Bit timing configuration for `TQ_PER_BIT`, without the prescaler
+const CNF2_TIMING: u8 = 0x80 | (7 << 3) | 2; // BTLMODE, PHSEG1 = 8 TQ, PRSEG = 3 TQ
+const CNF3_TIMING: u8 = 3; // PHSEG2 = 4 TQ
+
+/// MCP2515 on an SPI bus
+pub struct Mcp2515 {
+    spi: Box<dyn SpiBus>,
+}
+
+impl Mcp2515 {
+    /// Reset the controller, check that it answers like an MCP2515 and start
+    /// it at `bitrate` bit/s.
+    ///
+    /// `osc_hz` is the frequency of the crystal on the board, usually 8, 16
+    /// or 20 MHz. With `loopback`, transmitted frames are received back
+    /// without touching the bus.
+    pub fn probe(
+        spi: Box<dyn SpiBus>,
+        osc_hz: u32,
+        bitrate: u32,
+        loopback: bool,
+    ) -> Result<Self, CanError> {
+        let mut can = Self { spi };
+
+        can.spi.write(&[op::RESET])?;
+        if can.read_reg(reg::CANSTAT)? & MODE_MASK != MODE_CONFIG {
+            return Err(SpiError::WrongDevice.into());
+        }
+
+        let brp = prescaler(osc_hz, bitrate).ok_or(CanError::InvalidConfig)?;
+        can.write_regs(reg::CNF3, &[CNF3_TIMING, CNF2_TIMING, brp])?;
+        can.write_regs(reg::CANINTE, &[RX0IF | RX1IF])?;
+        can.write_regs(reg::RXB0CTRL, &[RXM_ANY | BUKT])?;
+        can.write_regs(reg::RXB1CTRL, &[RXM_ANY])?;
+
+        let mode = if loopback { MODE_LOOPBACK } else { MODE_NORMAL };
+        can.modify_reg(reg::CANCTRL, MODE_MASK, mode)?;
+        if can.read_reg(reg::CANSTAT)? & MODE_MASK != mode {
+            return Err(CanError::InvalidConfig);
+        }
+
+        Ok(can)
+    }
+
+    fn read_reg(&mut self, addr: u8) -> Result<u8, SpiError> {
+        let mut value = [0];
+        self.spi.transfer(&[op::READ, addr], &mut value)?;
+        Ok(value[0])
+    }
+
+    fn write_regs(&mut self, addr: u8, values: &[u8]) -> Result<(), SpiError> {
+        let mut buf = [0; 16];
+        buf[0] = op::WRITE;
+        buf[1] = addr;
+        buf[2..2 + values.len()].copy_from_slice(values);
+        self.spi.write(&buf[..2 + values.len()])
+    }
+
+    fn modify_reg(&mut self, addr: u8, mask: u8, value: u8) -> Result<(), SpiError> {
+        self.spi.write(&[op::BIT_MODIFY, addr, mask, value])
+    }
+}
+
+impl CanController for Mcp2515 {
+    fn send(&mut self, frame: &CanFrame) -> Result<(), CanError> {
+        if self.read_reg(reg::TXB0CTRL)? & TXREQ != 0 {
+            if self.read_reg(reg::EFLG)? & TXBO != 0 {
+                return Err(CanError::BusOff);
+            }
+            return Err(CanError::TxBusy);
+        }
+
+        let mut buf = [0; 14];
+        buf[0] = op::LOAD_TX0;
+        buf[1..5].copy_from_slice(&encode_id(frame.can_id));
+        let rtr = if frame.can_id & CAN_RTR_FLAG != 0 {
+            RTR
+        } else {
+            0
+        };
+        buf[5] = frame.len | rtr;
+        buf[6..].copy_from_slice(&frame.data);
+        self.spi.write(&buf)?;
+        self.spi.write(&[op::RTS_TX0])?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<CanFrame>, CanError> {
+        let mut status = [0];
+        self.spi.transfer(&[op::READ_STATUS], &mut status)?;
+        let read = if status[0] & RX0IF != 0 {
+            op::READ_RX0
+        } else if status[0] & RX1IF != 0 {
+            op::READ_RX1
+        } else {
+            return Ok(None);
+        };
+
+        // SIDH, SIDL, EID8, EID0, DLC, D0-D7
+        let mut buf = [0; 13];
+        self.spi.transfer(&[read], &mut buf)?;
+
+        let mut can_id = decode_id([buf[0], buf[1], buf[2], buf[3]]);
+        let remote = if can_id & CAN_EFF_FLAG != 0 {
+            buf[4] & RTR != 0
+        } else {
+            buf[1] & SRR != 0
+        };
+        if remote {
+            can_id |= CAN_RTR_FLAG;
+        }
+
+        let mut frame = CanFrame {
+            can_id,
+            len: (buf[4] & 0x0F).min(8),
+            ..CanFrame::default()
+        };
+        frame.data.copy_from_slice(&buf[5..]);
+        Ok(Some(frame))
+    }
+}
+
+/// Baud rate prescaler (CNF1.BRP) for `TQ_PER_BIT` quanta per bit, if
+/// `bitrate` can be reached exactly
+fn prescaler(osc_hz: u32, bitrate: u32) -> Option<u8> {
+    let per_bit = 2 * TQ_PER_BIT * bitrate;
+    if per_bit == 0 || osc_hz % per_bit != 0 {
+        return None;
+    }
+    u8::try_from(osc_hz / per_bit)
+        .ok()
+        .and_then(|div| div.checked_sub(1))
+        .filter(|&brp| brp < 64)
+}
+
+/// Identifier in SIDH, SIDL, EID8, EID0 layout
+fn encode_id(can_id: u32) -> [u8; 4] {
+    if can_id & CAN_EFF_FLAG != 0 {
+        let id = can_id & CAN_EFF_MASK;
+        let sid = id >> 18;
+        let eid = id & 0x3FFFF;
+        [
+            (sid >> 3) as u8,
+            ((sid & 0x7) << 5) as u8 | EXIDE | (eid >> 16) as u8,
+            (eid >> 8) as u8,
+            eid as u8,
+        ]
+    } else {
+        let sid = can_id & CAN_SFF_MASK;
+        [(sid >> 3) as u8, ((sid & 0x7) << 5) as u8, 0, 0]
+    }
+}
+
+/// Identifier with `CAN_EFF_FLAG` from SIDH, SIDL, EID8, EID0
+fn decode_id(regs: [u8; 4]) -> u32 {
+    let sid = u32::from(regs[0]) << 3 | u32::from(regs[1]) >> 5;
+    if regs[1] & EXIDE == 0 {
+        return sid;
+    }
+    let eid = u32::from(regs[1] & 0x3) << 16 | u32::from(regs[2]) << 8 | u32::from(regs[3]);
+    CAN_EFF_FLAG | sid << 18 | eid
+}
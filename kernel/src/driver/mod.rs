@@ -4,13 +4,23 @@ use core::sync::atomic::Ordering::Relaxed;
 
 use kernel_device::DeviceId;
 
+pub mod actuator;
 pub mod block;
+pub mod bus;
+pub mod can;
+pub mod gpio;
+pub mod i2c;
 pub mod iio;
+pub mod mcp2515;
+pub mod mpu6050;
 #[cfg(target_arch = "x86_64")]
 pub mod pci;
 pub mod raw;
+pub mod spi;
+pub mod uart;
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 pub mod virtio;
+pub mod watchdog;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct KernelDeviceId(u64);
@@ -32,6 +42,11 @@ impl KernelDeviceId {
         static COUNTER: AtomicU64 = AtomicU64::new(0);
         Self(COUNTER.fetch_add(1, Relaxed))
     }
+
+    #[must_use]
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
 }
 
 impl DeviceId for KernelDeviceId {}
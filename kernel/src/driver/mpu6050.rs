@@ -0,0 +1,271 @@
+//! InvenSense MPU-6050 IMU Driver
+//!
+//! The MPU-6050 is a 3-axis accelerometer and 3-axis gyroscope with a
+//! temperature sensor, usually found on breakout boards at I2C address 0x68.
+//! The driver configures it for ±2 g and ±250 °/s, turns each data-ready
+//! sample into one [`IioEvent`] per channel and reports values with IIO
+//! scales: m/s², rad/s and °C.
+//!
+//! [`Mpu6050Model`] emulates the register map as a software I2C target, so
+//! the driver and everything downstream of it can run without the chip.
+
+use alloc::boxed::Box;
+
+use kernel_bpf::attach::{IioChannel, IioEvent};
+
+use super::i2c::{I2cBus, I2cError, I2cTarget};
+use super::iio::IioDevice;
+
+/// Default I2C address (AD0 pin low)
+pub const DEFAULT_ADDR: u8 = 0x68;
+
+/// Register addresses
+mod reg {
+    /// Sample rate divider from the 1 kHz internal rate
+    pub const SMPLRT_DIV: u8 = 0x19;
+    /// Digital low pass filter
+    pub const CONFIG: u8 = 0x1A;
+    /// Gyroscope full scale range
+    pub const GYRO_CONFIG: u8 = 0x1B;
+    /// Accelerometer full scale range
+    pub const ACCEL_CONFIG: u8 = 0x1C;
+    /// Interrupt enables
+    pub const INT_ENABLE: u8 = 0x38;
+    /// Interrupt status, cleared on read
+    pub const INT_STATUS: u8 = 0x3A;
+    /// First of the 14 sample bytes
+    pub const ACCEL_XOUT_H: u8 = 0x3B;
+    /// Power management and clock source
+    pub const PWR_MGMT_1: u8 = 0x6B;
+    /// Device identity
+    pub const WHO_AM_I: u8 = 0x75;
+}
+
+/// Data ready interrupt (INT_ENABLE and INT_STATUS)
+const DATA_RDY: u8 = 1 << 0;
+
+/// Device reset (PWR_MGMT_1)
+const DEVICE_RESET: u8 = 1 << 7;
+
+/// Sleep mode (PWR_MGMT_1)
+const SLEEP: u8 = 1 << 6;
+
+/// Use the X gyroscope PLL as clock source (PWR_MGMT_1)
+const CLKSEL_PLL_X: u8 = 0x01;
+
+/// 44 Hz accelerometer / 42 Hz gyroscope low pass filter, 1 kHz internal
+/// sample rate (CONFIG)
+const DLPF_44HZ: u8 = 0x03;
+
+/// Value of WHO_AM_I
+const WHO_AM_I_VALUE: u8 = 0x68;
+
+/// Internal sample rate with the low pass filter enabled
+const BASE_RATE_HZ: u32 = 1000;
+
+/// Accelerometer scale at ±2 g: 9.80665 / 16384 m/s² per LSB, in 1e-6 units
+const ACCEL_SCALE: u32 = 599;
+
+/// Gyroscope scale at ±250 °/s: 250 / 32768 °/s per LSB in rad/s, in 1e-6
+/// units
+const GYRO_SCALE: u32 = 133;
+
+/// Temperature scale: 1/340 °C per LSB, in 1e-6 units
+const TEMP_SCALE: u32 = 2941;
+
+/// Temperature offset in LSB, so that `(raw + offset) / 340` is in °C: the
+/// datasheet puts raw 0 at 36.53 °C
+const TEMP_OFFSET: i32 = 12420;
+
+/// Channels in sample register order, with their scale and offset
+fn channels() -> [(IioChannel, u32, i32); 7] {
+    [
+        (IioChannel::AccelX, ACCEL_SCALE, 0),
+        (IioChannel::AccelY, ACCEL_SCALE, 0),
+        (IioChannel::AccelZ, ACCEL_SCALE, 0),
+        (IioChannel::Temp, TEMP_SCALE, TEMP_OFFSET),
+        (IioChannel::AnglVelX, GYRO_SCALE, 0),
+        (IioChannel::AnglVelY, GYRO_SCALE, 0),
+        (IioChannel::AnglVelZ, GYRO_SCALE, 0),
+    ]
+}
+
+/// MPU-6050 on an I2C bus
+pub struct Mpu6050 {
+    bus: Box<dyn I2cBus>,
+    addr: u8,
+    device_id: u32,
+}
+
+impl Mpu6050 {
+    /// Number of channels in each sample
+    pub const NUM_CHANNELS: usize = 7;
+
+    /// Check for an MPU-6050 at `addr` and wake it up.
+    ///
+    /// `device_id` is the IIO device id its events are reported under.
+    pub fn probe(mut bus: Box<dyn I2cBus>, addr: u8, device_id: u32) -> Result<Self, I2cError> {
+        if bus.read_reg(addr, reg::WHO_AM_I)? != WHO_AM_I_VALUE {
+            return Err(I2cError::WrongDevice);
+        }
+        bus.write_reg(addr, reg::PWR_MGMT_1, CLKSEL_PLL_X)?;
+
+        Ok(Self {
+            bus,
+            addr,
+            device_id,
+        })
+    }
+
+    /// Set the sample rate and enable the data-ready interrupt.
+    ///
+    /// The rate is rounded to a divisor of 1 kHz, between 4 Hz and 1 kHz.
+    pub fn configure(&mut self, rate_hz: u32) -> Result<(), I2cError> {
+        let divider = (BASE_RATE_HZ / rate_hz.max(1)).clamp(1, 256) - 1;
+
+        self.bus.write_reg(self.addr, reg::CONFIG, DLPF_44HZ)?;
+        self.bus
+            .write_reg(self.addr, reg::SMPLRT_DIV, divider as u8)?;
+        self.bus.write_reg(self.addr, reg::GYRO_CONFIG, 0)?;
+        self.bus.write_reg(self.addr, reg::ACCEL_CONFIG, 0)?;
+        self.bus.write_reg(self.addr, reg::INT_ENABLE, DATA_RDY)
+    }
+
+    /// Describe this sensor as an IIO device.
+    pub fn iio_device(&self) -> IioDevice {
+        let mut device = IioDevice::new(self.device_id, "mpu6050");
+        for (channel, _, _) in channels() {
+            device.add_channel(channel);
+        }
+        device
+    }
+
+    /// Read a new sample if one is ready.
+    ///
+    /// Returns one event per channel, stamped with `timestamp`.
+    pub fn poll(
+        &mut self,
+        timestamp: u64,
+    ) -> Result<Option<[IioEvent; Self::NUM_CHANNELS]>, I2cError> {
+        if self.bus.read_reg(self.addr, reg::INT_STATUS)? & DATA_RDY == 0 {
+            return Ok(None);
+        }
+
+        let mut raw = [0; 2 * Self::NUM_CHANNELS];
+        self.bus.read_regs(self.addr, reg::ACCEL_XOUT_H, &mut raw)?;
+
+        let channels = channels();
+        Ok(Some(core::array::from_fn(|i| {
+            let (channel, scale, offset) = &channels[i];
+            IioEvent {
+                timestamp,
+                device_id: self.device_id,
+                channel: channel.id().unwrap_or_default(),
+                value: i32::from(i16::from_be_bytes([raw[2 * i], raw[2 * i + 1]])),
+                scale: *scale,
+                offset: *offset,
+            }
+        })))
+    }
+}
+
+/// Software model of an MPU-6050
+///
+/// Implements the registers the driver uses. Every INT_STATUS read while
+/// awake reports a new sample: the device lying flat at 25 °C, slowly
+/// rocking about the Y axis.
+pub struct Mpu6050Model {
+    regs: [u8; 128],
+    /// Register pointer, auto-incremented on every access
+    pointer: u8,
+    samples: u32,
+}
+
+impl Default for Mpu6050Model {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mpu6050Model {
+    pub fn new() -> Self {
+        let mut model = Self {
+            regs: [0; 128],
+            pointer: 0,
+            samples: 0,
+        };
+        model.reset();
+        model
+    }
+
+    fn reset(&mut self) {
+        self.regs = [0; 128];
+        self.regs[reg::PWR_MGMT_1 as usize] = SLEEP;
+        self.regs[reg::WHO_AM_I as usize] = WHO_AM_I_VALUE;
+    }
+
+    /// Latch the next sample into the data registers.
+    fn sample(&mut self) {
+        // Triangle wave of ±0.25 g on X and the matching rotation about Y
+        let phase = (self.samples % 200) as i16;
+        let tilt = if phase < 100 { phase - 50 } else { 150 - phase };
+        self.samples = self.samples.wrapping_add(1);
+
+        let values: [i16; Mpu6050::NUM_CHANNELS] = [
+            tilt * 82,
+            0,
+            16384,
+            // 25 °C
+            -3920,
+            0,
+            if phase < 100 { 131 } else { -131 },
+            0,
+        ];
+        for (i, value) in values.iter().enumerate() {
+            let at = reg::ACCEL_XOUT_H as usize + 2 * i;
+            self.regs[at..at + 2].copy_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn write_reg(&mut self, reg: u8, value: u8) {
+        if reg == reg::PWR_MGMT_1 && value & DEVICE_RESET != 0 {
+            self.reset();
+        } else if reg != reg::WHO_AM_I && reg != reg::INT_STATUS {
+            self.regs[reg as usize] = value;
+        }
+    }
+
+    fn read_reg(&mut self, reg: u8) -> u8 {
+        if reg != reg::INT_STATUS {
+            return self.regs[reg as usize];
+        }
+
+        let awake = self.regs[reg::PWR_MGMT_1 as usize] & SLEEP == 0;
+        if awake && self.regs[reg::INT_ENABLE as usize] & DATA_RDY != 0 {
+            self.sample();
+            DATA_RDY
+        } else {
+            0
+        }
+    }
+}
+
+impl I2cTarget for Mpu6050Model {
+    fn write(&mut self, data: &[u8]) -> Result<(), I2cError> {
+        let (&pointer, values) = data.split_first().ok_or(I2cError::Nack)?;
+        self.pointer = pointer & 0x7F;
+        for &value in values {
+            self.write_reg(self.pointer, value);
+            self.pointer = (self.pointer + 1) & 0x7F;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<(), I2cError> {
+        for byte in buf {
+            *byte = self.read_reg(self.pointer);
+            self.pointer = (self.pointer + 1) & 0x7F;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! SPI bus abstraction
+//!
+//! Controller drivers implement [`SpiController`] and are registered with
+//! the [bus layer](super::bus), which hands out one [`SpiBus`] per device.
+//! Peripheral drivers talk to their device only through it, like
+//! [`I2cBus`](super::i2c::I2cBus) for I2C. A bus value addresses one device,
+//! so the controller asserts the same chip select for every transfer.
+//!
+//! [`LoopbackSpi`] stands in for a controller with MOSI wired to MISO, so
+//! SPI programs can be tested on boards without one.
+
+use alloc::vec::Vec;
+
+use thiserror::Error;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum SpiError {
+    #[error("transfer timeout")]
+    Timeout,
+    #[error("unexpected device")]
+    WrongDevice,
+    #[error("no such bus")]
+    NoSuchBus,
+    #[error("no such chip select")]
+    InvalidChipSelect,
+    #[error("rejected by BPF program")]
+    Rejected,
+}
+
+/// An SPI controller with one device selected.
+pub trait SpiBus: Send {
+    /// Clock out `write` and then clock in `read`, keeping chip select
+    /// asserted across both. Either buffer may be empty.
+    fn transfer(&mut self, write: &[u8], read: &mut [u8]) -> Result<(), SpiError>;
+
+    /// Clock out `write`, ignoring what the device sends back.
+    fn write(&mut self, write: &[u8]) -> Result<(), SpiError> {
+        self.transfer(write, &mut [])
+    }
+}
+
+/// An SPI controller.
+pub trait SpiController: Send {
+    /// Number of chip selects
+    fn chip_selects(&self) -> u8;
+
+    /// Clock out `write` and then clock in `read` with chip select `cs`
+    /// asserted across both. `cs` is below [`SpiController::chip_selects`].
+    fn transfer(&mut self, cs: u8, write: &[u8], read: &mut [u8]) -> Result<(), SpiError>;
+}
+
+/// Loopback SPI controller
+///
+/// With MOSI wired to MISO, the bytes clocked in while reading are the
+/// dummy bytes clocked out, which says little. Instead, a read returns the
+/// bytes of the last write to the same chip select, zero-padded, so a test
+/// can write a register and read it back.
+pub struct LoopbackSpi {
+    /// Last bytes written, per chip select
+    last: [Vec<u8>; Self::CHIP_SELECTS as usize],
+}
+
+impl LoopbackSpi {
+    const CHIP_SELECTS: u8 = 2;
+
+    pub fn new() -> Self {
+        Self {
+            last: [const { Vec::new() }; Self::CHIP_SELECTS as usize],
+        }
+    }
+}
+
+impl Default for LoopbackSpi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpiController for LoopbackSpi {
+    fn chip_selects(&self) -> u8 {
+        Self::CHIP_SELECTS
+    }
+
+    fn transfer(&mut self, cs: u8, write: &[u8], read: &mut [u8]) -> Result<(), SpiError> {
+        let last = self
+            .last
+            .get_mut(usize::from(cs))
+            .ok_or(SpiError::InvalidChipSelect)?;
+
+        if !write.is_empty() {
+            last.clear();
+            last.extend_from_slice(write);
+        }
+        for (i, byte) in read.iter_mut().enumerate() {
+            *byte = last.get(i).copied().unwrap_or(0);
+        }
+        Ok(())
+    }
+}
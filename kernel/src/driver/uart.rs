@@ -0,0 +1,594 @@
+//! UART subsystem
+//!
+//! Board UARTs implement [`Uart`] and are registered as ports `uart0`,
+//! `uart1`, ... in registration order. Receiving is split in two halves:
+//!
+//! - The port's interrupt handler drains the hardware FIFO into a per-port
+//!   ring, so bytes are not lost while the FIFO (32 bytes on a PL011) fills
+//!   between ticks. Ports without an interrupt are drained by the receive
+//!   task instead.
+//! - A receive task empties the rings once per timer tick. A port with
+//!   programs attached splits its bytes into frames with a
+//!   [`SerialFramer`]; each frame first runs the BPF programs attached to
+//!   the port, and is queued for userspace unless one of them returns
+//!   non-zero. Without programs, the bytes are queued as they arrive.
+//!
+//! Userspace reads one queued frame (or run of bytes) per read of
+//! `/dev/uartN`, and writes bytes to transmit.
+//!
+//! The ports are:
+//!
+//! - x86_64: COM2 (the 16550 at 0x2F8, ISA IRQ 3) if present; COM1 is the
+//!   console
+//! - aarch64 virt: the PL011 console UART, interrupt-driven
+//! - aarch64 rpi5: the RP1 UART0 console, polled, as RP1 peripheral
+//!   interrupts other than GPIO are not routed
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::vec::Vec;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use core::ffi::c_void;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+
+use conquer_once::spin::OnceCell;
+use kernel_bpf::attach::{
+    AttachError, AttachEvent, AttachHardware, AttachResult, Framing, SerialEvent, SerialFramer,
+};
+use kernel_bpf::execution::BpfContext;
+use kernel_devfs::DevFile;
+use kernel_vfs::path::AbsoluteOwnedPath;
+use kernel_vfs::{ReadError, Stat, StatError, WriteError};
+use spin::Mutex;
+use thiserror::Error;
+
+use crate::file::devfs::devfs;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::process::Process;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::scheduler::global::GlobalTaskQueue;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use crate::mcore::mtask::task::Task;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum UartError {
+    #[error("no such port")]
+    NoSuchPort,
+    #[error("too many ports")]
+    TooManyPorts,
+}
+
+/// A UART.
+pub trait Uart: Send {
+    /// Take the oldest received byte, if any.
+    fn try_read(&mut self) -> Option<u8>;
+
+    /// Transmit a byte, waiting for room in the transmit FIFO.
+    fn write(&mut self, byte: u8);
+
+    /// Enable the receive interrupt. It must stay asserted until the
+    /// receive FIFO is drained with [`Uart::try_read`].
+    fn enable_rx_interrupt(&mut self) {}
+}
+
+/// Number of ports that can be registered
+const MAX_PORTS: usize = 4;
+
+/// Bytes buffered per port between the interrupt handler and the receive
+/// task, about 90 ms at 115200 baud
+const RX_RING_LEN: usize = 1024;
+
+/// Frames queued for userspace per port; the oldest are dropped when full
+const RX_QUEUE_LEN: usize = 64;
+
+/// Received bytes, written by the interrupt handler and read by the
+/// receive task
+///
+/// Bytes are only pushed with the port's UART locked, so there is a single
+/// producer, and only the receive task pops them.
+struct RxRing {
+    buf: [AtomicU8; RX_RING_LEN],
+    /// Bytes pushed
+    head: AtomicUsize,
+    /// Bytes popped
+    tail: AtomicUsize,
+    /// Bytes were dropped because the ring was full
+    overrun: AtomicBool,
+}
+
+impl RxRing {
+    const fn new() -> Self {
+        Self {
+            buf: [const { AtomicU8::new(0) }; RX_RING_LEN],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overrun: AtomicBool::new(false),
+        }
+    }
+
+    fn push(&self, byte: u8) {
+        let head = self.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(self.tail.load(Ordering::Acquire)) == RX_RING_LEN {
+            self.overrun.store(true, Ordering::Relaxed);
+            return;
+        }
+        self.buf[head % RX_RING_LEN].store(byte, Ordering::Relaxed);
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+    }
+
+    fn pop(&self) -> Option<u8> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+        let byte = self.buf[tail % RX_RING_LEN].load(Ordering::Relaxed);
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(byte)
+    }
+
+    /// Check and clear the overrun flag.
+    fn take_overrun(&self) -> bool {
+        self.overrun.swap(false, Ordering::Relaxed)
+    }
+}
+
+struct Port {
+    /// Locked by the interrupt handler, so only lock it with interrupts
+    /// disabled elsewhere
+    uart: Mutex<Box<dyn Uart>>,
+    /// Interrupt that drains the UART, if any
+    irq: Option<u32>,
+    rx: RxRing,
+    /// Framer of the port while programs are attached
+    framer: Mutex<Option<SerialFramer>>,
+    /// Received frames not yet read by userspace
+    frames: Mutex<VecDeque<Vec<u8>>>,
+}
+
+/// Registered ports, indexed by port number
+///
+/// Slots are only ever filled, so the interrupt handler finds a port
+/// without taking a lock.
+static PORTS: [OnceCell<Port>; MAX_PORTS] = [const { OnceCell::uninit() }; MAX_PORTS];
+
+static NUM_PORTS: AtomicUsize = AtomicUsize::new(0);
+
+fn port(port: u32) -> Result<&'static Port, UartError> {
+    PORTS
+        .get(port as usize)
+        .and_then(OnceCell::get)
+        .ok_or(UartError::NoSuchPort)
+}
+
+fn ports() -> impl Iterator<Item = (u32, &'static Port)> {
+    let count = NUM_PORTS.load(Ordering::Relaxed).min(MAX_PORTS);
+    (0..count as u32).filter_map(|index| Some((index, port(index).ok()?)))
+}
+
+/// Register a UART as the next `uartN` port and create `/dev/uartN`. If
+/// `irq` is given, the board calls [`interrupt`] when it fires. Returns the
+/// port number.
+pub fn register(mut uart: Box<dyn Uart>, irq: Option<u32>) -> Result<u32, UartError> {
+    let index = NUM_PORTS.fetch_add(1, Ordering::Relaxed);
+    let slot = PORTS.get(index).ok_or(UartError::TooManyPorts)?;
+    if irq.is_some() {
+        uart.enable_rx_interrupt();
+    }
+    slot.init_once(|| Port {
+        uart: Mutex::new(uart),
+        irq,
+        rx: RxRing::new(),
+        framer: Mutex::new(None),
+        frames: Mutex::new(VecDeque::with_capacity(RX_QUEUE_LEN)),
+    });
+
+    let index = index as u32;
+    let path = AbsoluteOwnedPath::try_from(format!("/uart{index}").as_ref()).unwrap();
+    if let Err(e) = devfs()
+        .write()
+        .register_file(path.as_ref(), move || Ok(UartFile { port: index }))
+    {
+        log::warn!("uart{}: no device file ({})", index, e);
+    }
+
+    log::info!("Registered serial port uart{}", index);
+    Ok(index)
+}
+
+/// Move the bytes in the UART's receive FIFO to the port's ring.
+fn drain(port: &Port) {
+    let mut uart = port.uart.lock();
+    while let Some(byte) = uart.try_read() {
+        port.rx.push(byte);
+    }
+}
+
+/// Check if a port uses interrupt `irq`.
+pub fn is_port_irq(irq: u32) -> bool {
+    ports().any(|(_, port)| port.irq == Some(irq))
+}
+
+/// Drain the ports that use interrupt `irq`.
+///
+/// Called from the interrupt handler.
+pub fn interrupt(irq: u32) {
+    for (_, port) in ports().filter(|(_, port)| port.irq == Some(irq)) {
+        drain(port);
+    }
+}
+
+/// Transmit bytes on a port.
+pub fn write(index: u32, bytes: &[u8]) -> Result<(), UartError> {
+    let port = port(index)?;
+    crate::arch::without_interrupts(|| {
+        let mut uart = port.uart.lock();
+        for &byte in bytes {
+            uart.write(byte);
+        }
+    });
+    Ok(())
+}
+
+/// Take the oldest received frame of a port.
+pub fn read(index: u32) -> Result<Option<Vec<u8>>, UartError> {
+    Ok(port(index)?.frames.lock().pop_front())
+}
+
+fn queue(port: &Port, frame: Vec<u8>) {
+    let mut frames = port.frames.lock();
+    if frames.len() == RX_QUEUE_LEN {
+        frames.pop_front();
+    }
+    frames.push_back(frame);
+}
+
+/// Run the BPF programs attached to a received frame, then queue it for
+/// userspace unless a program dropped it.
+fn dispatch(index: u32, port: &Port, frame: &[u8], flags: u32) {
+    let event = SerialEvent::new(crate::time::get_kernel_time_ns(), index, frame, flags);
+
+    // SAFETY: SerialEvent is repr(C) plain data; the slice only lives while
+    // the programs run.
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            &event as *const _ as *const u8,
+            core::mem::size_of::<SerialEvent>(),
+        )
+    };
+    let ctx = BpfContext::from_slice(slice);
+    if crate::bpf::hooks::veto_hooks(AttachEvent::Serial(&event), &ctx) {
+        return;
+    }
+    queue(port, frame.to_vec());
+}
+
+/// Frame the received bytes of every port and run their programs.
+fn poll() {
+    for (index, port) in ports() {
+        if port.irq.is_none() {
+            crate::arch::without_interrupts(|| drain(port));
+        }
+
+        let mut framer = port.framer.lock();
+        let Some(framer) = framer.as_mut() else {
+            let bytes: Vec<u8> = core::iter::from_fn(|| port.rx.pop()).collect();
+            if !bytes.is_empty() {
+                queue(port, bytes);
+            }
+            continue;
+        };
+
+        if port.rx.take_overrun() {
+            framer.overrun();
+        }
+        while let Some(byte) = port.rx.pop() {
+            if let Some((frame, flags)) = framer.push(byte) {
+                dispatch(index, port, frame, flags);
+            }
+        }
+    }
+}
+
+/// UART receive task entry point
+///
+/// Polls once per timer tick.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+extern "C" fn uart_rx_task(_arg: *mut c_void) {
+    loop {
+        poll();
+
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            core::arch::asm!("hlt")
+        };
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            core::arch::asm!("wfi")
+        };
+    }
+}
+
+/// Register the board's serial ports and start receiving
+pub fn init() {
+    arch::init();
+    if NUM_PORTS.load(Ordering::Relaxed) == 0 {
+        log::info!("No serial ports");
+        return;
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    {
+        let task = Task::create_new(Process::root(), uart_rx_task, core::ptr::null_mut())
+            .expect("failed to create UART receive task");
+        GlobalTaskQueue::enqueue(Box::pin(task));
+
+        log::info!("Started UART receive task");
+    }
+}
+
+/// The framing of a `SerialAttach` point's port, in use while programs are
+/// attached
+pub struct UartRx {
+    port: u32,
+    framing: Framing,
+}
+
+impl UartRx {
+    pub fn new(port: u32, framing: Framing) -> Self {
+        Self { port, framing }
+    }
+}
+
+impl AttachHardware for UartRx {
+    fn enable(&mut self) -> AttachResult<()> {
+        let port = port(self.port).map_err(|_| AttachError::ResourceNotFound)?;
+        let mut framer = port.framer.lock();
+        // A port has one framing; programs with another one must wait
+        if framer.is_some() {
+            return Err(AttachError::ResourceBusy);
+        }
+        *framer = Some(SerialFramer::new(self.framing));
+        Ok(())
+    }
+
+    fn disable(&mut self) {
+        if let Ok(port) = port(self.port) {
+            *port.framer.lock() = None;
+        }
+    }
+}
+
+/// `/dev/uartN`: each read returns one received frame, truncated to the
+/// buffer; writes transmit
+struct UartFile {
+    port: u32,
+}
+
+impl DevFile for UartFile {
+    fn read(&mut self, buf: &mut [u8], _: usize) -> Result<usize, ReadError> {
+        let Some(frame) = read(self.port).map_err(|_| ReadError::ReadFailed)? else {
+            return Ok(0);
+        };
+        let len = frame.len().min(buf.len());
+        buf[..len].copy_from_slice(&frame[..len]);
+        Ok(len)
+    }
+
+    fn write(&mut self, buf: &[u8], _: usize) -> Result<usize, WriteError> {
+        write(self.port, buf).map_err(|_| WriteError::WriteFailed)?;
+        Ok(buf.len())
+    }
+
+    fn stat(&mut self, stat: &mut Stat) -> Result<(), StatError> {
+        stat.size = 0;
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    use alloc::boxed::Box;
+
+    use x2apic::ioapic::{IrqFlags, IrqMode, RedirectionTableEntry};
+    use x86_64::instructions::port::Port;
+
+    use super::Uart;
+    use crate::apic::io_apic;
+    use crate::arch::idt::InterruptIndex;
+    use crate::mcore::context::ExecutionContext;
+
+    /// COM2 I/O port base
+    const COM2: u16 = 0x2F8;
+
+    /// I/O APIC input of ISA IRQ 3
+    const COM2_IRQ: u8 = 3;
+
+    /// 16550 register offsets
+    mod reg {
+        /// Receive buffer / transmit holding (DLAB = 0), divisor low (DLAB = 1)
+        pub const DATA: u16 = 0;
+        /// Interrupt enable (DLAB = 0), divisor high (DLAB = 1)
+        pub const IER: u16 = 1;
+        /// FIFO control
+        pub const FCR: u16 = 2;
+        /// Line control
+        pub const LCR: u16 = 3;
+        /// Modem control
+        pub const MCR: u16 = 4;
+        /// Line status
+        pub const LSR: u16 = 5;
+        /// Scratch
+        pub const SCR: u16 = 7;
+    }
+
+    /// Line status: received data ready
+    const LSR_DR: u8 = 1 << 0;
+    /// Line status: transmit holding register empty
+    const LSR_THRE: u8 = 1 << 5;
+
+    /// A 16550-compatible UART
+    struct Uart16550 {
+        base: u16,
+    }
+
+    impl Uart16550 {
+        fn reg(&self, offset: u16) -> Port<u8> {
+            Port::new(self.base + offset)
+        }
+
+        fn read(&self, offset: u16) -> u8 {
+            // SAFETY: The UART's registers are only accessed through this
+            // driver.
+            unsafe { self.reg(offset).read() }
+        }
+
+        fn write_reg(&self, offset: u16, value: u8) {
+            // SAFETY: As above.
+            unsafe { self.reg(offset).write(value) }
+        }
+
+        /// Set up the UART for 115200 8N1 with FIFOs, if one is present.
+        fn probe(base: u16) -> Option<Self> {
+            let uart = Self { base };
+            uart.write_reg(reg::SCR, 0x5A);
+            if uart.read(reg::SCR) != 0x5A {
+                return None;
+            }
+
+            uart.write_reg(reg::IER, 0);
+            // Divisor 1: 115200 baud
+            uart.write_reg(reg::LCR, 0x80);
+            uart.write_reg(reg::DATA, 1);
+            uart.write_reg(reg::IER, 0);
+            // 8 data bits, no parity, 1 stop bit
+            uart.write_reg(reg::LCR, 0x03);
+            // Enable and clear FIFOs, interrupt at 14 bytes
+            uart.write_reg(reg::FCR, 0xC7);
+            // DTR, RTS and OUT2, which gates the interrupt line
+            uart.write_reg(reg::MCR, 0x0B);
+            Some(uart)
+        }
+    }
+
+    impl Uart for Uart16550 {
+        fn try_read(&mut self) -> Option<u8> {
+            (self.read(reg::LSR) & LSR_DR != 0).then(|| self.read(reg::DATA))
+        }
+
+        fn write(&mut self, byte: u8) {
+            while self.read(reg::LSR) & LSR_THRE == 0 {
+                core::hint::spin_loop();
+            }
+            self.write_reg(reg::DATA, byte);
+        }
+
+        fn enable_rx_interrupt(&mut self) {
+            // Received data available, which includes the FIFO timeout
+            self.write_reg(reg::IER, 0x01);
+        }
+    }
+
+    pub fn init() {
+        let Some(uart) = Uart16550::probe(COM2) else {
+            return;
+        };
+
+        let vector = InterruptIndex::Serial.as_u8();
+        if super::register(Box::new(uart), Some(u32::from(vector))).is_err() {
+            return;
+        }
+
+        let dest = u8::try_from(ExecutionContext::load().lapic_id()).expect("invalid lapic id");
+        let mut entry = RedirectionTableEntry::default();
+        entry.set_mode(IrqMode::Fixed);
+        entry.set_flags(IrqFlags::empty());
+        entry.set_vector(vector);
+        entry.set_dest(dest);
+        let mut io_apic = io_apic().lock();
+        // SAFETY: The input is only driven by COM2, whose vector has a
+        // handler.
+        unsafe {
+            io_apic.set_table_entry(COM2_IRQ, entry);
+            io_apic.enable_irq(COM2_IRQ);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    pub fn init() {
+        #[cfg(feature = "virt")]
+        virt::init();
+        #[cfg(feature = "rpi5")]
+        rpi5::init();
+    }
+
+    #[cfg(feature = "virt")]
+    mod virt {
+        use alloc::boxed::Box;
+
+        use crate::arch::aarch64::platform::virt::SERIAL_CONSOLE;
+        use crate::arch::aarch64::{dtb, gic};
+        use crate::driver::uart::{Uart, register};
+
+        /// The PL011 console; the console lock is taken with interrupts
+        /// disabled, so the interrupt handler can take it too
+        struct Console;
+
+        impl Uart for Console {
+            fn try_read(&mut self) -> Option<u8> {
+                SERIAL_CONSOLE.lock().try_getc()
+            }
+
+            fn write(&mut self, byte: u8) {
+                SERIAL_CONSOLE.lock().putc(byte);
+            }
+
+            fn enable_rx_interrupt(&mut self) {
+                SERIAL_CONSOLE.lock().set_rx_interrupt(true);
+            }
+        }
+
+        pub fn init() {
+            let irq = dtb::find_compatible(&["arm,pl011"]).and_then(|node| node.irq);
+            if register(Box::new(Console), irq).is_err() {
+                return;
+            }
+            if let Some(irq) = irq {
+                gic::set_priority(irq, 0x80);
+                gic::enable_irq(irq);
+            }
+        }
+    }
+
+    #[cfg(feature = "rpi5")]
+    mod rpi5 {
+        use alloc::boxed::Box;
+
+        use crate::arch::aarch64::platform::rpi5::UART;
+        use crate::driver::uart::{Uart, register};
+
+        /// The RP1 UART0 console
+        struct Console;
+
+        impl Uart for Console {
+            fn try_read(&mut self) -> Option<u8> {
+                UART.lock().try_getc()
+            }
+
+            fn write(&mut self, byte: u8) {
+                UART.lock().putc(byte);
+            }
+        }
+
+        pub fn init() {
+            let _ = register(Box::new(Console), None);
+        }
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    pub fn init() {}
+}
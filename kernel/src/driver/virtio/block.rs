@@ -3,6 +3,7 @@ use alloc::sync::Arc;
 use core::error::Error;
 use core::fmt::{Debug, Formatter};
 
+use kernel_bpf::attach::BlockRqEvent;
 use kernel_device::Device;
 use kernel_device::block::{BlockBuf, BlockDevice};
 #[cfg(target_arch = "x86_64")]
@@ -20,6 +21,7 @@ use virtio_drivers::transport::mmio::MmioTransport;
 use virtio_drivers::transport::pci::PciTransport;
 
 use crate::U64Ext;
+use crate::bpf::tracepoint;
 use crate::driver::KernelDeviceId;
 use crate::driver::block::BlockDevices;
 #[cfg(target_arch = "x86_64")]
@@ -125,6 +127,44 @@ pub struct VirtioBlockDevice {
     inner: Arc<Mutex<VirtioBlkInner>>,
 }
 
+impl VirtioBlockDevice {
+    /// Run a request for `len` bytes starting at `sector`, raising the block
+    /// request tracepoints around it.
+    fn request(
+        &self,
+        sector: usize,
+        len: usize,
+        write: bool,
+        io: impl FnOnce(&mut VirtioBlkInner) -> virtio_drivers::Result,
+    ) -> virtio_drivers::Result {
+        let event = |latency_ns, error| BlockRqEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            device: self.id.as_u64(),
+            sector: sector as u64,
+            latency_ns,
+            nr_sectors: (len / 512) as u32,
+            write: u32::from(write),
+            error,
+            _reserved: 0,
+        };
+
+        let issued = tracepoint::BLOCK_RQ_COMPLETE
+            .is_enabled()
+            .then(crate::time::get_kernel_time_ns);
+        tracepoint::BLOCK_RQ_ISSUE.emit(|| event(0, 0));
+
+        let result = io(&mut self.inner.lock());
+
+        tracepoint::BLOCK_RQ_COMPLETE.emit(|| {
+            let latency = issued.map_or(0, |issued| {
+                crate::time::get_kernel_time_ns().saturating_sub(issued)
+            });
+            event(latency, if result.is_ok() { 0 } else { -1 })
+        });
+        result
+    }
+}
+
 impl Debug for VirtioBlockDevice {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("VirtioBlockDevice")
@@ -149,12 +189,16 @@ impl BlockDevice<KernelDeviceId, 512> for VirtioBlockDevice {
         block_num: usize,
         buf: &mut BlockBuf<512>,
     ) -> Result<(), Box<dyn Error>> {
-        self.inner.lock().read_blocks(block_num, &mut buf[..])?;
+        self.request(block_num, buf.len(), false, |blk| {
+            blk.read_blocks(block_num, &mut buf[..])
+        })?;
         Ok(())
     }
 
     fn write_block(&mut self, block_num: usize, buf: &BlockBuf<512>) -> Result<(), Box<dyn Error>> {
-        self.inner.lock().write_blocks(block_num, &buf[..])?;
+        self.request(block_num, buf.len(), true, |blk| {
+            blk.write_blocks(block_num, &buf[..])
+        })?;
         Ok(())
     }
 
@@ -175,18 +219,19 @@ impl filesystem::BlockDevice for VirtioBlockDevice {
     }
 
     fn read_sector(&self, sector_index: usize, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        self.inner
-            .lock()
-            .read_blocks(sector_index, buf)
-            .map(|()| buf.len())
-            .map_err(|_| ())
+        let len = buf.len();
+        self.request(sector_index, len, false, |blk| {
+            blk.read_blocks(sector_index, buf)
+        })
+        .map(|()| len)
+        .map_err(|_| ())
     }
 
     fn write_sector(&mut self, sector_index: usize, buf: &[u8]) -> Result<usize, Self::Error> {
-        self.inner
-            .lock()
-            .write_blocks(sector_index, buf)
-            .map(|()| buf.len())
-            .map_err(|_| ())
+        self.request(sector_index, buf.len(), true, |blk| {
+            blk.write_blocks(sector_index, buf)
+        })
+        .map(|()| buf.len())
+        .map_err(|_| ())
     }
 }
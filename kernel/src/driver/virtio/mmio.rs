@@ -3,7 +3,7 @@ use virtio_drivers::{Hal, transport::{Transport, mmio::MmioTransport, DeviceType
 
 use crate::arch::aarch64::platform::virt::mmio::{VIRTIO_MMIO_BASE, VIRTIO_MMIO_SIZE, VIRTIO_MAX_DEVICES};
 use crate::driver::virtio::hal::HalImpl;
-use crate::driver::virtio::block;
+use crate::driver::virtio::{block, rng};
 
 /// Initialize VirtIO MMIO devices
 ///
@@ -37,6 +37,12 @@ pub fn init() {
                             warn!("Failed to initialize VirtIO Block device: {:?}", e);
                         }
                     }
+                    DeviceType::EntropySource => {
+                        info!("Initializing VirtIO entropy device at {:#x}", phys_addr);
+                        if let Err(e) = rng::init_mmio(transport) {
+                            warn!("Failed to initialize VirtIO entropy device: {:?}", e);
+                        }
+                    }
                     _ => {
                         // TODO: Support other devices (Network, Console, GPU, etc.)
                         // For now, we just acknowledge existence
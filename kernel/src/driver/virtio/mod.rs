@@ -4,3 +4,5 @@ mod gpu;
 mod hal;
 #[cfg(all(target_arch = "aarch64", feature = "virt"))]
 pub mod mmio;
+#[cfg(all(target_arch = "aarch64", feature = "virt"))]
+pub mod rng;
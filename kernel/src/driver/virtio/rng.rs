@@ -0,0 +1,46 @@
+//! VirtIO entropy device
+//!
+//! Used to seed the kernel PRNG on the QEMU virt platform, where there is no
+//! dedicated hardware RNG. Start QEMU with `-device virtio-rng-device` to
+//! provide one.
+
+use conquer_once::spin::OnceCell;
+use spin::Mutex;
+use virtio_drivers::device::rng::VirtIORng;
+use virtio_drivers::transport::mmio::MmioTransport;
+
+use crate::driver::virtio::hal::HalImpl;
+
+static RNG: OnceCell<Mutex<VirtIORng<HalImpl, MmioTransport<'static>>>> = OnceCell::uninit();
+
+pub fn init_mmio(transport: MmioTransport) -> Result<(), virtio_drivers::Error> {
+    // SAFETY: MMIO transport is backed by kernel-mapped memory that lives for the entire kernel lifetime.
+    let transport: MmioTransport<'static> = unsafe { core::mem::transmute(transport) };
+    let rng = VirtIORng::<HalImpl, _>::new(transport)?;
+    // Only the first entropy device is used
+    if RNG.try_init_once(|| Mutex::new(rng)).is_ok() {
+        // The CPUs were seeded before the device was probed
+        crate::random::reseed_all();
+    }
+    Ok(())
+}
+
+/// Fill `buf` with entropy from the device.
+///
+/// Returns `false` if there is no entropy device or it did not deliver
+/// enough bytes.
+pub fn read_entropy(buf: &mut [u8]) -> bool {
+    let Some(rng) = RNG.get() else {
+        return false;
+    };
+    let mut rng = rng.lock();
+
+    let mut filled = 0;
+    while filled < buf.len() {
+        match rng.request_entropy(&mut buf[filled..]) {
+            Ok(0) | Err(_) => return false,
+            Ok(n) => filled += n,
+        }
+    }
+    true
+}
@@ -0,0 +1,225 @@
+//! Watchdog supervision
+//!
+//! The watchdog is only petted while the BPF programs attached to the
+//! watchdog liveness attach point say the system is healthy. A liveness
+//! program typically checks that a control loop has recently written its
+//! heartbeat into a map, and returns non-zero if so. Without any liveness
+//! program attached the watchdog is never petted.
+//!
+//! Liveness is checked from the timer tick, a few times per timeout. When no
+//! check has passed for a whole timeout, the kernel runs the emergency stop,
+//! so no actuator stays energized, and then resets the system. The hardware
+//! watchdog runs with a margin on top of the timeout, so it only fires
+//! itself if the kernel is too stuck to get there.
+//!
+//! The Raspberry Pi 5 uses the BCM2712 PM watchdog. Other boards get
+//! [`SoftWatchdog`], which relies on the timer tick alone.
+
+use alloc::boxed::Box;
+
+use kernel_abi::ESTOP_SOURCE_WATCHDOG;
+use kernel_bpf::attach::AttachEvent;
+use kernel_bpf::execution::BpfContext;
+use spin::Mutex;
+use thiserror::Error;
+
+use super::actuator;
+use crate::time::get_kernel_time_ns;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Error)]
+pub enum WatchdogError {
+    #[error("timeout out of range")]
+    InvalidTimeout,
+    #[error("no watchdog")]
+    NoWatchdog,
+}
+
+/// A watchdog timer that resets the system when it is not petted in time.
+pub trait Watchdog: Send {
+    fn name(&self) -> &'static str;
+
+    /// Longest supported timeout.
+    fn max_timeout_ms(&self) -> u32;
+
+    /// Start counting down from `timeout_ms`.
+    fn start(&mut self, timeout_ms: u32);
+
+    /// Restart the countdown.
+    fn pet(&mut self);
+
+    /// Stop counting down.
+    fn stop(&mut self);
+
+    /// Reset the system right away.
+    fn reset(&mut self) -> !;
+}
+
+/// Emulated watchdog for boards without one
+///
+/// Expiry is detected by the timer tick only; the reset goes through the
+/// architecture's reboot.
+pub struct SoftWatchdog;
+
+impl Watchdog for SoftWatchdog {
+    fn name(&self) -> &'static str {
+        "soft"
+    }
+
+    fn max_timeout_ms(&self) -> u32 {
+        u32::MAX
+    }
+
+    fn start(&mut self, _timeout_ms: u32) {}
+
+    fn pet(&mut self) {}
+
+    fn stop(&mut self) {}
+
+    fn reset(&mut self) -> ! {
+        #[cfg(target_arch = "x86_64")]
+        crate::arch::x86_64::reboot();
+        #[cfg(target_arch = "aarch64")]
+        crate::arch::aarch64::shutdown::reboot();
+        #[cfg(target_arch = "riscv64")]
+        crate::arch::riscv64::shutdown::reboot();
+    }
+}
+
+/// Context of a liveness program
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LivenessContext {
+    /// Time of this check
+    pub timestamp: u64,
+    /// Time of the last passed check, or of arming
+    pub last_healthy: u64,
+    /// Configured timeout
+    pub timeout_ns: u64,
+}
+
+/// Shortest accepted timeout, a few timer ticks
+const MIN_TIMEOUT_MS: u32 = 100;
+
+/// Extra time the hardware watchdog gets, so the kernel's expiry path runs
+/// first
+const HARDWARE_MARGIN_MS: u32 = 1000;
+
+/// Liveness checks per timeout
+const CHECKS_PER_TIMEOUT: u64 = 4;
+
+struct Supervisor {
+    watchdog: Box<dyn Watchdog>,
+    /// Configured timeout, 0 while disarmed
+    timeout_ns: u64,
+    last_healthy: u64,
+    last_check: u64,
+}
+
+impl Supervisor {
+    fn check_liveness(&mut self, now: u64) -> bool {
+        let liveness = LivenessContext {
+            timestamp: now,
+            last_healthy: self.last_healthy,
+            timeout_ns: self.timeout_ns,
+        };
+        // SAFETY: LivenessContext is repr(C) plain data; the slice only
+        // lives while the programs run.
+        let slice = unsafe {
+            core::slice::from_raw_parts(
+                &liveness as *const _ as *const u8,
+                core::mem::size_of::<LivenessContext>(),
+            )
+        };
+        let ctx = BpfContext::from_slice(slice);
+        crate::bpf::hooks::check_hooks(AttachEvent::Watchdog, &ctx) == Some(true)
+    }
+
+    fn expire(&mut self, now: u64) -> ! {
+        actuator::emergency_stop(ESTOP_SOURCE_WATCHDOG, 0);
+        log::error!(
+            "Watchdog expired: no healthy liveness check for {} ms, resetting",
+            (now - self.last_healthy) / 1_000_000
+        );
+        self.watchdog.reset()
+    }
+}
+
+static SUPERVISOR: Mutex<Option<Supervisor>> = Mutex::new(None);
+
+/// Find the board's watchdog. It stays stopped until [`configure`] arms it.
+pub fn init() {
+    #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
+    let watchdog: Box<dyn Watchdog> = {
+        use crate::arch::aarch64::platform::rpi5::watchdog::Bcm2712Watchdog;
+
+        // SAFETY: This is the only driver for the PM watchdog.
+        Box::new(unsafe { Bcm2712Watchdog::new() })
+    };
+    #[cfg(not(all(target_arch = "aarch64", feature = "rpi5")))]
+    let watchdog: Box<dyn Watchdog> = Box::new(SoftWatchdog);
+
+    log::info!("Watchdog: {}", watchdog.name());
+    *SUPERVISOR.lock() = Some(Supervisor {
+        watchdog,
+        timeout_ns: 0,
+        last_healthy: 0,
+        last_check: 0,
+    });
+}
+
+/// Arm the watchdog with `timeout_ms`, or disarm it with 0.
+///
+/// Arming starts a full timeout for the first liveness check to pass.
+pub fn configure(timeout_ms: u32) -> Result<(), WatchdogError> {
+    let mut supervisor = SUPERVISOR.lock();
+    let supervisor = supervisor.as_mut().ok_or(WatchdogError::NoWatchdog)?;
+
+    if timeout_ms == 0 {
+        supervisor.watchdog.stop();
+        supervisor.timeout_ns = 0;
+        log::warn!("Watchdog disarmed");
+        return Ok(());
+    }
+
+    let hardware_ms = timeout_ms
+        .checked_add(HARDWARE_MARGIN_MS)
+        .filter(|&ms| timeout_ms >= MIN_TIMEOUT_MS && ms <= supervisor.watchdog.max_timeout_ms())
+        .ok_or(WatchdogError::InvalidTimeout)?;
+
+    let now = get_kernel_time_ns();
+    supervisor.timeout_ns = u64::from(timeout_ms) * 1_000_000;
+    supervisor.last_healthy = now;
+    supervisor.last_check = now;
+    supervisor.watchdog.start(hardware_ms);
+    log::info!("Watchdog armed with {} ms timeout", timeout_ms);
+    Ok(())
+}
+
+/// Check liveness if due, and run the expiry path once the timeout passed.
+///
+/// Called from the timer interrupt on every CPU. A tick that finds the
+/// watchdog busy, such as being configured, skips.
+pub fn tick() {
+    let Some(mut supervisor) = SUPERVISOR.try_lock() else {
+        return;
+    };
+    let Some(supervisor) = supervisor.as_mut() else {
+        return;
+    };
+    if supervisor.timeout_ns == 0 {
+        return;
+    }
+
+    let now = get_kernel_time_ns();
+    if now.saturating_sub(supervisor.last_check) >= supervisor.timeout_ns / CHECKS_PER_TIMEOUT {
+        supervisor.last_check = now;
+        if supervisor.check_liveness(now) {
+            supervisor.last_healthy = now;
+            supervisor.watchdog.pet();
+        }
+    }
+
+    if now.saturating_sub(supervisor.last_healthy) >= supervisor.timeout_ns {
+        supervisor.expire(now);
+    }
+}
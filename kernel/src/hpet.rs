@@ -85,6 +85,52 @@ impl Hpet<'_> {
     pub fn period_femtoseconds(&self) -> u32 {
         self.inner.capabilities_and_id().read().counter_clk_period()
     }
+
+    #[must_use]
+    pub fn num_timers(&self) -> u8 {
+        self.inner.capabilities_and_id().read().num_timers() + 1
+    }
+
+    fn timer(&self, n: u8) -> VolatilePtr<'_, HpetTimer> {
+        self.inner.timers().as_slice().index(usize::from(n))
+    }
+
+    /// Bitmask of the I/O APIC inputs comparator `n` can interrupt.
+    #[must_use]
+    pub fn timer_route_cap(&self, n: u8) -> u32 {
+        self.timer(n).config().read().int_route_cap()
+    }
+
+    /// Configure comparator `n` for 64-bit one-shot, edge-triggered
+    /// interrupts on I/O APIC input `irq`, initially disabled.
+    pub fn setup_oneshot(&self, n: u8, irq: u8) {
+        self.timer(n).config().update(|mut c| {
+            c.set_int_enb_cnf(false);
+            c.set_level_triggered_cnf(false);
+            c.set_periodic_cnf(false);
+            c.set_mode32_cnf(false);
+            c.set_fsb_en_cnf(false);
+            c.set_int_route_cnf(irq);
+            c
+        });
+    }
+
+    /// Interrupt when the main counter reaches `counter`.
+    pub fn arm(&self, n: u8, counter: u64) {
+        let timer = self.timer(n);
+        timer.comparator().write(counter);
+        timer.config().update(|mut c| {
+            c.set_int_enb_cnf(true);
+            c
+        });
+    }
+
+    pub fn disarm(&self, n: u8) {
+        self.timer(n).config().update(|mut c| {
+            c.set_int_enb_cnf(false);
+            c
+        });
+    }
 }
 
 #[repr(C)]
@@ -115,7 +161,14 @@ const _: () = assert!(1280 == size_of::<Inner>());
 #[repr(C)]
 #[derive(Debug, VolatileFieldAccess)]
 pub struct HpetTimer {
-    raw: [u64; 4], // TODO: implement once we use this
+    #[access(ReadWrite)]
+    pub config: HpetTimerConfig,
+    #[access(ReadWrite)]
+    pub comparator: u64,
+    #[access(ReadWrite)]
+    pub fsb_route: u64,
+    #[access(NoAccess)]
+    _reserved: u64,
 }
 
 const _: () = assert!(32 == size_of::<HpetTimer>());
@@ -143,3 +196,22 @@ bitfield! {
     pub bool, legacy_replacement_cnf, set_legacy_replacement_cnf: 1;
     pub bool, enable_cnf, set_enable_cnf: 0;
 }
+
+bitfield! {
+    #[repr(transparent)]
+    #[derive(Copy, Clone)]
+    pub struct HpetTimerConfig(u64);
+    impl Debug;
+
+    pub u32, int_route_cap, _: 63, 32;
+    pub bool, fsb_int_del_cap, _: 15;
+    pub bool, fsb_en_cnf, set_fsb_en_cnf: 14;
+    pub u8, int_route_cnf, set_int_route_cnf: 13, 9;
+    pub bool, mode32_cnf, set_mode32_cnf: 8;
+    pub bool, val_set_cnf, set_val_set_cnf: 6;
+    pub bool, size_cap, _: 5;
+    pub bool, periodic_int_cap, _: 4;
+    pub bool, periodic_cnf, set_periodic_cnf: 3;
+    pub bool, int_enb_cnf, set_int_enb_cnf: 2;
+    pub bool, level_triggered_cnf, set_level_triggered_cnf: 1;
+}
@@ -0,0 +1,338 @@
+//! High-resolution timers
+//!
+//! Runs the BPF programs of [`HrTimerAttach`] points at nanosecond deadlines,
+//! independent of the scheduler tick. Up to [`MAX_TIMERS`] timers share one
+//! one-shot hardware timer, which is always programmed for the earliest
+//! deadline:
+//!
+//! - x86_64 uses the last HPET comparator, routed through the I/O APIC to
+//!   [`InterruptIndex::HrTimer`](crate::arch::idt::InterruptIndex) on the
+//!   bootstrap CPU.
+//! - aarch64 uses the virtual timer of the ARM generic timer (PPI 11); the
+//!   scheduler tick runs on the physical one. Its interrupt is delivered to
+//!   the CPU that last programmed it.
+//!
+//! Deadlines are on the monotonic clock of [`now_ns`]. A periodic timer
+//! keeps its phase: when it fires late, the next deadline is still the next
+//! multiple of the period, and the periods skipped are reported to its
+//! programs as overruns. On other architectures timers never fire.
+//!
+//! [`HrTimerAttach`]: kernel_bpf::attach::HrTimerAttach
+
+use kernel_bpf::attach::{
+    AttachError, AttachEvent, AttachHardware, AttachResult, TimerEvent, advance_expiry,
+    first_expiry,
+};
+use kernel_bpf::execution::BpfContext;
+use spin::Mutex;
+
+use crate::bpf::{hooks, tracepoint};
+
+/// Number of timers, and one more than the highest timer number
+pub const MAX_TIMERS: usize = 32;
+
+#[derive(Debug, Clone, Copy)]
+struct Timer {
+    /// Period, 0 for one-shot
+    period_ns: u64,
+    /// Next expiry, `None` while a one-shot timer is not armed
+    deadline: Option<u64>,
+}
+
+/// Timers in use, indexed by timer number
+static TIMERS: Mutex<[Option<Timer>; MAX_TIMERS]> = Mutex::new([None; MAX_TIMERS]);
+
+/// Set up the hardware timer. Timers never fire if this fails.
+pub fn init() {
+    if arch::init() {
+        log::info!("hrtimer: initialized");
+    } else {
+        log::warn!("hrtimer: no hardware timer, high-resolution timers disabled");
+    }
+}
+
+/// Get the time of the high-resolution clock in nanoseconds since boot.
+pub fn now_ns() -> u64 {
+    arch::now_ns()
+}
+
+/// Arm one-shot timer `timer` to fire `delay_ns` from now, or move the next
+/// expiry of a periodic timer. Periodic timers keep the new phase.
+///
+/// Fails if the timer is not in use.
+pub fn start(timer: u32, delay_ns: u64) -> AttachResult<()> {
+    crate::arch::without_interrupts(|| {
+        let mut timers = TIMERS.lock();
+        let slot = timers
+            .get_mut(timer as usize)
+            .and_then(Option::as_mut)
+            .ok_or(AttachError::ResourceNotFound)?;
+        slot.deadline = Some(now_ns().saturating_add(delay_ns));
+        reprogram(&timers);
+        Ok(())
+    })
+}
+
+/// Run the programs of the timers that are due and program the hardware for
+/// the next deadline.
+///
+/// Called from the hardware timer interrupt.
+pub fn expire() {
+    let mut due = [None::<TimerEvent>; MAX_TIMERS];
+
+    let now = now_ns();
+    crate::arch::without_interrupts(|| {
+        let mut timers = TIMERS.lock();
+        for (idx, slot) in timers.iter_mut().enumerate() {
+            let Some(timer) = slot else {
+                continue;
+            };
+            let Some(deadline) = timer.deadline.filter(|&deadline| deadline <= now) else {
+                continue;
+            };
+
+            let overruns = if timer.period_ns == 0 {
+                timer.deadline = None;
+                0
+            } else {
+                let (next, overruns) = advance_expiry(deadline, timer.period_ns, now);
+                timer.deadline = Some(next);
+                overruns
+            };
+            due[idx] = Some(TimerEvent {
+                expected_ns: deadline,
+                actual_ns: 0,
+                overruns,
+                period_ns: timer.period_ns,
+                timer: idx as u32,
+                cpu: tracepoint::cpu(),
+            });
+        }
+        // Program the next deadline before running the programs, so that
+        // their run time does not delay it
+        reprogram(&timers);
+    });
+
+    for event in due.iter_mut().flatten() {
+        event.actual_ns = now_ns();
+        run(event);
+    }
+}
+
+fn run(event: &TimerEvent) {
+    // SAFETY: TimerEvent is repr(C) plain data; the slice only lives while
+    // the programs run.
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            event as *const _ as *const u8,
+            core::mem::size_of::<TimerEvent>(),
+        )
+    };
+    let ctx = BpfContext::from_slice(slice);
+    hooks::execute_hooks(AttachEvent::HrTimer(event), &ctx);
+}
+
+/// Program the hardware for the earliest deadline, or stop it if no timer
+/// is armed.
+fn reprogram(timers: &[Option<Timer>; MAX_TIMERS]) {
+    match timers.iter().flatten().filter_map(|t| t.deadline).min() {
+        Some(deadline) => arch::program(deadline),
+        None => arch::cancel(),
+    }
+}
+
+/// The queue entry of an `HrTimerAttach` point, in use while programs are
+/// attached
+pub struct HrTimerSlot {
+    timer: u32,
+    period_ns: u64,
+    phase_ns: u64,
+}
+
+impl HrTimerSlot {
+    pub fn new(timer: u32, period_ns: u64, phase_ns: u64) -> Self {
+        Self {
+            timer,
+            period_ns,
+            phase_ns,
+        }
+    }
+}
+
+impl AttachHardware for HrTimerSlot {
+    fn enable(&mut self) -> AttachResult<()> {
+        crate::arch::without_interrupts(|| {
+            let mut timers = TIMERS.lock();
+            let slot = timers
+                .get_mut(self.timer as usize)
+                .ok_or(AttachError::ResourceNotFound)?;
+            if slot.is_some() {
+                return Err(AttachError::ResourceBusy);
+            }
+
+            *slot = Some(Timer {
+                period_ns: self.period_ns,
+                deadline: first_expiry(self.period_ns, self.phase_ns, now_ns()),
+            });
+            reprogram(&timers);
+            Ok(())
+        })
+    }
+
+    fn disable(&mut self) {
+        crate::arch::without_interrupts(|| {
+            let mut timers = TIMERS.lock();
+            if let Some(slot) = timers.get_mut(self.timer as usize) {
+                *slot = None;
+            }
+            reprogram(&timers);
+        });
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    use conquer_once::spin::OnceCell;
+    use x2apic::ioapic::{IrqFlags, IrqMode, RedirectionTableEntry};
+
+    use crate::apic::io_apic;
+    use crate::arch::idt::InterruptIndex;
+    use crate::hpet::hpet;
+    use crate::mcore::context::ExecutionContext;
+
+    /// Fewest counter ticks to program ahead of the counter
+    const MIN_TICKS: u64 = 16;
+
+    /// I/O APIC inputs the comparator may use; lower ones are ISA IRQs
+    const ROUTES: core::ops::Range<u8> = 16..24;
+
+    /// The HPET comparator in use
+    static COMPARATOR: OnceCell<u8> = OnceCell::uninit();
+
+    pub fn init() -> bool {
+        let hpet = hpet().read();
+        // The last comparator, which legacy replacement never takes
+        let comparator = hpet.num_timers() - 1;
+        let route_cap = hpet.timer_route_cap(comparator);
+        let Some(irq) = ROUTES.clone().find(|irq| route_cap & (1 << irq) != 0) else {
+            return false;
+        };
+        hpet.setup_oneshot(comparator, irq);
+
+        let dest = u8::try_from(ExecutionContext::load().lapic_id()).expect("invalid lapic id");
+        let mut entry = RedirectionTableEntry::default();
+        entry.set_mode(IrqMode::Fixed);
+        entry.set_flags(IrqFlags::empty());
+        entry.set_vector(InterruptIndex::HrTimer.as_u8());
+        entry.set_dest(dest);
+        let mut io_apic = io_apic().lock();
+        // SAFETY: The input is only driven by the comparator set up above,
+        // whose vector has a handler.
+        unsafe {
+            io_apic.set_table_entry(irq, entry);
+            io_apic.enable_irq(irq);
+        }
+
+        COMPARATOR.init_once(|| comparator);
+        log::info!("hrtimer: HPET comparator {} on IRQ {}", comparator, irq);
+        true
+    }
+
+    fn ticks_to_ns(ticks: u64, period_fs: u32) -> u64 {
+        (u128::from(ticks) * u128::from(period_fs) / 1_000_000) as u64
+    }
+
+    /// Round up, so the comparator never matches before the deadline
+    fn ns_to_ticks(ns: u64, period_fs: u32) -> u64 {
+        let period_fs = u128::from(period_fs.max(1));
+        (u128::from(ns) * 1_000_000).div_ceil(period_fs) as u64
+    }
+
+    pub fn now_ns() -> u64 {
+        let hpet = hpet().read();
+        ticks_to_ns(hpet.main_counter_value(), hpet.period_femtoseconds())
+    }
+
+    pub fn program(deadline_ns: u64) {
+        let Some(&comparator) = COMPARATOR.get() else {
+            return;
+        };
+        let hpet = hpet().read();
+        let mut ticks = ns_to_ticks(deadline_ns, hpet.period_femtoseconds());
+        loop {
+            hpet.arm(comparator, ticks);
+            // A comparator behind the counter only matches after the counter
+            // wraps, so make sure it was set in time
+            let counter = hpet.main_counter_value();
+            if counter < ticks {
+                break;
+            }
+            ticks = counter + MIN_TICKS;
+        }
+    }
+
+    pub fn cancel() {
+        if let Some(&comparator) = COMPARATOR.get() {
+            hpet().read().disarm(comparator);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use crate::arch::aarch64::gic;
+
+    pub fn init() -> bool {
+        cancel();
+        gic::enable_irq(gic::irq::TIMER_VIRT);
+        gic::set_priority(gic::irq::TIMER_VIRT, 0x80);
+        true
+    }
+
+    fn frequency() -> u64 {
+        let freq: u64;
+        // SAFETY: Reading the counter frequency has no side effects.
+        unsafe { core::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq) };
+        freq.max(1)
+    }
+
+    pub fn now_ns() -> u64 {
+        let counter: u64;
+        // SAFETY: Reading the virtual counter has no side effects.
+        unsafe { core::arch::asm!("mrs {}, cntvct_el0", out(reg) counter) };
+        (u128::from(counter) * 1_000_000_000 / u128::from(frequency())) as u64
+    }
+
+    pub fn program(deadline_ns: u64) {
+        // Round up, so the timer never fires before the deadline. A compare
+        // value already passed fires right away.
+        let cval = (u128::from(deadline_ns) * u128::from(frequency())).div_ceil(1_000_000_000);
+        let cval = u64::try_from(cval).unwrap_or(u64::MAX);
+        // SAFETY: The virtual timer is only used by this module.
+        unsafe {
+            core::arch::asm!("msr cntv_cval_el0, {}", in(reg) cval);
+            // Enable, unmasked
+            core::arch::asm!("msr cntv_ctl_el0, {}", in(reg) 1u64);
+        }
+    }
+
+    pub fn cancel() {
+        // SAFETY: Disabling the virtual timer also clears its interrupt.
+        unsafe { core::arch::asm!("msr cntv_ctl_el0, {}", in(reg) 0u64) };
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    pub fn init() -> bool {
+        false
+    }
+
+    pub fn now_ns() -> u64 {
+        crate::time::get_kernel_time_ns()
+    }
+
+    pub fn program(_deadline_ns: u64) {}
+
+    pub fn cancel() {}
+}
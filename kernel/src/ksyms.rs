@@ -0,0 +1,108 @@
+//! Kernel symbol table
+//!
+//! Maps kernel function names to addresses and back, from the `.symtab` of
+//! the kernel ELF. On x86_64 the bootloader hands us the kernel file; other
+//! boot paths have no file to read, so the table stays empty there until
+//! something calls [`load`].
+//!
+//! Names are demangled without the hash suffix, e.g.
+//! `kernel::syscall::dispatch_syscall`.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use conquer_once::spin::OnceCell;
+use elf::abi::STT_FUNC;
+use elf::endian::NativeEndian;
+use elf::{ElfBytes, ParseError};
+use log::{info, warn};
+
+static SYMBOLS: OnceCell<Vec<Symbol>> = OnceCell::uninit();
+
+/// A kernel function
+#[derive(Debug)]
+pub struct Symbol {
+    /// Address of the first instruction
+    pub addr: u64,
+    /// Size in bytes
+    pub size: u64,
+    /// Demangled name
+    pub name: String,
+}
+
+impl Symbol {
+    /// Check if `addr` lies within the function.
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.addr && addr - self.addr < self.size.max(1)
+    }
+}
+
+/// Load the symbol table from the kernel ELF the bootloader provided.
+pub fn init() {
+    #[cfg(target_arch = "x86_64")]
+    {
+        use core::slice::from_raw_parts;
+
+        use crate::U64Ext;
+        use crate::limine::KERNEL_FILE_REQUEST;
+
+        let Some(kernel_file) = KERNEL_FILE_REQUEST.get_response() else {
+            warn!("no kernel file, kernel symbols unavailable");
+            return;
+        };
+        let file = kernel_file.file();
+        // SAFETY: we keep the part of limine's higher half mapping that
+        // contains the kernel file, so the file stays readable for the
+        // lifetime of the kernel.
+        let file = unsafe { from_raw_parts(file.addr(), file.size().into_usize()) };
+        match load(file) {
+            Ok(count) => info!("loaded {count} kernel symbols"),
+            Err(e) => warn!("failed to load kernel symbols: {e:?}"),
+        }
+    }
+}
+
+/// Load the function symbols of the kernel ELF `file`. Only the first call
+/// has an effect.
+///
+/// Returns the number of symbols in the table.
+pub fn load(file: &'static [u8]) -> Result<usize, ParseError> {
+    let elf = ElfBytes::<NativeEndian>::minimal_parse(file)?;
+    let Some((symtab, strtab)) = elf.symbol_table()? else {
+        return Ok(0);
+    };
+
+    let mut symbols = Vec::new();
+    for sym in symtab.iter() {
+        if sym.st_symtype() != STT_FUNC || sym.st_value == 0 {
+            continue;
+        }
+        let name = strtab.get(sym.st_name as usize)?;
+        symbols.push(Symbol {
+            addr: sym.st_value,
+            size: sym.st_size,
+            name: format!("{:#}", rustc_demangle::demangle(name)),
+        });
+    }
+    symbols.sort_unstable_by_key(|s| s.addr);
+
+    Ok(SYMBOLS.get_or_init(|| symbols).len())
+}
+
+/// Check if the symbol table is available.
+pub fn is_loaded() -> bool {
+    SYMBOLS.get().is_some_and(|s| !s.is_empty())
+}
+
+/// Find a function by its demangled name.
+pub fn lookup(name: &str) -> Option<&'static Symbol> {
+    SYMBOLS.get()?.iter().find(|s| s.name == name)
+}
+
+/// Find the function containing `addr`.
+pub fn resolve(addr: u64) -> Option<&'static Symbol> {
+    let symbols = SYMBOLS.get()?;
+    let idx = symbols.partition_point(|s| s.addr <= addr).checked_sub(1)?;
+    symbols.get(idx).filter(|s| s.contains(addr))
+}
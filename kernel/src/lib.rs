@@ -24,12 +24,16 @@ pub mod driver;
 pub mod file;
 #[cfg(target_arch = "x86_64")]
 pub mod hpet;
+pub mod hrtimer;
+pub mod ksyms;
 #[cfg(target_arch = "x86_64")]
 pub mod limine;
 mod log;
 #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 pub mod mcore;
 pub mod mem;
+pub mod random;
+pub mod rcu;
 mod serial;
 
 // Provide a dummy allocator for non-x86_64 and non-aarch64 targets
@@ -97,6 +101,8 @@ pub fn init() {
     backtrace::init();
     info!("Backtrace initialized");
 
+    ksyms::init();
+
     info!("Initializing VFS...");
     file::init();
     info!("VFS initialized");
@@ -110,6 +116,9 @@ pub fn init() {
         info!("Initializing multicore/scheduler...");
         mcore::init();
         info!("Multicore/scheduler initialized");
+
+        info!("Initializing high-resolution timers...");
+        hrtimer::init();
     }
 
     #[cfg(target_arch = "x86_64")]
@@ -124,9 +133,23 @@ pub fn init() {
         info!("VirtIO MMIO initialized");
     }
 
-    info!("Initializing simulated devices...");
-    driver::iio::init_simulated_device();
-    info!("Simulated devices initialized");
+    info!("Initializing I2C and SPI buses...");
+    driver::bus::init();
+    info!("I2C and SPI buses initialized");
+
+    info!("Initializing sensors...");
+    driver::iio::init_sensors();
+    info!("Sensors initialized");
+
+    info!("Initializing CAN...");
+    driver::can::init();
+    info!("CAN initialized");
+
+    info!("Initializing UARTs...");
+    driver::uart::init();
+    info!("UARTs initialized");
+
+    driver::watchdog::init();
 
     info!("kernel initialized");
 }
@@ -17,6 +17,7 @@ use crate::mcore::lapic::Lapic;
 use crate::mcore::mtask::process::{Process, ProcessId};
 use crate::mcore::mtask::scheduler::Scheduler;
 use crate::mcore::mtask::task::Task;
+use crate::random::Prng;
 
 #[derive(Debug)]
 pub struct ExecutionContext {
@@ -35,6 +36,8 @@ pub struct ExecutionContext {
     _idt: &'static InterruptDescriptorTable,
 
     scheduler: UnsafeCell<Scheduler>,
+
+    prng: Prng,
 }
 
 impl ExecutionContext {
@@ -54,6 +57,7 @@ impl ExecutionContext {
             sel,
             _idt: idt,
             scheduler: UnsafeCell::new(Scheduler::new_cpu_local()),
+            prng: Prng::new(cpu.id as usize),
         }
     }
 
@@ -62,6 +66,7 @@ impl ExecutionContext {
         ExecutionContext {
             cpu_id,
             scheduler: UnsafeCell::new(Scheduler::new_cpu_local()),
+            prng: Prng::new(cpu_id),
         }
     }
 
@@ -116,6 +121,12 @@ impl ExecutionContext {
         self.cpu_id
     }
 
+    /// This CPU's pseudo-random number generator.
+    #[must_use]
+    pub fn prng(&self) -> &Prng {
+        &self.prng
+    }
+
     #[cfg(target_arch = "x86_64")]
     pub fn lapic_id(&self) -> usize {
         self.lapic_id
@@ -172,6 +172,25 @@ unsafe extern "C" fn cpu_init_and_idle(cpu: &limine::mp::Cpu) -> ! {
     turn_idle()
 }
 
+/// Returns the number of CPUs that were brought up.
+#[must_use]
+pub fn cpu_count() -> usize {
+    #[cfg(target_arch = "x86_64")]
+    {
+        // SAFETY: The response is only written during `init`, before any
+        // caller can observe it; this is a read-only access.
+        let resp = unsafe {
+            #[allow(static_mut_refs)]
+            MP_REQUEST.get_response()
+        };
+        resp.map_or(1, |resp| resp.cpus().len())
+    }
+
+    // Only the bootstrap CPU is started on AArch64.
+    #[cfg(target_arch = "aarch64")]
+    1
+}
+
 /// Makes the current task an idle task.
 ///
 /// This adapts the current task priority and affinity.
@@ -3,9 +3,10 @@ use core::slice;
 
 use kernel_vfs::node::VfsNode;
 use spin::mutex::Mutex;
-use crate::arch::{PhysFrame, PhysFrameRange as PhysFrameRangeInclusive, VirtAddr};
+use crate::arch::{PhysFrame, PhysFrameRange as PhysFrameRangeInclusive, Size4KiB, VirtAddr};
 
 use crate::UsizeExt;
+use crate::mem::address_space::AddressSpace;
 use crate::mem::virt::OwnedSegment;
 
 pub struct MemoryRegions {
@@ -33,18 +34,36 @@ impl MemoryRegions {
     where
         F: FnOnce(&MemoryRegion) -> R,
     {
-        self.regions
-            .lock()
-            .iter()
-            .find(|r| r.addr() <= addr && r.addr() + r.size().into_u64() > addr)
-            .map(f)
+        self.regions.lock().iter().find(|r| r.contains(addr)).map(f)
     }
 
     pub fn is_memory_region_at_address(&self, addr: VirtAddr) -> bool {
-        self.regions
-            .lock()
-            .iter()
-            .any(|r| r.addr() <= addr && r.addr() + r.size().into_u64() > addr)
+        self.regions.lock().iter().any(|r| r.contains(addr))
+    }
+
+    /// Unmap the pages of the shared regions from `address_space`.
+    ///
+    /// Their frames belong to whoever handed them out, such as a BPF map, so
+    /// they stay allocated.
+    pub fn unmap_shared(&self, address_space: &AddressSpace) {
+        let regions = self.regions.lock();
+        address_space.with_active(|address_space| {
+            for region in regions.iter() {
+                if let MemoryRegion::Shared(shared) = region {
+                    shared.unmap(address_space);
+                }
+            }
+        });
+    }
+
+    /// Run `f` on the regions if they are not locked.
+    ///
+    /// For callers that must not spin, such as code running in interrupts.
+    pub fn try_with_regions<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&[MemoryRegion]) -> R,
+    {
+        self.regions.try_lock().map(|regions| f(&regions))
     }
 }
 
@@ -67,6 +86,12 @@ pub enum MemoryRegion {
     ///
     /// - [`FileBackedMemoryRegion`]
     FileBacked(FileBackedMemoryRegion),
+    /// A memory region that maps memory owned by the kernel, such as
+    /// the buffers behind an `mmap`ed device file. The physical frames
+    /// are not freed with the region.
+    ///
+    /// - [`SharedMemoryRegion`]
+    Shared(SharedMemoryRegion),
 }
 
 impl MemoryRegion {
@@ -77,6 +102,7 @@ impl MemoryRegion {
             MemoryRegion::FileBacked(file_backed_memory_region) => {
                 file_backed_memory_region.region.segment.start
             }
+            MemoryRegion::Shared(shared_memory_region) => shared_memory_region.segment.start,
         }
     }
 
@@ -87,9 +113,14 @@ impl MemoryRegion {
             MemoryRegion::FileBacked(file_backed_memory_region) => {
                 file_backed_memory_region.region.size
             }
+            MemoryRegion::Shared(shared_memory_region) => shared_memory_region.size,
         }
     }
 
+    pub fn contains(&self, addr: VirtAddr) -> bool {
+        self.addr() <= addr && self.addr() + self.size().into_u64() > addr
+    }
+
     pub fn as_slice(&self) -> &[u8] {
         // SAFETY: The memory region represents valid memory with the tracked size.
         // We assume the caller ensures the memory is accessible.
@@ -153,6 +184,24 @@ impl Drop for MappedMemoryRegion {
     }
 }
 
+#[derive(Debug)]
+pub struct SharedMemoryRegion {
+    segment: OwnedSegment<'static>,
+    size: usize,
+}
+
+impl SharedMemoryRegion {
+    pub fn new(segment: OwnedSegment<'static>, size: usize) -> Self {
+        Self { segment, size }
+    }
+
+    /// Remove the region's pages from `address_space` without freeing the
+    /// frames behind them.
+    fn unmap(&self, address_space: &AddressSpace) {
+        address_space.unmap_range::<Size4KiB>(&*self.segment, |_| {});
+    }
+}
+
 #[derive(Debug)]
 pub struct FileBackedMemoryRegion {
     region: LazyMemoryRegion,
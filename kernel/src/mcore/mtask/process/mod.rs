@@ -25,6 +25,7 @@ use x86_64::registers::rflags::RFlags;
 #[cfg(target_arch = "x86_64")]
 use x86_64::structures::idt::InterruptStackFrameValue;
 
+use crate::bpf::syscall_filter::SyscallFilter;
 use crate::file::{OpenFileDescription, vfs};
 use crate::mcore::context::ExecutionContext;
 use crate::mcore::mtask::process::fd::{FdNum, FileDescriptor, FileDescriptorFlags};
@@ -69,6 +70,10 @@ pub struct Process {
     memory_regions: MemoryRegions,
 
     file_descriptors: RwLock<BTreeMap<FdNum, FileDescriptor>>,
+
+    /// Newest syscall filter, shared with children spawned after it was
+    /// installed
+    syscall_filter: RwLock<Option<Arc<SyscallFilter>>>,
 }
 
 impl Process {
@@ -94,6 +99,7 @@ impl Process {
                 telemetry: Telemetry::default(),
                 memory_regions: MemoryRegions::new(),
                 file_descriptors: RwLock::new(BTreeMap::new()),
+                syscall_filter: RwLock::new(None),
             });
             process_tree().write().processes.insert(pid, root.clone());
             root
@@ -128,6 +134,7 @@ impl Process {
             telemetry: Telemetry::default(),
             memory_regions: MemoryRegions::new(),
             file_descriptors: RwLock::new(BTreeMap::new()),
+            syscall_filter: RwLock::new(parent.syscall_filter.read().clone()),
         };
 
         let res = Arc::new(process);
@@ -188,6 +195,15 @@ impl Process {
         &self.name
     }
 
+    /// Whether the process may perform privileged operations.
+    ///
+    /// There are no users, so privilege follows the process tree: the root
+    /// process (kernel tasks) and its direct children (init) are privileged.
+    pub fn is_privileged(&self) -> bool {
+        let root = Self::root().pid();
+        self.pid == root || self.ppid() == root
+    }
+
     pub fn file_descriptors(&self) -> &RwLock<BTreeMap<FdNum, FileDescriptor>> {
         &self.file_descriptors
     }
@@ -213,6 +229,10 @@ impl Process {
     pub fn telemetry(&self) -> &Telemetry {
         &self.telemetry
     }
+
+    pub fn syscall_filter(&self) -> &RwLock<Option<Arc<SyscallFilter>>> {
+        &self.syscall_filter
+    }
 }
 
 impl Debug for Process {
@@ -240,6 +260,12 @@ impl Drop for Process {
             }
         }
 
+        // Shared mappings show memory owned elsewhere, which must not be
+        // reachable through this address space once it is gone
+        if let Some(address_space) = &self.address_space {
+            self.memory_regions.unmap_shared(address_space);
+        }
+
         // TODO: deallocate all physical frames that are not part of a shared mapping
     }
 }
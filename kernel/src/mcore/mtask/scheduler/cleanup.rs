@@ -17,11 +17,12 @@ impl TaskCleanup {
         log::info!("TaskCleanup: initialized");
     }
 
-    pub fn enqueue(_task: Pin<Box<Task>>) {
+    pub fn enqueue(task: Pin<Box<Task>>) {
         // TODO: implement actual cleanup queue
         // For now, we just let the task be dropped if it's finished,
         // but we need to be careful about where it's dropped.
         log::trace!("TaskCleanup: received zombie task");
+        crate::bpf::kprobe::release_task(task.id().as_u64());
     }
 
     extern "C" fn run(_arg: *mut core::ffi::c_void) {
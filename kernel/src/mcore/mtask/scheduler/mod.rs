@@ -7,6 +7,7 @@ use core::mem::swap;
 use core::pin::Pin;
 
 use cleanup::TaskCleanup;
+use kernel_bpf::attach::SchedSwitchEvent;
 #[cfg(target_arch = "x86_64")]
 use x86_64::instructions::interrupts;
 #[cfg(target_arch = "x86_64")]
@@ -16,6 +17,7 @@ use x86_64::registers::model_specific::FsBase;
 use crate::arch::aarch64::Aarch64 as Arch;
 #[cfg(all(target_arch = "aarch64", feature = "aarch64_arch"))]
 use crate::arch::traits::Architecture;
+use crate::bpf::tracepoint;
 use crate::mcore::mtask::scheduler::global::GlobalTaskQueue;
 use crate::mcore::mtask::scheduler::switch::switch_impl;
 use crate::mcore::mtask::task::Task;
@@ -92,6 +94,15 @@ impl Scheduler {
 
         let mut old_task = self.swap_current_task(next_task);
         log::trace!("reschedule: swapped current task, old task was {}", old_task.id());
+        tracepoint::SCHED_SWITCH.emit(|| SchedSwitchEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            prev_pid: old_task.process().pid().as_u64(),
+            prev_tid: old_task.id().as_u64(),
+            next_pid: self.current_task.process().pid().as_u64(),
+            next_tid: self.current_task.id().as_u64(),
+            cpu: tracepoint::cpu(),
+            prev_exiting: u32::from(old_task.should_terminate()),
+        });
         let old_stack_ptr = if old_task.should_terminate() {
             self.dummy_old_stack_ptr.get()
         } else {
@@ -10,10 +10,12 @@ use core::sync::atomic::Ordering::Relaxed;
 
 use cordyceps::Linked;
 use cordyceps::mpsc_queue::Links;
+use kernel_bpf::attach::TaskEvent;
 use log::trace;
 use spin::RwLock;
 
 use crate::U64Ext;
+use crate::bpf::tracepoint;
 use crate::mcore::context::ExecutionContext;
 use crate::mcore::mtask::process::Process;
 use crate::mem::memapi::{LowerHalfAllocation, Writable};
@@ -113,6 +115,11 @@ impl Task {
         let state = State::Ready;
         let last_stack_ptr = Box::pin(stack.initial_rsp().as_u64().into_usize());
         let links = Links::default();
+        tracepoint::TASK_CREATE.emit(|| TaskEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            pid: process.pid().as_u64(),
+            tid: tid.as_u64(),
+        });
         Self {
             tid,
             name,
@@ -154,6 +161,11 @@ impl Task {
     pub(crate) extern "C" fn exit() {
         let task = ExecutionContext::load().current_task();
         trace!("exiting task {}", task.name());
+        tracepoint::TASK_EXIT.emit(|| TaskEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            pid: task.process().pid().as_u64(),
+            tid: task.id().as_u64(),
+        });
 
         // SAFETY: We are forcefully unlocking locks held by the exiting task to avoid deadlocks
         // and cleaning up resources. This is done just before termination.
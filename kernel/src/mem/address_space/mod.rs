@@ -649,12 +649,36 @@ impl AddressSpace {
         self.inner.read().translate(vaddr)
     }
 
+    /// Call `f` with a [`translate`](Self::translate) function, keeping the
+    /// page tables unchanged until it returns. `None` rather than spinning
+    /// while the page tables are being changed.
+    #[cfg(target_arch = "x86_64")]
+    pub fn try_with_translate<R>(
+        &self,
+        f: impl FnOnce(&dyn Fn(VirtAddr) -> Option<PhysAddr>) -> R,
+    ) -> Option<R> {
+        let inner = self.inner.try_read()?;
+        Some(f(&|vaddr| inner.translate(vaddr)))
+    }
+
     #[allow(dead_code)]
     #[cfg(target_arch = "aarch64")]
     pub fn translate(&self, vaddr: VirtAddr) -> Option<PhysAddr> {
         self.inner.read().translate(vaddr)
     }
 
+    /// Call `f` with a [`translate`](Self::translate) function, keeping the
+    /// page tables unchanged until it returns. `None` rather than spinning
+    /// while the page tables are being changed.
+    #[cfg(target_arch = "aarch64")]
+    pub fn try_with_translate<R>(
+        &self,
+        f: impl FnOnce(&dyn Fn(VirtAddr) -> Option<PhysAddr>) -> R,
+    ) -> Option<R> {
+        let inner = self.inner.try_read()?;
+        Some(f(&|vaddr| inner.translate(vaddr)))
+    }
+
     #[cfg(target_arch = "aarch64")]
     pub fn map<S: PageSize>(
         &self,
@@ -0,0 +1,151 @@
+//! Kernel random numbers
+//!
+//! Hardware entropy sources are slow and not always present, so they are only
+//! used to seed a fast per-CPU pseudo-random generator. The generator is
+//! *not* cryptographically secure; it is meant for sampling and sharding,
+//! e.g. by `bpf_get_prandom_u32`.
+//!
+//! Entropy sources, in order of preference:
+//! - x86_64: `RDRAND`
+//! - Raspberry Pi 5: the BCM2712 RNG200 true random number generator
+//! - QEMU virt: a virtio entropy device, if one was found at boot
+//!
+//! If none is available, the seed is derived from the kernel clock. The
+//! virtio device is only probed after the CPUs are up, so once it is found
+//! every CPU's generator is reseeded from it with [`reseed_all`].
+
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering::Relaxed;
+
+use crate::time::get_kernel_time_ns;
+
+/// SplitMix64 increment (the golden ratio in 64-bit fixed point)
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Per-CPU reseed slots, more than the kernel brings up
+const CPU_SLOTS: usize = 64;
+
+/// Seeds read by [`reseed_all`], taken by each CPU's generator on its next
+/// use. 0 when none is pending.
+static RESEED: [AtomicU64; CPU_SLOTS] = [const { AtomicU64::new(0) }; CPU_SLOTS];
+
+/// Per-CPU pseudo-random number generator (SplitMix64).
+///
+/// The generator is seeded from hardware entropy when it is created with the
+/// CPU's execution context, as entropy sources take locks that interrupt
+/// handlers must not. It is only used from that CPU, but the state is atomic
+/// so that interrupt handlers may use it too.
+#[derive(Debug)]
+pub struct Prng {
+    /// Generator state
+    state: AtomicU64,
+    /// CPU the generator belongs to
+    cpu: usize,
+}
+
+impl Prng {
+    /// A generator for `cpu`, seeded from hardware entropy.
+    #[must_use]
+    pub fn new(cpu: usize) -> Self {
+        Self::from_seed(cpu, hardware_seed())
+    }
+
+    #[must_use]
+    pub const fn from_seed(cpu: usize, seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+            cpu,
+        }
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let reseed = RESEED[self.cpu % CPU_SLOTS].swap(0, Relaxed);
+        if reseed != 0 {
+            self.state.store(reseed, Relaxed);
+        }
+
+        let mut z = self
+            .state
+            .fetch_add(GOLDEN_GAMMA, Relaxed)
+            .wrapping_add(GOLDEN_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+}
+
+/// Read a 64-bit seed from the best available entropy source.
+#[must_use]
+pub fn hardware_seed() -> u64 {
+    read_hardware_entropy().unwrap_or_else(|| {
+        log::warn!("No entropy source available, seeding PRNG from the clock");
+        // No entropy source: fall back to the clock, mixed with the address
+        // of a stack variable so that CPUs booting together still diverge.
+        let marker = 0u8;
+        get_kernel_time_ns() ^ (&raw const marker as u64).rotate_left(32)
+    })
+}
+
+/// Reseed every CPU's generator from hardware entropy, for entropy sources
+/// that only come up after the CPUs.
+///
+/// Each generator picks up its new seed on its next use.
+pub fn reseed_all() {
+    let cpus = crate::mcore::cpu_count().min(CPU_SLOTS);
+    for slot in &RESEED[..cpus] {
+        let Some(seed) = read_hardware_entropy() else {
+            log::warn!("Entropy source failed, keeping PRNG seeds");
+            return;
+        };
+        slot.store(seed, Relaxed);
+    }
+    log::info!("Reseeded PRNG of {} CPU(s) from hardware entropy", cpus);
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_hardware_entropy() -> Option<u64> {
+    use raw_cpuid::CpuId;
+
+    if !CpuId::new()
+        .get_feature_info()
+        .is_some_and(|info| info.has_rdrand())
+    {
+        return None;
+    }
+
+    // RDRAND may transiently fail; Intel recommends retrying up to 10 times
+    (0..10).find_map(|_| {
+        let mut value = 0u64;
+        // SAFETY: CPUID reported RDRAND support above.
+        let ok = unsafe { core::arch::x86_64::_rdrand64_step(&mut value) };
+        (ok == 1).then_some(value)
+    })
+}
+
+#[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
+fn read_hardware_entropy() -> Option<u64> {
+    use crate::arch::aarch64::platform::rpi5::TRNG;
+
+    let trng = TRNG.lock();
+    let low = trng.read()?;
+    let high = trng.read()?;
+    Some(u64::from(high) << 32 | u64::from(low))
+}
+
+#[cfg(all(target_arch = "aarch64", feature = "virt", not(feature = "rpi5")))]
+fn read_hardware_entropy() -> Option<u64> {
+    let mut seed = [0u8; 8];
+    crate::driver::virtio::rng::read_entropy(&mut seed).then(|| u64::from_ne_bytes(seed))
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(target_arch = "aarch64", any(feature = "rpi5", feature = "virt"))
+)))]
+fn read_hardware_entropy() -> Option<u64> {
+    None
+}
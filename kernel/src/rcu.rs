@@ -0,0 +1,173 @@
+//! Epoch-based read-copy-update
+//!
+//! [`Rcu`] holds a pointer to an immutable value that readers use without
+//! taking a lock, which makes it safe to read from interrupt handlers. Writers
+//! replace the whole value and retire the old one; retired values are freed
+//! once no reader that could still see them is active.
+//!
+//! Every CPU has a reader slot recording how deeply it is nested in read-side
+//! sections and the global epoch at which the outermost one began. A value
+//! retired at epoch `r` is freed once every slot is either idle or entered
+//! after `r`. CPUs beyond [`READER_SLOTS`] share slots, which only delays
+//! reclamation.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Deref;
+use core::ptr;
+use core::sync::atomic::Ordering::SeqCst;
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64};
+
+use spin::Mutex;
+
+/// Number of reader slots
+pub const READER_SLOTS: usize = 64;
+
+/// Global epoch, advanced every time a value is retired
+static EPOCH: AtomicU64 = AtomicU64::new(0);
+
+static READERS: [ReaderSlot; READER_SLOTS] = [const { ReaderSlot::new() }; READER_SLOTS];
+
+struct ReaderSlot {
+    /// Depth of read-side sections active on this slot
+    nesting: AtomicU32,
+    /// Epoch at which the outermost active section began
+    epoch: AtomicU64,
+}
+
+impl ReaderSlot {
+    const fn new() -> Self {
+        Self {
+            nesting: AtomicU32::new(0),
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Check if no reader on this slot can see a value retired at `epoch`.
+    fn quiescent_since(&self, epoch: u64) -> bool {
+        self.nesting.load(SeqCst) == 0 || self.epoch.load(SeqCst) > epoch
+    }
+}
+
+fn current_slot() -> usize {
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    {
+        crate::mcore::context::ExecutionContext::try_load().map_or(0, |ctx| ctx.cpu_id())
+            % READER_SLOTS
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    0
+}
+
+/// A read-side critical section on the current CPU's slot.
+///
+/// The section ends when this is dropped, on whichever CPU that happens.
+struct ReadSection {
+    slot: usize,
+}
+
+impl ReadSection {
+    fn enter() -> Self {
+        let slot = current_slot();
+        let reader = &READERS[slot];
+        // The nesting count goes up before the epoch is recorded, so a
+        // writer that sees the slot idle also sees a stale epoch and waits.
+        if reader.nesting.fetch_add(1, SeqCst) == 0 {
+            reader.epoch.store(EPOCH.load(SeqCst), SeqCst);
+        }
+        Self { slot }
+    }
+}
+
+impl Drop for ReadSection {
+    fn drop(&mut self) {
+        READERS[self.slot].nesting.fetch_sub(1, SeqCst);
+    }
+}
+
+/// Access to the value of an [`Rcu`], valid until dropped.
+pub struct RcuReadGuard<'a, T> {
+    value: &'a T,
+    _section: ReadSection,
+}
+
+impl<T> Deref for RcuReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+/// A value that is read without locks and replaced as a whole.
+pub struct Rcu<T> {
+    current: AtomicPtr<T>,
+    /// Replaced values with the epoch they were retired at
+    retired: Mutex<Vec<(u64, Box<T>)>>,
+}
+
+// SAFETY: Readers on any CPU get shared references to the value, and writers
+// may drop it on any CPU.
+unsafe impl<T: Send + Sync> Sync for Rcu<T> {}
+// SAFETY: See above.
+unsafe impl<T: Send + Sync> Send for Rcu<T> {}
+
+impl<T> Rcu<T> {
+    /// Create an empty `Rcu`.
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicPtr::new(ptr::null_mut()),
+            retired: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get the current value, or `None` if nothing has been published yet.
+    ///
+    /// Never blocks, so it may be called from interrupt handlers.
+    pub fn read(&self) -> Option<RcuReadGuard<'_, T>> {
+        let section = ReadSection::enter();
+        // SAFETY: Published pointers come from `Box::into_raw`, and a
+        // replaced value is not freed while a section that may have loaded
+        // it is active.
+        let value = unsafe { self.current.load(SeqCst).as_ref() }?;
+        Some(RcuReadGuard {
+            value,
+            _section: section,
+        })
+    }
+
+    /// Replace the value. The old value is freed once no reader can see it.
+    ///
+    /// Must not be called from a read-side section or an interrupt handler.
+    pub fn publish(&self, value: Box<T>) {
+        let old = self.current.swap(Box::into_raw(value), SeqCst);
+        if !old.is_null() {
+            let epoch = EPOCH.fetch_add(1, SeqCst);
+            // SAFETY: `old` came from `Box::into_raw` and is no longer
+            // reachable through `current`.
+            let old = unsafe { Box::from_raw(old) };
+            self.retired.lock().push((epoch, old));
+        }
+        self.reclaim();
+    }
+
+    /// Free retired values that no reader can see anymore.
+    pub fn reclaim(&self) {
+        // Drop outside the lock; values may be arbitrarily expensive to free
+        let freed: Vec<_> = {
+            let mut retired = self.retired.lock();
+            let (freed, kept) = retired
+                .drain(..)
+                .partition(|(epoch, _)| READERS.iter().all(|r| r.quiescent_since(*epoch)));
+            *retired = kept;
+            freed
+        };
+        drop(freed);
+    }
+}
+
+impl<T> Default for Rcu<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
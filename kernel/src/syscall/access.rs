@@ -1,6 +1,7 @@
 use alloc::sync::Arc;
 use core::sync::atomic::Ordering::Relaxed;
 
+use kernel_bpf::attach::VfsIoEvent;
 use kernel_syscall::access::{CwdAccess, FileAccess};
 use kernel_syscall::stat::{StatAccess, UserStat, mode};
 use kernel_vfs::node::VfsNode;
@@ -8,6 +9,7 @@ use kernel_vfs::path::AbsolutePath;
 use spin::rwlock::RwLock;
 
 use crate::U64Ext;
+use crate::bpf::tracepoint;
 use crate::file::{OpenFileDescription, vfs};
 use crate::mcore::context::ExecutionContext;
 use crate::mcore::mtask::process::Process;
@@ -33,6 +35,27 @@ impl<'a> KernelAccess<'a> {
     }
 }
 
+impl KernelAccess<'_> {
+    /// Build the `vfs_read`/`vfs_write` tracepoint context.
+    fn io_event(
+        &self,
+        fd: FdNum,
+        offset: u64,
+        count: u64,
+        result: Result<usize, ()>,
+    ) -> VfsIoEvent {
+        VfsIoEvent {
+            timestamp: crate::time::get_kernel_time_ns(),
+            pid: self.process.pid().as_u64(),
+            offset,
+            count,
+            ret: result.map_or(-1, |n| n as i64),
+            fd: fd.into(),
+            _reserved: 0,
+        }
+    }
+}
+
 impl CwdAccess for KernelAccess<'_> {
     fn current_working_directory(&self) -> &RwLock<kernel_vfs::path::AbsoluteOwnedPath> {
         self.process.current_working_directory()
@@ -79,6 +102,9 @@ impl FileAccess for KernelAccess<'_> {
 
         self.process.file_descriptors().write().insert(num, fd);
 
+        tracepoint::VFS_OPEN.emit(|| {
+            tracepoint::vfs_open_event(self.process.pid().as_u64(), num.into(), info.node.path())
+        });
         Ok(num)
     }
 
@@ -89,7 +115,10 @@ impl FileAccess for KernelAccess<'_> {
         let desc = guard.get(&fd).ok_or(())?;
         let ofd = desc.file_description();
         let offset = ofd.position().fetch_add(buf.len() as u64, Relaxed); // TODO: respect file max len
-        ofd.read(buf, offset.into_usize()).map_err(|_| ())
+        let count = buf.len() as u64;
+        let result = ofd.read(buf, offset.into_usize()).map_err(|_| ());
+        tracepoint::VFS_READ.emit(|| self.io_event(fd, offset, count, result));
+        result
     }
 
     fn write(&self, fd: Self::Fd, buf: &[u8]) -> Result<usize, ()> {
@@ -99,7 +128,9 @@ impl FileAccess for KernelAccess<'_> {
         let desc = guard.get(&fd).ok_or(())?;
         let ofd = desc.file_description();
         let offset = ofd.position().fetch_add(buf.len() as u64, Relaxed); // TODO: respect file max len
-        ofd.write(buf, offset.into_usize()).map_err(|_| ())
+        let result = ofd.write(buf, offset.into_usize()).map_err(|_| ());
+        tracepoint::VFS_WRITE.emit(|| self.io_event(fd, offset, buf.len() as u64, result));
+        result
     }
 
     fn close(&self, fd: Self::Fd) -> Result<(), ()> {
@@ -220,6 +251,21 @@ impl kernel_syscall::access::MemoryRegionAccess for KernelAccess<'_> {
         Ok(addr)
     }
 
+    fn create_and_track_file_mapping(
+        &self,
+        location: kernel_syscall::access::Location,
+        size: usize,
+        prot: kernel_abi::ProtFlags,
+        fd: core::ffi::c_int,
+        offset: usize,
+    ) -> Result<kernel_syscall::UserspacePtr<u8>, kernel_syscall::access::CreateMappingError> {
+        let region_handle = self.create_file_mapping(location, size, prot, fd.into(), offset)?;
+        let addr = region_handle.addr;
+        self.add_memory_region(region_handle);
+
+        Ok(addr)
+    }
+
     fn add_memory_region(&self, region: Self::Region) {
         self.process.memory_regions().add_region(region.inner);
     }
@@ -1,13 +1,21 @@
+use alloc::vec::Vec;
+
+use kernel_abi::ProtFlags;
 use kernel_syscall::UserspacePtr;
 use kernel_syscall::access::{
     AllocationStrategy, CreateMappingError, Location, Mapping, MemoryAccess,
 };
+use kernel_vfs::MmapError;
 use kernel_virtual_memory::Segment;
 
-use crate::arch::types::{PageSize, PageTableFlags, PhysFrameRangeInclusive, Size4KiB, VirtAddr};
+use crate::arch::types::{
+    PageSize, PageTableFlags, PhysFrame, PhysFrameRangeInclusive, Size4KiB, VirtAddr,
+};
 
 use crate::UsizeExt;
-use crate::mcore::mtask::process::mem::{MappedMemoryRegion, MemoryRegion};
+use crate::mcore::mtask::process::fd::FdNum;
+use crate::mcore::mtask::process::mem::{MappedMemoryRegion, MemoryRegion, SharedMemoryRegion};
+use crate::mem::address_space::AddressSpace;
 use crate::mem::phys::PhysicalMemory;
 use crate::mem::virt::{OwnedSegment, VirtualMemoryAllocator};
 use crate::syscall::access::{KernelAccess, KernelMemoryRegionHandle};
@@ -71,6 +79,83 @@ impl MemoryAccess for KernelAccess<'_> {
     }
 }
 
+impl KernelAccess<'_> {
+    /// Map `size` bytes of the file open at `fd`, starting at `offset`, to
+    /// the memory the file hands out, and return the tracked region. The
+    /// pages are read-only unless `prot` allows writes.
+    pub(super) fn create_file_mapping(
+        &self,
+        location: Location,
+        size: usize,
+        prot: ProtFlags,
+        fd: FdNum,
+        offset: usize,
+    ) -> Result<KernelMemoryRegionHandle, CreateMappingError> {
+        let page_aligned_size = size.next_multiple_of(Size4KiB::SIZE as usize);
+        let page_count = page_aligned_size / Size4KiB::SIZE as usize;
+
+        let kernel_addr = {
+            let fds = self.process.file_descriptors();
+            let guard = fds.read();
+            let desc = guard.get(&fd).ok_or(CreateMappingError::BadFile)?;
+            desc.file_description()
+                .mmap(offset, page_aligned_size)
+                .map_err(|e| match e {
+                    MmapError::NotMappable => CreateMappingError::NotMappable,
+                    MmapError::OutOfMemory => CreateMappingError::OutOfMemory,
+                    MmapError::InvalidRange | MmapError::FsError(_) => {
+                        CreateMappingError::InvalidRange
+                    }
+                })?
+        };
+
+        // The file's memory need not be physically contiguous, so every
+        // page is looked up on its own.
+        let frames = (0..page_count)
+            .map(|i| {
+                let page = VirtAddr::new((kernel_addr + i * Size4KiB::SIZE as usize).into_u64());
+                AddressSpace::kernel()
+                    .translate(page)
+                    .map(PhysFrame::<Size4KiB>::containing_address)
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(CreateMappingError::InvalidRange)?;
+
+        let segment = if let Location::Fixed(addr) = location {
+            self.process
+                .vmm()
+                .mark_as_reserved(Segment::new(
+                    VirtAddr::from_ptr(addr.as_ptr()),
+                    page_aligned_size.into_u64(),
+                ))
+                .map_err(|_| CreateMappingError::LocationAlreadyMapped)?
+        } else {
+            self.process
+                .vmm()
+                .reserve(page_count)
+                .ok_or(CreateMappingError::OutOfMemory)?
+        };
+
+        let mut flags =
+            PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE | PageTableFlags::NO_EXECUTE;
+        if prot.contains(ProtFlags::WRITE) {
+            flags |= PageTableFlags::WRITABLE;
+        }
+        self.process
+            .address_space()
+            .map_range::<Size4KiB>(&*segment, frames.into_iter(), flags)
+            .map_err(|_| CreateMappingError::OutOfMemory)?;
+
+        let addr = segment
+            .start
+            .as_ptr::<u8>()
+            .try_into()
+            .expect("file mapping should be located in user space");
+        let inner = MemoryRegion::Shared(SharedMemoryRegion::new(segment, size));
+        Ok(KernelMemoryRegionHandle { addr, size, inner })
+    }
+}
+
 pub struct KernelMapping {
     addr: VirtAddr,
     size: usize,
@@ -2,13 +2,84 @@ use alloc::vec::Vec;
 use core::mem::size_of;
 
 use kernel_abi::{
-    BPF_MAP_CREATE, BPF_MAP_DELETE_ELEM, BPF_MAP_LOOKUP_ELEM, BPF_MAP_UPDATE_ELEM, BPF_PROG_ATTACH,
+    BPF_BTF_LOAD, BPF_MAP_CREATE, BPF_MAP_DELETE_ELEM, BPF_MAP_GET_NEXT_KEY, BPF_MAP_LOOKUP_ELEM,
+    BPF_MAP_QUERY_WINDOW, BPF_MAP_UPDATE_ELEM, BPF_PROG_ATTACH, BPF_PROG_DETACH, BPF_PROG_DUMP,
     BPF_PROG_LOAD, BPF_PROG_LOAD_ELF, BpfAttr,
 };
 use kernel_bpf::bytecode::insn::BpfInsn;
+use kernel_bpf::debug::LineTable;
+use kernel_bpf::loader::BpfLineInfo;
 
 use super::validation::{copy_from_userspace, copy_to_userspace, read_userspace_slice};
 use crate::BPF_MANAGER;
+use crate::bpf::{ATTACH_TYPE_SYSCALL_FILTER, attach, syscall_filter};
+
+/// Maximum number of line info records accepted with a program.
+const MAX_LINE_INFO: usize = 4096;
+
+/// Copy text into the userspace log buffer described by `attr`.
+///
+/// The text is truncated to `log_size - 1` bytes and NUL-terminated.
+/// Returns the number of bytes written, excluding the terminator.
+fn write_log(attr: &BpfAttr, text: &str) -> Result<usize, ()> {
+    if attr.log_buf == 0 || attr.log_size == 0 {
+        return Ok(0);
+    }
+
+    let len = text.len().min(attr.log_size as usize - 1);
+    let mut buf = Vec::with_capacity(len + 1);
+    buf.extend_from_slice(&text.as_bytes()[..len]);
+    buf.push(0);
+
+    copy_to_userspace(attr.log_buf as usize, &buf).map_err(|_| ())?;
+    Ok(len)
+}
+
+/// Read the line info records supplied with `BPF_PROG_LOAD`.
+///
+/// The records refer to strings in the BTF blob named by `prog_btf_fd`.
+/// Returns `Ok(None)` when no line info was supplied.
+fn read_line_table(attr: &BpfAttr, insn_cnt: usize) -> Result<Option<LineTable>, ()> {
+    let cnt = attr.line_info_cnt as usize;
+    if cnt == 0 {
+        return Ok(None);
+    }
+
+    let rec_size = attr.line_info_rec_size as usize;
+    if rec_size < BpfLineInfo::SIZE || cnt > MAX_LINE_INFO || attr.line_info == 0 {
+        log::error!(
+            "sys_bpf: invalid line info (rec_size={}, cnt={})",
+            rec_size,
+            cnt
+        );
+        return Err(());
+    }
+
+    let bytes = read_userspace_slice(attr.line_info as usize, cnt * rec_size).map_err(|_| ())?;
+
+    let mut records = Vec::with_capacity(cnt);
+    for chunk in bytes.chunks_exact(rec_size) {
+        let record = BpfLineInfo::from_bytes(chunk).map_err(|_| ())?;
+        if record.insn_off as usize >= insn_cnt {
+            log::error!(
+                "sys_bpf: line info for instruction {} out of range",
+                record.insn_off
+            );
+            return Err(());
+        }
+        records.push(record);
+    }
+
+    let manager = BPF_MANAGER.get().ok_or(())?;
+    let manager = manager.lock();
+    let btf = manager.btf(attr.prog_btf_fd).ok_or_else(|| {
+        log::error!("sys_bpf: unknown BTF id {}", attr.prog_btf_fd);
+    })?;
+
+    LineTable::from_btf(btf, &records).map(Some).map_err(|_| {
+        log::error!("sys_bpf: line info refers to invalid BTF strings");
+    })
+}
 
 pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
     // Security Hardening: Validate the attribute size matches expected struct size
@@ -184,6 +255,52 @@ pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
                 -1
             }
         }
+        BPF_MAP_GET_NEXT_KEY => {
+            log::debug!("sys_bpf: MAP_GET_NEXT_KEY");
+            let attr = match copy_from_userspace::<BpfAttr>(attr_ptr) {
+                Ok(a) => a,
+                Err(_) => return -1,
+            };
+
+            // A null key asks for the first key
+            let map_id = attr.map_fd;
+            let key_ptr = attr.key as *const u8;
+            let next_key_ptr = attr.value as *mut u8;
+
+            if next_key_ptr.is_null() {
+                return -1;
+            }
+
+            if let Some(manager) = BPF_MANAGER.get() {
+                let mgr = manager.lock();
+
+                let key_size = if let Some(def) = mgr.get_map_def(map_id) {
+                    def.key_size as usize
+                } else {
+                    return -1; // Invalid map_fd
+                };
+
+                let key = if key_ptr.is_null() {
+                    None
+                } else {
+                    match read_userspace_slice(key_ptr as usize, key_size) {
+                        Ok(k) => Some(k),
+                        Err(_) => return -1,
+                    }
+                };
+
+                if let Some(next_key) = mgr.map_get_next_key(map_id, key.as_deref()) {
+                    if copy_to_userspace(next_key_ptr as usize, &next_key).is_err() {
+                        return -1;
+                    }
+                    0
+                } else {
+                    -2 // ENOENT
+                }
+            } else {
+                -1
+            }
+        }
         BPF_PROG_ATTACH => {
             log::info!("sys_bpf: PROG_ATTACH");
             let attr = match copy_from_userspace::<BpfAttr>(attr_ptr) {
@@ -194,63 +311,28 @@ pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
             let attach_type = attr.attach_btf_id;
             let prog_id = attr.attach_prog_fd;
 
-            if let Some(manager) = BPF_MANAGER.get() {
-                match manager.lock().attach(attach_type, prog_id) {
-                    Ok(_) => {
-                        log::info!("sys_bpf: attached prog {} to type {}", prog_id, attach_type);
-
-                        // For GPIO attach type, also configure hardware interrupts
-                        #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
-                        if attach_type == crate::bpf::ATTACH_TYPE_GPIO {
-                            // Use key as GPIO pin number, value as edge flags
-                            // edge flags: 1 = rising, 2 = falling, 3 = both
-                            let pin = attr.key as u8;
-                            let edge_flags = attr.value as u32;
-
-                            if pin < 28 {
-                                // SAFETY: Rp1Gpio::new() creates an interface to memory-mapped
-                                // GPIO registers. This is safe because:
-                                // 1. We are on aarch64 with rpi5 feature enabled (checked by cfg)
-                                // 2. The GPIO base address is hardcoded for RPi5 platform
-                                // 3. We have validated the pin number is in range 0-27
-                                // 4. The kernel has exclusive access to GPIO hardware
-                                let gpio = unsafe {
-                                    crate::arch::aarch64::platform::rpi5::gpio::Rp1Gpio::new()
-                                };
-
-                                // Configure pin as input for edge detection
-                                gpio.configure_input(pin);
-
-                                // Enable interrupts based on edge flags
-                                let rising = (edge_flags & 1) != 0;
-                                let falling = (edge_flags & 2) != 0;
-
-                                // Default to both edges if none specified
-                                let (rising, falling) = if !rising && !falling {
-                                    (true, true)
-                                } else {
-                                    (rising, falling)
-                                };
-
-                                gpio.enable_interrupt(pin, rising, falling);
-                                log::info!(
-                                    "sys_bpf: enabled GPIO{} interrupt (rising={}, falling={})",
-                                    pin,
-                                    rising,
-                                    falling
-                                );
-                            } else {
-                                log::warn!("sys_bpf: invalid GPIO pin {} (must be 0-27)", pin);
-                            }
-                        }
+            if attach_type == ATTACH_TYPE_SYSCALL_FILTER {
+                return match syscall_filter::install(prog_id) {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        log::error!("sys_bpf: syscall filter install failed: {}", e);
+                        -1
+                    }
+                };
+            }
 
-                        // For IIO attach type, we might want to validate or configure the sensor channel
-                        if attach_type == crate::bpf::ATTACH_TYPE_IIO {
-                            log::info!("sys_bpf: attached BPF program {} to IIO sensor event", prog_id);
-                            // In a full implementation, we would use attr.key and attr.value
-                            // to identify the specific sensor device and channel to enable.
-                        }
+            let (config, point) = match attach::resolve(attach_type, attr.key, attr.value) {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    log::warn!("sys_bpf: {}", e);
+                    return -1;
+                }
+            };
 
+            if let Some(manager) = BPF_MANAGER.get() {
+                match manager.lock().attach(&config, point, prog_id) {
+                    Ok(_) => {
+                        log::info!("sys_bpf: attached prog {} to {}", prog_id, config.target);
                         0
                     }
                     Err(e) => {
@@ -262,6 +344,35 @@ pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
                 -1
             }
         }
+        BPF_PROG_DETACH => {
+            log::info!("sys_bpf: PROG_DETACH");
+            let attr = match copy_from_userspace::<BpfAttr>(attr_ptr) {
+                Ok(a) => a,
+                Err(_) => return -1,
+            };
+
+            // Same fields as PROG_ATTACH
+            let prog_id = attr.attach_prog_fd;
+            let config = match attach::resolve(attr.attach_btf_id, attr.key, attr.value) {
+                Ok((config, _)) => config,
+                Err(e) => {
+                    log::warn!("sys_bpf: {}", e);
+                    return -1;
+                }
+            };
+
+            if let Some(manager) = BPF_MANAGER.get() {
+                match manager.lock().detach(&config, prog_id) {
+                    Ok(()) => 0,
+                    Err(e) => {
+                        log::error!("sys_bpf: detach failed: {}", e);
+                        -1
+                    }
+                }
+            } else {
+                -1
+            }
+        }
         BPF_PROG_LOAD => {
             log::info!("sys_bpf: PROG_LOAD");
 
@@ -301,10 +412,22 @@ pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
                 }
             }
 
+            let line_table = match read_line_table(&attr, insn_cnt) {
+                Ok(table) => table,
+                Err(()) => return -1,
+            };
+
             if let Some(manager) = BPF_MANAGER.get() {
-                match manager.lock().load_raw_program(insns) {
+                let mut manager = manager.lock();
+                match manager.load_raw_program(insns, line_table) {
                     Ok(id) => {
                         log::info!("sys_bpf: program loaded with id {}", id);
+                        if let Some(report) = manager.verifier_report(id) {
+                            log::warn!("sys_bpf: verifier: {}", report);
+                            if attr.log_level != 0 && write_log(&attr, &report).is_err() {
+                                return -1;
+                            }
+                        }
                         id as isize
                     }
                     Err(e) => {
@@ -349,9 +472,16 @@ pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
             };
 
             if let Some(manager) = BPF_MANAGER.get() {
-                match manager.lock().load_program(&elf_bytes) {
+                let mut manager = manager.lock();
+                match manager.load_program(&elf_bytes) {
                     Ok(id) => {
                         log::info!("sys_bpf: ELF program loaded with id {}", id);
+                        if let Some(report) = manager.verifier_report(id) {
+                            log::warn!("sys_bpf: verifier: {}", report);
+                            if attr.log_level != 0 && write_log(&attr, &report).is_err() {
+                                return -1;
+                            }
+                        }
                         id as isize
                     }
                     Err(e) => {
@@ -364,6 +494,117 @@ pub fn sys_bpf(cmd: usize, attr_ptr: usize, size: usize) -> isize {
                 -1
             }
         }
+        BPF_BTF_LOAD => {
+            log::info!("sys_bpf: BTF_LOAD");
+
+            let attr = match copy_from_userspace::<BpfAttr>(attr_ptr) {
+                Ok(a) => a,
+                Err(_) => return -1,
+            };
+
+            // reusing insn_cnt for blob size and insns for blob pointer
+            let btf_size = attr.insn_cnt as usize;
+            let btf_ptr = attr.insns as usize;
+
+            if btf_ptr == 0 || btf_size == 0 || btf_size > 1024 * 1024 {
+                log::error!(
+                    "sys_bpf: invalid BTF blob (ptr={:#x}, size={})",
+                    btf_ptr,
+                    btf_size
+                );
+                return -1;
+            }
+
+            let btf_bytes = match read_userspace_slice(btf_ptr, btf_size) {
+                Ok(bytes) => bytes,
+                Err(_) => return -1,
+            };
+
+            if let Some(manager) = BPF_MANAGER.get() {
+                match manager.lock().load_btf(&btf_bytes) {
+                    Ok(id) => {
+                        log::info!("sys_bpf: BTF loaded with id {}", id);
+                        id as isize
+                    }
+                    Err(_) => {
+                        log::error!("sys_bpf: invalid BTF blob");
+                        -1
+                    }
+                }
+            } else {
+                log::error!("sys_bpf: BPF_MANAGER not initialized");
+                -1
+            }
+        }
+        BPF_PROG_DUMP => {
+            log::info!("sys_bpf: PROG_DUMP");
+
+            let attr = match copy_from_userspace::<BpfAttr>(attr_ptr) {
+                Ok(a) => a,
+                Err(_) => return -1,
+            };
+
+            // attach_prog_fd -> program id, log_buf/log_size -> output buffer
+            let prog_id = attr.attach_prog_fd;
+
+            let Some(manager) = BPF_MANAGER.get() else {
+                log::error!("sys_bpf: BPF_MANAGER not initialized");
+                return -1;
+            };
+
+            let Some(dump) = manager.lock().dump_program(prog_id) else {
+                log::error!("sys_bpf: no program with id {}", prog_id);
+                return -1;
+            };
+
+            match write_log(&attr, &dump) {
+                Ok(len) => len as isize,
+                Err(()) => -1,
+            }
+        }
+        BPF_MAP_QUERY_WINDOW => {
+            log::debug!("sys_bpf: MAP_QUERY_WINDOW");
+
+            let attr = match copy_from_userspace::<BpfAttr>(attr_ptr) {
+                Ok(a) => a,
+                Err(_) => return -1,
+            };
+
+            // map_fd -> map id, key/value -> window start/end in ns,
+            // log_buf/log_size -> output buffer
+            let Some(manager) = BPF_MANAGER.get() else {
+                log::error!("sys_bpf: BPF_MANAGER not initialized");
+                return -1;
+            };
+
+            let samples = match manager
+                .lock()
+                .map_query_window(attr.map_fd, attr.key, attr.value)
+            {
+                Ok(samples) => samples,
+                Err(e) => {
+                    log::error!("sys_bpf: MAP_QUERY_WINDOW failed: {}", e);
+                    return -1;
+                }
+            };
+
+            // Whole (timestamp, value) records, as many as fit, oldest first
+            let mut buf = Vec::new();
+            let mut count = 0;
+            for (timestamp, value) in &samples {
+                if buf.len() + size_of::<u64>() + value.len() > attr.log_size as usize {
+                    break;
+                }
+                buf.extend_from_slice(&timestamp.to_ne_bytes());
+                buf.extend_from_slice(value);
+                count += 1;
+            }
+
+            if !buf.is_empty() && copy_to_userspace(attr.log_buf as usize, &buf).is_err() {
+                return -1;
+            }
+            count
+        }
         _ => {
             log::warn!("sys_bpf: Unknown command {}", cmd);
             -1
@@ -4,7 +4,9 @@ use core::slice::{from_raw_parts, from_raw_parts_mut};
 
 #[cfg(target_arch = "x86_64")]
 use access::KernelAccess;
-use kernel_abi::{EINVAL, Errno, syscall_name};
+use kernel_abi::{EAGAIN, EBUSY, EEXIST, EINVAL, EIO, ENODEV, EPERM, Errno, syscall_name};
+use kernel_bpf::attach::AttachEvent;
+use kernel_bpf::execution::{BpfContext, SyscallExitContext, SyscallTraceContext};
 #[cfg(target_arch = "x86_64")]
 use kernel_syscall::{
     UserspaceMutPtr, UserspacePtr,
@@ -23,6 +25,11 @@ use log::{error, trace};
 #[cfg(target_arch = "x86_64")]
 use x86_64::instructions::hlt;
 
+use crate::bpf::syscall_filter::{self, Verdict};
+use crate::driver::actuator::{self, Actuator, ActuatorError, Output};
+use crate::driver::can::{self, CanError};
+use crate::driver::watchdog::{self, WatchdogError};
+
 #[cfg(not(target_arch = "x86_64"))]
 fn hlt() {
     #[cfg(target_arch = "riscv64")]
@@ -46,7 +53,7 @@ mod access;
 pub mod bpf;
 #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
 pub mod pwm;
-mod validation;
+pub(crate) mod validation;
 
 #[must_use]
 pub fn dispatch_syscall(
@@ -63,53 +70,34 @@ pub fn dispatch_syscall(
         syscall_name(n)
     );
 
-    // Run BPF hooks (AttachType::Syscall = 2) at syscall entry
-    #[cfg(target_arch = "x86_64")]
-    if let Some(manager) = crate::BPF_MANAGER.get() {
-        use kernel_bpf::execution::SyscallTraceContext;
-
-        let trace_ctx = SyscallTraceContext {
-            syscall_nr: n as u64,
-            arg1: arg1 as u64,
-            arg2: arg2 as u64,
-            arg3: arg3 as u64,
-            arg4: arg4 as u64,
-            arg5: arg5 as u64,
-            arg6: arg6 as u64,
-        };
-
-        // SAFETY: We are creating a slice from a stack-allocated struct.
-        // The slice is only used within this scope to create the BpfContext.
-        let slice = unsafe {
-            core::slice::from_raw_parts(
-                &trace_ctx as *const _ as *const u8,
-                core::mem::size_of::<SyscallTraceContext>(),
-            )
-        };
+    let trace_ctx = SyscallTraceContext {
+        syscall_nr: n as u64,
+        arg1: arg1 as u64,
+        arg2: arg2 as u64,
+        arg3: arg3 as u64,
+        arg4: arg4 as u64,
+        arg5: arg5 as u64,
+        arg6: arg6 as u64,
+    };
+    run_hooks(AttachEvent::Syscall(&trace_ctx), &trace_ctx);
 
-        let ctx = kernel_bpf::execution::BpfContext::from_slice(slice);
-        manager.lock().execute_hooks(crate::bpf::ATTACH_TYPE_SYSCALL, &ctx);
+    match syscall_filter::check(&trace_ctx) {
+        Verdict::Allow => {}
+        Verdict::Errno(e) => {
+            trace!("syscall {} ({n}) denied by syscall filter", syscall_name(n));
+            return finish(n, Err(e));
+        }
+        Verdict::Kill => {
+            error!(
+                "syscall {} ({n}) denied by syscall filter, killing process",
+                syscall_name(n)
+            );
+            exit_current(syscall_filter::KILL_EXIT_CODE);
+        }
     }
 
     let result: Result<usize, Errno> = match n {
-        #[cfg(target_arch = "x86_64")]
-        kernel_abi::SYS_EXIT => {
-            let status = i32::try_from(arg1).unwrap_or(0);
-            let task = crate::mcore::context::ExecutionContext::load().current_task();
-            let process = task.process();
-            *process.exit_code().write() = Some(status);
-            task.set_should_terminate(true);
-            loop {
-                hlt();
-            }
-        }
-        #[cfg(not(target_arch = "x86_64"))]
-        kernel_abi::SYS_EXIT => {
-            error!("SYS_EXIT not implemented for aarch64/riscv64");
-            loop {
-                hlt();
-            }
-        }
+        kernel_abi::SYS_EXIT => exit_current(i32::try_from(arg1).unwrap_or(0)),
         kernel_abi::SYS_GETCWD => dispatch_sys_getcwd(arg1, arg2),
         kernel_abi::SYS_MMAP => dispatch_sys_mmap(arg1, arg2, arg3, arg4, arg5, arg6),
         kernel_abi::SYS_OPEN => dispatch_sys_open(arg1, arg2, arg3, arg4),
@@ -128,6 +116,13 @@ pub fn dispatch_syscall(
         kernel_abi::SYS_CLOCK_GETTIME => dispatch_sys_clock_gettime(arg1, arg2),
         kernel_abi::SYS_NANOSLEEP => dispatch_sys_nanosleep(arg1, arg2),
         kernel_abi::SYS_SPAWN => dispatch_sys_spawn(arg1, arg2),
+        kernel_abi::SYS_ACTUATOR_REGISTER => dispatch_sys_actuator_register(arg1, arg2, arg3),
+        kernel_abi::SYS_ESTOP => dispatch_sys_estop(arg1),
+        kernel_abi::SYS_ESTOP_REARM => dispatch_sys_estop_rearm(),
+        kernel_abi::SYS_ESTOP_STATUS => dispatch_sys_estop_status(arg1),
+        kernel_abi::SYS_WATCHDOG_CONFIG => dispatch_sys_watchdog_config(arg1),
+        kernel_abi::SYS_CAN_SEND => dispatch_sys_can_send(arg1, arg2),
+        kernel_abi::SYS_CAN_RECV => dispatch_sys_can_recv(arg1, arg2),
         _ => {
             error!("unimplemented syscall: {} ({n})", syscall_name(n));
             loop {
@@ -136,7 +131,13 @@ pub fn dispatch_syscall(
         }
     };
 
-    match result {
+    finish(n, result)
+}
+
+/// Convert the result of syscall `n` to its return value and run the exit
+/// hooks.
+fn finish(n: usize, result: Result<usize, Errno>) -> isize {
+    let ret = match result {
         Ok(ret) => {
             trace!("syscall {} ({n}) returned {ret}", syscall_name(n));
             ret as isize
@@ -145,6 +146,64 @@ pub fn dispatch_syscall(
             error!("syscall {} ({n}) failed with error: {e:?}", syscall_name(n));
             Into::<isize>::into(e).neg()
         }
+    };
+
+    let exit_ctx = SyscallExitContext {
+        syscall_nr: n as u64,
+        ret: ret as i64,
+    };
+    run_hooks(AttachEvent::SyscallExit(&exit_ctx), &exit_ctx);
+
+    ret
+}
+
+/// Run the programs attached to a syscall hook on `ctx`.
+fn run_hooks<T>(event: AttachEvent<'_>, ctx: &T) {
+    // SAFETY: the contexts are repr(C) plain data on the stack; the slice
+    // only lives while the programs run.
+    let slice = unsafe {
+        core::slice::from_raw_parts(ctx as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    crate::bpf::hooks::execute_hooks(event, &BpfContext::from_slice(slice));
+}
+
+/// Terminate the current process with exit code `status`.
+#[cfg(target_arch = "x86_64")]
+fn exit_current(status: i32) -> ! {
+    let task = crate::mcore::context::ExecutionContext::load().current_task();
+    let process = task.process();
+    *process.exit_code().write() = Some(status);
+    task.set_should_terminate(true);
+    loop {
+        hlt();
+    }
+}
+
+/// Terminate the current process with exit code `status`.
+#[cfg(target_arch = "aarch64")]
+fn exit_current(status: i32) -> ! {
+    let ctx = crate::mcore::context::ExecutionContext::load();
+    let task = ctx.current_task();
+    let process = task.process();
+    *process.exit_code().write() = Some(status);
+    task.set_should_terminate(true);
+    // The SVC exception masks IRQs, so the timer never preempts this task;
+    // switch away directly. The scheduler does not resume terminated tasks.
+    loop {
+        crate::arch::without_interrupts(|| {
+            // SAFETY: IRQs are masked, so no timer tick on this CPU holds the
+            // scheduler.
+            unsafe { ctx.scheduler_mut().reschedule() }
+        });
+        hlt();
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn exit_current(_status: i32) -> ! {
+    error!("SYS_EXIT not implemented for riscv64");
+    loop {
+        hlt();
     }
 }
 
@@ -366,12 +425,15 @@ fn dispatch_sys_fstat(_fd: usize, _statbuf: usize) -> Result<usize, Errno> {
 
 #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
 fn dispatch_sys_pwm_config(pwm_id: usize, freq_hz: usize) -> Result<usize, Errno> {
-    let ret = pwm::sys_pwm_config(pwm_id, freq_hz);
-    if ret < 0 {
-        Err(EINVAL)
-    } else {
-        Ok(ret as usize)
-    }
+    // The frequency of both channels changes, which changes their duty cycle
+    let ret = actuator::write_guarded(pwm_output(pwm_id, 1), || {
+        actuator::write_guarded(pwm_output(pwm_id, 2), || {
+            pwm::sys_pwm_config(pwm_id, freq_hz)
+        })
+    })
+    .and_then(|r| r)
+    .map_err(actuator_errno)?;
+    pwm_result(ret)
 }
 
 #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
@@ -380,24 +442,185 @@ fn dispatch_sys_pwm_write(
     channel: usize,
     duty_percent: usize,
 ) -> Result<usize, Errno> {
-    let ret = pwm::sys_pwm_write(pwm_id, channel, duty_percent);
-    if ret < 0 {
-        Err(EINVAL)
-    } else {
-        Ok(ret as usize)
-    }
+    let ret = actuator::write_guarded(pwm_output(pwm_id, channel), || {
+        pwm::sys_pwm_write(pwm_id, channel, duty_percent)
+    })
+    .map_err(actuator_errno)?;
+    pwm_result(ret)
 }
 
 #[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
 fn dispatch_sys_pwm_enable(pwm_id: usize, channel: usize, enable: usize) -> Result<usize, Errno> {
-    let ret = pwm::sys_pwm_enable(pwm_id, channel, enable);
-    if ret < 0 {
-        Err(EINVAL)
+    let ret = actuator::write_guarded(pwm_output(pwm_id, channel), || {
+        pwm::sys_pwm_enable(pwm_id, channel, enable)
+    })
+    .map_err(actuator_errno)?;
+    pwm_result(ret)
+}
+
+#[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
+fn pwm_output(pwm_id: usize, channel: usize) -> Output {
+    Output::Pwm {
+        controller: pwm_id as u32,
+        channel: channel as u32,
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", feature = "rpi5"))]
+fn pwm_result(ret: isize) -> Result<usize, Errno> {
+    match ret {
+        -2 => Err(EPERM), // Vetoed by an attached program
+        r if r < 0 => Err(EINVAL),
+        r => Ok(r as usize),
+    }
+}
+
+fn actuator_errno(e: ActuatorError) -> Errno {
+    match e {
+        ActuatorError::Stopped => EBUSY,
+        ActuatorError::NoSuchOutput => ENODEV,
+        ActuatorError::AlreadyRegistered => EEXIST,
+        ActuatorError::InvalidSafeState => EINVAL,
+    }
+}
+
+/// Fail unless the calling process is privileged.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn require_privileged() -> Result<(), Errno> {
+    let process = crate::mcore::context::ExecutionContext::load().current_process();
+    if process.is_privileged() {
+        Ok(())
     } else {
-        Ok(ret as usize)
+        Err(EPERM)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn require_privileged() -> Result<(), Errno> {
+    Err(EPERM)
+}
+
+/// Register an actuator with its safe state.
+///
+/// `target` is `controller << 32 | channel` for PWM and `chip << 32 | line`
+/// for GPIO. Privileged.
+fn dispatch_sys_actuator_register(
+    kind: usize,
+    target: usize,
+    safe_state: usize,
+) -> Result<usize, Errno> {
+    require_privileged()?;
+
+    let (id, index) = ((target as u64 >> 32) as u32, target as u32);
+    let output = match kind {
+        kernel_abi::ACTUATOR_PWM => Output::Pwm {
+            controller: id,
+            channel: index,
+        },
+        kernel_abi::ACTUATOR_GPIO => Output::Gpio {
+            chip: id,
+            line: index,
+        },
+        _ => return Err(EINVAL),
+    };
+    let safe_state = u32::try_from(safe_state)?;
+
+    actuator::register(Actuator { output, safe_state }).map_err(actuator_errno)?;
+    Ok(0)
+}
+
+/// Trigger an emergency stop. Anyone may stop.
+fn dispatch_sys_estop(reason: usize) -> Result<usize, Errno> {
+    actuator::emergency_stop(kernel_abi::ESTOP_SOURCE_SYSCALL, reason as i32);
+    Ok(0)
+}
+
+/// Release a latched emergency stop. Privileged.
+///
+/// Returns 1 if a stop was latched, 0 otherwise.
+fn dispatch_sys_estop_rearm() -> Result<usize, Errno> {
+    require_privileged()?;
+    Ok(usize::from(actuator::rearm()))
+}
+
+/// Get the safety event of the latched emergency stop.
+///
+/// Returns 1 and copies the event to `event` (if not null) while a stop is
+/// latched, 0 otherwise.
+fn dispatch_sys_estop_status(event: usize) -> Result<usize, Errno> {
+    let Some(status) = actuator::status() else {
+        return Ok(0);
+    };
+
+    if event != 0 {
+        // SAFETY: SafetyEvent is repr(C) plain data, viewed as bytes only
+        // for the copy.
+        let slice = unsafe {
+            core::slice::from_raw_parts(
+                &status as *const _ as *const u8,
+                core::mem::size_of::<kernel_abi::SafetyEvent>(),
+            )
+        };
+        validation::copy_to_userspace(event, slice)?;
+    }
+    Ok(1)
+}
+
+/// Arm the watchdog with a timeout in milliseconds, or disarm it with 0.
+/// Privileged.
+fn dispatch_sys_watchdog_config(timeout_ms: usize) -> Result<usize, Errno> {
+    require_privileged()?;
+    let timeout_ms = u32::try_from(timeout_ms)?;
+    watchdog::configure(timeout_ms).map_err(|e| match e {
+        WatchdogError::InvalidTimeout => EINVAL,
+        WatchdogError::NoWatchdog => ENODEV,
+    })?;
+    Ok(0)
+}
+
+fn can_errno(e: CanError) -> Errno {
+    match e {
+        CanError::NoSuchInterface => ENODEV,
+        CanError::InvalidFrame => EINVAL,
+        CanError::TxBusy => EAGAIN,
+        CanError::Busy => EBUSY,
+        CanError::TooManyInterfaces
+        | CanError::InvalidConfig
+        | CanError::BusOff
+        | CanError::Spi(_) => EIO,
     }
 }
 
+/// Send a frame on a CAN interface.
+fn dispatch_sys_can_send(iface: usize, frame: usize) -> Result<usize, Errno> {
+    let iface = u32::try_from(iface)?;
+    let frame: kernel_abi::CanFrame = validation::copy_from_userspace(frame)?;
+    can::send(iface, &frame).map_err(can_errno)?;
+    Ok(0)
+}
+
+/// Receive a frame from a CAN interface without blocking.
+///
+/// Returns 1 and copies the oldest queued frame to `frame`, or 0 if none is
+/// queued.
+fn dispatch_sys_can_recv(iface: usize, frame: usize) -> Result<usize, Errno> {
+    let iface = u32::try_from(iface)?;
+    let Some(received) = can::recv(iface).map_err(can_errno)? else {
+        return Ok(0);
+    };
+
+    // SAFETY: CanFrame is repr(C) plain data, viewed as bytes only for the
+    // copy.
+    let slice = unsafe {
+        core::slice::from_raw_parts(
+            &received as *const _ as *const u8,
+            core::mem::size_of::<kernel_abi::CanFrame>(),
+        )
+    };
+    validation::copy_to_userspace(frame, slice)?;
+    Ok(1)
+}
+
 fn dispatch_sys_clock_gettime(_clock_id: usize, tp: usize) -> Result<usize, Errno> {
     // We strictly support CLOCK_REALTIME/MONOTONIC which are mapped to kernel time for now.
     let ns = crate::time::get_kernel_time_ns();
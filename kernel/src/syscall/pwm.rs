@@ -1,4 +1,7 @@
 //! PWM Syscall Implementation
+//!
+//! All calls return -1 for invalid arguments and -2 if a BPF program
+//! attached to the channel vetoed the change.
 
 use crate::arch::aarch64::platform::rpi5::pwm::{PWM0, PWM1};
 
@@ -11,15 +14,14 @@ pub fn sys_pwm_config(pwm_id: usize, freq_hz: usize) -> isize {
     match pwm_id {
         0 => {
             let pwm = PWM0.lock();
-            pwm.set_frequency(1, freq_hz as u32);
-            pwm.set_frequency(2, freq_hz as u32); // Set both channels to same freq for now
-            0
+            // Set both channels to same freq for now
+            let ok = pwm.set_frequency(1, freq_hz as u32) & pwm.set_frequency(2, freq_hz as u32);
+            vetoed_unless(ok)
         }
         1 => {
             let pwm = PWM1.lock();
-            pwm.set_frequency(1, freq_hz as u32);
-            pwm.set_frequency(2, freq_hz as u32);
-            0
+            let ok = pwm.set_frequency(1, freq_hz as u32) & pwm.set_frequency(2, freq_hz as u32);
+            vetoed_unless(ok)
         }
         _ => -1, // Invalid PWM ID
     }
@@ -39,13 +41,11 @@ pub fn sys_pwm_write(pwm_id: usize, channel: usize, duty_percent: usize) -> isiz
     match pwm_id {
         0 => {
             let pwm = PWM0.lock();
-            pwm.set_duty_cycle(channel as u8, duty_percent as u32);
-            0
+            vetoed_unless(pwm.set_duty_cycle(channel as u8, duty_percent as u32))
         }
         1 => {
             let pwm = PWM1.lock();
-            pwm.set_duty_cycle(channel as u8, duty_percent as u32);
-            0
+            vetoed_unless(pwm.set_duty_cycle(channel as u8, duty_percent as u32))
         }
         _ => -1,
     }
@@ -66,21 +66,25 @@ pub fn sys_pwm_enable(pwm_id: usize, channel: usize, enable: usize) -> isize {
         0 => {
             let pwm = PWM0.lock();
             if enable != 0 {
-                pwm.enable(channel as u8);
+                vetoed_unless(pwm.enable(channel as u8))
             } else {
                 pwm.disable(channel as u8);
+                0
             }
-            0
         }
         1 => {
             let pwm = PWM1.lock();
             if enable != 0 {
-                pwm.enable(channel as u8);
+                vetoed_unless(pwm.enable(channel as u8))
             } else {
                 pwm.disable(channel as u8);
+                0
             }
-            0
         }
         _ => -1,
     }
 }
+
+fn vetoed_unless(applied: bool) -> isize {
+    if applied { 0 } else { -2 }
+}
@@ -4,6 +4,10 @@ use core::mem::{align_of, size_of};
 use kernel_abi::{EFAULT, EINVAL, Errno};
 use kernel_syscall::UserspacePtr;
 
+use crate::arch::types::{PageSize, Size4KiB, VirtAddr};
+use crate::mcore::context::ExecutionContext;
+use crate::mem::address_space::AddressSpace;
+
 /// Copy a struct from userspace to kernel.
 /// Validates: non-null, canonical address, alignment, within userspace range.
 pub fn copy_from_userspace<T: Copy>(ptr: usize) -> Result<T, Errno> {
@@ -71,3 +75,71 @@ pub fn copy_to_userspace(ptr: usize, data: &[u8]) -> Result<(), Errno> {
     unsafe { core::ptr::copy_nonoverlapping(data.as_ptr(), ptr as *mut u8, data.len()) }
     Ok(())
 }
+
+/// Copy `dst.len()` bytes at `ptr` into `dst` without faulting.
+///
+/// User addresses must lie in the lower half, inside one of the current
+/// process's memory regions, and be mapped in its address space. Lazy pages
+/// that have not been touched yet are rejected rather than faulted in, and
+/// the regions stay locked during the copy so they cannot be unmapped under
+/// it. Kernel addresses must lie in the upper half and be mapped in the
+/// kernel address space.
+///
+/// Callers such as BPF helpers may run in interrupts, so locks are only
+/// tried: if the regions or page tables are locked, this fails with
+/// `EFAULT` instead of spinning.
+pub fn read_nofault(ptr: usize, dst: &mut [u8], user: bool) -> Result<(), Errno> {
+    if ptr == 0 || dst.is_empty() {
+        return Err(EFAULT);
+    }
+
+    // SAFETY: The pointer is only used to classify the address, never dereferenced.
+    let user_ptr = unsafe { UserspacePtr::<u8>::try_from_usize(ptr) };
+    if !user {
+        if user_ptr.is_ok() {
+            return Err(EFAULT);
+        }
+        return copy_mapped(AddressSpace::kernel(), ptr, dst, |_| true);
+    }
+
+    user_ptr?.validate_range(dst.len())?;
+    let process = ExecutionContext::load().current_process();
+    process
+        .memory_regions()
+        .try_with_regions(|regions| {
+            copy_mapped(process.address_space(), ptr, dst, |vaddr| {
+                regions.iter().any(|r| r.contains(vaddr))
+            })
+        })
+        .unwrap_or(Err(EFAULT))
+}
+
+/// Copy `dst.len()` bytes from `ptr` if every page is mapped and passes
+/// `in_region`, holding the page tables unchanged from check to copy.
+fn copy_mapped(
+    address_space: &AddressSpace,
+    ptr: usize,
+    dst: &mut [u8],
+    in_region: impl Fn(VirtAddr) -> bool,
+) -> Result<(), Errno> {
+    let page_size = Size4KiB::SIZE as usize;
+    let end = ptr.checked_add(dst.len()).ok_or(EFAULT)?;
+    address_space
+        .try_with_translate(|translate| {
+            let mut addr = ptr;
+            while addr < end {
+                let vaddr = VirtAddr::new(addr as u64);
+                if !in_region(vaddr) || translate(vaddr).is_none() {
+                    return Err(EFAULT);
+                }
+                addr = (addr & !(page_size - 1)) + page_size;
+            }
+
+            // SAFETY: Every page of the source is mapped, and stays mapped
+            // while the page tables are held, so the copy cannot fault.
+            let src = unsafe { core::slice::from_raw_parts(ptr as *const u8, dst.len()) };
+            dst.copy_from_slice(src);
+            Ok(())
+        })
+        .unwrap_or(Err(EFAULT))
+}
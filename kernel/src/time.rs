@@ -22,10 +22,31 @@ impl TimestampExt for Timestamp {
     }
 }
 
-#[cfg(not(target_arch = "x86_64"))]
+#[cfg(target_arch = "aarch64")]
 impl TimestampExt for Timestamp {
     fn now() -> Self {
-        // TODO: Implement proper time handling for aarch64/riscv64
+        let counter: u64;
+        let freq: u64;
+        // SAFETY: Reading the generic timer's virtual counter and its
+        // frequency has no side effects and is allowed at EL1.
+        unsafe {
+            core::arch::asm!("mrs {}, cntvct_el0", out(reg) counter);
+            core::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq);
+        }
+        let nanos = (u128::from(counter) * 1_000_000_000 / u128::from(freq.max(1))) as u64;
+        let secs = BOOT_TIME_SECONDS.get().unwrap() + nanos / 1_000_000_000;
+        Timestamp::new(
+            i64::try_from(secs).expect("shouldn't have more seconds than i64::MAX"),
+            (nanos % 1_000_000_000) as i32,
+        )
+        .unwrap()
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl TimestampExt for Timestamp {
+    fn now() -> Self {
+        // TODO: Implement proper time handling for riscv64
         let secs = BOOT_TIME_SECONDS.get().unwrap();
         Timestamp::new(
             i64::try_from(*secs).expect("shouldn't have more seconds than i64::MAX"),
@@ -12,6 +12,7 @@ pub const STRUCTURE: Dir<'static> = Dir::new(
                 File::new("iio_demo", Kind::Executable),
                 File::new("syscall_demo", Kind::Executable),
                 File::new("file_io_demo", Kind::Executable),
+                File::new("profile", Kind::Executable),
             ],
         ),
         Dir::new("dev", &[Dir::new("fd", &[], &[])], &[]),
@@ -139,7 +139,8 @@ pub extern "C" fn _start() -> ! {
     let attach_attr = BpfAttr {
         attach_btf_id: ATTACH_TYPE_IIO,
         attach_prog_fd: prog_id as u32,
-        key: 0, // Device ID 0
+        key: 0,   // Device ID 0
+        value: 0, // Channel 0 (in_accel_x)
         ..Default::default()
     };
 
@@ -105,7 +105,7 @@ pub extern "C" fn _start() -> ! {
     // *(u32 *)(r10 - 4) = 0  // key = 0 on stack
     // r1 = r6                 // map_id
     // r2 = r10 - 4            // key pointer
-    // call bpf_map_lookup_elem (3)
+    // call bpf_map_lookup_elem (5)
     // if r0 == 0, goto exit
     // r1 = *(u64 *)(r0)       // load current value
     // r1 += 1                  // increment
@@ -155,12 +155,12 @@ pub extern "C" fn _start() -> ! {
             off: 0,
             imm: -4,
         },
-        // call bpf_map_lookup_elem (helper 3)
+        // call bpf_map_lookup_elem (helper 5)
         BpfInsn {
             code: 0x85,
             dst_src: 0x00,
             off: 0,
-            imm: 3,
+            imm: 5,
         },
         // if r0 == 0, skip 3 (goto exit)
         BpfInsn {
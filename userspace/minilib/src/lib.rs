@@ -285,4 +285,70 @@ pub fn open(path: &str, flags: c_int, mode: c_int) -> c_int {
 
 pub fn spawn(path: &str) -> c_int {
     syscall2(56, path.as_ptr() as usize, path.len()) as i32
-}
\ No newline at end of file
+}
+pub const ACTUATOR_PWM: usize = 0;
+pub const ACTUATOR_GPIO: usize = 1;
+
+/// Register a PWM channel (`ACTUATOR_PWM`, target `controller << 32 | channel`)
+/// or GPIO line (`ACTUATOR_GPIO`, target `chip << 32 | line`) as an actuator
+/// with the state it is driven to on an emergency stop.
+pub fn actuator_register(kind: usize, target: u64, safe_state: u32) -> c_int {
+    syscall3(57, kind, target as usize, safe_state as usize) as i32
+}
+
+pub fn estop(reason: i32) -> c_int {
+    syscall1(58, reason as usize) as i32
+}
+
+pub fn estop_rearm() -> c_int {
+    syscall0(59) as i32
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct safety_event {
+    pub timestamp_ns: u64,
+    pub event_type: u32,
+    pub cpu: u32,
+    pub pid: u32,
+    pub _reserved: u32,
+    pub safety_type: u32,
+    pub source_id: u32,
+    pub value: i32,
+    pub action: u32,
+}
+
+/// Returns 1 and fills `event` while an emergency stop is latched, 0 otherwise.
+pub fn estop_status(event: *mut safety_event) -> c_int {
+    syscall1(60, event as usize) as i32
+}
+
+/// Arm the watchdog with `timeout_ms`, or disarm it with 0. Once armed it is
+/// only petted while the attached liveness programs report healthy.
+pub fn watchdog_config(timeout_ms: u32) -> c_int {
+    syscall1(61, timeout_ms as usize) as i32
+}
+
+pub const CAN_EFF_FLAG: u32 = 0x8000_0000;
+pub const CAN_RTR_FLAG: u32 = 0x4000_0000;
+
+#[repr(C, align(8))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct can_frame {
+    pub can_id: u32,
+    pub len: u8,
+    pub _pad: u8,
+    pub _res0: u8,
+    pub _len8_dlc: u8,
+    pub data: [u8; 8],
+}
+
+pub fn can_send(iface: u32, frame: *const can_frame) -> c_int {
+    syscall2(62, iface as usize, frame as usize) as i32
+}
+
+/// Returns 1 and fills `frame` with the oldest received frame, or 0 if none
+/// is queued.
+pub fn can_recv(iface: u32, frame: *mut can_frame) -> c_int {
+    syscall2(63, iface as usize, frame as usize) as i32
+}
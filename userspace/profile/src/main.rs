@@ -0,0 +1,341 @@
+#![no_std]
+#![no_main]
+
+//! Sampling profiler
+//!
+//! Attaches a program to the timer tick that counts samples by process and
+//! kernel and user stack, lets it run for a few seconds, then dumps the
+//! counts and stacks as `rkprof` lines on stdout:
+//!
+//! ```text
+//! rkprof begin <sample period in ns>
+//! rkprof sample <pid> <kernel stack id> <user stack id> <count> <comm>
+//! rkprof stack <id> <instruction pointers in hex, innermost first>
+//! rkprof end
+//! ```
+//!
+//! Negative stack IDs are errors from `bpf_get_stackid`, e.g. a kernel stack
+//! for a sample taken in user mode. `rk profile` symbolizes the dump into
+//! folded stacks.
+
+use kernel_abi::{
+    BPF_MAP_CREATE, BPF_MAP_GET_NEXT_KEY, BPF_MAP_LOOKUP_ELEM, BPF_PROG_ATTACH, BPF_PROG_DETACH,
+    BPF_PROG_LOAD, BpfAttr,
+};
+use minilib::{bpf, exit, sleep, write};
+
+// Map types
+const MAP_TYPE_HASH: u32 = 1;
+const MAP_TYPE_STACK_TRACE: u32 = 7;
+
+// BPF Helper IDs
+const HELPER_MAP_LOOKUP_ELEM: i32 = 5;
+const HELPER_MAP_UPDATE_ELEM: i32 = 6;
+const HELPER_GET_CURRENT_PID_TGID: i32 = 9;
+const HELPER_GET_CURRENT_COMM: i32 = 11;
+const HELPER_GET_STACKID: i32 = 27;
+
+const BPF_F_USER_STACK: i32 = 1 << 8;
+
+// ATTACH_TYPE_TIMER = 1
+const ATTACH_TYPE_TIMER: u32 = 1;
+
+/// Period of the kernel timer tick
+const SAMPLE_PERIOD_NS: u64 = 10_000_000;
+
+/// How long to sample for
+const DURATION_SECS: u64 = 5;
+
+/// Frames kept per stack
+const STACK_DEPTH: usize = 32;
+const STACK_ENTRIES: u32 = 128;
+const COUNT_ENTRIES: u32 = 512;
+
+#[repr(C)]
+struct BpfInsn {
+    code: u8,
+    dst_src: u8,
+    off: i16,
+    imm: i32,
+}
+
+const fn insn(code: u8, dst_src: u8, off: i16, imm: i32) -> BpfInsn {
+    BpfInsn {
+        code,
+        dst_src,
+        off,
+        imm,
+    }
+}
+
+/// Key of the counts map, built by the program at `r10 - 32`
+#[repr(C)]
+#[derive(Default)]
+struct SampleKey {
+    pid: u32,
+    kstack: i32,
+    ustack: i32,
+    _pad: u32,
+    comm: [u8; 16],
+}
+
+// SAFETY: Entry point for the profiler. Called by the startup code.
+#[unsafe(no_mangle)]
+pub extern "C" fn _start() -> ! {
+    let stacks = create_map(
+        MAP_TYPE_STACK_TRACE,
+        4,
+        (STACK_DEPTH * 8) as u32,
+        STACK_ENTRIES,
+    );
+    let counts = create_map(
+        MAP_TYPE_HASH,
+        core::mem::size_of::<SampleKey>() as u32,
+        8,
+        COUNT_ENTRIES,
+    );
+
+    // Program:
+    //   key.pid = bpf_get_current_pid_tgid() >> 32
+    //   bpf_get_current_comm(key.comm, 16)
+    //   key.kstack = bpf_get_stackid(ctx, stacks, 0)
+    //   key.ustack = bpf_get_stackid(ctx, stacks, BPF_F_USER_STACK)
+    //   if (count = lookup(counts, &key)) *count += 1
+    //   else update(counts, &key, &1)
+    let insns = [
+        insn(0xbf, 0x16, 0, 0),                           // r6 = r1 (ctx)
+        insn(0x85, 0x00, 0, HELPER_GET_CURRENT_PID_TGID), // call
+        insn(0x77, 0x00, 0, 32),                          // r0 >>= 32
+        insn(0x63, 0x0a, -32, 0),                         // key.pid = r0
+        insn(0xb7, 0x01, 0, 0),                           // r1 = 0
+        insn(0x63, 0x1a, -20, 0),                         // key._pad = r1
+        insn(0xbf, 0xa1, 0, 0),                           // r1 = r10
+        insn(0x07, 0x01, 0, -16),                         // r1 += -16 (key.comm)
+        insn(0xb7, 0x02, 0, 16),                          // r2 = 16
+        insn(0x85, 0x00, 0, HELPER_GET_CURRENT_COMM),     // call
+        insn(0xbf, 0x61, 0, 0),                           // r1 = r6
+        insn(0xb7, 0x02, 0, stacks),                      // r2 = stacks
+        insn(0xb7, 0x03, 0, 0),                           // r3 = 0
+        insn(0x85, 0x00, 0, HELPER_GET_STACKID),          // call
+        insn(0x63, 0x0a, -28, 0),                         // key.kstack = r0
+        insn(0xbf, 0x61, 0, 0),                           // r1 = r6
+        insn(0xb7, 0x02, 0, stacks),                      // r2 = stacks
+        insn(0xb7, 0x03, 0, BPF_F_USER_STACK),            // r3 = BPF_F_USER_STACK
+        insn(0x85, 0x00, 0, HELPER_GET_STACKID),          // call
+        insn(0x63, 0x0a, -24, 0),                         // key.ustack = r0
+        insn(0xb7, 0x01, 0, counts),                      // r1 = counts
+        insn(0xbf, 0xa2, 0, 0),                           // r2 = r10
+        insn(0x07, 0x02, 0, -32),                         // r2 += -32 (key)
+        insn(0x85, 0x00, 0, HELPER_MAP_LOOKUP_ELEM),      // call
+        insn(0x55, 0x00, 10, 0),                          // if r0 != 0 goto INC
+        insn(0xb7, 0x01, 0, 1),                           // r1 = 1
+        insn(0x7b, 0x1a, -40, 0),                         // *(u64 *)(r10 - 40) = r1
+        insn(0xb7, 0x01, 0, counts),                      // r1 = counts
+        insn(0xbf, 0xa2, 0, 0),                           // r2 = r10
+        insn(0x07, 0x02, 0, -32),                         // r2 += -32 (key)
+        insn(0xbf, 0xa3, 0, 0),                           // r3 = r10
+        insn(0x07, 0x03, 0, -40),                         // r3 += -40 (value)
+        insn(0xb7, 0x04, 0, 0),                           // r4 = 0 (BPF_ANY)
+        insn(0x85, 0x00, 0, HELPER_MAP_UPDATE_ELEM),      // call
+        insn(0x05, 0x00, 3, 0),                           // goto OUT
+        insn(0x79, 0x01, 0, 0),                           // INC: r1 = *(u64 *)(r0 + 0)
+        insn(0x07, 0x01, 0, 1),                           // r1 += 1
+        insn(0x7b, 0x10, 0, 0),                           // *(u64 *)(r0 + 0) = r1
+        insn(0xb7, 0x00, 0, 0),                           // OUT: r0 = 0
+        insn(0x95, 0x00, 0, 0),                           // exit
+    ];
+
+    let load_attr = BpfAttr {
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        ..Default::default()
+    };
+    let prog_id = sys_bpf(BPF_PROG_LOAD, &load_attr);
+    if prog_id < 0 {
+        fail("failed to load the sampling program");
+    }
+
+    let attach_attr = BpfAttr {
+        attach_btf_id: ATTACH_TYPE_TIMER,
+        attach_prog_fd: prog_id as u32,
+        ..Default::default()
+    };
+    if sys_bpf(BPF_PROG_ATTACH, &attach_attr) < 0 {
+        fail("failed to attach to the timer");
+    }
+
+    print("rkprof: sampling for ");
+    print_num(DURATION_SECS);
+    print("s\n");
+    sleep(DURATION_SECS);
+
+    // The program updates the maps from the timer interrupt, so it has to be
+    // gone before the maps are read.
+    if sys_bpf(BPF_PROG_DETACH, &attach_attr) < 0 {
+        fail("failed to detach from the timer");
+    }
+
+    print("rkprof begin ");
+    print_num(SAMPLE_PERIOD_NS);
+    print("\n");
+    dump_counts(counts);
+    dump_stacks(stacks);
+    print("rkprof end\n");
+
+    exit(0);
+}
+
+fn dump_counts(map: i32) {
+    let mut key = SampleKey::default();
+    let mut first = true;
+    loop {
+        let mut next = SampleKey::default();
+        if !next_key(map, (!first).then_some(&key), &mut next) {
+            break;
+        }
+        first = false;
+        key = next;
+
+        let mut count = 0u64;
+        if !lookup(map, &key, &mut count) {
+            continue;
+        }
+
+        print("rkprof sample ");
+        print_num(u64::from(key.pid));
+        print(" ");
+        print_signed(i64::from(key.kstack));
+        print(" ");
+        print_signed(i64::from(key.ustack));
+        print(" ");
+        print_num(count);
+        print(" ");
+        let len = key.comm.iter().position(|&b| b == 0).unwrap_or(16);
+        write(1, &key.comm[..len]);
+        print("\n");
+    }
+}
+
+fn dump_stacks(map: i32) {
+    let mut id = 0u32;
+    let mut first = true;
+    loop {
+        let mut next = 0u32;
+        if !next_key(map, (!first).then_some(&id), &mut next) {
+            break;
+        }
+        first = false;
+        id = next;
+
+        let mut ips = [0u64; STACK_DEPTH];
+        if !lookup(map, &id, &mut ips) {
+            continue;
+        }
+
+        print("rkprof stack ");
+        print_num(u64::from(id));
+        for &ip in ips.iter().take_while(|&&ip| ip != 0) {
+            print(" ");
+            print_hex(ip);
+        }
+        print("\n");
+    }
+}
+
+fn create_map(map_type: u32, key_size: u32, value_size: u32, max_entries: u32) -> i32 {
+    let attr = BpfAttr {
+        prog_type: map_type,
+        insn_cnt: key_size,
+        insns: u64::from(value_size) | (u64::from(max_entries) << 32),
+        ..Default::default()
+    };
+    let map_id = sys_bpf(BPF_MAP_CREATE, &attr);
+    if map_id < 0 {
+        fail("failed to create map");
+    }
+    map_id
+}
+
+/// Read the key after `key`, or the first key without one, into `next`.
+fn next_key<K>(map: i32, key: Option<&K>, next: &mut K) -> bool {
+    let attr = BpfAttr {
+        map_fd: map as u32,
+        key: key.map_or(0, |key| key as *const K as u64),
+        value: next as *mut K as u64,
+        ..Default::default()
+    };
+    sys_bpf(BPF_MAP_GET_NEXT_KEY, &attr) == 0
+}
+
+fn lookup<K, V>(map: i32, key: &K, value: &mut V) -> bool {
+    let attr = BpfAttr {
+        map_fd: map as u32,
+        key: key as *const K as u64,
+        value: value as *mut V as u64,
+        ..Default::default()
+    };
+    sys_bpf(BPF_MAP_LOOKUP_ELEM, &attr) == 0
+}
+
+fn sys_bpf(cmd: u32, attr: &BpfAttr) -> i32 {
+    bpf(
+        cmd as i32,
+        attr as *const BpfAttr as *const u8,
+        core::mem::size_of::<BpfAttr>() as i32,
+    )
+}
+
+fn fail(msg: &str) -> ! {
+    print("rkprof: ");
+    print(msg);
+    print("\n");
+    exit(1);
+}
+
+fn print(s: &str) {
+    write(1, s.as_bytes());
+}
+
+fn print_signed(n: i64) {
+    if n < 0 {
+        print("-");
+    }
+    print_num(n.unsigned_abs());
+}
+
+fn print_num(mut n: u64) {
+    if n == 0 {
+        print("0");
+        return;
+    }
+
+    let mut buf = [0u8; 20];
+    let mut i = buf.len();
+    while n > 0 {
+        i -= 1;
+        buf[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+    write(1, &buf[i..]);
+}
+
+fn print_hex(mut n: u64) {
+    let mut buf = [0u8; 16];
+    let mut i = buf.len();
+    loop {
+        i -= 1;
+        buf[i] = b"0123456789abcdef"[(n & 0xf) as usize];
+        n >>= 4;
+        if n == 0 {
+            break;
+        }
+    }
+    write(1, &buf[i..]);
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &::core::panic::PanicInfo) -> ! {
+    print("Panic!\n");
+    exit(1);
+}
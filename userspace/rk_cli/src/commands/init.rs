@@ -108,16 +108,27 @@ typedef signed long long __s64;
 #include "types.h"
 
 // BPF helper function definitions
-// These are provided by the kernel at runtime
-
-static long (*bpf_trace_printk)(const char *fmt, __u32 fmt_size, ...) = (void *) 6;
-static long (*bpf_get_current_pid_tgid)(void) = (void *) 14;
-static long (*bpf_ktime_get_ns)(void) = (void *) 5;
+// These are provided by the kernel at runtime; IDs match
+// kernel_bpf::verifier::HelperId
+
+static long (*bpf_ktime_get_ns)(void) = (void *) 1;
+static long (*bpf_trace_printk)(const char *fmt, __u32 fmt_size, ...) = (void *) 2;
+static __u32 (*bpf_get_prandom_u32)(void) = (void *) 3;
+static __u32 (*bpf_get_smp_processor_id)(void) = (void *) 4;
+static long (*bpf_get_current_pid_tgid)(void) = (void *) 9;
+static long (*bpf_perf_event_output)(void *ctx, void *map, __u64 flags, void *data, __u64 size) = (void *) 25;
+static long (*bpf_get_stackid)(void *ctx, void *map, __u64 flags) = (void *) 27;
+static long (*bpf_get_stack)(void *ctx, void *buf, __u32 size, __u64 flags) = (void *) 67;
 
 // rkBPF-specific helpers
-static long (*rkbpf_motor_stop)(__u32 motor_id) = (void *) 1000;
-static long (*rkbpf_gpio_read)(__u32 pin) = (void *) 1001;
-static long (*rkbpf_timeseries_push)(__u32 map_id, __u64 timestamp, __u64 value) = (void *) 1002;
+static long (*rkbpf_motor_emergency_stop)(__u32 reason) = (void *) 1000;
+static long (*rkbpf_timeseries_push)(__u32 map_id, const void *value) = (void *) 1001;
+static __u64 (*rkbpf_sensor_last_timestamp)(__u32 device) = (void *) 1002;
+static long (*rkbpf_gpio_read)(__u32 pin) = (void *) 1004;
+static long (*rkbpf_iio_read)(__u32 device, __u32 channel) = (void *) 1006;
+static long (*rkbpf_can_send)(__u32 iface, __u32 id, const void *data, __u32 len) = (void *) 1007;
+static long (*rkbpf_timer_start)(__u32 timer, __u64 delay_ns) = (void *) 1008;
+static long (*rkbpf_timeseries_stats)(__u32 map_id, __u32 n, void *stats, __u32 size) = (void *) 1009;
 
 #endif /* RKBPF_HELPERS_H */
 "#;
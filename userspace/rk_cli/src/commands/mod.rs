@@ -6,6 +6,7 @@ pub mod info;
 pub mod init;
 pub mod key;
 pub mod list;
+pub mod profile;
 pub mod sign;
 pub mod unload;
 pub mod verify;
@@ -0,0 +1,407 @@
+//! Sampling profiler command.
+//!
+//! `/bin/profile` on the device samples the kernel and user stacks of
+//! whatever each timer tick interrupts and dumps the aggregated counts as
+//! `rkprof` lines. This command turns such a dump into folded stacks for
+//! flamegraph tools, symbolizing kernel frames against the kernel ELF and
+//! user frames against the binaries in the OS disk image.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::Command;
+
+use anyhow::{anyhow, bail, Context, Result};
+use colored::Colorize;
+
+use crate::image::DiskImage;
+use crate::symbols::Symbols;
+
+/// Program on the device that samples and dumps the stacks.
+const PROFILER_PATH: &str = "/bin/profile";
+
+/// Suffix marking kernel frames, as used by flamegraph color schemes.
+const KERNEL_SUFFIX: &str = "_[k]";
+
+/// One aggregated sample line of a dump.
+struct Sample {
+    comm: String,
+    kstack: i64,
+    ustack: i64,
+    count: u64,
+}
+
+/// A parsed `rkprof` dump.
+struct Dump {
+    period_ns: u64,
+    samples: Vec<Sample>,
+    /// Instruction pointers by stack ID, innermost first
+    stacks: HashMap<i64, Vec<u64>>,
+}
+
+/// Profile a device and write folded stacks.
+///
+/// The dump is read from `input` (`-` for stdin), such as a captured serial
+/// console, or by running the profiler on `target` over ssh.
+pub fn profile(
+    kernel: &str,
+    image: Option<&str>,
+    input: Option<&str>,
+    target: Option<&str>,
+    output: Option<&str>,
+) -> Result<()> {
+    let text = match (input, target) {
+        (Some("-"), _) => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .context("Failed to read dump from stdin")?;
+            text
+        }
+        (Some(path), _) => {
+            fs::read_to_string(path).with_context(|| format!("Failed to read dump: {}", path))?
+        }
+        (None, Some(target)) => run_remote(target)?,
+        (None, None) => bail!("Either --input or --target is required"),
+    };
+    let dump = parse_dump(&text)?;
+
+    let kernel_data =
+        fs::read(kernel).with_context(|| format!("Failed to read kernel: {}", kernel))?;
+    let kernel_symbols =
+        Symbols::from_elf(&kernel_data).context("Failed to load kernel symbols")?;
+    let image = image.map(DiskImage::open).transpose()?;
+
+    let mut user_symbols = UserSymbols {
+        image: image.as_ref(),
+        by_comm: HashMap::new(),
+    };
+    let folded = fold(&dump, &kernel_symbols, &mut user_symbols);
+
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => {
+            Box::new(fs::File::create(path).with_context(|| format!("Failed to create {}", path))?)
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    for (stack, count) in &folded {
+        writeln!(out, "{} {}", stack, count)?;
+    }
+    out.flush()?;
+
+    let total: u64 = dump.samples.iter().map(|s| s.count).sum();
+    eprintln!(
+        "{} {} samples ({:.2}s at {}ms per sample) into {} stacks",
+        "Folded".green(),
+        total,
+        (total * dump.period_ns) as f64 / 1e9,
+        dump.period_ns / 1_000_000,
+        folded.len()
+    );
+
+    Ok(())
+}
+
+fn run_remote(target: &str) -> Result<String> {
+    let host = target.strip_prefix("ssh://").unwrap_or(target);
+    eprintln!(
+        "{} {} on {}...",
+        "Running".cyan(),
+        PROFILER_PATH,
+        host.green()
+    );
+
+    let output = Command::new("ssh")
+        .arg(host)
+        .arg(PROFILER_PATH)
+        .output()
+        .context("Failed to run ssh command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("Profiler failed: {}", stderr);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Parse the `rkprof` lines of `text`, skipping everything else.
+///
+/// Lines may carry a prefix, such as console log output, before `rkprof`.
+fn parse_dump(text: &str) -> Result<Dump> {
+    let mut dump = None;
+    let mut ended = false;
+
+    for line in text.lines() {
+        let Some(start) = line.find("rkprof ") else {
+            continue;
+        };
+        let mut fields = line[start + "rkprof ".len()..].split_whitespace();
+        match fields.next() {
+            Some("begin") => {
+                // A later dump replaces an earlier one
+                dump = Some(Dump {
+                    period_ns: parse_field(fields.next(), "period")?,
+                    samples: Vec::new(),
+                    stacks: HashMap::new(),
+                });
+                ended = false;
+            }
+            Some("sample") => {
+                let dump = dump
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("Sample before 'rkprof begin'"))?;
+                let _pid: u32 = parse_field(fields.next(), "pid")?;
+                let kstack = parse_field(fields.next(), "kernel stack id")?;
+                let ustack = parse_field(fields.next(), "user stack id")?;
+                let count = parse_field(fields.next(), "count")?;
+                dump.samples.push(Sample {
+                    comm: fields.collect::<Vec<_>>().join(" "),
+                    kstack,
+                    ustack,
+                    count,
+                });
+            }
+            Some("stack") => {
+                let dump = dump
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("Stack before 'rkprof begin'"))?;
+                let id = parse_field(fields.next(), "stack id")?;
+                let ips = fields
+                    .map(|ip| u64::from_str_radix(ip, 16))
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| format!("Invalid frame in stack {}", id))?;
+                dump.stacks.insert(id, ips);
+            }
+            Some("end") => ended = true,
+            _ => {}
+        }
+    }
+
+    let dump = dump.ok_or_else(|| anyhow!("No 'rkprof begin' line in the dump"))?;
+    if !ended {
+        eprintln!(
+            "{} Dump has no 'rkprof end' line, it may be truncated",
+            "Warning:".yellow()
+        );
+    }
+    Ok(dump)
+}
+
+fn parse_field<T: std::str::FromStr>(field: Option<&str>, what: &str) -> Result<T> {
+    field
+        .and_then(|f| f.parse().ok())
+        .ok_or_else(|| anyhow!("Missing or invalid {} in dump", what))
+}
+
+/// Symbols of the binaries in the disk image, looked up by process name.
+struct UserSymbols<'a> {
+    image: Option<&'a DiskImage>,
+    by_comm: HashMap<String, Option<Symbols>>,
+}
+
+impl UserSymbols<'_> {
+    fn get(&mut self, comm: &str) -> Option<&Symbols> {
+        let image = self.image;
+        self.by_comm
+            .entry(comm.to_string())
+            .or_insert_with(|| {
+                let image = image?;
+                let path = find_binary(image, comm)?;
+                let data = image.read_file(&path).ok()?;
+                match Symbols::from_elf(&data) {
+                    Ok(symbols) => Some(symbols),
+                    Err(e) => {
+                        eprintln!("{} {}: {}", "Warning:".yellow(), path, e);
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+}
+
+/// Find the binary a process named `comm` runs.
+///
+/// Process names are the executable's path, truncated to 15 bytes, so a
+/// name that is not a file is matched as a prefix within its directory.
+fn find_binary(image: &DiskImage, comm: &str) -> Option<String> {
+    if !comm.starts_with('/') {
+        return None;
+    }
+    if image.read_file(comm).is_ok() {
+        return Some(comm.to_string());
+    }
+
+    let (dir, prefix) = comm.rsplit_once('/')?;
+    let names = image
+        .list_dir(if dir.is_empty() { "/" } else { dir })
+        .ok()?;
+    let name = unique_prefix_match(&names, prefix)?;
+    Some(format!("{}/{}", dir, name))
+}
+
+/// The only name in `names` starting with `prefix`.
+fn unique_prefix_match<'a>(names: &'a [String], prefix: &str) -> Option<&'a str> {
+    let mut matches = names.iter().filter(|name| name.starts_with(prefix));
+    match (matches.next(), matches.next()) {
+        (Some(name), None) => Some(name),
+        _ => None,
+    }
+}
+
+/// Fold the samples of `dump` into `comm;user frames;kernel frames` stacks,
+/// outermost first, with their summed counts.
+fn fold(dump: &Dump, kernel: &Symbols, user: &mut UserSymbols<'_>) -> BTreeMap<String, u64> {
+    let mut folded = BTreeMap::new();
+
+    for sample in &dump.samples {
+        let mut frames = vec![sample.comm.clone()];
+
+        let ustack = dump.stacks.get(&sample.ustack);
+        let kstack = dump.stacks.get(&sample.kstack);
+
+        if let Some(ips) = ustack {
+            let symbols = user.get(&sample.comm);
+            frames.extend(symbolize(ips, symbols, ""));
+        }
+        if let Some(ips) = kstack {
+            frames.extend(symbolize(ips, Some(kernel), KERNEL_SUFFIX));
+        }
+        if ustack.is_none() && kstack.is_none() {
+            frames.push("[unknown]".to_string());
+        }
+
+        *folded.entry(frames.join(";")).or_insert(0) += sample.count;
+    }
+
+    folded
+}
+
+/// Name the frames of `ips`, outermost first.
+fn symbolize(ips: &[u64], symbols: Option<&Symbols>, suffix: &str) -> Vec<String> {
+    ips.iter()
+        .enumerate()
+        .rev()
+        .map(|(i, &ip)| {
+            // Return addresses point after the call, which may be the start
+            // of the next function
+            let addr = if i == 0 { ip } else { ip.saturating_sub(1) };
+            match symbols.and_then(|s| s.lookup(addr)) {
+                Some(name) => format!("{}{}", name.replace(';', ":"), suffix),
+                None => format!("{:#x}{}", ip, suffix),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_user_symbols() -> UserSymbols<'static> {
+        UserSymbols {
+            image: None,
+            by_comm: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn parses_prefixed_lines() {
+        let dump = parse_dump(
+            "[  1.000] boot noise\n\
+             [  2.000] rkprof begin 1000000\n\
+             [  2.001] rkprof sample 7 1 -14 3 /bin/sh\n\
+             [  2.002] rkprof stack 1 ffff800000001000 ffff800000002000\n\
+             [  2.003] rkprof end\n",
+        )
+        .unwrap();
+        assert_eq!(dump.period_ns, 1_000_000);
+        assert_eq!(dump.samples.len(), 1);
+        assert_eq!(dump.samples[0].comm, "/bin/sh");
+        assert_eq!(dump.samples[0].kstack, 1);
+        assert_eq!(dump.samples[0].ustack, -14);
+        assert_eq!(dump.samples[0].count, 3);
+        assert_eq!(
+            dump.stacks[&1],
+            [0xffff_8000_0000_1000, 0xffff_8000_0000_2000]
+        );
+    }
+
+    #[test]
+    fn accepts_missing_end() {
+        let dump = parse_dump("rkprof begin 10\nrkprof sample 1 -14 -14 2 init\n").unwrap();
+        assert_eq!(dump.samples.len(), 1);
+    }
+
+    #[test]
+    fn later_begin_replaces_dump() {
+        let dump = parse_dump(
+            "rkprof begin 10\n\
+             rkprof sample 1 -14 -14 2 old\n\
+             rkprof begin 20\n\
+             rkprof sample 2 -14 -14 5 new\n\
+             rkprof end\n",
+        )
+        .unwrap();
+        assert_eq!(dump.period_ns, 20);
+        assert_eq!(dump.samples.len(), 1);
+        assert_eq!(dump.samples[0].comm, "new");
+    }
+
+    #[test]
+    fn rejects_malformed_dumps() {
+        assert!(parse_dump("no dump here\n").is_err());
+        assert!(parse_dump("rkprof sample 1 -14 -14 2 init\n").is_err());
+        assert!(parse_dump("rkprof begin 10\nrkprof stack 1 xyz\n").is_err());
+    }
+
+    #[test]
+    fn folds_outermost_first() {
+        let dump = parse_dump(
+            "rkprof begin 10\n\
+             rkprof sample 1 1 2 3 /bin/sh\n\
+             rkprof sample 1 1 2 4 /bin/sh\n\
+             rkprof sample 2 1 -14 1 idle\n\
+             rkprof sample 3 -14 -14 1 lost\n\
+             rkprof stack 1 1008 2004\n\
+             rkprof stack 2 400000 400100\n\
+             rkprof end\n",
+        )
+        .unwrap();
+        let kernel = Symbols::from_functions(&[(0x1000, 0x10, "inner"), (0x2000, 0, "outer")]);
+
+        let folded = fold(&dump, &kernel, &mut no_user_symbols());
+        let lines: Vec<_> = folded.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(
+            lines,
+            [
+                ("/bin/sh;0x400100;0x400000;outer_[k];inner_[k]", 7),
+                ("idle;outer_[k];inner_[k]", 1),
+                ("lost;[unknown]", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn return_address_at_function_start_names_caller() {
+        // The outer frame returns to the first byte of `next`, right after
+        // the call at the end of `caller`
+        let symbols = Symbols::from_functions(&[(0x1000, 0x10, "caller"), (0x1010, 0x10, "next")]);
+        assert_eq!(
+            symbolize(&[0x1010, 0x1010], Some(&symbols), ""),
+            ["caller", "next"]
+        );
+        // A zero frame has nothing before it
+        assert_eq!(symbolize(&[0, 0], None, ""), ["0x0", "0x0"]);
+    }
+
+    #[test]
+    fn finds_binary_by_truncated_name() {
+        let names = ["hello".to_string(), "help".to_string(), "init".to_string()];
+        assert_eq!(unique_prefix_match(&names, "hello"), Some("hello"));
+        assert_eq!(unique_prefix_match(&names, "in"), Some("init"));
+        // Ambiguous or missing prefixes match nothing
+        assert_eq!(unique_prefix_match(&names, "hel"), None);
+        assert_eq!(unique_prefix_match(&names, "missing"), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! Read-only access to the OS disk image.
+//!
+//! The image is read with the same `mkfs-ext2` crate the kernel mounts it
+//! with, backed by the image file held in memory.
+
+use std::fs;
+
+use anyhow::{anyhow, bail, Context, Result};
+use ext2::{Ext2Fs, Inode, InodeAddress, RegularFile, Type};
+use filesystem::BlockDevice;
+
+const SECTOR_SIZE: usize = 512;
+
+/// An image file as a read-only block device.
+struct ImageDevice(Vec<u8>);
+
+impl BlockDevice for ImageDevice {
+    type Error = ();
+
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> usize {
+        self.0.len() / SECTOR_SIZE
+    }
+
+    fn read_sector(&self, sector_index: usize, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let start = sector_index * SECTOR_SIZE;
+        let sector = self.0.get(start..start + buf.len()).ok_or(())?;
+        buf.copy_from_slice(sector);
+        Ok(buf.len())
+    }
+
+    fn write_sector(&mut self, _sector_index: usize, _buf: &[u8]) -> Result<usize, Self::Error> {
+        Err(())
+    }
+}
+
+/// An ext2 disk image loaded into memory.
+pub struct DiskImage {
+    fs: Ext2Fs<ImageDevice>,
+}
+
+impl DiskImage {
+    /// Load the image at `path`.
+    pub fn open(path: &str) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("Failed to read image: {}", path))?;
+        let fs = Ext2Fs::try_new(ImageDevice(data))
+            .map_err(|e| anyhow!("Not an ext2 image: {}: {:?}", path, e))?;
+        Ok(Self { fs })
+    }
+
+    /// Read the file at the absolute `path`.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let (addr, inode) = self.lookup(path)?;
+        if inode.typ() != Type::RegularFile {
+            bail!("{} is not a regular file", path);
+        }
+        let file: RegularFile = (addr, inode)
+            .try_into()
+            .map_err(|_| anyhow!("{} is not a regular file", path))?;
+
+        let mut data = vec![0; file.len()];
+        let mut read = 0;
+        while read < data.len() {
+            let n = self
+                .fs
+                .read_from_file(&file, read, &mut data[read..])
+                .map_err(|e| anyhow!("Failed to read {} from image: {:?}", path, e))?;
+            if n == 0 {
+                bail!("{} is truncated in image", path);
+            }
+            read += n;
+        }
+        Ok(data)
+    }
+
+    /// List the names in the directory at the absolute `path`.
+    pub fn list_dir(&self, path: &str) -> Result<Vec<String>> {
+        let (_, inode) = self.lookup(path)?;
+        if inode.typ() != Type::Directory {
+            bail!("{} is not a directory", path);
+        }
+        Ok(self
+            .fs
+            .list_dir(&inode)
+            .map_err(|e| anyhow!("Failed to list {} in image: {:?}", path, e))?
+            .iter()
+            .filter_map(|entry| entry.name())
+            .filter(|name| *name != "." && *name != "..")
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Resolve the absolute `path` to its inode.
+    fn lookup(&self, path: &str) -> Result<(InodeAddress, Inode)> {
+        let not_found = || anyhow!("{} not found in image", path);
+
+        let (mut addr, mut inode) = self
+            .fs
+            .read_root_inode()
+            .map_err(|e| anyhow!("Failed to read root directory of image: {:?}", e))?
+            .into_inner();
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            if inode.typ() != Type::Directory {
+                return Err(not_found());
+            }
+            let entry = self
+                .fs
+                .list_dir(&inode)
+                .map_err(|e| anyhow!("Failed to look up {} in image: {:?}", path, e))?
+                .into_iter()
+                .find(|entry| entry.name() == Some(component))
+                .ok_or_else(not_found)?;
+            (addr, inode) = self
+                .fs
+                .resolve_dir_entry(entry)
+                .map_err(|e| anyhow!("Failed to look up {} in image: {:?}", path, e))?;
+        }
+        Ok((addr, inode))
+    }
+}
@@ -5,7 +5,9 @@
 
 mod commands;
 mod config;
+mod image;
 mod signing;
+mod symbols;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
@@ -120,6 +122,34 @@ enum Commands {
         input: String,
     },
 
+    /// Profile a device and write folded stacks for flamegraphs
+    Profile {
+        /// Kernel ELF to symbolize kernel frames against
+        #[arg(short, long)]
+        kernel: String,
+
+        /// OS disk image (ext2) holding the user binaries
+        #[arg(long)]
+        image: Option<String>,
+
+        /// Captured profiler output, e.g. a serial console log ("-" for stdin)
+        #[arg(
+            short,
+            long,
+            conflicts_with = "target",
+            required_unless_present = "target"
+        )]
+        input: Option<String>,
+
+        /// Target device to run the profiler on (e.g., ssh://user@host)
+        #[arg(short, long)]
+        target: Option<String>,
+
+        /// Output file for the folded stacks (default: stdout)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+
     /// Initialize a new rkBPF project
     Init {
         /// Project name
@@ -203,6 +233,19 @@ fn main() -> Result<()> {
         Commands::List { target } => commands::list::list_programs(&target),
         Commands::Unload { program, target } => commands::unload::unload_program(&program, &target),
         Commands::Info { input } => commands::info::show_info(&input),
+        Commands::Profile {
+            kernel,
+            image,
+            input,
+            target,
+            output,
+        } => commands::profile::profile(
+            &kernel,
+            image.as_deref(),
+            input.as_deref(),
+            target.as_deref(),
+            output.as_deref(),
+        ),
         Commands::Init { name, profile } => commands::init::init_project(&name, &profile),
     }
 }
@@ -0,0 +1,112 @@
+//! Function symbols of ELF binaries.
+//!
+//! The kernel and userspace binaries are linked statically at fixed
+//! addresses, so the instruction pointers in a stack are symbolized against
+//! the ELF symbol table directly.
+
+use anyhow::{anyhow, Result};
+use elf::endian::AnyEndian;
+use elf::ElfBytes;
+
+/// A function symbol covering `start..end`.
+struct Function {
+    start: u64,
+    end: u64,
+    name: String,
+}
+
+/// Function symbols of one binary, sorted by address.
+pub struct Symbols {
+    functions: Vec<Function>,
+}
+
+impl Symbols {
+    /// Read the function symbols of the ELF file `data`.
+    pub fn from_elf(data: &[u8]) -> Result<Self> {
+        let file = ElfBytes::<AnyEndian>::minimal_parse(data)
+            .map_err(|e| anyhow!("Failed to parse ELF: {}", e))?;
+        let (symtab, strtab) = file
+            .symbol_table()
+            .map_err(|e| anyhow!("Failed to read symbol table: {}", e))?
+            .ok_or_else(|| anyhow!("ELF has no symbol table"))?;
+
+        let mut functions = Vec::new();
+        for sym in symtab.iter() {
+            if sym.st_symtype() != elf::abi::STT_FUNC || sym.st_value == 0 {
+                continue;
+            }
+            let Ok(name) = strtab.get(sym.st_name as usize) else {
+                continue;
+            };
+            functions.push(Function {
+                start: sym.st_value,
+                end: sym.st_value + sym.st_size,
+                name: format!("{:#}", rustc_demangle::demangle(name)),
+            });
+        }
+        functions.sort_by_key(|f| f.start);
+
+        Ok(Self { functions })
+    }
+
+    /// Name of the function containing `addr`.
+    pub fn lookup(&self, addr: u64) -> Option<&str> {
+        let idx = self.functions.partition_point(|f| f.start <= addr);
+        let function = &self.functions[idx.checked_sub(1)?];
+        // Symbols without a size cover everything up to the next one
+        (addr < function.end || function.start == function.end).then_some(function.name.as_str())
+    }
+}
+
+#[cfg(test)]
+impl Symbols {
+    /// Symbols from `(start, size, name)` triples.
+    pub(crate) fn from_functions(functions: &[(u64, u64, &str)]) -> Self {
+        let mut functions: Vec<_> = functions
+            .iter()
+            .map(|&(start, size, name)| Function {
+                start,
+                end: start + size,
+                name: name.to_string(),
+            })
+            .collect();
+        functions.sort_by_key(|f| f.start);
+        Self { functions }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_respects_bounds() {
+        let symbols = Symbols::from_functions(&[(0x1000, 0x10, "a"), (0x1020, 0x10, "b")]);
+        assert_eq!(symbols.lookup(0xfff), None);
+        assert_eq!(symbols.lookup(0x1000), Some("a"));
+        assert_eq!(symbols.lookup(0x100f), Some("a"));
+        // Gap between a and b
+        assert_eq!(symbols.lookup(0x1010), None);
+        assert_eq!(symbols.lookup(0x1020), Some("b"));
+        assert_eq!(symbols.lookup(0x1030), None);
+    }
+
+    #[test]
+    fn zero_size_symbol_extends_to_next() {
+        let symbols = Symbols::from_functions(&[(0x1000, 0, "start"), (0x2000, 0x10, "main")]);
+        assert_eq!(symbols.lookup(0x1000), Some("start"));
+        assert_eq!(symbols.lookup(0x1fff), Some("start"));
+        assert_eq!(symbols.lookup(0x2000), Some("main"));
+        assert_eq!(symbols.lookup(0x2010), None);
+    }
+
+    #[test]
+    fn empty_table() {
+        assert_eq!(Symbols::from_functions(&[]).lookup(0x1000), None);
+    }
+
+    #[test]
+    fn rejects_non_elf_data() {
+        assert!(Symbols::from_elf(b"not an elf file").is_err());
+    }
+}
@@ -78,6 +78,7 @@ pub extern "C" fn _start() -> ! {
         attach_btf_id: ATTACH_TYPE_SYSCALL,
         attach_prog_fd: prog_id as u32,
         key: 0,
+        value: 0, // Syscall mask, 0 = all syscalls
         ..Default::default()
     };
 